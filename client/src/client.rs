@@ -1,103 +1,372 @@
-use entities::{Account, LoginChallenge, Operation};
+use entities::{
+    Account, BalanceReport, CardStatement, Category, Claim, ClaimedExpense, CreditCard,
+    ErrorResponse, ExchangeRate, Forecast, Goal, GoalProgress, Holding, InstallmentPlan,
+    InstallmentSchedule, Ledger, LoginChallenge, LoginResponse, MemberBalance, NewLedger,
+    Operation, Price, RatesImported, RecurringOperation, Reimbursement, RepaymentsRecorded,
+    Security, Settlement, SharedExpense, TotpConfirmation, TotpEnrolment, Trade,
+};
 
 use crate::error::ClientError;
 use crate::log;
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use wasm_bindgen::{JsCast, JsValue};
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{Request, RequestInit, Response};
+use web_sys::{HtmlDocument, Request, RequestInit, Response};
+
+const CSRF_COOKIE: &str = "csrf";
+const CSRF_HEADER: &str = "X-CSRF-Token";
+const LEDGER_COOKIE: &str = "ledger";
 
 pub async fn accounts() -> Result<Vec<Account>, ClientError> {
-    let mut opts = RequestInit::new();
-    opts.method("GET");
+    let resp = fetch::<()>("GET", "/account", None).await?;
 
-    let request = Request::new_with_str_and_init("/account", &opts)?;
-    let window = web_sys::window().ok_or(ClientError::UnexpectedError)?;
-    let resp = JsFuture::from(window.fetch_with_request(&request)).await?;
+    deserialize_into(resp).await
+}
+
+/// Balances valued in the ledger's currency.
+pub async fn report() -> Result<BalanceReport, ClientError> {
+    let resp = fetch::<()>("GET", "/report", None).await?;
 
     deserialize_into(resp).await
 }
 
 pub async fn transfer(operation: Operation) -> Result<(), ClientError> {
-    let mut opts = RequestInit::new();
-    opts.method("POST");
-    let operation_str = serde_json::to_string(&operation)?;
-    opts.body(Some(&JsValue::from_str(&operation_str)));
-
-    log(&operation_str);
+    log(&serde_json::to_string(&operation)?);
 
-    let request = Request::new_with_str_and_init("/operation", &opts)?;
+    let resp = fetch("POST", "/operation", Some(&operation)).await?;
 
-    request.headers().set("Content-Type", "application/json")?;
+    expect_success(resp).await
+}
 
-    let window = web_sys::window().ok_or(ClientError::UnexpectedError)?;
-    let resp = JsFuture::from(window.fetch_with_request(&request)).await?;
+pub async fn login(user: String, token: String, code: Option<String>) -> Result<(), ClientError> {
+    let challenge = LoginChallenge { user, token, code };
+    let resp = fetch("POST", "/login", Some(&challenge)).await?;
 
-    let resp: Response = resp.dyn_into()?;
     if resp.status() < 400 {
-        Ok(())
-    } else {
-        Err(ClientError::InternalError(resp.status_text()))
+        return Ok(());
+    }
+
+    let body = response_text(&resp).await?;
+    match serde_json::from_str::<LoginResponse>(&body) {
+        Ok(LoginResponse {
+            second_factor: true,
+        }) => Err(ClientError::SecondFactorRequired),
+        _ => Err(server_error(&resp, &body)),
     }
 }
 
-pub async fn login(token: String) -> Result<(), ClientError> {
-    let mut opts = RequestInit::new();
-    opts.method("POST");
-    let login_str = serde_json::to_string(&LoginChallenge { token })?;
-    opts.body(Some(&JsValue::from_str(&login_str)));
+pub async fn enrol_totp() -> Result<TotpEnrolment, ClientError> {
+    let resp = fetch::<()>("POST", "/totp", None).await?;
 
-    let request = Request::new_with_str_and_init("/login", &opts)?;
+    deserialize_into(resp).await
+}
 
-    request.headers().set("Content-Type", "application/json")?;
+pub async fn confirm_totp(code: String) -> Result<(), ClientError> {
+    let resp = fetch("PUT", "/totp", Some(&TotpConfirmation { code })).await?;
 
-    let window = web_sys::window().ok_or(ClientError::UnexpectedError)?;
-    let resp = JsFuture::from(window.fetch_with_request(&request)).await?;
-
-    let resp: Response = resp.dyn_into()?;
-    if resp.status() < 400 {
-        Ok(())
-    } else {
-        Err(ClientError::ServerError(resp.status_text()))
-    }
+    expect_success(resp).await
 }
 
 pub async fn add_account(account: Account) -> Result<(), ClientError> {
-    let mut opts = RequestInit::new();
-    opts.method("POST");
-    let account_str = serde_json::to_string(&account)?;
-    opts.body(Some(&JsValue::from_str(&account_str)));
+    let resp = fetch("POST", "/account", Some(&account)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn categories() -> Result<Vec<Category>, ClientError> {
+    let resp = fetch::<()>("GET", "/category", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn add_category(category: Category) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/category", Some(&category)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn add_rate(rate: ExchangeRate) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/rate", Some(&rate)).await?;
+
+    expect_success(resp).await
+}
 
-    let request = Request::new_with_str_and_init("/account", &opts)?;
+/// Uploads rates as CSV rows of base, quote, date and rate, returning how many were stored.
+pub async fn import_rates(csv: String) -> Result<usize, ClientError> {
+    let resp = send("POST", "/rate/import", Some((csv, "text/csv"))).await?;
+    let imported: RatesImported = deserialize_into(resp).await?;
 
-    request.headers().set("Content-Type", "application/json")?;
+    Ok(imported.count)
+}
+
+pub async fn add_security(security: Security) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/security", Some(&security)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn add_trade(trade: Trade) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/trade", Some(&trade)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn add_price(price: Price) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/price", Some(&price)).await?;
+
+    expect_success(resp).await
+}
+
+/// What the ledger's accounts hold, valued at the latest prices.
+pub async fn investments() -> Result<Vec<Holding>, ClientError> {
+    let resp = fetch::<()>("GET", "/investment", None).await?;
+
+    deserialize_into(resp).await
+}
+
+/// Where each credit card stands against its latest statement, soonest due first.
+pub async fn cards() -> Result<Vec<CardStatement>, ClientError> {
+    let resp = fetch::<()>("GET", "/card", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn set_card(card: CreditCard) -> Result<(), ClientError> {
+    let resp = fetch("PUT", "/card", Some(&card)).await?;
+
+    expect_success(resp).await
+}
+
+/// How balances are expected to move over the next `months`, with recent spending by category
+/// kept up if `history` is set.
+pub async fn forecast(months: u32, history: bool) -> Result<Forecast, ClientError> {
+    let url = format!("/forecast?months={}&history={}", months, history);
+    let resp = fetch::<()>("GET", &url, None).await?;
+
+    deserialize_into(resp).await
+}
+
+/// Every installment plan with its amortisation schedule.
+pub async fn installments() -> Result<Vec<InstallmentSchedule>, ClientError> {
+    let resp = fetch::<()>("GET", "/installment", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn add_installment_plan(plan: InstallmentPlan) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/installment", Some(&plan)).await?;
+
+    expect_success(resp).await
+}
+
+/// Records the repayments due by today, returning how many there were.
+pub async fn repay_installments() -> Result<usize, ClientError> {
+    let resp = fetch::<()>("POST", "/installment/repay", None).await?;
+    let recorded: RepaymentsRecorded = deserialize_into(resp).await?;
+
+    Ok(recorded.count)
+}
+
+/// Shares the operation among household members.
+pub async fn share_operation(operation: i64, expense: SharedExpense) -> Result<(), ClientError> {
+    let url = format!("/operation/{}/share", operation);
+    let resp = fetch("PUT", &url, Some(&expense)).await?;
+
+    expect_success(resp).await
+}
+
+/// Where each member stands across the shared expenses.
+pub async fn shared_balances() -> Result<Vec<MemberBalance>, ClientError> {
+    let resp = fetch::<()>("GET", "/shared", None).await?;
+
+    deserialize_into(resp).await
+}
+
+/// The transfers that would settle the shared expenses.
+pub async fn settle_up() -> Result<Vec<Settlement>, ClientError> {
+    let resp = fetch::<()>("GET", "/shared/settle", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn set_claim(operation: i64, claim: Claim) -> Result<(), ClientError> {
+    let url = format!("/operation/{}/claim", operation);
+    let resp = fetch("PUT", &url, Some(&claim)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn add_reimbursement(reimbursement: Reimbursement) -> Result<(), ClientError> {
+    let resp = fetch("POST", "/reimbursement", Some(&reimbursement)).await?;
+
+    expect_success(resp).await
+}
+
+/// The reimbursable expenses not yet paid back, oldest first.
+pub async fn claims() -> Result<Vec<ClaimedExpense>, ClientError> {
+    let resp = fetch::<()>("GET", "/claim", None).await?;
+
+    deserialize_into(resp).await
+}
+
+/// Each savings goal with how far along it is, soonest first.
+pub async fn goals() -> Result<Vec<GoalProgress>, ClientError> {
+    let resp = fetch::<()>("GET", "/goal", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn set_goal(goal: Goal) -> Result<(), ClientError> {
+    let resp = fetch("PUT", "/goal", Some(&goal)).await?;
+
+    expect_success(resp).await
+}
+
+/// The ledger's recurring operations by name.
+pub async fn recurring() -> Result<Vec<RecurringOperation>, ClientError> {
+    let resp = fetch::<()>("GET", "/recurring", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn set_recurring(item: RecurringOperation) -> Result<(), ClientError> {
+    let resp = fetch("PUT", "/recurring", Some(&item)).await?;
+
+    expect_success(resp).await
+}
+
+pub async fn ledgers() -> Result<Vec<Ledger>, ClientError> {
+    let resp = fetch::<()>("GET", "/ledger", None).await?;
+
+    deserialize_into(resp).await
+}
+
+pub async fn add_ledger(
+    name: String,
+    currency: String,
+    timezone: String,
+) -> Result<Ledger, ClientError> {
+    let ledger = NewLedger {
+        name,
+        currency,
+        timezone,
+    };
+    let resp = fetch("POST", "/ledger", Some(&ledger)).await?;
+
+    deserialize_into(resp).await
+}
+
+/// The ledger chosen in this browser, which the server otherwise defaults to the first one.
+pub fn active_ledger() -> Option<i64> {
+    cookie(LEDGER_COOKIE)?.parse().ok()
+}
+
+pub fn set_active_ledger(ledger: i64) -> Result<(), ClientError> {
+    let document = html_document().ok_or(ClientError::DomError)?;
+    document.set_cookie(&format!(
+        "{}={}; path=/; max-age=31536000; SameSite=Strict",
+        LEDGER_COOKIE, ledger
+    ))?;
+
+    Ok(())
+}
+
+/// Sends a request to the server, echoing the CSRF cookie back for state-changing methods.
+async fn fetch<B: Serialize>(
+    method: &str,
+    url: &str,
+    body: Option<&B>,
+) -> Result<Response, ClientError> {
+    let body = match body {
+        Some(body) => Some((serde_json::to_string(body)?, "application/json")),
+        None => None,
+    };
+
+    send(method, url, body).await
+}
+
+/// Like `fetch`, for a body that's already encoded, given with its content type.
+async fn send(
+    method: &str,
+    url: &str,
+    body: Option<(String, &str)>,
+) -> Result<Response, ClientError> {
+    let opts = RequestInit::new();
+    opts.set_method(method);
+    if let Some((body, _)) = &body {
+        opts.set_body(&JsValue::from_str(body));
+    }
+
+    let request = Request::new_with_str_and_init(url, &opts)?;
+
+    if let Some((_, content_type)) = body {
+        request.headers().set("Content-Type", content_type)?;
+    }
+    if method != "GET" {
+        if let Some(token) = cookie(CSRF_COOKIE) {
+            request.headers().set(CSRF_HEADER, &token)?;
+        }
+    }
 
     let window = web_sys::window().ok_or(ClientError::UnexpectedError)?;
     let resp = JsFuture::from(window.fetch_with_request(&request)).await?;
 
-    let resp: Response = resp.dyn_into()?;
+    Ok(resp.dyn_into()?)
+}
+
+fn html_document() -> Option<HtmlDocument> {
+    web_sys::window()?.document()?.dyn_into().ok()
+}
+
+fn cookie(name: &str) -> Option<String> {
+    let cookies = html_document()?.cookie().ok()?;
+
+    cookies.split(';').find_map(|cookie| {
+        let mut pair = cookie.trim().splitn(2, '=');
+        match (pair.next(), pair.next()) {
+            (Some(key), Some(value)) if key == name => Some(value.to_owned()),
+            _ => None,
+        }
+    })
+}
+
+async fn expect_success(resp: Response) -> Result<(), ClientError> {
     if resp.status() < 400 {
         Ok(())
     } else {
-        Err(ClientError::ServerError(resp.status_text()))
+        let body = response_text(&resp).await?;
+        Err(server_error(&resp, &body))
     }
 }
 
-async fn deserialize_into<T>(resp_value: JsValue) -> Result<T, ClientError>
+async fn deserialize_into<T>(resp: Response) -> Result<T, ClientError>
 where
     T: DeserializeOwned,
 {
-    let resp: Response = resp_value.dyn_into()?;
+    let text = response_text(&resp).await?;
 
     if resp.status() >= 400 {
-        let server_resp = JsFuture::from(resp.text()?).await?;
-        return Err(ClientError::ServerError(format!(
-            "{:?}",
-            server_resp.as_string()
-        )));
+        return Err(server_error(&resp, &text));
     }
 
-    let json = JsFuture::from(resp.json()?).await?;
+    serde_json::from_str(&text).map_err(ClientError::from)
+}
+
+async fn response_text(resp: &Response) -> Result<String, ClientError> {
+    let text = JsFuture::from(resp.text()?).await?;
+
+    text.as_string().ok_or(ClientError::UnexpectedError)
+}
+
+/// Reads the server's error body, falling back to the status line for responses that
+/// didn't come from a handler, such as a proxy error page.
+fn server_error(resp: &Response, body: &str) -> ClientError {
+    let error = serde_json::from_str(body).unwrap_or_else(|_| ErrorResponse {
+        code: resp.status().to_string(),
+        message: resp.status_text(),
+        field: None,
+    });
 
-    json.into_serde().map_err(ClientError::from)
+    ClientError::ServerError(error)
 }
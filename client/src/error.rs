@@ -1,13 +1,38 @@
+use entities::money::MoneyError;
+use entities::validation::ValidationError;
+use entities::ErrorResponse;
+use std::fmt;
 use std::num::ParseFloatError;
 use std::num::ParseIntError;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum ClientError {
     InternalError(String),
     UnexpectedError,
     DomError,
     InputError(String),
-    ServerError(String),
+    ServerError(ErrorResponse),
+    SecondFactorRequired,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InternalError(message) | ClientError::InputError(message) => {
+                write!(f, "{}", message)
+            }
+            ClientError::UnexpectedError => write!(f, "Unexpected error."),
+            ClientError::DomError => write!(f, "The page is broken."),
+            ClientError::ServerError(ErrorResponse {
+                message,
+                field: Some(field),
+                ..
+            }) => write!(f, "{}: {}", field, message),
+            ClientError::ServerError(ErrorResponse { message, .. }) => write!(f, "{}", message),
+            ClientError::SecondFactorRequired => write!(f, "An authenticator code is required."),
+        }
+    }
 }
 
 impl From<wasm_bindgen::JsValue> for ClientError {
@@ -24,7 +49,19 @@ impl From<serde_json::Error> for ClientError {
 
 impl From<ClientError> for wasm_bindgen::JsValue {
     fn from(e: ClientError) -> Self {
-        wasm_bindgen::JsValue::from(format!("{:?}", e))
+        wasm_bindgen::JsValue::from(e.to_string())
+    }
+}
+
+impl From<ValidationError> for ClientError {
+    fn from(e: ValidationError) -> Self {
+        ClientError::InputError(e.to_string())
+    }
+}
+
+impl From<MoneyError> for ClientError {
+    fn from(e: MoneyError) -> Self {
+        ClientError::InputError(e.to_string())
     }
 }
 
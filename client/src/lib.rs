@@ -1,9 +1,20 @@
 use crate::error::ClientError;
-use entities::{Account, Operation};
+use chrono::NaiveDate;
+use entities::money::{format_decimal, parse_decimal};
+use entities::time::{parse_timezone, Tz, DEFAULT_TIMEZONE};
+use entities::{
+    Account, BalanceReport, Category, Claim, ClaimStatus, CreditCard, ExchangeRate, Frequency,
+    Goal, InstallmentPlan, Leg, MemberShare, Money, Operation, Price, RecurringOperation,
+    Reimbursement, Security, ShareMethod, SharedExpense, Timestamp, Trade, TradeSide,
+    DEFAULT_CURRENCY, PRICE_DIGITS, QUANTITY_DIGITS, RATE_DIGITS,
+};
 use js_sys::Date;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
-use web_sys::{Document, Element, HtmlElement, HtmlInputElement, HtmlSelectElement, Window};
+use web_sys::{
+    Document, Element, HtmlElement, HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement,
+    Window,
+};
 
 mod client;
 mod error;
@@ -36,6 +47,10 @@ extern "C" {
 
 #[wasm_bindgen(start)]
 pub async fn main() -> Result<(), JsValue> {
+    match init_ledgers().await {
+        Ok(_) => (),
+        Err(e) => log_owned(format!("{:?}", e)),
+    }
     match init_accounts().await {
         Ok(_) => log("Assembly initialized."),
         Err(e) => log_owned(format!("{:?}", e)),
@@ -46,7 +61,8 @@ pub async fn main() -> Result<(), JsValue> {
 
 pub async fn init_accounts() -> Result<(), ClientError> {
     let accounts = client::accounts().await?;
-    refresh_content(&accounts).await?;
+    refresh_content(&client::report().await?).await?;
+    refresh_cards().await?;
 
     let options = accounts
         .iter()
@@ -54,11 +70,12 @@ pub async fn init_accounts() -> Result<(), ClientError> {
         .map(|(idx, account)| {
             format!(
                 r#"
-                    <option value="{}" {}>{}</option>
+                    <option value="{}" data-currency="{}" {}>{}</option>
                 "#,
-                account.name,
+                escape_html(&account.name),
+                escape_html(&account.currency),
                 if idx == 0 { "selected" } else { "" },
-                account.name
+                escape_html(&account.name)
             )
         })
         .fold("".to_owned(), |x, y| x + &y);
@@ -66,44 +83,244 @@ pub async fn init_accounts() -> Result<(), ClientError> {
     get_element_by_id!("from_account", HtmlElement).set_inner_html(&options);
 
     get_element_by_id!("to_account", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("trade_account", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("card_account", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("card_pay_from", HtmlElement).set_inner_html(&format!(
+        r#"<option value="">Paid from</option>{}"#,
+        options
+    ));
+    get_element_by_id!("card_pay_from", HtmlSelectElement).set_value("");
+    get_element_by_id!("plan_account", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("plan_from", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("goal_account", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("recurring_from", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("recurring_to", HtmlElement).set_inner_html(&options);
+    get_element_by_id!("legs", HtmlElement).set_inner_html("");
+
+    init_categories().await
+}
+
+pub async fn init_categories() -> Result<(), ClientError> {
+    let categories = client::categories().await?;
+    let options = categories
+        .iter()
+        .map(|category| format!(r#"<option value="{}">"#, escape_html(&category.name)))
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("categories", HtmlElement).set_inner_html(&options);
+
+    Ok(())
+}
+
+pub async fn init_ledgers() -> Result<(), ClientError> {
+    let ledgers = client::ledgers().await?;
+    let active = client::active_ledger()
+        .filter(|active| ledgers.iter().any(|ledger| ledger.id == *active))
+        .or_else(|| ledgers.first().map(|ledger| ledger.id));
+
+    let options = ledgers
+        .iter()
+        .map(|ledger| {
+            format!(
+                r#"
+                    <option value="{}" data-timezone="{}" {}>{} ({})</option>
+                "#,
+                ledger.id,
+                escape_html(&ledger.timezone),
+                if Some(ledger.id) == active {
+                    "selected"
+                } else {
+                    ""
+                },
+                escape_html(&ledger.name),
+                ledger.role.as_str()
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+
+    get_element_by_id!("ledger_select", HtmlElement).set_inner_html(&options);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn switch_ledger() -> Result<(), JsValue> {
+    let ledger: i64 = get_element_by_id!("ledger_select", HtmlSelectElement)
+        .value()
+        .parse()
+        .map_err(ClientError::from)?;
+    client::set_active_ledger(ledger)?;
+    init_accounts().await?;
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_ledger() -> Result<(), JsValue> {
+    let name = get_element_by_id!("ledger_name", HtmlInputElement).value();
+    let currency = get_element_by_id!("ledger_currency", HtmlInputElement).value();
+    let timezone = get_element_by_id!("ledger_timezone", HtmlInputElement).value();
+    let ledger = client::add_ledger(
+        name,
+        currency.trim().to_uppercase(),
+        timezone.trim().to_owned(),
+    )
+    .await
+    .map_err(alert_error)?;
+    client::set_active_ledger(ledger.id)?;
+    init_ledgers().await?;
+    init_accounts().await?;
+    alert("Success");
 
     Ok(())
 }
 
 #[wasm_bindgen]
 pub async fn do_transfer() -> Result<(), JsValue> {
-    let operation = make_operation()?;
-    client::transfer(operation).await?;
+    let operation = make_operation().map_err(alert_error)?;
+    if let Err(e) = operation.validate(Date::now() as i64) {
+        focus_field(match e.field {
+            "from" => "from_account",
+            "to" => "to_account",
+            "datetime" => "date",
+            "currency" => "from_account",
+            field => field,
+        });
+        return Err(alert_error(e.into()));
+    }
+    client::transfer(operation).await.map_err(alert_error)?;
     alert("Success");
 
     Ok(())
 }
 
+/// Adds a row to split the transfer into, going to the chosen account or else to "To".
+#[wasm_bindgen]
+pub fn add_leg() -> Result<(), JsValue> {
+    let legs = get_by_id("legs");
+    let idx = legs.child_element_count();
+    let accounts = get_by_id("to_account").inner_html();
+    let html = format!(
+        r#"
+            <div class="input-group mb-3">
+                <select class="custom-select" id="leg_account_{idx}">
+                    <option value="" selected>Same as To</option>
+                    {accounts}
+                </select>
+                <input type="text" class="form-control" list="categories" placeholder="Category(optional)" id="leg_category_{idx}">
+                <input type="text" class="form-control" placeholder="Decimal amount" id="leg_amount_{idx}">
+                <input type="text" class="form-control" placeholder="Memo(optional)" id="leg_memo_{idx}">
+            </div>
+        "#,
+        idx = idx,
+        accounts = accounts
+    );
+    legs.insert_adjacent_html("beforeend", &html)?;
+
+    Ok(())
+}
+
 fn make_operation() -> Result<Operation, ClientError> {
     let from = get_element_by_id!("from_account", HtmlSelectElement).value();
     let to = get_element_by_id!("to_account", HtmlSelectElement).value();
-    let amount: f64 = get_element_by_id!("amount", HtmlInputElement)
+    let currency = selected_currency("from_account");
+    let amount = parse_amount("amount", &currency)?;
+    let to_amount = match get_element_by_id!("to_amount", HtmlInputElement)
         .value()
-        .parse()?;
+        .trim()
+        .is_empty()
+    {
+        true => None,
+        false => Some(parse_amount("to_amount", &selected_currency("to_account"))?),
+    };
     let comment = get_element_by_id!("comment", HtmlInputElement).value();
-    let datetime = match get_element_by_id!("date", HtmlInputElement).value() {
-        date_str if !date_str.is_empty() => Date::parse(&date_str),
-        _ => Date::new_0().value_of(),
-    } as i64;
+    let datetime = parse_datetime("date")?;
 
     Ok(Operation {
         from,
         to,
-        amount: (amount * 100.0) as i64,
+        amount,
         comment,
         datetime,
+        legs: make_legs(&currency)?,
+        // The server takes the currency of the account the money leaves.
+        currency: None,
+        to_amount,
     })
 }
 
+/// Reads the split rows, skipping those left without an amount. Legs are in `currency`, the
+/// currency of the account the money leaves.
+fn make_legs(currency: &str) -> Result<Vec<Leg>, ClientError> {
+    let optional = |value: String| Some(value).filter(|value| !value.trim().is_empty());
+    let mut legs = Vec::new();
+    for idx in 0..get_by_id("legs").child_element_count() {
+        let amount_id = format!("leg_amount_{}", idx);
+        if get_element_by_id!(&amount_id, HtmlInputElement)
+            .value()
+            .trim()
+            .is_empty()
+        {
+            continue;
+        }
+        let value = |field: &str| {
+            get_element_by_id!(&format!("leg_{}_{}", field, idx), HtmlInputElement).value()
+        };
+        legs.push(Leg {
+            account: optional(
+                get_element_by_id!(&format!("leg_account_{}", idx), HtmlSelectElement).value(),
+            ),
+            category: optional(value("category")),
+            amount: parse_amount(&amount_id, currency)?,
+            memo: value("memo"),
+        });
+    }
+
+    Ok(legs)
+}
+
+/// Reads a picked date as the start of that day where the ledger is kept, not in the browser,
+/// or the current time if none was picked.
+fn parse_datetime(id: &str) -> Result<i64, ClientError> {
+    let date = get_element_by_id!(id, HtmlInputElement).value();
+    match date.trim() {
+        "" => Ok(Date::now() as i64),
+        date => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|date| Timestamp::from_local_date(date, selected_timezone()).millis())
+            .map_err(|_| ClientError::InputError(format!("{} is not a YYYY-MM-DD date.", date))),
+    }
+}
+
+/// Parses a decimal amount typed into the input, in minor units of `currency`.
+fn parse_amount(id: &str, currency: &str) -> Result<i64, ClientError> {
+    let value = get_element_by_id!(id, HtmlInputElement).value();
+
+    Ok(Money::parse(&value, currency)?.minor())
+}
+
+/// The currency of the account chosen in the select, as rendered by `init_accounts`.
+fn selected_currency(id: &str) -> String {
+    let index = get_element_by_id!(id, HtmlSelectElement).selected_index();
+    get_element_by_id!(id, HtmlSelectElement)
+        .item(index.max(0) as u32)
+        .and_then(|option| option.get_attribute("data-currency"))
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_owned())
+}
+
+/// The home timezone of the ledger chosen in the select, as rendered by `init_ledgers`.
+fn selected_timezone() -> Tz {
+    let index = get_element_by_id!("ledger_select", HtmlSelectElement).selected_index();
+    get_element_by_id!("ledger_select", HtmlSelectElement)
+        .item(index.max(0) as u32)
+        .and_then(|option| option.get_attribute("data-timezone"))
+        .and_then(|timezone| parse_timezone(&timezone))
+        .or_else(|| parse_timezone(DEFAULT_TIMEZONE))
+        .unwrap_or(Tz::UTC)
+}
+
 #[wasm_bindgen]
 pub async fn update_accounts() -> Result<(), JsValue> {
-    let accounts = client::accounts().await?;
-    refresh_content(&accounts).await?;
+    refresh_content(&client::report().await?).await?;
+    refresh_cards().await?;
 
     Ok(())
 }
@@ -111,31 +328,802 @@ pub async fn update_accounts() -> Result<(), JsValue> {
 #[wasm_bindgen]
 pub async fn add_account() -> Result<(), JsValue> {
     let name = get_element_by_id!("account_name", HtmlInputElement).value();
-    client::add_account(Account { name, balance: 0 }).await?;
+    let currency = get_element_by_id!("account_currency", HtmlInputElement).value();
+    let account = Account {
+        name,
+        balance: 0,
+        currency: currency.trim().to_uppercase(),
+    };
+    if let Err(e) = account.validate() {
+        focus_field(match e.field {
+            "currency" => "account_currency",
+            _ => "account_name",
+        });
+        return Err(alert_error(e.into()));
+    }
+    client::add_account(account).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn set_card() -> Result<(), JsValue> {
+    let card = make_card().map_err(alert_error)?;
+    if let Err(e) = card.validate() {
+        focus_field(match e.field {
+            "statement_day" => "card_statement_day",
+            "due_day" => "card_due_day",
+            "limit" => "card_limit",
+            "pay_from" => "card_pay_from",
+            _ => "card_account",
+        });
+        return Err(alert_error(e.into()));
+    }
+    client::set_card(card).await.map_err(alert_error)?;
+    refresh_cards().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+fn make_card() -> Result<CreditCard, ClientError> {
+    let day = |id: &str| {
+        get_element_by_id!(id, HtmlInputElement)
+            .value()
+            .trim()
+            .parse()
+    };
+
+    Ok(CreditCard {
+        account: get_element_by_id!("card_account", HtmlSelectElement).value(),
+        statement_day: day("card_statement_day")?,
+        due_day: day("card_due_day")?,
+        limit: parse_amount("card_limit", &selected_currency("card_account"))?,
+        pay_from: Some(get_element_by_id!("card_pay_from", HtmlSelectElement).value())
+            .filter(|pay_from| !pay_from.is_empty()),
+    })
+}
+
+#[wasm_bindgen]
+pub async fn add_category() -> Result<(), JsValue> {
+    let name = get_element_by_id!("category_name", HtmlInputElement).value();
+    let category = Category { name };
+    if let Err(e) = category.validate() {
+        focus_field("category_name");
+        return Err(alert_error(e.into()));
+    }
+    client::add_category(category).await.map_err(alert_error)?;
+    init_categories().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_rate() -> Result<(), JsValue> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("rate_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let rate = ExchangeRate {
+        base: value("base").to_uppercase(),
+        quote: value("quote").to_uppercase(),
+        date: value("date"),
+        rate: parse_decimal(&value("value"), RATE_DIGITS)
+            .map_err(ClientError::from)
+            .map_err(alert_error)?,
+    };
+    if let Err(e) = rate.validate() {
+        focus_field(match e.field {
+            "base" => "rate_base",
+            "quote" => "rate_quote",
+            "date" => "rate_date",
+            _ => "rate_value",
+        });
+        return Err(alert_error(e.into()));
+    }
+    client::add_rate(rate).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn import_rates() -> Result<(), JsValue> {
+    let csv = get_element_by_id!("rates_csv", HtmlTextAreaElement).value();
+    let count = client::import_rates(csv).await.map_err(alert_error)?;
+    alert(&format!("Imported {} rates", count));
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_security() -> Result<(), JsValue> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("security_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let security = Security {
+        symbol: value("symbol").to_uppercase(),
+        name: value("name"),
+        currency: value("currency").to_uppercase(),
+    };
+    if let Err(e) = security.validate() {
+        focus_field(&format!("security_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::add_security(security).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_trade() -> Result<(), JsValue> {
+    let trade = make_trade().map_err(alert_error)?;
+    if let Err(e) = trade.validate(Date::now() as i64) {
+        focus_field(&format!("trade_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::add_trade(trade).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+fn make_trade() -> Result<Trade, ClientError> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("trade_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let side = match get_element_by_id!("trade_side", HtmlSelectElement)
+        .value()
+        .as_str()
+    {
+        "sell" => TradeSide::Sell,
+        _ => TradeSide::Buy,
+    };
+
+    Ok(Trade {
+        account: get_element_by_id!("trade_account", HtmlSelectElement).value(),
+        symbol: value("symbol").to_uppercase(),
+        side,
+        quantity: parse_decimal(&value("quantity"), QUANTITY_DIGITS)?,
+        amount: parse_amount("trade_amount", &selected_currency("trade_account"))?,
+        datetime: parse_datetime("trade_datetime")?,
+        comment: value("comment"),
+    })
+}
+
+#[wasm_bindgen]
+pub async fn add_price() -> Result<(), JsValue> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("price_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let price = Price {
+        symbol: value("symbol").to_uppercase(),
+        date: value("date"),
+        price: parse_decimal(&value("price"), PRICE_DIGITS)
+            .map_err(ClientError::from)
+            .map_err(alert_error)?,
+    };
+    if let Err(e) = price.validate() {
+        focus_field(&format!("price_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::add_price(price).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn update_investments() -> Result<(), JsValue> {
+    let holdings = client::investments().await.map_err(alert_error)?;
+    let html = holdings
+        .iter()
+        .map(|holding| {
+            let money = |minor: i64| Money::new(minor, &holding.currency).decimal();
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{} {}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&holding.account),
+                escape_html(&holding.symbol),
+                format_decimal(holding.quantity, QUANTITY_DIGITS),
+                money(holding.cost),
+                match &holding.price_date {
+                    Some(date) => format!("{} ({})", money(holding.value), date),
+                    None => format!("{} (at cost)", money(holding.value)),
+                },
+                holding.unrealised.map_or_else(|| "-".to_owned(), money),
+                money(holding.realised)
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("investment_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn update_installments() -> Result<(), JsValue> {
+    let schedules = client::installments().await.map_err(alert_error)?;
+    let html = schedules
+        .iter()
+        .map(|schedule| {
+            let money = |minor: i64| Money::new(minor, &schedule.currency).decimal();
+            let next = schedule
+                .installments
+                .iter()
+                .find(|installment| !installment.paid);
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{} of {}</td>
+                    </tr>
+            "#,
+                escape_html(&schedule.plan.name),
+                escape_html(&schedule.plan.account),
+                match next {
+                    Some(installment) => format!(
+                        "{} on {}",
+                        money(installment.principal + installment.interest + installment.fee),
+                        installment.due_date
+                    ),
+                    None => "Repaid".to_owned(),
+                },
+                money(schedule.remaining),
+                schedule.plan.paid,
+                schedule.plan.term
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("installment_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_installment_plan() -> Result<(), JsValue> {
+    let plan = make_installment_plan().map_err(alert_error)?;
+    if let Err(e) = plan.validate() {
+        focus_field(&format!("plan_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::add_installment_plan(plan)
+        .await
+        .map_err(alert_error)?;
+    update_installments().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+fn make_installment_plan() -> Result<InstallmentPlan, ClientError> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("plan_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let currency = selected_currency("plan_account");
+    let optional = |field: &str| Some(value(field)).filter(|text| !text.is_empty());
+
+    Ok(InstallmentPlan {
+        name: value("name"),
+        account: get_element_by_id!("plan_account", HtmlSelectElement).value(),
+        from: get_element_by_id!("plan_from", HtmlSelectElement).value(),
+        cost_account: optional("cost_account"),
+        principal: parse_amount("plan_principal", &currency)?,
+        annual_rate: optional("annual_rate").map_or(Ok(0.0), |rate| rate.parse())?,
+        fee: match optional("fee") {
+            Some(fee) => Money::parse(&fee, &currency)?.minor(),
+            None => 0,
+        },
+        term: value("term").parse()?,
+        first_due: value("first_due"),
+        paid: optional("paid").map_or(Ok(0), |paid| paid.parse())?,
+    })
+}
+
+#[wasm_bindgen]
+pub async fn repay_installments() -> Result<(), JsValue> {
+    let count = client::repay_installments().await.map_err(alert_error)?;
+    update_installments().await?;
+    alert(&format!("Recorded {} repayments", count));
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn update_shared() -> Result<(), JsValue> {
+    let balances = client::shared_balances().await.map_err(alert_error)?;
+    let html = balances
+        .iter()
+        .map(|balance| {
+            let money = |minor: i64| Money::new(minor, &balance.currency).decimal();
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&balance.member),
+                money(balance.paid),
+                money(balance.owed),
+                money(balance.balance)
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("shared_content", HtmlElement).set_inner_html(&html);
+
+    let settlements = client::settle_up().await.map_err(alert_error)?;
+    let html = settlements
+        .iter()
+        .map(|settlement| {
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&settlement.from),
+                escape_html(&settlement.to),
+                Money::new(settlement.amount, &settlement.currency).decimal()
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("settlement_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn share_operation() -> Result<(), JsValue> {
+    let operation = parse_id("share_operation").map_err(alert_error)?;
+    let expense = make_shared_expense().map_err(|e| {
+        focus_field("share_shares");
+        alert_error(e)
+    })?;
+    if let Err(e) = expense.validate() {
+        focus_field(&format!("share_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::share_operation(operation, expense)
+        .await
+        .map_err(alert_error)?;
+    update_shared().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+/// Reads shares written as `member:share`, separated by commas. Percentages may have two
+/// decimal places.
+fn make_shared_expense() -> Result<SharedExpense, ClientError> {
+    let method: ShareMethod = get_element_by_id!("share_method", HtmlSelectElement)
+        .value()
+        .parse()
+        .map_err(ClientError::InputError)?;
+    let shares = get_element_by_id!("share_shares", HtmlInputElement)
+        .value()
+        .split(',')
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| {
+            let (member, share) = part.rsplit_once(':').ok_or_else(|| {
+                ClientError::InputError(format!("{} is not member:share.", part.trim()))
+            })?;
+            let share = match method {
+                ShareMethod::Shares => share.trim().parse()?,
+                ShareMethod::Percent => parse_decimal(share, 2)?,
+            };
+            Ok(MemberShare {
+                member: member.trim().to_owned(),
+                share,
+            })
+        })
+        .collect::<Result<_, ClientError>>()?;
+
+    Ok(SharedExpense {
+        paid_by: get_element_by_id!("share_paid_by", HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned(),
+        method,
+        shares,
+    })
+}
+
+#[wasm_bindgen]
+pub async fn update_claims() -> Result<(), JsValue> {
+    let claims = client::claims().await.map_err(alert_error)?;
+    let timezone = selected_timezone();
+    let html = claims
+        .iter()
+        .map(|claim| {
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                claim.operation,
+                Timestamp::from_millis(claim.datetime)
+                    .local_date(timezone)
+                    .format("%Y-%m-%d"),
+                escape_html(&claim.to),
+                escape_html(&claim.comment),
+                Money::new(claim.amount, &claim.currency).decimal(),
+                claim.status.as_str()
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("claim_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn set_claim() -> Result<(), JsValue> {
+    let operation = parse_id("claim_operation").map_err(alert_error)?;
+    let status: ClaimStatus = get_element_by_id!("claim_status", HtmlSelectElement)
+        .value()
+        .parse()
+        .map_err(|e| alert_error(ClientError::InputError(e)))?;
+    let claim = Claim {
+        status,
+        reimbursement: None,
+    };
+    client::set_claim(operation, claim)
+        .await
+        .map_err(alert_error)?;
+    update_claims().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn add_reimbursement() -> Result<(), JsValue> {
+    let reimbursement = make_reimbursement().map_err(alert_error)?;
+    if let Err(e) = reimbursement.validate() {
+        focus_field(&format!("reimbursement_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::add_reimbursement(reimbursement)
+        .await
+        .map_err(alert_error)?;
+    update_claims().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+/// Reads the expenses a reimbursement paid back, as operation numbers separated by commas.
+fn make_reimbursement() -> Result<Reimbursement, ClientError> {
+    let expenses = get_element_by_id!("reimbursement_expenses", HtmlInputElement)
+        .value()
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| {
+            id.trim().parse().map_err(|_| {
+                focus_field("reimbursement_expenses");
+                ClientError::InputError(format!("{} is not an operation number.", id.trim()))
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Reimbursement {
+        operation: parse_id("reimbursement_operation")?,
+        expenses,
+    })
+}
+
+#[wasm_bindgen]
+pub async fn update_forecast() -> Result<(), JsValue> {
+    let months_text = get_element_by_id!("forecast_months", HtmlInputElement).value();
+    let months: u32 = months_text.trim().parse().map_err(|_| {
+        focus_field("forecast_months");
+        alert_error(ClientError::InputError(format!(
+            "{} is not a number of months.",
+            months_text.trim()
+        )))
+    })?;
+    let history = get_element_by_id!("forecast_history", HtmlInputElement).checked();
+    let forecast = client::forecast(months, history)
+        .await
+        .map_err(alert_error)?;
+    let html = forecast
+        .accounts
+        .iter()
+        .map(|account| {
+            let money = |amount: i64| Money::new(amount, &account.currency).decimal();
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&account.account),
+                money(account.lowest),
+                account.lowest_date,
+                account.below_zero_date.as_deref().unwrap_or("Never"),
+                money(account.balances.last().copied().unwrap_or_default())
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("forecast_content", HtmlElement).set_inner_html(&html);
+    let html = forecast
+        .spending
+        .iter()
+        .map(|spending| {
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&spending.account),
+                escape_html(&spending.category),
+                Money::new(spending.monthly, &spending.currency).decimal()
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("forecast_spending", HtmlElement).set_inner_html(&html);
+
+    update_recurring().await
+}
+
+#[wasm_bindgen]
+pub async fn update_recurring() -> Result<(), JsValue> {
+    let recurring = client::recurring().await.map_err(alert_error)?;
+    let accounts = client::accounts().await.map_err(alert_error)?;
+    let html = recurring
+        .iter()
+        .map(|item| {
+            let currency = accounts
+                .iter()
+                .find(|account| account.name == item.from)
+                .map_or(DEFAULT_CURRENCY, |account| &account.currency);
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&item.name),
+                escape_html(&item.from),
+                escape_html(&item.to),
+                Money::new(item.amount, currency).decimal(),
+                item.frequency.as_str(),
+                item.start,
+                item.end.as_deref().unwrap_or(""),
+                escape_html(item.category.as_deref().unwrap_or(""))
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("recurring_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn set_recurring() -> Result<(), JsValue> {
+    let item = make_recurring().map_err(alert_error)?;
+    if let Err(e) = item.validate() {
+        focus_field(&format!("recurring_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::set_recurring(item).await.map_err(alert_error)?;
+    update_recurring().await?;
     alert("Success");
 
     Ok(())
 }
 
+fn make_recurring() -> Result<RecurringOperation, ClientError> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("recurring_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+    let optional = |field: &str| Some(value(field)).filter(|value| !value.is_empty());
+    let frequency = get_element_by_id!("recurring_frequency", HtmlSelectElement).value();
+
+    Ok(RecurringOperation {
+        name: value("name"),
+        from: get_element_by_id!("recurring_from", HtmlSelectElement).value(),
+        to: get_element_by_id!("recurring_to", HtmlSelectElement).value(),
+        amount: parse_amount("recurring_amount", &selected_currency("recurring_from"))?,
+        frequency: frequency
+            .parse::<Frequency>()
+            .map_err(ClientError::InputError)?,
+        start: value("start"),
+        end: optional("end"),
+        category: optional("category"),
+    })
+}
+
+#[wasm_bindgen]
+pub async fn update_goals() -> Result<(), JsValue> {
+    let goals = client::goals().await.map_err(alert_error)?;
+    let html = goals
+        .iter()
+        .map(|progress| {
+            let money = |amount: i64| Money::new(amount, &progress.currency).decimal();
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{} of {}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&progress.goal.name),
+                escape_html(&progress.goal.account),
+                money(progress.saved),
+                money(progress.goal.target),
+                progress.goal.target_date,
+                money(progress.monthly_required),
+                money(progress.monthly_contribution),
+                progress.projected_date.as_deref().unwrap_or("Never"),
+                if progress.on_track { "Yes" } else { "No" }
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("goal_content", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn set_goal() -> Result<(), JsValue> {
+    let goal = make_goal().map_err(alert_error)?;
+    if let Err(e) = goal.validate() {
+        focus_field(&format!("goal_{}", e.field));
+        return Err(alert_error(e.into()));
+    }
+    client::set_goal(goal).await.map_err(alert_error)?;
+    update_goals().await?;
+    alert("Success");
+
+    Ok(())
+}
+
+fn make_goal() -> Result<Goal, ClientError> {
+    let value = |field: &str| {
+        get_element_by_id!(&format!("goal_{}", field), HtmlInputElement)
+            .value()
+            .trim()
+            .to_owned()
+    };
+
+    Ok(Goal {
+        name: value("name"),
+        account: get_element_by_id!("goal_account", HtmlSelectElement).value(),
+        target: parse_amount("goal_target", &selected_currency("goal_account"))?,
+        target_date: value("target_date"),
+    })
+}
+
+/// Reads the operation number typed into the input `id`.
+fn parse_id(id: &str) -> Result<i64, ClientError> {
+    let text = get_element_by_id!(id, HtmlInputElement).value();
+    text.trim().parse().map_err(|_| {
+        focus_field(id);
+        ClientError::InputError(format!("{} is not an operation number.", text.trim()))
+    })
+}
+
 #[wasm_bindgen]
 pub async fn login() -> Result<(), JsValue> {
+    let user = get_element_by_id!("user", HtmlInputElement).value();
     let code = get_element_by_id!("code", HtmlInputElement).value();
-    match client::login(code).await {
+    let totp_group = get_by_id("totp_group");
+    let totp = match totp_group.has_attribute("hidden") {
+        true => None,
+        false => Some(get_element_by_id!("totp", HtmlInputElement).value()),
+    };
+    match client::login(user, code, totp).await {
         Ok(_) => {
             alert("Success");
             redirect("/")?;
         }
+        Err(ClientError::SecondFactorRequired) => {
+            totp_group.remove_attribute("hidden")?;
+            get_element_by_id!("totp", HtmlInputElement).focus()?;
+        }
         Err(e) => {
-            alert(&format!("{:?}", e));
+            alert(&e.to_string());
         }
     }
 
     Ok(())
 }
 
-async fn refresh_content(accounts: &[Account]) -> Result<(), ClientError> {
-    let html = accounts
+#[wasm_bindgen]
+pub async fn enrol_totp() -> Result<(), JsValue> {
+    let enrolment = client::enrol_totp().await.map_err(alert_error)?;
+    let html = format!(
+        r#"
+            <p>Add this URI to your authenticator app, then confirm with a code.</p>
+            <pre>{}</pre>
+            <p>Recovery codes, each usable once:</p>
+            <pre>{}</pre>
+        "#,
+        escape_html(&enrolment.uri),
+        escape_html(&enrolment.recovery_codes.join("\n"))
+    );
+    get_element_by_id!("totp_enrolment", HtmlElement).set_inner_html(&html);
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn confirm_totp() -> Result<(), JsValue> {
+    let code = get_element_by_id!("totp_code", HtmlInputElement).value();
+    client::confirm_totp(code).await.map_err(alert_error)?;
+    alert("Success");
+
+    Ok(())
+}
+
+async fn refresh_content(report: &BalanceReport) -> Result<(), ClientError> {
+    let html = report
+        .accounts
         .iter()
+        .chain(&report.holdings)
         .enumerate()
         .map(|(idx, account)| {
             format!(
@@ -143,21 +1131,92 @@ async fn refresh_content(accounts: &[Account]) -> Result<(), ClientError> {
                     <tr>
                         <td scope="row">{}</td>
                         <td>{}</td>
-                        <td>{:.2}</td>
+                        <td>{}</td>
+                        <td>{}</td>
                     </tr>
             "#,
                 idx + 1,
-                account.name,
-                (account.balance as f64) / 100.0
+                escape_html(&account.name),
+                Money::new(account.balance, &account.currency),
+                match account.converted {
+                    Some(converted) => Money::new(converted, &report.base).decimal(),
+                    None => "-".to_owned(),
+                }
             )
         })
         .fold("".to_owned(), |x, y| x + &y);
 
     get_element_by_id!("balance_content", HtmlElement).set_inner_html(&html);
+    let mut total = Money::new(report.total, &report.base).to_string();
+    if !report.unconverted.is_empty() {
+        total += &format!(" (no rate for {})", report.unconverted.join(", "));
+    }
+    get_element_by_id!("balance_total", HtmlElement).set_inner_text(&total);
+
+    Ok(())
+}
+
+async fn refresh_cards() -> Result<(), ClientError> {
+    let html = client::cards()
+        .await?
+        .iter()
+        .map(|card| {
+            let money = |minor: i64| Money::new(minor, &card.currency).decimal();
+            format!(
+                r#"
+                    <tr>
+                        <td scope="row">{}</td>
+                        <td>{}</td>
+                        <td>{} of {}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                        <td>{}</td>
+                    </tr>
+            "#,
+                escape_html(&card.account),
+                card.due_date,
+                money(card.remaining),
+                money(card.statement_balance),
+                money(card.minimum_payment),
+                money(card.owed),
+                money(card.available_credit)
+            )
+        })
+        .fold("".to_owned(), |x, y| x + &y);
+    get_element_by_id!("card_content", HtmlElement).set_inner_html(&html);
 
     Ok(())
 }
 
+/// Makes names and other text from the ledger safe to put into markup, as any editor can set
+/// them.
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// Tells the user why an action failed instead of leaving it in the console.
+fn alert_error(e: ClientError) -> JsValue {
+    alert(&e.to_string());
+    e.into()
+}
+
+fn focus_field(id: &str) {
+    if let Some(element) = get_by_id(id).dyn_ref::<HtmlElement>() {
+        let _ = element.focus();
+    }
+}
+
 fn get_by_id(id: &str) -> Element {
     fn get_by_id_option(id: &str) -> Option<Element> {
         let document: Document = web_sys::window()?.document()?;
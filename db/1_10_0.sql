@@ -0,0 +1,9 @@
+-- Credit cards. An account with a row here is billed monthly: a statement covers everything up
+-- to the end of STATEMENT_DAY and is due on the next DUE_DAY. CREDIT_LIMIT is in minor units.
+CREATE TABLE CREDIT_CARD
+(
+    ACCOUNT       INTEGER PRIMARY KEY REFERENCES ACCOUNT (ID),
+    STATEMENT_DAY INTEGER NOT NULL,
+    DUE_DAY       INTEGER NOT NULL,
+    CREDIT_LIMIT  INTEGER NOT NULL
+);
@@ -0,0 +1,19 @@
+-- Installment plans. PRINCIPAL was borrowed on ACCOUNT and is repaid from FROM_ACCOUNT in TERM
+-- monthly repayments starting on FIRST_DUE, with interest at ANNUAL_RATE percent and FEE per
+-- repayment going to COST_ACCOUNT. PAID counts the repayments recorded so far.
+CREATE TABLE INSTALLMENT_PLAN
+(
+    ID           INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER       INTEGER NOT NULL REFERENCES LEDGER (ID),
+    NAME         TEXT    NOT NULL,
+    ACCOUNT      INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    FROM_ACCOUNT INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    COST_ACCOUNT INTEGER REFERENCES ACCOUNT (ID),
+    PRINCIPAL    INTEGER NOT NULL,
+    ANNUAL_RATE  REAL    NOT NULL,
+    FEE          INTEGER NOT NULL,
+    TERM         INTEGER NOT NULL,
+    FIRST_DUE    TEXT    NOT NULL,
+    PAID         INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (LEDGER, NAME)
+);
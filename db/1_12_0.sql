@@ -0,0 +1,19 @@
+-- Shared expenses. An operation with a row in SHARED_EXPENSE was fronted by PAID_BY for the
+-- household members listed in EXPENSE_SHARE, who each owe a part of its AMOUNT in proportion
+-- to their SHARE. METHOD is how the shares were entered: 'shares' or 'percent'.
+CREATE TABLE SHARED_EXPENSE
+(
+    OPERATION INTEGER PRIMARY KEY REFERENCES OPERATION (ID),
+    PAID_BY   TEXT NOT NULL,
+    METHOD    TEXT NOT NULL
+);
+
+CREATE TABLE EXPENSE_SHARE
+(
+    ID        INTEGER PRIMARY KEY AUTOINCREMENT,
+    OPERATION INTEGER NOT NULL REFERENCES SHARED_EXPENSE (OPERATION),
+    MEMBER    TEXT    NOT NULL,
+    SHARE     INTEGER NOT NULL
+);
+
+CREATE INDEX EXPENSE_SHARE_OPERATION ON EXPENSE_SHARE (OPERATION);
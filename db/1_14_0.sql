@@ -0,0 +1,11 @@
+-- Savings goals: putting TARGET aside in ACCOUNT by TARGET_DATE.
+CREATE TABLE GOAL
+(
+    ID          INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER      INTEGER NOT NULL REFERENCES LEDGER (ID),
+    NAME        TEXT    NOT NULL,
+    ACCOUNT     INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    TARGET      INTEGER NOT NULL,
+    TARGET_DATE TEXT    NOT NULL,
+    UNIQUE (LEDGER, NAME)
+);
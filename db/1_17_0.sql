@@ -0,0 +1,16 @@
+-- Transfers expected to repeat, like rent or a salary: AMOUNT from FROM_ACCOUNT to TO_ACCOUNT
+-- on START_DATE and every week, month or year (FREQUENCY) after it until END_DATE, if set.
+CREATE TABLE RECURRING_OPERATION
+(
+    ID           INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER       INTEGER NOT NULL REFERENCES LEDGER (ID),
+    NAME         TEXT    NOT NULL,
+    FROM_ACCOUNT INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    TO_ACCOUNT   INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    CATEGORY     INTEGER REFERENCES CATEGORY (ID),
+    AMOUNT       INTEGER NOT NULL,
+    FREQUENCY    TEXT    NOT NULL,
+    START_DATE   TEXT    NOT NULL,
+    END_DATE     TEXT,
+    UNIQUE (LEDGER, NAME)
+);
@@ -0,0 +1,4 @@
+-- IMPORT_KEY identifies the statement transaction an imported operation records, so importing
+-- the same statement again, or one that overlaps it, doesn't record it twice.
+ALTER TABLE OPERATION ADD COLUMN IMPORT_KEY TEXT;
+CREATE UNIQUE INDEX OPERATION_IMPORT_KEY ON OPERATION (LEDGER, IMPORT_KEY);
@@ -0,0 +1,4 @@
+-- USER.TOKEN holds an argon2 hash of the password, in PHC format, once TOKEN_HASHED is set.
+-- SQL can't compute those, so the tokens stored in plain text before this are hashed by the
+-- server right after it migrates, see password::hash_stored_tokens.
+ALTER TABLE USER ADD COLUMN TOKEN_HASHED INTEGER NOT NULL DEFAULT 0;
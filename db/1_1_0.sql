@@ -0,0 +1,17 @@
+CREATE TABLE TOTP
+(
+    ID        INTEGER PRIMARY KEY AUTOINCREMENT,
+    SECRET    TEXT,
+    CONFIRMED INTEGER,
+    LAST_STEP INTEGER
+);
+
+CREATE TABLE RECOVERY_CODE
+(
+    ID      INTEGER PRIMARY KEY AUTOINCREMENT,
+    TOTP    INTEGER,
+    HASH    TEXT,
+    USED_AT DATETIME
+);
+
+INSERT INTO DB_VERSION (VERSION, DEPLOY_AT) VALUES ('1_1_0', STRFTIME('%s','now'));
@@ -0,0 +1,17 @@
+-- RATE becomes an integer count of 10^-8, entities::RATE_DIGITS, so rates are exact. SQLite
+-- can't change a column's type, so the table is rebuilt.
+CREATE TABLE EXCHANGE_RATE_NEW
+(
+    ID     INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER INTEGER NOT NULL REFERENCES LEDGER (ID),
+    BASE   TEXT    NOT NULL,
+    QUOTE  TEXT    NOT NULL,
+    DATE   TEXT    NOT NULL,
+    RATE   INTEGER NOT NULL,
+    UNIQUE (LEDGER, BASE, QUOTE, DATE)
+);
+INSERT INTO EXCHANGE_RATE_NEW (ID, LEDGER, BASE, QUOTE, DATE, RATE)
+SELECT ID, LEDGER, BASE, QUOTE, DATE, CAST(ROUND(RATE * 100000000) AS INTEGER)
+FROM EXCHANGE_RATE;
+DROP TABLE EXCHANGE_RATE;
+ALTER TABLE EXCHANGE_RATE_NEW RENAME TO EXCHANGE_RATE;
@@ -0,0 +1,15 @@
+-- PRICE becomes an integer count of 10^-8, entities::PRICE_DIGITS, so prices are exact. SQLite
+-- can't change a column's type, so the table is rebuilt.
+CREATE TABLE PRICE_NEW
+(
+    ID       INTEGER PRIMARY KEY AUTOINCREMENT,
+    SECURITY INTEGER NOT NULL REFERENCES SECURITY (ID),
+    DATE     TEXT    NOT NULL,
+    PRICE    INTEGER NOT NULL,
+    UNIQUE (SECURITY, DATE)
+);
+INSERT INTO PRICE_NEW (ID, SECURITY, DATE, PRICE)
+SELECT ID, SECURITY, DATE, CAST(ROUND(PRICE * 100000000) AS INTEGER)
+FROM PRICE;
+DROP TABLE PRICE;
+ALTER TABLE PRICE_NEW RENAME TO PRICE;
@@ -0,0 +1,69 @@
+CREATE TABLE USER
+(
+    ID    INTEGER PRIMARY KEY AUTOINCREMENT,
+    NAME  TEXT NOT NULL UNIQUE,
+    TOKEN TEXT NOT NULL,
+    ADMIN INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE LEDGER
+(
+    ID   INTEGER PRIMARY KEY AUTOINCREMENT,
+    NAME TEXT NOT NULL
+);
+
+CREATE TABLE LEDGER_MEMBER
+(
+    LEDGER INTEGER NOT NULL REFERENCES LEDGER (ID),
+    USER   INTEGER NOT NULL REFERENCES USER (ID),
+    ROLE   TEXT    NOT NULL,
+    PRIMARY KEY (LEDGER, USER)
+);
+
+-- The single shared secret becomes the administrator, owning everything that exists so far.
+INSERT INTO USER (NAME, TOKEN, ADMIN) SELECT 'admin', TOKEN, 1 FROM SECRET LIMIT 1;
+INSERT INTO LEDGER (NAME) VALUES ('Default');
+INSERT INTO LEDGER_MEMBER (LEDGER, USER, ROLE) SELECT 1, ID, 'owner' FROM USER;
+DROP TABLE SECRET;
+
+ALTER TABLE TOTP ADD COLUMN USER INTEGER REFERENCES USER (ID);
+UPDATE TOTP SET USER = (SELECT ID FROM USER WHERE NAME = 'admin');
+
+-- Account and category names are now unique per ledger rather than globally.
+CREATE TABLE ACCOUNT_NEW
+(
+    ID      INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER  INTEGER NOT NULL REFERENCES LEDGER (ID),
+    NAME    TEXT    NOT NULL,
+    BALANCE INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (LEDGER, NAME)
+);
+INSERT INTO ACCOUNT_NEW (ID, LEDGER, NAME, BALANCE) SELECT ID, 1, NAME, BALANCE FROM ACCOUNT;
+DROP TABLE ACCOUNT;
+ALTER TABLE ACCOUNT_NEW RENAME TO ACCOUNT;
+
+CREATE TABLE CATEGORY_NEW
+(
+    ID     INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER INTEGER NOT NULL REFERENCES LEDGER (ID),
+    NAME   TEXT    NOT NULL,
+    UNIQUE (LEDGER, NAME)
+);
+INSERT INTO CATEGORY_NEW (ID, LEDGER, NAME) SELECT ID, 1, NAME FROM CATEGORY;
+DROP TABLE CATEGORY;
+ALTER TABLE CATEGORY_NEW RENAME TO CATEGORY;
+
+CREATE TABLE OPERATION
+(
+    ID           INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER       INTEGER NOT NULL REFERENCES LEDGER (ID),
+    FROM_ACCOUNT INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    TO_ACCOUNT   INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    COMMENT      TEXT,
+    AMOUNT       INTEGER NOT NULL,
+    DATETIME     DATETIME
+);
+
+CREATE INDEX OPERATION_LEDGER ON OPERATION (LEDGER);
+
+INSERT INTO DB_VERSION (VERSION, DEPLOY_AT) VALUES ('1_2_0', STRFTIME('%s','now'));
@@ -0,0 +1,60 @@
+-- Full-text index over what people remember an operation by: its comment, where imported
+-- transaction descriptions end up, and the accounts it moved money between. The row id is the
+-- operation's.
+CREATE VIRTUAL TABLE OPERATION_SEARCH USING fts5
+(
+    COMMENT,
+    COUNTERPARTIES,
+    LEDGER UNINDEXED
+);
+
+INSERT INTO OPERATION_SEARCH (ROWID, COMMENT, COUNTERPARTIES, LEDGER)
+SELECT O.ID, COALESCE(O.COMMENT, ''), F.NAME || ' ' || T.NAME, O.LEDGER
+FROM OPERATION O
+         JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+         JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT;
+
+CREATE TRIGGER OPERATION_SEARCH_INSERT
+    AFTER INSERT
+    ON OPERATION
+BEGIN
+    INSERT INTO OPERATION_SEARCH (ROWID, COMMENT, COUNTERPARTIES, LEDGER)
+    SELECT NEW.ID, COALESCE(NEW.COMMENT, ''), F.NAME || ' ' || T.NAME, NEW.LEDGER
+    FROM ACCOUNT F,
+         ACCOUNT T
+    WHERE F.ID = NEW.FROM_ACCOUNT
+      AND T.ID = NEW.TO_ACCOUNT;
+END;
+
+CREATE TRIGGER OPERATION_SEARCH_UPDATE
+    AFTER UPDATE
+    ON OPERATION
+BEGIN
+    DELETE FROM OPERATION_SEARCH WHERE ROWID = OLD.ID;
+    INSERT INTO OPERATION_SEARCH (ROWID, COMMENT, COUNTERPARTIES, LEDGER)
+    SELECT NEW.ID, COALESCE(NEW.COMMENT, ''), F.NAME || ' ' || T.NAME, NEW.LEDGER
+    FROM ACCOUNT F,
+         ACCOUNT T
+    WHERE F.ID = NEW.FROM_ACCOUNT
+      AND T.ID = NEW.TO_ACCOUNT;
+END;
+
+CREATE TRIGGER OPERATION_SEARCH_DELETE
+    AFTER DELETE
+    ON OPERATION
+BEGIN
+    DELETE FROM OPERATION_SEARCH WHERE ROWID = OLD.ID;
+END;
+
+CREATE TRIGGER OPERATION_SEARCH_ACCOUNT
+    AFTER UPDATE OF NAME
+    ON ACCOUNT
+BEGIN
+    UPDATE OPERATION_SEARCH
+    SET COUNTERPARTIES = (SELECT F.NAME || ' ' || T.NAME
+                          FROM OPERATION O
+                                   JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                                   JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+                          WHERE O.ID = OPERATION_SEARCH.ROWID)
+    WHERE ROWID IN (SELECT ID FROM OPERATION WHERE FROM_ACCOUNT = NEW.ID OR TO_ACCOUNT = NEW.ID);
+END;
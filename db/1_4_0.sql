@@ -0,0 +1,22 @@
+-- Files attached to operations. Content is addressed by its SHA-256 so a file attached twice is
+-- stored once, in ATTACHMENT_CONTENT unless the server keeps attachments in a directory.
+CREATE TABLE ATTACHMENT_CONTENT
+(
+    HASH    TEXT PRIMARY KEY,
+    CONTENT BLOB NOT NULL
+);
+
+CREATE TABLE ATTACHMENT
+(
+    ID           INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER       INTEGER NOT NULL REFERENCES LEDGER (ID),
+    OPERATION    INTEGER NOT NULL REFERENCES OPERATION (ID),
+    NAME         TEXT    NOT NULL,
+    CONTENT_TYPE TEXT    NOT NULL,
+    SIZE         INTEGER NOT NULL,
+    HASH         TEXT    NOT NULL,
+    CREATED_AT   INTEGER NOT NULL
+);
+
+CREATE INDEX ATTACHMENT_OPERATION ON ATTACHMENT (OPERATION);
+CREATE INDEX ATTACHMENT_HASH ON ATTACHMENT (HASH);
@@ -0,0 +1,13 @@
+-- The parts an operation's amount is split into. An operation without legs pays its whole
+-- amount to TO_ACCOUNT; a leg without an account pays into TO_ACCOUNT as well.
+CREATE TABLE OPERATION_LEG
+(
+    ID        INTEGER PRIMARY KEY AUTOINCREMENT,
+    OPERATION INTEGER NOT NULL REFERENCES OPERATION (ID),
+    ACCOUNT   INTEGER REFERENCES ACCOUNT (ID),
+    CATEGORY  INTEGER REFERENCES CATEGORY (ID),
+    AMOUNT    INTEGER NOT NULL,
+    MEMO      TEXT    NOT NULL DEFAULT ''
+);
+
+CREATE INDEX OPERATION_LEG_OPERATION ON OPERATION_LEG (OPERATION);
@@ -0,0 +1,36 @@
+-- Investments. A security is bought and sold in QUANTITY units of 10^-4 through an account in
+-- its currency, whose BALANCE pays AMOUNT for a buy and receives it for a sale. Lots and gains
+-- are worked out from the trades, oldest first. PRICE is what one unit was worth on DATE.
+CREATE TABLE SECURITY
+(
+    ID       INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER   INTEGER NOT NULL REFERENCES LEDGER (ID),
+    SYMBOL   TEXT    NOT NULL,
+    NAME     TEXT    NOT NULL,
+    CURRENCY TEXT    NOT NULL,
+    UNIQUE (LEDGER, SYMBOL)
+);
+
+CREATE TABLE TRADE
+(
+    ID       INTEGER PRIMARY KEY AUTOINCREMENT,
+    LEDGER   INTEGER NOT NULL REFERENCES LEDGER (ID),
+    ACCOUNT  INTEGER NOT NULL REFERENCES ACCOUNT (ID),
+    SECURITY INTEGER NOT NULL REFERENCES SECURITY (ID),
+    SIDE     TEXT    NOT NULL,
+    QUANTITY INTEGER NOT NULL,
+    AMOUNT   INTEGER NOT NULL,
+    DATETIME INTEGER NOT NULL,
+    COMMENT  TEXT    NOT NULL DEFAULT ''
+);
+
+CREATE INDEX TRADE_LEDGER ON TRADE (LEDGER);
+
+CREATE TABLE PRICE
+(
+    ID       INTEGER PRIMARY KEY AUTOINCREMENT,
+    SECURITY INTEGER NOT NULL REFERENCES SECURITY (ID),
+    DATE     TEXT    NOT NULL,
+    PRICE    REAL    NOT NULL,
+    UNIQUE (SECURITY, DATE)
+);
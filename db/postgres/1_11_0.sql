@@ -0,0 +1,19 @@
+-- Installment plans. PRINCIPAL was borrowed on ACCOUNT and is repaid from FROM_ACCOUNT in TERM
+-- monthly repayments starting on FIRST_DUE, with interest at ANNUAL_RATE percent and FEE per
+-- repayment going to COST_ACCOUNT. PAID counts the repayments recorded so far.
+CREATE TABLE INSTALLMENT_PLAN
+(
+    ID           BIGSERIAL PRIMARY KEY,
+    LEDGER       BIGINT           NOT NULL REFERENCES LEDGER (ID),
+    NAME         TEXT             NOT NULL,
+    ACCOUNT      BIGINT           NOT NULL REFERENCES ACCOUNT (ID),
+    FROM_ACCOUNT BIGINT           NOT NULL REFERENCES ACCOUNT (ID),
+    COST_ACCOUNT BIGINT REFERENCES ACCOUNT (ID),
+    PRINCIPAL    BIGINT           NOT NULL,
+    ANNUAL_RATE  DOUBLE PRECISION NOT NULL,
+    FEE          BIGINT           NOT NULL,
+    TERM         INTEGER          NOT NULL,
+    FIRST_DUE    TEXT             NOT NULL,
+    PAID         INTEGER          NOT NULL DEFAULT 0,
+    UNIQUE (LEDGER, NAME)
+);
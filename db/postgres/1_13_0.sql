@@ -0,0 +1,11 @@
+-- Reimbursable expenses. An operation with a row in CLAIM was paid personally and is to be
+-- claimed back. STATUS is 'pending', 'submitted' or 'paid', and a paid claim names the
+-- operation that paid it back in REIMBURSEMENT.
+CREATE TABLE CLAIM
+(
+    OPERATION     BIGINT PRIMARY KEY REFERENCES OPERATION (ID),
+    STATUS        TEXT NOT NULL,
+    REIMBURSEMENT BIGINT REFERENCES OPERATION (ID)
+);
+
+CREATE INDEX CLAIM_REIMBURSEMENT ON CLAIM (REIMBURSEMENT);
@@ -0,0 +1,11 @@
+-- Savings goals: putting TARGET aside in ACCOUNT by TARGET_DATE.
+CREATE TABLE GOAL
+(
+    ID          BIGSERIAL PRIMARY KEY,
+    LEDGER      BIGINT NOT NULL REFERENCES LEDGER (ID),
+    NAME        TEXT   NOT NULL,
+    ACCOUNT     BIGINT NOT NULL REFERENCES ACCOUNT (ID),
+    TARGET      BIGINT NOT NULL,
+    TARGET_DATE TEXT   NOT NULL,
+    UNIQUE (LEDGER, NAME)
+);
@@ -0,0 +1,2 @@
+-- The account a credit card's statements are paid from, for forecasting its payments.
+ALTER TABLE CREDIT_CARD ADD COLUMN PAY_FROM BIGINT REFERENCES ACCOUNT (ID);
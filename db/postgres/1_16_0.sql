@@ -0,0 +1,9 @@
+-- The migration log and the recovery code replay guard were still written in seconds. Like
+-- every other timestamp they're now milliseconds since the epoch in UTC.
+UPDATE MIGRATION
+SET APPLIED_AT = APPLIED_AT * 1000
+WHERE APPLIED_AT < 100000000000;
+
+UPDATE RECOVERY_CODE
+SET USED_AT = USED_AT * 1000
+WHERE USED_AT < 100000000000;
@@ -0,0 +1,3 @@
+-- RATE becomes an integer count of 10^-8, entities::RATE_DIGITS, so rates are exact.
+ALTER TABLE EXCHANGE_RATE
+    ALTER COLUMN RATE TYPE BIGINT USING ROUND(RATE * 100000000);
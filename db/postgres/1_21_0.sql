@@ -0,0 +1,3 @@
+-- PRICE becomes an integer count of 10^-8, entities::PRICE_DIGITS, so prices are exact.
+ALTER TABLE PRICE
+    ALTER COLUMN PRICE TYPE BIGINT USING ROUND(PRICE * 100000000);
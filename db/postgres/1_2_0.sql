@@ -0,0 +1,76 @@
+-- PostgreSQL starts from the schema SQLite reached in 1_2_0, minus the unused TRANSACTION
+-- table. Later versions are added to both directories with the same number. USER is reserved
+-- here, so it's always quoted.
+CREATE TABLE "USER"
+(
+    ID    BIGSERIAL PRIMARY KEY,
+    NAME  TEXT    NOT NULL UNIQUE,
+    TOKEN TEXT    NOT NULL,
+    ADMIN BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE LEDGER
+(
+    ID   BIGSERIAL PRIMARY KEY,
+    NAME TEXT NOT NULL
+);
+
+CREATE TABLE LEDGER_MEMBER
+(
+    LEDGER BIGINT NOT NULL REFERENCES LEDGER (ID),
+    "USER" BIGINT NOT NULL REFERENCES "USER" (ID),
+    ROLE   TEXT   NOT NULL,
+    PRIMARY KEY (LEDGER, "USER")
+);
+
+CREATE TABLE TOTP
+(
+    ID        BIGSERIAL PRIMARY KEY,
+    "USER"    BIGINT  NOT NULL REFERENCES "USER" (ID),
+    SECRET    TEXT    NOT NULL,
+    CONFIRMED BOOLEAN NOT NULL DEFAULT FALSE,
+    LAST_STEP BIGINT  NOT NULL DEFAULT 0
+);
+
+CREATE TABLE RECOVERY_CODE
+(
+    ID      BIGSERIAL PRIMARY KEY,
+    TOTP    BIGINT NOT NULL REFERENCES TOTP (ID),
+    HASH    TEXT   NOT NULL,
+    USED_AT BIGINT
+);
+
+CREATE TABLE ACCOUNT
+(
+    ID      BIGSERIAL PRIMARY KEY,
+    LEDGER  BIGINT NOT NULL REFERENCES LEDGER (ID),
+    NAME    TEXT   NOT NULL,
+    BALANCE BIGINT NOT NULL DEFAULT 0,
+    UNIQUE (LEDGER, NAME)
+);
+
+CREATE TABLE CATEGORY
+(
+    ID     BIGSERIAL PRIMARY KEY,
+    LEDGER BIGINT NOT NULL REFERENCES LEDGER (ID),
+    NAME   TEXT   NOT NULL,
+    UNIQUE (LEDGER, NAME)
+);
+
+CREATE TABLE OPERATION
+(
+    ID           BIGSERIAL PRIMARY KEY,
+    LEDGER       BIGINT NOT NULL REFERENCES LEDGER (ID),
+    FROM_ACCOUNT BIGINT NOT NULL REFERENCES ACCOUNT (ID),
+    TO_ACCOUNT   BIGINT NOT NULL REFERENCES ACCOUNT (ID),
+    COMMENT      TEXT,
+    AMOUNT       BIGINT NOT NULL,
+    DATETIME     BIGINT
+);
+
+CREATE INDEX OPERATION_LEDGER ON OPERATION (LEDGER);
+
+-- Same defaults as a fresh SQLite database: an administrator owning one ledger.
+INSERT INTO "USER" (NAME, TOKEN, ADMIN) VALUES ('admin', '123456', TRUE);
+INSERT INTO LEDGER (NAME) VALUES ('Default');
+INSERT INTO LEDGER_MEMBER (LEDGER, "USER", ROLE) VALUES (1, 1, 'owner');
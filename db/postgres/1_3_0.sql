@@ -0,0 +1,66 @@
+-- Full-text index over what people remember an operation by: its comment, where imported
+-- transaction descriptions end up, and the accounts it moved money between. Matches in the
+-- comment weigh more than ones in account names.
+CREATE TABLE OPERATION_SEARCH
+(
+    OPERATION      BIGINT PRIMARY KEY REFERENCES OPERATION (ID) ON DELETE CASCADE,
+    LEDGER         BIGINT   NOT NULL,
+    COMMENT        TEXT     NOT NULL,
+    COUNTERPARTIES TEXT     NOT NULL,
+    DOCUMENT       TSVECTOR NOT NULL
+);
+
+CREATE INDEX OPERATION_SEARCH_DOCUMENT ON OPERATION_SEARCH USING GIN (DOCUMENT);
+
+CREATE FUNCTION OPERATION_SEARCH_REFRESH(OPERATION_ID BIGINT) RETURNS VOID AS
+$$
+INSERT INTO OPERATION_SEARCH (OPERATION, LEDGER, COMMENT, COUNTERPARTIES, DOCUMENT)
+SELECT O.ID,
+       O.LEDGER,
+       COALESCE(O.COMMENT, ''),
+       F.NAME || ' ' || T.NAME,
+       SETWEIGHT(TO_TSVECTOR('simple', COALESCE(O.COMMENT, '')), 'A') ||
+       SETWEIGHT(TO_TSVECTOR('simple', F.NAME || ' ' || T.NAME), 'B')
+FROM OPERATION O
+         JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+         JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+WHERE O.ID = OPERATION_ID
+ON CONFLICT (OPERATION) DO UPDATE SET LEDGER         = EXCLUDED.LEDGER,
+                                      COMMENT        = EXCLUDED.COMMENT,
+                                      COUNTERPARTIES = EXCLUDED.COUNTERPARTIES,
+                                      DOCUMENT       = EXCLUDED.DOCUMENT;
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION OPERATION_SEARCH_OPERATION() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM OPERATION_SEARCH_REFRESH(NEW.ID);
+    RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE FUNCTION OPERATION_SEARCH_ACCOUNT() RETURNS TRIGGER AS
+$$
+BEGIN
+    PERFORM OPERATION_SEARCH_REFRESH(ID)
+    FROM OPERATION
+    WHERE FROM_ACCOUNT = NEW.ID
+       OR TO_ACCOUNT = NEW.ID;
+    RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+-- Deleting an operation removes its row through the foreign key.
+CREATE TRIGGER OPERATION_SEARCH_OPERATION
+    AFTER INSERT OR UPDATE
+    ON OPERATION
+    FOR EACH ROW
+EXECUTE PROCEDURE OPERATION_SEARCH_OPERATION();
+
+CREATE TRIGGER OPERATION_SEARCH_ACCOUNT
+    AFTER UPDATE OF NAME
+    ON ACCOUNT
+    FOR EACH ROW
+EXECUTE PROCEDURE OPERATION_SEARCH_ACCOUNT();
+
+SELECT OPERATION_SEARCH_REFRESH(ID) FROM OPERATION;
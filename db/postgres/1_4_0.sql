@@ -0,0 +1,22 @@
+-- Files attached to operations. Content is addressed by its SHA-256 so a file attached twice is
+-- stored once, in ATTACHMENT_CONTENT unless the server keeps attachments in a directory.
+CREATE TABLE ATTACHMENT_CONTENT
+(
+    HASH    TEXT PRIMARY KEY,
+    CONTENT BYTEA NOT NULL
+);
+
+CREATE TABLE ATTACHMENT
+(
+    ID           BIGSERIAL PRIMARY KEY,
+    LEDGER       BIGINT NOT NULL REFERENCES LEDGER (ID),
+    OPERATION    BIGINT NOT NULL REFERENCES OPERATION (ID),
+    NAME         TEXT   NOT NULL,
+    CONTENT_TYPE TEXT   NOT NULL,
+    SIZE         BIGINT NOT NULL,
+    HASH         TEXT   NOT NULL,
+    CREATED_AT   BIGINT NOT NULL
+);
+
+CREATE INDEX ATTACHMENT_OPERATION ON ATTACHMENT (OPERATION);
+CREATE INDEX ATTACHMENT_HASH ON ATTACHMENT (HASH);
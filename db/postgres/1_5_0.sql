@@ -0,0 +1,13 @@
+-- The parts an operation's amount is split into. An operation without legs pays its whole
+-- amount to TO_ACCOUNT; a leg without an account pays into TO_ACCOUNT as well.
+CREATE TABLE OPERATION_LEG
+(
+    ID        BIGSERIAL PRIMARY KEY,
+    OPERATION BIGINT NOT NULL REFERENCES OPERATION (ID),
+    ACCOUNT   BIGINT REFERENCES ACCOUNT (ID),
+    CATEGORY  BIGINT REFERENCES CATEGORY (ID),
+    AMOUNT    BIGINT NOT NULL,
+    MEMO      TEXT   NOT NULL DEFAULT ''
+);
+
+CREATE INDEX OPERATION_LEG_OPERATION ON OPERATION_LEG (OPERATION);
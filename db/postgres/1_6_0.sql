@@ -0,0 +1,7 @@
+-- Currencies as ISO 4217 codes. Everything recorded so far was in yuan. An operation's amount
+-- is in the currency of its FROM_ACCOUNT; TO_AMOUNT is what TO_ACCOUNT received when its
+-- currency differs, and NULL otherwise.
+ALTER TABLE LEDGER ADD COLUMN CURRENCY TEXT NOT NULL DEFAULT 'CNY';
+ALTER TABLE ACCOUNT ADD COLUMN CURRENCY TEXT NOT NULL DEFAULT 'CNY';
+ALTER TABLE OPERATION ADD COLUMN CURRENCY TEXT NOT NULL DEFAULT 'CNY';
+ALTER TABLE OPERATION ADD COLUMN TO_AMOUNT BIGINT;
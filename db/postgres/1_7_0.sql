@@ -0,0 +1,12 @@
+-- Exchange rates by day: one unit of BASE was worth RATE units of QUOTE on DATE, a
+-- YYYY-MM-DD string. Reports use the latest rate on or before the day they value balances at.
+CREATE TABLE EXCHANGE_RATE
+(
+    ID     BIGSERIAL PRIMARY KEY,
+    LEDGER BIGINT           NOT NULL REFERENCES LEDGER (ID),
+    BASE   TEXT             NOT NULL,
+    QUOTE  TEXT             NOT NULL,
+    DATE   TEXT             NOT NULL,
+    RATE   DOUBLE PRECISION NOT NULL,
+    UNIQUE (LEDGER, BASE, QUOTE, DATE)
+);
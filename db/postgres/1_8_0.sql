@@ -0,0 +1,15 @@
+-- Timestamps are milliseconds since the epoch in UTC, see entities::Timestamp. Rows written in
+-- seconds, which would date from 1973 at the latest as milliseconds, came from the statement
+-- parsers, which took the bank's Asia/Shanghai wall-clock time for UTC; they're moved back the
+-- eight hours to the instant they happened as they're scaled up. Attachment times were always
+-- real UTC seconds and are only scaled.
+-- LEDGER.TIMEZONE is the IANA name of the timezone dates are entered and reported in.
+ALTER TABLE LEDGER ADD COLUMN TIMEZONE TEXT NOT NULL DEFAULT 'Asia/Shanghai';
+
+UPDATE OPERATION
+SET DATETIME = (DATETIME - 8 * 60 * 60) * 1000
+WHERE DATETIME < 100000000000;
+
+UPDATE ATTACHMENT
+SET CREATED_AT = CREATED_AT * 1000
+WHERE CREATED_AT < 100000000000;
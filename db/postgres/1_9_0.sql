@@ -0,0 +1,36 @@
+-- Investments. A security is bought and sold in QUANTITY units of 10^-4 through an account in
+-- its currency, whose BALANCE pays AMOUNT for a buy and receives it for a sale. Lots and gains
+-- are worked out from the trades, oldest first. PRICE is what one unit was worth on DATE.
+CREATE TABLE SECURITY
+(
+    ID       BIGSERIAL PRIMARY KEY,
+    LEDGER   BIGINT NOT NULL REFERENCES LEDGER (ID),
+    SYMBOL   TEXT   NOT NULL,
+    NAME     TEXT   NOT NULL,
+    CURRENCY TEXT   NOT NULL,
+    UNIQUE (LEDGER, SYMBOL)
+);
+
+CREATE TABLE TRADE
+(
+    ID       BIGSERIAL PRIMARY KEY,
+    LEDGER   BIGINT NOT NULL REFERENCES LEDGER (ID),
+    ACCOUNT  BIGINT NOT NULL REFERENCES ACCOUNT (ID),
+    SECURITY BIGINT NOT NULL REFERENCES SECURITY (ID),
+    SIDE     TEXT   NOT NULL,
+    QUANTITY BIGINT NOT NULL,
+    AMOUNT   BIGINT NOT NULL,
+    DATETIME BIGINT NOT NULL,
+    COMMENT  TEXT   NOT NULL DEFAULT ''
+);
+
+CREATE INDEX TRADE_LEDGER ON TRADE (LEDGER);
+
+CREATE TABLE PRICE
+(
+    ID       BIGSERIAL PRIMARY KEY,
+    SECURITY BIGINT           NOT NULL REFERENCES SECURITY (ID),
+    DATE     TEXT             NOT NULL,
+    PRICE    DOUBLE PRECISION NOT NULL,
+    UNIQUE (SECURITY, DATE)
+);
@@ -1,9 +1,34 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+pub mod money;
+pub mod time;
+pub mod validation;
+
+pub use money::Money;
+pub use time::Timestamp;
+
+/// The currency of accounts and ledgers created without one, which is everything from before
+/// currencies were recorded.
+pub const DEFAULT_CURRENCY: &str = "CNY";
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_owned()
+}
+
+fn default_timezone() -> String {
+    time::DEFAULT_TIMEZONE.to_owned()
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Account {
     pub name: String,
+    /// In minor units of `currency`.
     pub balance: i64,
+    /// ISO 4217 code.
+    #[serde(default = "default_currency")]
+    pub currency: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -12,11 +37,64 @@ pub struct Operation {
     pub to: String,
     pub comment: String,
     pub amount: i64,
+    /// Milliseconds since the epoch, see `Timestamp`.
     pub datetime: i64,
+    /// How the amount is split, such as a receipt covering groceries and household goods.
+    /// Empty when it all goes to `to` uncategorised.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub legs: Vec<Leg>,
+    /// The currency of `amount`, which is always that of `from`. Filled in by the server when
+    /// left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// What `to` receives in its own currency, when that differs from `from`'s.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_amount: Option<i64>,
+}
+
+impl Operation {
+    /// The accounts the operation pays into with how much each receives, in leg order and
+    /// possibly naming an account more than once.
+    pub fn credits(&self) -> Vec<(&str, i64)> {
+        if self.legs.is_empty() {
+            return vec![(self.to.as_str(), self.to_amount.unwrap_or(self.amount))];
+        }
+
+        self.legs
+            .iter()
+            .map(|leg| (leg.account.as_deref().unwrap_or(&self.to), leg.amount))
+            .collect()
+    }
+
+    /// How many minor units of `to`'s currency one minor unit of `from`'s bought, for
+    /// operations between currencies.
+    pub fn implied_rate(&self) -> Option<f64> {
+        self.to_amount
+            .map(|to_amount| to_amount as f64 / self.amount as f64)
+    }
+}
+
+/// A part of an operation's amount. The legs of an operation add up to its amount.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Leg {
+    /// The account this part goes to, if not the operation's `to`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    pub amount: i64,
+    #[serde(default)]
+    pub memo: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Transaction {
+    /// Milliseconds since the epoch, see `Timestamp`.
     pub datetime: i64,
     pub from: String,
     pub to: String,
@@ -24,13 +102,852 @@ pub struct Transaction {
     pub description: String,
 }
 
+impl Transaction {
+    /// The operation recording the transaction between `from` and `to`, with its description
+    /// as the comment so it can be searched for. Statements write expenses as negative amounts,
+    /// but which way the money went is already in `from` and `to`.
+    pub fn to_operation(&self, from: &str, to: &str) -> Operation {
+        Operation {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            comment: self.description.clone(),
+            amount: self.amount.saturating_abs(),
+            datetime: self.datetime,
+            legs: Vec::new(),
+            currency: None,
+            to_amount: None,
+        }
+    }
+}
+
+/// The body of `POST /transaction/import`: transactions parsed from the statement of `account`.
+/// Parsers name the other side of a transaction after the merchant, or leave it empty when the
+/// statement only has the merchant in the description. It's recorded against the account
+/// `counterparts` maps that name, or else the description, to, or `counterpart` if neither is
+/// mapped, such as an account for spending yet to be sorted out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionImport {
+    pub account: String,
+    pub counterpart: String,
+    #[serde(default)]
+    pub counterparts: BTreeMap<String, String>,
+    pub transactions: Vec<Transaction>,
+}
+
+impl TransactionImport {
+    /// The operations recording the transactions, or `None` if one isn't to or from `account`.
+    pub fn to_operations(&self) -> Option<Vec<Operation>> {
+        let counterpart = |name: &String, transaction: &Transaction| {
+            let name = if name.is_empty() {
+                &transaction.description
+            } else {
+                name
+            };
+            self.counterparts.get(name).unwrap_or(&self.counterpart)
+        };
+        self.transactions
+            .iter()
+            .map(|transaction| {
+                if transaction.from == self.account {
+                    let to = counterpart(&transaction.to, transaction);
+                    Some(transaction.to_operation(&self.account, to))
+                } else if transaction.to == self.account {
+                    let from = counterpart(&transaction.from, transaction);
+                    Some(transaction.to_operation(from, &self.account))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginChallenge {
+    pub user: String,
+    pub token: String,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub second_factor: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpEnrolment {
+    pub uri: String,
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpConfirmation {
+    pub code: String,
+}
+
+/// A member's permissions within a ledger, ordered from least to most privileged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            _ => Err(format!("Unknown role: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Ledger {
+    pub id: i64,
+    pub name: String,
+    pub role: Role,
+    /// The base currency reports convert balances to.
+    pub currency: String,
+    /// The IANA name of the timezone dates are entered and reported in.
+    pub timezone: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewLedger {
+    pub name: String,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerMember {
+    pub user: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewUser {
+    pub name: String,
     pub token: String,
 }
 
+/// The body of every failed response from the server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String,
+    pub field: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DbVersion {
     pub version: String,
+    /// Milliseconds since the epoch.
     pub deploy_at: i64,
 }
+
+/// A snapshot written to the server's backup directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Returned when an operation is recorded, to refer to it later.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationCreated {
+    pub id: i64,
+}
+
+/// A file uploaded for an operation, before it's stored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewAttachment {
+    pub name: String,
+    pub content_type: String,
+    pub size: i64,
+}
+
+/// A file attached to an operation, such as a receipt or an invoice.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    pub operation: i64,
+    pub name: String,
+    pub content_type: String,
+    pub size: i64,
+    /// Hex SHA-256 of the content.
+    pub sha256: String,
+    /// Milliseconds since the epoch.
+    pub created_at: i64,
+}
+
+/// The parameters of `GET /search`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// An operation matching a search, returned best match first.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub id: i64,
+    pub operation: Operation,
+    /// An excerpt of the matching text, HTML-escaped, with matches wrapped in `<mark>`.
+    pub snippet: String,
+}
+
+/// The format of `LedgerExport`, bumped whenever an older server couldn't import it. Version 2
+/// added `Operation::legs`, version 3 currencies, version 4 exchange rates, version 5
+/// investments, version 6 credit cards, version 7 installment plans, version 8 shared expenses,
+/// version 9 reimbursable claims, version 10 savings goals, version 11 recurring operations
+/// and version 12 exchange rates and prices as integers of `RATE_DIGITS` and `PRICE_DIGITS`;
+/// older versions are still accepted.
+pub const EXPORT_VERSION: u32 = 12;
+
+/// Everything in a ledger, for moving it between servers or archiving it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LedgerExport {
+    pub version: u32,
+    /// Milliseconds since the epoch, like `Operation::datetime`.
+    pub exported_at: i64,
+    pub settings: LedgerSettings,
+    pub accounts: Vec<Account>,
+    pub categories: Vec<Category>,
+    pub operations: Vec<Operation>,
+    #[serde(default)]
+    pub rates: Vec<ExchangeRate>,
+    #[serde(default)]
+    pub securities: Vec<Security>,
+    /// Oldest first, like `operations`.
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    #[serde(default)]
+    pub prices: Vec<Price>,
+    #[serde(default)]
+    pub credit_cards: Vec<CreditCard>,
+    #[serde(default)]
+    pub installment_plans: Vec<InstallmentPlan>,
+    #[serde(default)]
+    pub shared_expenses: Vec<ExportedShare>,
+    #[serde(default)]
+    pub claims: Vec<ExportedClaim>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub recurring: Vec<RecurringOperation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerSettings {
+    pub name: String,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// The IANA name of the ledger's home timezone, such as `Asia/Shanghai`.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+/// An operation between accounts in different currencies, as evidence of the rate between
+/// them at the time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conversion {
+    pub from_currency: String,
+    pub to_currency: String,
+    pub amount: i64,
+    pub to_amount: i64,
+    pub datetime: i64,
+}
+
+/// The decimal places of exchange rates, enough for currencies worth a fraction of a cent.
+pub const RATE_DIGITS: u32 = 8;
+
+/// What one unit of `base` was worth in `quote` on `date`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    pub base: String,
+    pub quote: String,
+    /// `YYYY-MM-DD`, so dates sort as text.
+    pub date: String,
+    /// Units of `quote` per unit of `base`, in units of `10^-RATE_DIGITS`.
+    #[serde(deserialize_with = "deserialize_rate")]
+    pub rate: i64,
+}
+
+fn deserialize_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    money::deserialize_scaled(deserializer, RATE_DIGITS)
+}
+
+/// The response to `POST /rate/import`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RatesImported {
+    pub count: usize,
+}
+
+/// The response to `POST /transaction/import`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsImported {
+    pub count: usize,
+    /// Transactions left out as an earlier import already recorded them.
+    pub skipped: usize,
+}
+
+/// The decimal places of security quantities, enough for fund units.
+pub const QUANTITY_DIGITS: u32 = 4;
+
+/// A fund, stock or other commodity bought and sold in units, priced in `currency`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Security {
+    /// The ticker or fund code, such as `VTI` or `510300`.
+    pub symbol: String,
+    pub name: String,
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+impl TradeSide {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradeSide::Buy => "buy",
+            TradeSide::Sell => "sell",
+        }
+    }
+}
+
+impl FromStr for TradeSide {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buy" => Ok(TradeSide::Buy),
+            "sell" => Ok(TradeSide::Sell),
+            _ => Err(format!("Unknown trade side: {}", s)),
+        }
+    }
+}
+
+/// Units of a security bought or sold through an account, whose balance pays for them or
+/// receives the proceeds. The account must be in the security's currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub account: String,
+    pub symbol: String,
+    pub side: TradeSide,
+    /// In units of `10^-QUANTITY_DIGITS`.
+    pub quantity: i64,
+    /// What was paid or received with fees included, in minor units of the currency.
+    pub amount: i64,
+    /// Milliseconds since the epoch, see `Timestamp`.
+    pub datetime: i64,
+    #[serde(default)]
+    pub comment: String,
+}
+
+/// The decimal places of security prices, enough for fund unit values.
+pub const PRICE_DIGITS: u32 = 8;
+
+/// What one unit of a security was worth on `date`, in the security's currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Price {
+    pub symbol: String,
+    /// `YYYY-MM-DD`, like `ExchangeRate::date`.
+    pub date: String,
+    /// In units of `10^-PRICE_DIGITS` of the currency.
+    #[serde(deserialize_with = "deserialize_price")]
+    pub price: i64,
+}
+
+fn deserialize_price<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    money::deserialize_scaled(deserializer, PRICE_DIGITS)
+}
+
+/// Units bought together. Sales use up the oldest lots first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lot {
+    pub datetime: i64,
+    pub quantity: i64,
+    /// What the remaining units of the lot cost, in minor units.
+    pub cost: i64,
+}
+
+/// A security held in an account, with what it cost and gained in the security's currency.
+/// Holdings that were sold off entirely are kept for their realised gains.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Holding {
+    pub account: String,
+    pub symbol: String,
+    pub currency: String,
+    pub quantity: i64,
+    /// What the lots still held cost.
+    pub cost: i64,
+    /// Oldest first.
+    pub lots: Vec<Lot>,
+    /// What sales brought in over the cost of the lots they used up.
+    pub realised: i64,
+    /// The latest price on or before the day of the report, if any, like `Price::price`.
+    pub price: Option<i64>,
+    pub price_date: Option<String>,
+    /// `quantity` at `price`, or at cost while no price is known.
+    pub value: i64,
+    /// `value` less `cost`, if there's a price.
+    pub unrealised: Option<i64>,
+}
+
+/// The billing cycle of an account used as a credit card, whose balance goes negative as it's
+/// spent. Days are of the month; statements cover everything up to the end of the statement
+/// day and are due on the next due day after it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreditCard {
+    pub account: String,
+    pub statement_day: u32,
+    pub due_day: u32,
+    /// The credit limit, in minor units of the account's currency.
+    pub limit: i64,
+    /// The account statements are paid from, which `GET /forecast` pays each one out of in
+    /// full on its due date. Must be in the card's currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pay_from: Option<String>,
+}
+
+/// Where a credit card stands against its latest statement, returned by `GET /card` soonest
+/// due first. Amounts are in minor units of `currency`, positive when owed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CardStatement {
+    pub account: String,
+    pub currency: String,
+    /// `YYYY-MM-DD` in the ledger's timezone, the last day the statement covers.
+    pub statement_date: String,
+    pub due_date: String,
+    /// What was owed at the end of the statement date.
+    pub statement_balance: i64,
+    /// What's left of the statement balance after payments since.
+    pub remaining: i64,
+    /// What's left to pay by the due date to avoid a late fee.
+    pub minimum_payment: i64,
+    /// What's owed now, including spending since the statement.
+    pub owed: i64,
+    pub limit: i64,
+    pub available_credit: i64,
+}
+
+/// A purchase or loan repaid in monthly installments (分期), such as one spread over a credit
+/// card's statements or Huabei's. The principal was borrowed on `account`, a liability whose
+/// balance went negative by it; each repayment moves money from `from` to pay it back, with
+/// interest and fees going to `cost_account`. Amounts are in minor units of the accounts'
+/// shared currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallmentPlan {
+    pub name: String,
+    pub account: String,
+    pub from: String,
+    /// Required when the plan charges interest or fees.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_account: Option<String>,
+    pub principal: i64,
+    /// The yearly interest rate in percent, charged monthly on the principal left. Plans with
+    /// it repay the same amount every month.
+    #[serde(default)]
+    pub annual_rate: f64,
+    /// The fee charged with every repayment.
+    #[serde(default)]
+    pub fee: i64,
+    /// How many monthly repayments there are.
+    pub term: u32,
+    /// `YYYY-MM-DD`, when the first repayment is due. Later ones fall on the same day of the
+    /// following months, or on the last day of shorter ones.
+    pub first_due: String,
+    /// How many repayments have been recorded, counting any made before the plan was added.
+    /// The server records each one as an operation on the day it falls due.
+    #[serde(default)]
+    pub paid: u32,
+}
+
+/// One repayment of an installment plan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Installment {
+    /// From 1.
+    pub number: u32,
+    pub due_date: String,
+    pub principal: i64,
+    pub interest: i64,
+    pub fee: i64,
+    /// The principal left once this is repaid.
+    pub remaining: i64,
+    pub paid: bool,
+}
+
+/// An installment plan with its amortisation schedule, returned by `GET /installment`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallmentSchedule {
+    pub plan: InstallmentPlan,
+    pub currency: String,
+    pub installments: Vec<Installment>,
+    /// The principal left after the repayments recorded so far.
+    pub remaining: i64,
+}
+
+/// Saving up `target` in `account` by `target_date`, set with `PUT /goal`. Setting a goal with
+/// the same name again changes it. The account's whole balance counts towards the goal, so
+/// each account saves for one goal at most.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Goal {
+    pub name: String,
+    pub account: String,
+    /// In minor units of the account's currency.
+    pub target: i64,
+    /// `YYYY-MM-DD`.
+    pub target_date: String,
+}
+
+/// Where a goal stands, returned by `GET /goal` soonest target first. Amounts are in minor
+/// units of `currency`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub currency: String,
+    /// The account's balance, or nothing while it's negative.
+    pub saved: i64,
+    pub remaining: i64,
+    /// Whole months from today to the target date.
+    pub months_left: u32,
+    /// What has to go in each month from now on to reach the target in time.
+    pub monthly_required: i64,
+    /// How much the account grew a month on average recently.
+    pub monthly_contribution: i64,
+    /// When the target is reached if the account keeps growing at `monthly_contribution`, or
+    /// `None` if it isn't growing fast enough to get there within a century.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projected_date: Option<String>,
+    /// Whether `projected_date` is no later than the target date.
+    pub on_track: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareMethod {
+    /// Split in proportion to whole-number shares, such as 2 for a couple and 1 for a single.
+    Shares,
+    /// Split by percentages, in hundredths of a percent adding up to 100%.
+    Percent,
+}
+
+impl ShareMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShareMethod::Shares => "shares",
+            ShareMethod::Percent => "percent",
+        }
+    }
+}
+
+impl FromStr for ShareMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shares" => Ok(ShareMethod::Shares),
+            "percent" => Ok(ShareMethod::Percent),
+            _ => Err(format!("Unknown share method: {}", s)),
+        }
+    }
+}
+
+/// How an operation's amount is shared among household members, set with
+/// `PUT /operation/{id}/share`. Members are named freely, so flatmates without a login can
+/// take part. Whoever fronted the money is owed everyone else's part of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedExpense {
+    pub paid_by: String,
+    pub method: ShareMethod,
+    pub shares: Vec<MemberShare>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemberShare {
+    pub member: String,
+    pub share: i64,
+}
+
+/// A shared expense in a `LedgerExport`, which names its operation by position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedShare {
+    /// The index of the operation in `LedgerExport::operations`.
+    pub operation: usize,
+    #[serde(flatten)]
+    pub expense: SharedExpense,
+}
+
+/// Where a member stands across the shared expenses in one currency, returned by
+/// `GET /shared`. Amounts are in minor units of `currency`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemberBalance {
+    pub member: String,
+    pub currency: String,
+    /// What the member fronted.
+    pub paid: i64,
+    /// The member's part of what was spent.
+    pub owed: i64,
+    /// `paid - owed`: positive when the others owe the member, negative when the member owes
+    /// them.
+    pub balance: i64,
+}
+
+/// A transfer suggested by `GET /shared/settle` to even out the balances. Once made, record it
+/// as an operation shared entirely to `to` and paid by `from`.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settlement {
+    pub from: String,
+    pub to: String,
+    pub currency: String,
+    pub amount: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClaimStatus {
+    /// Still to be claimed.
+    Pending,
+    /// Claimed and waiting to be paid back.
+    Submitted,
+    /// Paid back by the `reimbursement` operation.
+    Paid,
+}
+
+impl ClaimStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ClaimStatus::Pending => "pending",
+            ClaimStatus::Submitted => "submitted",
+            ClaimStatus::Paid => "paid",
+        }
+    }
+}
+
+impl FromStr for ClaimStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(ClaimStatus::Pending),
+            "submitted" => Ok(ClaimStatus::Submitted),
+            "paid" => Ok(ClaimStatus::Paid),
+            _ => Err(format!("Unknown claim status: {}", s)),
+        }
+    }
+}
+
+/// Flags an operation as a work expense paid personally, to be claimed back. Set with
+/// `PUT /operation/{id}/claim`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claim {
+    pub status: ClaimStatus,
+    /// The id of the operation that paid the claim back, set exactly when it's `Paid`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reimbursement: Option<i64>,
+}
+
+/// The body of `POST /reimbursement`: the income operation that paid back `expenses`, which
+/// are all marked paid by it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Reimbursement {
+    pub operation: i64,
+    pub expenses: Vec<i64>,
+}
+
+/// A reimbursable expense as listed by `GET /claim`, which returns those not yet paid back,
+/// oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimedExpense {
+    pub operation: i64,
+    /// Milliseconds since the epoch, like `Operation::datetime`.
+    pub datetime: i64,
+    /// Whom the expense was paid to.
+    pub to: String,
+    pub comment: String,
+    /// In minor units of `currency`, the currency of the account it was paid from.
+    pub amount: i64,
+    pub currency: String,
+    pub status: ClaimStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reimbursement: Option<i64>,
+}
+
+/// A claim in a `LedgerExport`, which names operations by position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedClaim {
+    /// The index of the expense in `LedgerExport::operations`.
+    pub operation: usize,
+    pub status: ClaimStatus,
+    /// The index of the reimbursement in `LedgerExport::operations`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reimbursement: Option<usize>,
+}
+
+/// The response to `POST /installment/repay`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepaymentsRecorded {
+    pub count: usize,
+}
+
+/// The parameters of `GET /report`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportQuery {
+    /// Defaults to the ledger's currency.
+    pub base: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Weekly,
+    /// On the start date's day of the month, or the month's last day when it's shorter.
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Weekly => "weekly",
+            Frequency::Monthly => "monthly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "weekly" => Ok(Frequency::Weekly),
+            "monthly" => Ok(Frequency::Monthly),
+            "yearly" => Ok(Frequency::Yearly),
+            _ => Err(format!("Unknown frequency: {}", s)),
+        }
+    }
+}
+
+/// A transfer expected to repeat, like rent or a salary, set with `PUT /recurring`. Setting
+/// one with the same name again changes it. Recurring operations aren't recorded in the
+/// ledger; the forecast expects one on every occurrence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringOperation {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// In minor units of the accounts' currency, which they must share.
+    pub amount: i64,
+    pub frequency: Frequency,
+    /// `YYYY-MM-DD`: the first occurrence.
+    pub start: String,
+    /// `YYYY-MM-DD`: no occurrence falls after it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// The parameters of `GET /forecast`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForecastQuery {
+    /// How many months after today to forecast.
+    pub months: u32,
+    /// Whether to add in each account's average spending by category over the last three
+    /// months.
+    #[serde(default)]
+    pub history: bool,
+}
+
+/// How balances are expected to move from the end of today, returned by `GET /forecast`. The
+/// forecast counts operations dated after today, every occurrence of each `RecurringOperation`,
+/// the installments still to be repaid and, for cards with `CreditCard::pay_from`, statements
+/// paid in full when due.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Forecast {
+    /// `YYYY-MM-DD` in the ledger's timezone: tomorrow, then the last day forecast.
+    pub first_date: String,
+    pub last_date: String,
+    pub accounts: Vec<AccountForecast>,
+    /// The averages added in when asked for, by account then category.
+    #[serde(default)]
+    pub spending: Vec<AverageSpending>,
+}
+
+/// One account's forecast. Amounts are in minor units of `currency`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountForecast {
+    pub account: String,
+    pub currency: String,
+    /// The balance at the end of each day from `Forecast::first_date` on.
+    pub balances: Vec<i64>,
+    /// The lowest of `balances`, first reached on `lowest_date`.
+    pub lowest: i64,
+    pub lowest_date: String,
+    /// The first day that ends with the balance below zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below_zero_date: Option<String>,
+}
+
+/// What an account spent a month on a category, averaged over recent months. Spending keeps
+/// to this pace in the forecast, leaving the account a day at a time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AverageSpending {
+    pub account: String,
+    pub category: String,
+    pub currency: String,
+    pub monthly: i64,
+}
+
+/// Every account's balance with its value in a base currency.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BalanceReport {
+    pub base: String,
+    pub accounts: Vec<ConvertedBalance>,
+    /// The sum of the converted balances, in minor units of `base`.
+    pub total: i64,
+    /// Currencies without a known rate to `base`, whose balances are left out of `total`.
+    pub unconverted: Vec<String>,
+    /// The value of each security held, named by account and symbol, which `total` includes
+    /// like the balances.
+    #[serde(default)]
+    pub holdings: Vec<ConvertedBalance>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConvertedBalance {
+    pub name: String,
+    pub currency: String,
+    pub balance: i64,
+    /// `balance` in minor units of the base currency, if a rate is known.
+    pub converted: Option<i64>,
+    /// Units of the base currency per unit of `currency`.
+    pub rate: Option<f64>,
+    /// The date of the exchange rate used, or `None` when the rate came from the ledger's own
+    /// transfers between the currencies.
+    pub rate_date: Option<String>,
+}
@@ -0,0 +1,349 @@
+use serde::de::{self, Deserializer, Visitor};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Currencies whose minor unit isn't a hundredth, from ISO 4217.
+const MINOR_DIGITS: &[(&str, u32)] = &[
+    ("BHD", 3),
+    ("BIF", 0),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("IQD", 3),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("TND", 3),
+    ("UGX", 0),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+    ("XPF", 0),
+];
+
+/// The number of decimal places of the currency's minor unit.
+pub fn minor_digits(currency: &str) -> u32 {
+    MINOR_DIGITS
+        .iter()
+        .find(|(code, _)| *code == currency)
+        .map_or(2, |(_, digits)| *digits)
+}
+
+/// An amount in integer minor units of a currency, like the `i64` amounts and balances of the
+/// other entities, with exact conversion from and to decimal text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    /// Not a plain decimal number such as `-12.30`.
+    Invalid(String),
+    /// More decimal places than the currency has, other than trailing zeros.
+    TooPrecise {
+        text: String,
+        digits: u32,
+    },
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Invalid(text) => write!(f, "{} is not an amount.", text),
+            MoneyError::TooPrecise { text, digits } => {
+                write!(f, "{} has more than {} decimal places.", text, digits)
+            }
+            MoneyError::Overflow => write!(f, "The amount is too large."),
+        }
+    }
+}
+
+impl Money {
+    pub fn new(minor: i64, currency: &str) -> Self {
+        Money {
+            minor,
+            currency: currency.to_owned(),
+        }
+    }
+
+    /// Reads a decimal amount such as `12.3`, `-0.29` or `+5` without going through floating
+    /// point, so every written amount maps to exactly one number of minor units.
+    pub fn parse(text: &str, currency: &str) -> Result<Self, MoneyError> {
+        let minor = parse_decimal(text, minor_digits(currency))?;
+
+        Ok(Money::new(minor, currency))
+    }
+
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    /// The amount without its currency, with all the currency's decimal places: `-0.05`.
+    pub fn decimal(&self) -> String {
+        format_decimal(self.minor, minor_digits(&self.currency))
+    }
+
+    /// Adds two amounts of the same currency, or returns `None`.
+    pub fn checked_add(&self, other: &Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        let minor = self.minor.checked_add(other.minor)?;
+
+        Some(Money::new(minor, &self.currency))
+    }
+
+    /// Subtracts an amount of the same currency, or returns `None`.
+    pub fn checked_sub(&self, other: &Money) -> Option<Money> {
+        self.checked_add(&Money::new(other.minor.checked_neg()?, &other.currency))
+    }
+
+    /// Converts to `currency` at `numerator / denominator` units of it per unit of this
+    /// amount's currency, rounding half away from zero to the nearest minor unit.
+    pub fn convert(&self, numerator: i128, denominator: i128, currency: &str) -> Option<Money> {
+        let from = i128::pow(10, minor_digits(&self.currency));
+        let to = i128::pow(10, minor_digits(currency));
+        let numerator = i128::from(self.minor)
+            .checked_mul(numerator)?
+            .checked_mul(to)?;
+        let denominator = denominator.checked_mul(from)?;
+        let minor = divide_rounded(numerator, denominator)?;
+
+        Some(Money::new(i64::try_from(minor).ok()?, currency))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.decimal(), self.currency)
+    }
+}
+
+/// Reads a decimal number as an integer count of `10^-digits`, exactly like `Money::parse`.
+/// Also used for quantities of securities.
+pub fn parse_decimal(text: &str, digits: u32) -> Result<i64, MoneyError> {
+    let invalid = || MoneyError::Invalid(text.to_owned());
+    let trimmed = text.trim();
+    let (negative, unsigned) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    let (whole, fraction) = match unsigned.find('.') {
+        Some(dot) => (&unsigned[..dot], Some(&unsigned[dot + 1..])),
+        None => (unsigned, None),
+    };
+    let is_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !is_digits(whole) || !fraction.is_none_or(is_digits) {
+        return Err(invalid());
+    }
+
+    let fraction = fraction.unwrap_or("");
+    let (kept, dropped) = fraction.split_at(fraction.len().min(digits as usize));
+    if dropped.bytes().any(|b| b != b'0') {
+        return Err(MoneyError::TooPrecise {
+            text: text.to_owned(),
+            digits,
+        });
+    }
+
+    let mut value: i64 = 0;
+    let padding = std::iter::repeat_n(b'0', digits as usize - kept.len());
+    for digit in whole.bytes().chain(kept.bytes()).chain(padding) {
+        value = value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(i64::from(digit - b'0')))
+            .ok_or(MoneyError::Overflow)?;
+    }
+
+    Ok(if negative { -value } else { value })
+}
+
+/// Writes a count of `10^-digits` with all `digits` decimal places: `-0.05`.
+pub fn format_decimal(value: i64, digits: u32) -> String {
+    let sign = if value < 0 { "-" } else { "" };
+    // Through u64 so that i64::MIN has an absolute value.
+    let units = u64::pow(10, digits);
+    let absolute = value.unsigned_abs();
+    match digits {
+        0 => format!("{}{}", sign, absolute),
+        _ => format!(
+            "{}{}.{:0width$}",
+            sign,
+            absolute / units,
+            absolute % units,
+            width = digits as usize
+        ),
+    }
+}
+
+/// Reads a count of `10^-digits` written as an integer, or written as a decimal number the way
+/// exports before version 12 wrote exchange rates and prices, rounded to `digits` places.
+pub fn deserialize_scaled<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    digits: u32,
+) -> Result<i64, D::Error> {
+    struct Scaled(u32);
+
+    impl<'de> Visitor<'de> for Scaled {
+        type Value = i64;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a number in units of 10^-{}", self.0)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<i64, E> {
+            Ok(value)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<i64, E> {
+            i64::try_from(value).map_err(|_| E::custom(MoneyError::Overflow))
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<i64, E> {
+            let scaled = (value * 10f64.powi(self.0 as i32)).round();
+            // i64::MAX as f64 rounds up to 2^63, which is out of range.
+            if scaled.is_finite() && scaled.abs() < i64::MAX as f64 {
+                Ok(scaled as i64)
+            } else {
+                Err(E::custom(MoneyError::Overflow))
+            }
+        }
+    }
+
+    deserializer.deserialize_any(Scaled(digits))
+}
+
+/// Divides rounding half away from zero.
+fn divide_rounded(numerator: i128, denominator: i128) -> Option<i128> {
+    if denominator == 0 {
+        return None;
+    }
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if remainder.checked_abs()? * 2 >= denominator.checked_abs()? {
+        Some(quotient + numerator.signum() * denominator.signum())
+    } else {
+        Some(quotient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cny(minor: i64) -> Money {
+        Money::new(minor, "CNY")
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |text: &str, currency: &str| Money::parse(text, currency).map(|m| m.minor());
+        // 0.29 * 100.0 is 28.999999999999996 in floating point.
+        assert_eq!(parse("0.29", "CNY"), Ok(29));
+        assert_eq!(parse("1.15", "CNY"), Ok(115));
+        assert_eq!(parse(" -12.3 ", "CNY"), Ok(-1230));
+        assert_eq!(parse("+5", "CNY"), Ok(500));
+        assert_eq!(parse("1.500", "CNY"), Ok(150));
+        assert_eq!(parse("1500", "JPY"), Ok(1500));
+        assert_eq!(parse("1.5", "KWD"), Ok(1500));
+        assert_eq!(parse("92233720368547758.07", "CNY"), Ok(i64::MAX));
+
+        for text in &["", "-", "1.", ".5", "1.2.3", "1,000", "1e3", "¥5", "--1"] {
+            assert_eq!(
+                parse(text, "CNY"),
+                Err(MoneyError::Invalid((*text).to_owned()))
+            );
+        }
+        assert_eq!(
+            parse("1.005", "CNY"),
+            Err(MoneyError::TooPrecise {
+                text: "1.005".to_owned(),
+                digits: 2
+            })
+        );
+        assert!(matches!(
+            parse("1.5", "JPY"),
+            Err(MoneyError::TooPrecise { .. })
+        ));
+        assert_eq!(
+            parse("92233720368547758.08", "CNY"),
+            Err(MoneyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(cny(29).to_string(), "0.29 CNY");
+        assert_eq!(cny(-5).decimal(), "-0.05");
+        assert_eq!(cny(-1230).decimal(), "-12.30");
+        assert_eq!(Money::new(1500, "JPY").decimal(), "1500");
+        assert_eq!(Money::new(1500, "KWD").decimal(), "1.500");
+        assert_eq!(cny(i64::MIN).decimal(), "-92233720368547758.08");
+        for minor in &[0, 1, -1, 99, 100, -101, 123_456_789] {
+            assert_eq!(Money::parse(&cny(*minor).decimal(), "CNY"), Ok(cny(*minor)));
+        }
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(cny(150).checked_add(&cny(-200)), Some(cny(-50)));
+        assert_eq!(cny(150).checked_sub(&cny(200)), Some(cny(-50)));
+        assert_eq!(cny(1).checked_add(&Money::new(1, "USD")), None);
+        assert_eq!(cny(i64::MAX).checked_add(&cny(1)), None);
+        assert_eq!(cny(0).checked_sub(&cny(i64::MIN)), None);
+    }
+
+    #[test]
+    fn test_convert_rounds_half_away_from_zero() {
+        let convert = |minor: i64, numerator: i128, denominator: i128, currency: &str| {
+            cny(minor)
+                .convert(numerator, denominator, currency)
+                .map(|m| m.minor())
+        };
+        assert_eq!(convert(5, 1, 2, "USD"), Some(3));
+        assert_eq!(convert(-5, 1, 2, "USD"), Some(-3));
+        assert_eq!(convert(7, 1, 3, "USD"), Some(2));
+        assert_eq!(convert(-7, 1, 3, "USD"), Some(-2));
+        assert_eq!(convert(7, -1, -3, "USD"), Some(2));
+        // 1.00 CNY at 21.4 yen is 21.4 yen, which has no minor unit.
+        assert_eq!(convert(100, 214, 10, "JPY"), Some(21));
+        assert_eq!(convert(100, 215, 10, "JPY"), Some(22));
+        assert_eq!(
+            Money::new(1_000, "JPY").convert(1, 21, "CNY"),
+            Some(cny(4_762))
+        );
+        assert_eq!(convert(i64::MAX, 2, 1, "USD"), None);
+        assert_eq!(convert(1, 1, 0, "USD"), None);
+    }
+
+    #[test]
+    fn test_deserialize_scaled() {
+        let read = |json: &str| {
+            deserialize_scaled(&mut serde_json::Deserializer::from_str(json), 8)
+                .map_err(|e| e.to_string())
+        };
+        assert_eq!(read("712340000"), Ok(712_340_000));
+        // As older exports wrote rates. 7.1234 * 1e8 is 712340000.0000001 in floating point.
+        assert_eq!(read("7.1234"), Ok(712_340_000));
+        assert_eq!(read("7.0"), Ok(700_000_000));
+        assert_eq!(read("0.000000016"), Ok(2));
+        assert!(read("1e300").is_err());
+        assert!(read("\"7.1\"").is_err());
+    }
+}
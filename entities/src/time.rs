@@ -0,0 +1,123 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use std::fmt;
+
+pub use chrono_tz::Tz;
+
+/// The home timezone of ledgers created without one. Every statement parsed so far was from a
+/// Chinese bank or payment app.
+pub const DEFAULT_TIMEZONE: &str = "Asia/Shanghai";
+
+/// An instant as milliseconds since 1970-01-01T00:00:00Z, which is what every `datetime` and
+/// `…_at` field of the other entities holds. It has no timezone of its own: times written
+/// without one, such as a statement's or the date picked in the client, are read in the
+/// ledger's home timezone with `from_local`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub fn from_millis(millis: i64) -> Self {
+        Timestamp(millis)
+    }
+
+    /// For Unix times in seconds, or `None` if out of range.
+    pub fn from_seconds(seconds: i64) -> Option<Self> {
+        seconds.checked_mul(1000).map(Timestamp)
+    }
+
+    /// Reads a wall-clock time in `timezone`. A time repeated when clocks go back is taken the
+    /// first time round; one skipped when they go forward is read with the offset from before.
+    pub fn from_local(local: NaiveDateTime, timezone: Tz) -> Self {
+        let datetime = match timezone.from_local_datetime(&local).earliest() {
+            Some(datetime) => datetime.naive_utc(),
+            None => {
+                let before = timezone.offset_from_utc_datetime(&(local - Duration::days(1)));
+                local - Duration::seconds(i64::from(before.fix().local_minus_utc()))
+            }
+        };
+
+        Timestamp(datetime.timestamp_millis())
+    }
+
+    /// The start of `date` in `timezone`.
+    pub fn from_local_date(date: NaiveDate, timezone: Tz) -> Self {
+        Self::from_local(date.and_hms(0, 0, 0), timezone)
+    }
+
+    pub fn millis(self) -> i64 {
+        self.0
+    }
+
+    /// The wall-clock time in `timezone`.
+    pub fn to_local(self, timezone: Tz) -> NaiveDateTime {
+        timezone.timestamp_millis(self.0).naive_local()
+    }
+
+    /// The date in `timezone`, such as the day an operation falls on for the ledger.
+    pub fn local_date(self, timezone: Tz) -> NaiveDate {
+        self.to_local(timezone).date()
+    }
+}
+
+/// RFC 3339 in UTC: `2020-03-31T16:00:00.000Z`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let datetime = Utc.timestamp_millis(self.0);
+        write!(f, "{}", datetime.format("%Y-%m-%dT%H:%M:%S%.3fZ"))
+    }
+}
+
+/// Reads an IANA timezone name such as `Asia/Shanghai` or `UTC`.
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{Asia::Shanghai, Europe::Berlin};
+
+    fn local(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %T").unwrap()
+    }
+
+    #[test]
+    fn test_local_times() {
+        // Midnight in Shanghai is 16:00 the day before in UTC.
+        let midnight = Timestamp::from_local(local("2020-04-01 00:00:00"), Shanghai);
+        assert_eq!(midnight.millis(), 1_585_670_400_000);
+        assert_eq!(midnight.to_string(), "2020-03-31T16:00:00.000Z");
+        assert_eq!(
+            Timestamp::from_local_date(NaiveDate::from_ymd(2020, 4, 1), Shanghai),
+            midnight
+        );
+        assert_eq!(
+            midnight.local_date(Shanghai),
+            NaiveDate::from_ymd(2020, 4, 1)
+        );
+        assert_eq!(
+            midnight.local_date(Tz::UTC),
+            NaiveDate::from_ymd(2020, 3, 31)
+        );
+        assert_eq!(midnight.to_local(Shanghai), local("2020-04-01 00:00:00"));
+        assert_eq!(Timestamp::from_seconds(1_585_670_400), Some(midnight));
+        assert_eq!(Timestamp::from_seconds(i64::MAX), None);
+    }
+
+    #[test]
+    fn test_daylight_saving_changes() {
+        // 02:30 didn't happen in Berlin on 2020-03-29, so it's read as 02:30 CET.
+        let skipped = Timestamp::from_local(local("2020-03-29 02:30:00"), Berlin);
+        assert_eq!(skipped.to_string(), "2020-03-29T01:30:00.000Z");
+        // 02:30 happened twice on 2020-10-25; the first time was still CEST.
+        let repeated = Timestamp::from_local(local("2020-10-25 02:30:00"), Berlin);
+        assert_eq!(repeated.to_string(), "2020-10-25T00:30:00.000Z");
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert_eq!(parse_timezone("Asia/Shanghai"), Some(Shanghai));
+        assert_eq!(parse_timezone("UTC"), Some(Tz::UTC));
+        assert_eq!(parse_timezone("Mars/Olympus"), None);
+        assert_eq!(parse_timezone(""), None);
+    }
+}
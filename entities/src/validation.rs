@@ -0,0 +1,2011 @@
+use crate::time::parse_timezone;
+use crate::{
+    Account, Category, Claim, ClaimStatus, CreditCard, ExchangeRate, ForecastQuery, Goal,
+    InstallmentPlan, LedgerExport, LedgerSettings, Money, NewAttachment, NewLedger, Operation,
+    Price, RecurringOperation, Reimbursement, ReportQuery, SearchQuery, Security, ShareMethod,
+    SharedExpense, Trade, TradeSide, EXPORT_VERSION, PRICE_DIGITS, RATE_DIGITS,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+pub const MAX_NAME_LEN: usize = 64;
+pub const MAX_COMMENT_LEN: usize = 256;
+/// More lines than a long supermarket receipt needs.
+pub const MAX_LEGS: usize = 32;
+pub const MAX_QUERY_LEN: usize = 128;
+pub const MAX_FILE_NAME_LEN: usize = 255;
+/// Large enough for a phone photo of a receipt or a scanned invoice.
+pub const MAX_ATTACHMENT_SIZE: i64 = 10 * 1024 * 1024;
+/// Images and PDFs, which browsers display without running anything.
+pub const ATTACHMENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "image/gif",
+    "image/heic",
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+];
+/// Ten billion in minor units, far beyond any household transfer.
+pub const MAX_AMOUNT: i64 = 1_000_000_000_000;
+/// A million units of quote per unit of base, more than any pair of currencies in use, in
+/// `RATE_DIGITS` decimal places.
+pub const MAX_RATE: i64 = 100_000_000_000_000;
+/// A hundred million units, in `QUANTITY_DIGITS` decimal places.
+pub const MAX_QUANTITY: i64 = 1_000_000_000_000;
+/// A billion units of currency per unit, above any share price, in `PRICE_DIGITS` decimal
+/// places.
+pub const MAX_PRICE: i64 = 100_000_000_000_000_000;
+pub const MAX_SYMBOL_LEN: usize = 16;
+/// Statement and due days stop at 28 so that every month has them.
+pub const MAX_BILLING_DAY: u32 = 28;
+/// Thirty years of monthly repayments, as long as mortgages run.
+pub const MAX_TERM: u32 = 360;
+/// In percent a year, well above what any lender may legally charge.
+pub const MAX_ANNUAL_RATE: f64 = 100.0;
+/// Enough for a shared house or a group trip.
+pub const MAX_MEMBERS: usize = 32;
+/// 100% in the hundredths of a percent `ShareMethod::Percent` counts in, and the most shares a
+/// member can have.
+pub const MAX_SHARE: i64 = 10_000;
+/// The most expenses one reimbursement pays back, a long claim form's worth.
+pub const MAX_REIMBURSED: usize = 100;
+/// A year ahead, about as far as operations can be scheduled.
+pub const MAX_FORECAST_MONTHS: u32 = 12;
+/// 2000-01-01T00:00:00Z in milliseconds; nothing in the ledger predates it.
+pub const MIN_DATETIME: i64 = 946_684_800_000;
+/// How far ahead an operation may be dated, to allow entering scheduled payments.
+pub const MAX_FUTURE_MILLIS: i64 = 366 * 24 * 60 * 60 * 1000;
+
+/// A rule broken by one field of a submitted entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        ValidationError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl Operation {
+    /// Checks the operation on its own; whether both accounts exist is up to the caller.
+    /// `now` is in milliseconds like `datetime`.
+    pub fn validate(&self, now: i64) -> Result<(), ValidationError> {
+        validate_name("from", &self.from)?;
+        validate_name("to", &self.to)?;
+        if self.from == self.to {
+            return Err(ValidationError::new(
+                "to",
+                "Can't transfer to the same account.",
+            ));
+        }
+        if self.amount <= 0 {
+            return Err(ValidationError::new("amount", "Amount must be positive."));
+        }
+        if self.amount > MAX_AMOUNT {
+            return Err(ValidationError::new("amount", "Amount is too large."));
+        }
+        if self.comment.chars().count() > MAX_COMMENT_LEN {
+            return Err(ValidationError::new(
+                "comment",
+                format!("Comment is longer than {} characters.", MAX_COMMENT_LEN),
+            ));
+        }
+        if self.datetime < MIN_DATETIME || self.datetime > now + MAX_FUTURE_MILLIS {
+            return Err(ValidationError::new(
+                "datetime",
+                "Date is out of the supported range.",
+            ));
+        }
+        if let Some(currency) = &self.currency {
+            validate_currency("currency", currency)?;
+        }
+        if let Some(to_amount) = self.to_amount {
+            if to_amount <= 0 || to_amount > MAX_AMOUNT {
+                return Err(ValidationError::new(
+                    "to_amount",
+                    "Received amount must be positive and not too large.",
+                ));
+            }
+            if !self.legs.is_empty() {
+                return Err(ValidationError::new(
+                    "to_amount",
+                    "A transfer between currencies can't be split.",
+                ));
+            }
+        }
+        self.validate_legs()
+    }
+
+    /// Checks the operation against the currencies of the accounts it names, which must all be
+    /// in `currencies`, returning the currency of `amount`. Money only changes currency from
+    /// `from` to `to`, with `to_amount` saying how much arrives.
+    pub fn validate_currencies(
+        &self,
+        currencies: &HashMap<&str, &str>,
+    ) -> Result<String, ValidationError> {
+        let currency_of = |name: &str| currencies.get(name).copied().unwrap_or_default();
+        let from = currency_of(&self.from);
+        if let Some(currency) = &self.currency {
+            if currency != from {
+                return Err(ValidationError::new(
+                    "currency",
+                    format!("{} holds {}, not {}.", self.from, from, currency),
+                ));
+            }
+        }
+        let to = currency_of(&self.to);
+        match (to == from, self.to_amount) {
+            (true, Some(_)) => {
+                return Err(ValidationError::new(
+                    "to_amount",
+                    format!("{} and {} both hold {}.", self.from, self.to, from),
+                ))
+            }
+            (false, None) => {
+                return Err(ValidationError::new(
+                    "to_amount",
+                    format!("{} holds {}, so enter what it receives.", self.to, to),
+                ))
+            }
+            _ => (),
+        }
+        for (i, leg) in self.legs.iter().enumerate() {
+            if let Some(account) = &leg.account {
+                if currency_of(account) != from {
+                    return Err(ValidationError::new(
+                        "legs",
+                        format!(
+                            "Part {}: {} holds {}, not {}.",
+                            i + 1,
+                            account,
+                            currency_of(account),
+                            from
+                        ),
+                    ));
+                }
+            }
+        }
+
+        Ok(from.to_owned())
+    }
+
+    fn validate_legs(&self) -> Result<(), ValidationError> {
+        if self.legs.len() > MAX_LEGS {
+            return Err(ValidationError::new(
+                "legs",
+                format!("An operation can be split at most {} ways.", MAX_LEGS),
+            ));
+        }
+        let mut total = 0;
+        for (i, leg) in self.legs.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("legs", format!("Part {}: {}", i + 1, message))
+            };
+            if let Some(account) = &leg.account {
+                validate_name("legs", account).map_err(|e| invalid(&e.message))?;
+                if *account == self.from {
+                    return Err(invalid("Can't transfer to the same account."));
+                }
+            }
+            if let Some(category) = &leg.category {
+                validate_name("legs", category).map_err(|e| invalid(&e.message))?;
+            }
+            if leg.amount <= 0 || leg.amount > self.amount {
+                return Err(invalid("Amount must be positive and within the total."));
+            }
+            if leg.memo.chars().count() > MAX_COMMENT_LEN {
+                return Err(invalid(&format!(
+                    "Memo is longer than {} characters.",
+                    MAX_COMMENT_LEN
+                )));
+            }
+            total += leg.amount;
+        }
+        if !self.legs.is_empty() && total != self.amount {
+            return Err(ValidationError::new(
+                "legs",
+                format!(
+                    "The parts add up to {} instead of the total {}.",
+                    total, self.amount
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Category {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)
+    }
+}
+
+impl NewLedger {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_currency("currency", &self.currency)?;
+        validate_timezone("timezone", &self.timezone)
+    }
+}
+
+impl LedgerSettings {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_currency("currency", &self.currency)?;
+        validate_timezone("timezone", &self.timezone)
+    }
+}
+
+impl Account {
+    /// Checks a new account. Balances only change through operations, so it starts at zero.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_currency("currency", &self.currency)?;
+        if self.balance != 0 {
+            return Err(ValidationError::new(
+                "balance",
+                "A new account must start with a zero balance.",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl SearchQuery {
+    /// The words to look for, lowercased. Punctuation only separates them, so the terms can be
+    /// passed to a full-text engine without escaping.
+    pub fn terms(&self) -> Vec<String> {
+        self.q
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|term| !term.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.q.chars().count() > MAX_QUERY_LEN {
+            return Err(ValidationError::new(
+                "q",
+                format!("Search is longer than {} characters.", MAX_QUERY_LEN),
+            ));
+        }
+        if self.terms().is_empty() {
+            return Err(ValidationError::new("q", "Enter a word to search for."));
+        }
+
+        Ok(())
+    }
+}
+
+impl ExchangeRate {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_currency("base", &self.base)?;
+        validate_currency("quote", &self.quote)?;
+        if self.base == self.quote {
+            return Err(ValidationError::new(
+                "quote",
+                "A rate needs two different currencies.",
+            ));
+        }
+        validate_date("date", &self.date)?;
+        if !(self.rate > 0 && self.rate <= MAX_RATE) {
+            return Err(ValidationError::new(
+                "rate",
+                format!(
+                    "Rate must be above 0 and at most {}.",
+                    MAX_RATE / i64::pow(10, RATE_DIGITS)
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Security {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_symbol("symbol", &self.symbol)?;
+        validate_name("name", &self.name)?;
+        validate_currency("currency", &self.currency)
+    }
+}
+
+impl Trade {
+    /// Checks the trade on its own; whether the account holds enough to sell is up to the
+    /// caller. `now` is in milliseconds like `datetime`.
+    pub fn validate(&self, now: i64) -> Result<(), ValidationError> {
+        validate_name("account", &self.account)?;
+        validate_symbol("symbol", &self.symbol)?;
+        if self.quantity <= 0 || self.quantity > MAX_QUANTITY {
+            return Err(ValidationError::new(
+                "quantity",
+                "Quantity must be positive and not too large.",
+            ));
+        }
+        if self.amount < 0 || self.amount > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "amount",
+                "Amount can't be negative or too large.",
+            ));
+        }
+        if self.datetime < MIN_DATETIME || self.datetime > now + MAX_FUTURE_MILLIS {
+            return Err(ValidationError::new(
+                "datetime",
+                "Date is out of the supported range.",
+            ));
+        }
+        if self.comment.chars().count() > MAX_COMMENT_LEN {
+            return Err(ValidationError::new(
+                "comment",
+                format!("Comment is longer than {} characters.", MAX_COMMENT_LEN),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// How the trade changes the quantity held.
+    pub fn signed_quantity(&self) -> i64 {
+        match self.side {
+            TradeSide::Buy => self.quantity,
+            TradeSide::Sell => -self.quantity,
+        }
+    }
+
+    /// How the trade changes the account's balance.
+    pub fn signed_amount(&self) -> i64 {
+        match self.side {
+            TradeSide::Buy => -self.amount,
+            TradeSide::Sell => self.amount,
+        }
+    }
+}
+
+impl Price {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_symbol("symbol", &self.symbol)?;
+        validate_date("date", &self.date)?;
+        if !(self.price > 0 && self.price <= MAX_PRICE) {
+            return Err(ValidationError::new(
+                "price",
+                format!(
+                    "Price must be above 0 and at most {}.",
+                    MAX_PRICE / i64::pow(10, PRICE_DIGITS)
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl CreditCard {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("account", &self.account)?;
+        for (field, day) in &[
+            ("statement_day", self.statement_day),
+            ("due_day", self.due_day),
+        ] {
+            if *day < 1 || *day > MAX_BILLING_DAY {
+                return Err(ValidationError::new(
+                    field,
+                    format!("Day must be between 1 and {}.", MAX_BILLING_DAY),
+                ));
+            }
+        }
+        if self.limit < 0 || self.limit > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "limit",
+                "Limit can't be negative or too large.",
+            ));
+        }
+        if let Some(pay_from) = &self.pay_from {
+            validate_name("pay_from", pay_from)?;
+            if *pay_from == self.account {
+                return Err(ValidationError::new(
+                    "pay_from",
+                    "Statements must be paid from another account.",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl InstallmentPlan {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_name("account", &self.account)?;
+        validate_name("from", &self.from)?;
+        if self.from == self.account {
+            return Err(ValidationError::new(
+                "from",
+                "Repayments must come from another account.",
+            ));
+        }
+        if let Some(cost_account) = &self.cost_account {
+            validate_name("cost_account", cost_account)?;
+        }
+        if self.principal <= 0 || self.principal > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "principal",
+                "Principal must be positive and not too large.",
+            ));
+        }
+        if !(self.annual_rate >= 0.0 && self.annual_rate <= MAX_ANNUAL_RATE) {
+            return Err(ValidationError::new(
+                "annual_rate",
+                format!("Rate must be between 0 and {}%.", MAX_ANNUAL_RATE),
+            ));
+        }
+        if self.fee < 0 || self.fee > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "fee",
+                "Fee can't be negative or too large.",
+            ));
+        }
+        if (self.annual_rate > 0.0 || self.fee > 0) && self.cost_account.is_none() {
+            return Err(ValidationError::new(
+                "cost_account",
+                "Interest and fees need an account to go to.",
+            ));
+        }
+        if self.term < 1 || self.term > MAX_TERM {
+            return Err(ValidationError::new(
+                "term",
+                format!("Term must be between 1 and {} months.", MAX_TERM),
+            ));
+        }
+        validate_date("first_due", &self.first_due)?;
+        if self.paid > self.term {
+            return Err(ValidationError::new(
+                "paid",
+                "More repayments can't be made than the term has.",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Goal {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_name("account", &self.account)?;
+        if self.target <= 0 || self.target > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "target",
+                "Target must be positive and not too large.",
+            ));
+        }
+        validate_date("target_date", &self.target_date)
+    }
+}
+
+impl RecurringOperation {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("name", &self.name)?;
+        validate_name("from", &self.from)?;
+        validate_name("to", &self.to)?;
+        if self.from == self.to {
+            return Err(ValidationError::new(
+                "to",
+                "A transfer needs two different accounts.",
+            ));
+        }
+        if self.amount <= 0 || self.amount > MAX_AMOUNT {
+            return Err(ValidationError::new(
+                "amount",
+                "Amount must be positive and not too large.",
+            ));
+        }
+        validate_date("start", &self.start)?;
+        if let Some(end) = &self.end {
+            validate_date("end", end)?;
+            // Dates in this form sort the same as text.
+            if *end < self.start {
+                return Err(ValidationError::new(
+                    "end",
+                    "It can't end before it starts.",
+                ));
+            }
+        }
+        if let Some(category) = &self.category {
+            validate_name("category", category)?;
+        }
+        Ok(())
+    }
+}
+
+impl SharedExpense {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_name("paid_by", &self.paid_by)?;
+        if self.shares.is_empty() || self.shares.len() > MAX_MEMBERS {
+            return Err(ValidationError::new(
+                "shares",
+                format!("An expense is shared among 1 to {} members.", MAX_MEMBERS),
+            ));
+        }
+        validate_unique("shares", self.shares.iter().map(|s| s.member.as_str()))?;
+        if self
+            .shares
+            .iter()
+            .any(|s| s.share <= 0 || s.share > MAX_SHARE)
+        {
+            return Err(ValidationError::new(
+                "shares",
+                format!("Shares must be between 1 and {}.", MAX_SHARE),
+            ));
+        }
+        let total: i64 = self.shares.iter().map(|s| s.share).sum();
+        if self.method == ShareMethod::Percent && total != MAX_SHARE {
+            return Err(ValidationError::new(
+                "shares",
+                "Percentages must add up to 100%.",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Claim {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_reimbursement(self.status, self.reimbursement.is_some())
+    }
+}
+
+impl Reimbursement {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.expenses.is_empty() || self.expenses.len() > MAX_REIMBURSED {
+            return Err(ValidationError::new(
+                "expenses",
+                format!(
+                    "A reimbursement pays back 1 to {} expenses.",
+                    MAX_REIMBURSED
+                ),
+            ));
+        }
+        let mut seen = HashSet::new();
+        for expense in &self.expenses {
+            if *expense == self.operation {
+                return Err(ValidationError::new(
+                    "expenses",
+                    "A reimbursement can't pay itself back.",
+                ));
+            }
+            if !seen.insert(expense) {
+                return Err(ValidationError::new(
+                    "expenses",
+                    format!("Operation {} is listed twice.", expense),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A claim names the operation that paid it back exactly when it's been paid.
+fn validate_reimbursement(status: ClaimStatus, reimbursed: bool) -> Result<(), ValidationError> {
+    match (status, reimbursed) {
+        (ClaimStatus::Paid, false) => Err(ValidationError::new(
+            "reimbursement",
+            "A paid claim needs the operation that paid it back.",
+        )),
+        (ClaimStatus::Pending, true) | (ClaimStatus::Submitted, true) => Err(ValidationError::new(
+            "reimbursement",
+            "Only a paid claim has a reimbursement.",
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl ReportQuery {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match &self.base {
+            Some(base) => validate_currency("base", base),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ForecastQuery {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.months < 1 || self.months > MAX_FORECAST_MONTHS {
+            return Err(ValidationError::new(
+                "months",
+                format!("Forecasts run 1 to {} months ahead.", MAX_FORECAST_MONTHS),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl NewAttachment {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::new("name", "File name can't be empty."));
+        }
+        if self.name.chars().count() > MAX_FILE_NAME_LEN {
+            return Err(ValidationError::new(
+                "name",
+                format!("File name is longer than {} characters.", MAX_FILE_NAME_LEN),
+            ));
+        }
+        if self
+            .name
+            .chars()
+            .any(|c| c.is_control() || c == '/' || c == '\\')
+        {
+            return Err(ValidationError::new(
+                "name",
+                "File name can't contain control characters or slashes.",
+            ));
+        }
+        if !ATTACHMENT_TYPES.contains(&self.content_type.as_str()) {
+            return Err(ValidationError::new(
+                "content_type",
+                "Only images and PDF files can be attached.",
+            ));
+        }
+        if self.size <= 0 {
+            return Err(ValidationError::new("size", "File is empty."));
+        }
+        if self.size > MAX_ATTACHMENT_SIZE {
+            return Err(ValidationError::new(
+                "size",
+                format!(
+                    "File is larger than {} MB.",
+                    MAX_ATTACHMENT_SIZE / 1024 / 1024
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl LedgerExport {
+    /// Checks that the document can be loaded as a whole: every entity is valid on its own,
+    /// names are unique, operations only reference listed accounts and categories, and each
+    /// balance is what its operations add up to.
+    pub fn validate(&self, now: i64) -> Result<(), ValidationError> {
+        if self.version == 0 || self.version > EXPORT_VERSION {
+            return Err(ValidationError::new(
+                "version",
+                format!("Unsupported export version {}.", self.version),
+            ));
+        }
+        self.settings
+            .validate()
+            .map_err(|e| ValidationError::new("settings", e.message))?;
+        validate_unique("accounts", self.accounts.iter().map(|a| a.name.as_str()))?;
+        for account in &self.accounts {
+            validate_currency("accounts", &account.currency)?;
+        }
+        let currencies: HashMap<&str, &str> = self
+            .accounts
+            .iter()
+            .map(|account| (account.name.as_str(), account.currency.as_str()))
+            .collect();
+        validate_unique(
+            "categories",
+            self.categories.iter().map(|c| c.name.as_str()),
+        )?;
+
+        let mut balances: HashMap<&str, i64> = self
+            .accounts
+            .iter()
+            .map(|account| (account.name.as_str(), 0))
+            .collect();
+        for (i, operation) in self.operations.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("operations", format!("Operation {}: {}", i + 1, message))
+            };
+            operation.validate(now).map_err(|e| invalid(&e.message))?;
+            let credits = operation.credits();
+            let accounts = credits.iter().map(|(name, _)| *name);
+            for name in accounts.chain(vec![operation.from.as_str(), operation.to.as_str()]) {
+                if !balances.contains_key(name) {
+                    return Err(invalid(&format!("Account {} is not in the export.", name)));
+                }
+            }
+            operation
+                .validate_currencies(&currencies)
+                .map_err(|e| invalid(&e.message))?;
+            for category in operation
+                .legs
+                .iter()
+                .filter_map(|leg| leg.category.as_ref())
+            {
+                if !self.categories.iter().any(|c| c.name == *category) {
+                    return Err(invalid(&format!(
+                        "Category {} is not in the export.",
+                        category
+                    )));
+                }
+            }
+            *balances.get_mut(operation.from.as_str()).unwrap() -= operation.amount;
+            for (name, amount) in credits {
+                *balances.get_mut(name).unwrap() += amount;
+            }
+        }
+
+        validate_unique_symbols(&self.securities)?;
+        let security_currencies: HashMap<&str, &str> = self
+            .securities
+            .iter()
+            .map(|security| (security.symbol.as_str(), security.currency.as_str()))
+            .collect();
+        // What each account holds of each security, and when it last traded it.
+        let mut held: HashMap<(&str, &str), (i64, i64)> = HashMap::new();
+        for (i, trade) in self.trades.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("trades", format!("Trade {}: {}", i + 1, message))
+            };
+            trade.validate(now).map_err(|e| invalid(&e.message))?;
+            let account = currencies.get(trade.account.as_str()).ok_or_else(|| {
+                invalid(&format!("Account {} is not in the export.", trade.account))
+            })?;
+            let security = security_currencies
+                .get(trade.symbol.as_str())
+                .ok_or_else(|| {
+                    invalid(&format!("Security {} is not in the export.", trade.symbol))
+                })?;
+            if account != security {
+                return Err(invalid(&format!(
+                    "{} is in {} but {} holds {}.",
+                    trade.symbol, security, trade.account, account
+                )));
+            }
+            let (quantity, latest) = held
+                .entry((trade.account.as_str(), trade.symbol.as_str()))
+                .or_insert((0, i64::MIN));
+            if trade.datetime < *latest {
+                return Err(invalid("Trades must be listed oldest first."));
+            }
+            *latest = trade.datetime;
+            *quantity += trade.signed_quantity();
+            if *quantity < 0 {
+                return Err(invalid(&format!(
+                    "{} sells more {} than it holds.",
+                    trade.account, trade.symbol
+                )));
+            }
+            *balances.get_mut(trade.account.as_str()).unwrap() += trade.signed_amount();
+        }
+
+        let mut priced = HashSet::new();
+        for (i, price) in self.prices.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("prices", format!("Price {}: {}", i + 1, message))
+            };
+            price.validate().map_err(|e| invalid(&e.message))?;
+            if !security_currencies.contains_key(price.symbol.as_str()) {
+                return Err(invalid(&format!(
+                    "Security {} is not in the export.",
+                    price.symbol
+                )));
+            }
+            if !priced.insert((&price.symbol, &price.date)) {
+                return Err(invalid("Listed twice for the same date."));
+            }
+        }
+
+        let mut cards = HashSet::new();
+        for (i, card) in self.credit_cards.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("credit_cards", format!("Card {}: {}", i + 1, message))
+            };
+            card.validate().map_err(|e| invalid(&e.message))?;
+            if !currencies.contains_key(card.account.as_str()) {
+                return Err(invalid(&format!(
+                    "Account {} is not in the export.",
+                    card.account
+                )));
+            }
+            if let Some(pay_from) = &card.pay_from {
+                let account = currencies[card.account.as_str()];
+                match currencies.get(pay_from.as_str()) {
+                    None => {
+                        return Err(invalid(&format!(
+                            "Account {} is not in the export.",
+                            pay_from
+                        )))
+                    }
+                    Some(currency) if *currency != account => {
+                        return Err(invalid(&format!(
+                            "{} is in {} but {} is in {}.",
+                            pay_from, currency, card.account, account
+                        )))
+                    }
+                    _ => (),
+                }
+            }
+            if !cards.insert(&card.account) {
+                return Err(invalid("Listed twice for the same account."));
+            }
+        }
+
+        let mut plans = HashSet::new();
+        for (i, plan) in self.installment_plans.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("installment_plans", format!("Plan {}: {}", i + 1, message))
+            };
+            plan.validate().map_err(|e| invalid(&e.message))?;
+            let currency = |name: &str| {
+                currencies
+                    .get(name)
+                    .ok_or_else(|| invalid(&format!("Account {} is not in the export.", name)))
+            };
+            let expected = currency(&plan.account)?;
+            for name in std::iter::once(&plan.from).chain(&plan.cost_account) {
+                let actual = currency(name)?;
+                if actual != expected {
+                    return Err(invalid(&format!(
+                        "{} is in {} but {} is in {}.",
+                        name, actual, plan.account, expected
+                    )));
+                }
+            }
+            if !plans.insert(&plan.name) {
+                return Err(invalid("Listed twice."));
+            }
+        }
+
+        let mut shared = HashSet::new();
+        for (i, share) in self.shared_expenses.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new(
+                    "shared_expenses",
+                    format!("Shared expense {}: {}", i + 1, message),
+                )
+            };
+            share.expense.validate().map_err(|e| invalid(&e.message))?;
+            if share.operation >= self.operations.len() {
+                return Err(invalid(&format!(
+                    "Operation {} is not in the export.",
+                    share.operation + 1
+                )));
+            }
+            if !shared.insert(share.operation) {
+                return Err(invalid("Listed twice for the same operation."));
+            }
+        }
+
+        let mut claimed = HashSet::new();
+        for (i, claim) in self.claims.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("claims", format!("Claim {}: {}", i + 1, message))
+            };
+            validate_reimbursement(claim.status, claim.reimbursement.is_some())
+                .map_err(|e| invalid(&e.message))?;
+            for operation in std::iter::once(claim.operation).chain(claim.reimbursement) {
+                if operation >= self.operations.len() {
+                    return Err(invalid(&format!(
+                        "Operation {} is not in the export.",
+                        operation + 1
+                    )));
+                }
+            }
+            if claim.reimbursement == Some(claim.operation) {
+                return Err(invalid("A reimbursement can't pay itself back."));
+            }
+            if !claimed.insert(claim.operation) {
+                return Err(invalid("Listed twice for the same operation."));
+            }
+        }
+
+        let mut goals = HashSet::new();
+        let mut saving = HashSet::new();
+        for (i, goal) in self.goals.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("goals", format!("Goal {}: {}", i + 1, message))
+            };
+            goal.validate().map_err(|e| invalid(&e.message))?;
+            if !currencies.contains_key(goal.account.as_str()) {
+                return Err(invalid(&format!(
+                    "Account {} is not in the export.",
+                    goal.account
+                )));
+            }
+            if !goals.insert(&goal.name) {
+                return Err(invalid("Listed twice."));
+            }
+            if !saving.insert(&goal.account) {
+                return Err(invalid(&format!(
+                    "Account {} already saves towards another goal.",
+                    goal.account
+                )));
+            }
+        }
+
+        let mut recurring = HashSet::new();
+        for (i, item) in self.recurring.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new(
+                    "recurring",
+                    format!("Recurring operation {}: {}", i + 1, message),
+                )
+            };
+            item.validate().map_err(|e| invalid(&e.message))?;
+            let from = currencies.get(item.from.as_str());
+            let to = currencies.get(item.to.as_str());
+            let (from, to) = match (from, to) {
+                (Some(from), Some(to)) => (from, to),
+                (None, _) => {
+                    return Err(invalid(&format!(
+                        "Account {} is not in the export.",
+                        item.from
+                    )))
+                }
+                (_, None) => {
+                    return Err(invalid(&format!(
+                        "Account {} is not in the export.",
+                        item.to
+                    )))
+                }
+            };
+            if from != to {
+                return Err(invalid(&format!(
+                    "{} is in {} but {} is in {}.",
+                    item.from, from, item.to, to
+                )));
+            }
+            if let Some(category) = &item.category {
+                if !self.categories.iter().any(|c| c.name == *category) {
+                    return Err(invalid(&format!(
+                        "Category {} is not in the export.",
+                        category
+                    )));
+                }
+            }
+            if !recurring.insert(&item.name) {
+                return Err(invalid("Listed twice."));
+            }
+        }
+
+        let mut dated = HashSet::new();
+        for (i, rate) in self.rates.iter().enumerate() {
+            let invalid = |message: &str| {
+                ValidationError::new("rates", format!("Rate {}: {}", i + 1, message))
+            };
+            rate.validate().map_err(|e| invalid(&e.message))?;
+            if !dated.insert((&rate.base, &rate.quote, &rate.date)) {
+                return Err(invalid("Listed twice for the same date."));
+            }
+        }
+
+        for account in &self.accounts {
+            let expected = balances[account.name.as_str()];
+            if account.balance != expected {
+                return Err(ValidationError::new(
+                    "accounts",
+                    format!(
+                        "Balance of {} is {} but its operations add up to {}.",
+                        account.name,
+                        Money::new(account.balance, &account.currency),
+                        Money::new(expected, &account.currency)
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_unique<'a>(
+    field: &'static str,
+    names: impl Iterator<Item = &'a str>,
+) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+    for name in names {
+        validate_name(field, name)?;
+        if !seen.insert(name) {
+            return Err(ValidationError::new(
+                field,
+                format!("{} is listed twice.", name),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_unique_symbols(securities: &[Security]) -> Result<(), ValidationError> {
+    let mut seen = HashSet::new();
+    for security in securities {
+        security
+            .validate()
+            .map_err(|e| ValidationError::new("securities", e.message))?;
+        if !seen.insert(&security.symbol) {
+            return Err(ValidationError::new(
+                "securities",
+                format!("{} is listed twice.", security.symbol),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Accepts tickers and fund codes such as `BRK.B` or `510300`: uppercase letters, digits, dots
+/// and dashes.
+fn validate_symbol(field: &'static str, symbol: &str) -> Result<(), ValidationError> {
+    if symbol.is_empty()
+        || symbol.len() > MAX_SYMBOL_LEN
+        || !symbol
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '.' || c == '-')
+    {
+        return Err(ValidationError::new(
+            field,
+            format!(
+                "{} is not a symbol of up to {} uppercase letters, digits, dots or dashes.",
+                symbol, MAX_SYMBOL_LEN
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Accepts ISO 4217 codes, without checking they're assigned.
+fn validate_currency(field: &'static str, code: &str) -> Result<(), ValidationError> {
+    if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(ValidationError::new(
+            field,
+            format!("{} is not a three-letter currency code.", code),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Accepts `YYYY-MM-DD` dates that exist in the Gregorian calendar.
+fn validate_date(field: &'static str, date: &str) -> Result<(), ValidationError> {
+    let invalid = || ValidationError::new(field, format!("{} is not a YYYY-MM-DD date.", date));
+    let parts: Vec<&str> = date.split('-').collect();
+    if parts.len() != 3
+        || [4, 2, 2]
+            .iter()
+            .zip(&parts)
+            .any(|(len, part)| part.len() != *len || !part.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Err(invalid());
+    }
+    let number = |part: &str| part.parse::<u32>().map_err(|_| invalid());
+    let (year, month, day) = (number(parts[0])?, number(parts[1])?, number(parts[2])?);
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days {
+        return Err(invalid());
+    }
+
+    Ok(())
+}
+
+/// Accepts IANA names such as `Asia/Shanghai`.
+fn validate_timezone(field: &'static str, name: &str) -> Result<(), ValidationError> {
+    match parse_timezone(name) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new(
+            field,
+            format!("{} is not a known timezone.", name),
+        )),
+    }
+}
+
+fn validate_name(field: &'static str, name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() {
+        return Err(ValidationError::new(field, "Name can't be empty."));
+    }
+    if name.trim() != name {
+        return Err(ValidationError::new(
+            field,
+            "Name can't start or end with spaces.",
+        ));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(ValidationError::new(
+            field,
+            format!("Name is longer than {} characters.", MAX_NAME_LEN),
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(ValidationError::new(
+            field,
+            "Name can't contain control characters.",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExportedClaim, ExportedShare, Frequency, LedgerSettings, Leg, MemberShare};
+
+    const NOW: i64 = 1_585_699_200_000;
+
+    type Mutation = fn(&mut Operation);
+    type ExportMutation = fn(&mut LedgerExport);
+
+    fn operation() -> Operation {
+        Operation {
+            from: "Bank".to_owned(),
+            to: "Cash".to_owned(),
+            comment: "ATM".to_owned(),
+            amount: 10_000,
+            datetime: NOW,
+            legs: Vec::new(),
+            currency: None,
+            to_amount: None,
+        }
+    }
+
+    /// Buys dollars from the bank at 7 yuan each.
+    fn exchange() -> Operation {
+        let mut op = operation();
+        op.to = "Dollars".to_owned();
+        op.amount = 7_000;
+        op.currency = Some("CNY".to_owned());
+        op.to_amount = Some(1_000);
+        op
+    }
+
+    fn leg(account: Option<&str>, category: Option<&str>, amount: i64) -> Leg {
+        Leg {
+            account: account.map(str::to_owned),
+            category: category.map(str::to_owned),
+            amount,
+            memo: String::new(),
+        }
+    }
+
+    /// A receipt paid from Bank, partly groceries at the shop and partly cash back.
+    fn split() -> Operation {
+        let mut op = operation();
+        op.to = "Shop".to_owned();
+        op.legs = vec![
+            leg(None, Some("Food"), 7_000),
+            leg(Some("Cash"), None, 3_000),
+        ];
+        op
+    }
+
+    #[test]
+    fn test_valid_operation() {
+        assert_eq!(operation().validate(NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_operations() {
+        let cases: Vec<(Mutation, &str)> = vec![
+            (|op| op.to = op.from.clone(), "to"),
+            (|op| op.from = " ".to_owned(), "from"),
+            (|op| op.amount = 0, "amount"),
+            (|op| op.amount = -1, "amount"),
+            (|op| op.amount = MAX_AMOUNT + 1, "amount"),
+            (|op| op.comment = "x".repeat(MAX_COMMENT_LEN + 1), "comment"),
+            (|op| op.datetime = 0, "datetime"),
+            (|op| op.datetime = NOW + MAX_FUTURE_MILLIS + 1, "datetime"),
+            (|op| op.currency = Some("yuan".to_owned()), "currency"),
+            (|op| op.to_amount = Some(0), "to_amount"),
+        ];
+
+        for (mutate, field) in cases {
+            let mut op = operation();
+            mutate(&mut op);
+            assert_eq!(op.validate(NOW).unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_split_operations() {
+        assert_eq!(split().validate(NOW), Ok(()));
+        assert_eq!(split().credits(), vec![("Shop", 7_000), ("Cash", 3_000)]);
+        assert_eq!(operation().credits(), vec![("Cash", 10_000)]);
+
+        let cases: Vec<Mutation> = vec![
+            |op| op.legs[0].amount = 6_000,
+            |op| op.legs[1].amount = 0,
+            |op| op.legs[0].amount = 13_000,
+            |op| op.legs[1].account = Some("Bank".to_owned()),
+            |op| op.legs[0].category = Some(" ".to_owned()),
+            |op| op.legs[0].memo = "x".repeat(MAX_COMMENT_LEN + 1),
+            |op| {
+                op.amount = 100 * MAX_LEGS as i64;
+                op.legs = (0..=MAX_LEGS).map(|_| leg(None, None, 100)).collect();
+            },
+        ];
+        for mutate in cases {
+            let mut op = split();
+            mutate(&mut op);
+            assert_eq!(op.validate(NOW).unwrap_err().field, "legs");
+        }
+    }
+
+    #[test]
+    fn test_currencies() {
+        let currencies: HashMap<&str, &str> = vec![
+            ("Bank", "CNY"),
+            ("Cash", "CNY"),
+            ("Shop", "CNY"),
+            ("Dollars", "USD"),
+        ]
+        .into_iter()
+        .collect();
+        let check = |op: &Operation| op.validate_currencies(&currencies);
+
+        assert_eq!(exchange().validate(NOW), Ok(()));
+        assert_eq!(check(&exchange()), Ok("CNY".to_owned()));
+        assert_eq!(check(&operation()), Ok("CNY".to_owned()));
+        assert_eq!(exchange().credits(), vec![("Dollars", 1_000)]);
+        assert_eq!(exchange().implied_rate(), Some(1_000.0 / 7_000.0));
+
+        let cases: Vec<(Mutation, &str)> = vec![
+            (|op| op.currency = Some("USD".to_owned()), "currency"),
+            (|op| op.to_amount = None, "to_amount"),
+            (|op| op.to = "Cash".to_owned(), "to_amount"),
+        ];
+        for (mutate, field) in cases {
+            let mut op = exchange();
+            mutate(&mut op);
+            assert_eq!(check(&op).unwrap_err().field, field);
+        }
+        let mut op = split();
+        op.legs[1].account = Some("Dollars".to_owned());
+        assert_eq!(check(&op).unwrap_err().field, "legs");
+        op.to_amount = Some(100);
+        assert_eq!(op.validate(NOW).unwrap_err().field, "to_amount");
+    }
+
+    fn export() -> LedgerExport {
+        LedgerExport {
+            version: EXPORT_VERSION,
+            exported_at: NOW,
+            settings: LedgerSettings {
+                name: "Home".to_owned(),
+                currency: "CNY".to_owned(),
+                timezone: "Asia/Shanghai".to_owned(),
+            },
+            accounts: vec![
+                Account {
+                    name: "Bank".to_owned(),
+                    balance: -27_000,
+                    currency: "CNY".to_owned(),
+                },
+                Account {
+                    name: "Cash".to_owned(),
+                    balance: 13_000,
+                    currency: "CNY".to_owned(),
+                },
+                Account {
+                    name: "Shop".to_owned(),
+                    balance: 7_000,
+                    currency: "CNY".to_owned(),
+                },
+                Account {
+                    name: "Dollars".to_owned(),
+                    balance: 1_000 - 600 + 250,
+                    currency: "USD".to_owned(),
+                },
+            ],
+            categories: vec![Category {
+                name: "Food".to_owned(),
+            }],
+            operations: vec![operation(), split(), exchange()],
+            rates: vec![rate("2020-03-31", 710_000_000)],
+            securities: vec![Security {
+                symbol: "VTI".to_owned(),
+                name: "Vanguard Total Stock Market".to_owned(),
+                currency: "USD".to_owned(),
+            }],
+            trades: vec![
+                trade(TradeSide::Buy, 30_000, 600),
+                trade(TradeSide::Sell, 10_000, 250),
+            ],
+            prices: vec![Price {
+                symbol: "VTI".to_owned(),
+                date: "2020-03-31".to_owned(),
+                price: 195_000_000,
+            }],
+            credit_cards: vec![card()],
+            installment_plans: vec![plan()],
+            shared_expenses: vec![ExportedShare {
+                operation: 0,
+                expense: shared(),
+            }],
+            claims: vec![
+                ExportedClaim {
+                    operation: 1,
+                    status: ClaimStatus::Paid,
+                    reimbursement: Some(0),
+                },
+                ExportedClaim {
+                    operation: 2,
+                    status: ClaimStatus::Submitted,
+                    reimbursement: None,
+                },
+            ],
+            goals: vec![goal()],
+            recurring: vec![rent()],
+        }
+    }
+
+    fn rent() -> RecurringOperation {
+        RecurringOperation {
+            name: "Rent".to_owned(),
+            from: "Bank".to_owned(),
+            to: "Shop".to_owned(),
+            amount: 300_000,
+            frequency: Frequency::Monthly,
+            start: "2020-01-31".to_owned(),
+            end: None,
+            category: Some("Food".to_owned()),
+        }
+    }
+
+    fn goal() -> Goal {
+        Goal {
+            name: "Holiday".to_owned(),
+            account: "Cash".to_owned(),
+            target: 500_000,
+            target_date: "2020-12-31".to_owned(),
+        }
+    }
+
+    fn shared() -> SharedExpense {
+        SharedExpense {
+            paid_by: "Alice".to_owned(),
+            method: ShareMethod::Shares,
+            shares: vec![
+                MemberShare {
+                    member: "Alice".to_owned(),
+                    share: 1,
+                },
+                MemberShare {
+                    member: "Bob".to_owned(),
+                    share: 2,
+                },
+            ],
+        }
+    }
+
+    fn plan() -> InstallmentPlan {
+        InstallmentPlan {
+            name: "Phone".to_owned(),
+            account: "Bank".to_owned(),
+            from: "Cash".to_owned(),
+            cost_account: Some("Shop".to_owned()),
+            principal: 600_000,
+            annual_rate: 0.0,
+            fee: 3_600,
+            term: 12,
+            first_due: "2020-04-25".to_owned(),
+            paid: 0,
+        }
+    }
+
+    fn card() -> CreditCard {
+        CreditCard {
+            account: "Bank".to_owned(),
+            statement_day: 5,
+            due_day: 25,
+            limit: 5_000_000,
+            pay_from: Some("Cash".to_owned()),
+        }
+    }
+
+    fn trade(side: TradeSide, quantity: i64, amount: i64) -> Trade {
+        Trade {
+            account: "Dollars".to_owned(),
+            symbol: "VTI".to_owned(),
+            side,
+            quantity,
+            amount,
+            datetime: NOW,
+            comment: String::new(),
+        }
+    }
+
+    fn rate(date: &str, rate: i64) -> ExchangeRate {
+        ExchangeRate {
+            base: "USD".to_owned(),
+            quote: "CNY".to_owned(),
+            date: date.to_owned(),
+            rate,
+        }
+    }
+
+    #[test]
+    fn test_valid_export() {
+        assert_eq!(export().validate(NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_invalid_exports() {
+        let cases: Vec<(ExportMutation, &str)> = vec![
+            (|doc| doc.version = EXPORT_VERSION + 1, "version"),
+            (|doc| doc.settings.name = String::new(), "settings"),
+            (|doc| doc.settings.currency = "RMB1".to_owned(), "settings"),
+            (|doc| doc.settings.timezone = "CST".to_owned(), "settings"),
+            (
+                |doc| doc.accounts[3].currency = "usd".to_owned(),
+                "accounts",
+            ),
+            (
+                |doc| doc.accounts[3].currency = "CNY".to_owned(),
+                "operations",
+            ),
+            (|doc| doc.operations[2].to_amount = None, "operations"),
+            (|doc| doc.accounts[1].name = "Bank".to_owned(), "accounts"),
+            (|doc| doc.accounts[0].balance = 0, "accounts"),
+            (
+                |doc| {
+                    doc.categories.push(Category {
+                        name: "Food".to_owned(),
+                    })
+                },
+                "categories",
+            ),
+            (|doc| doc.operations[0].to = "Card".to_owned(), "operations"),
+            (|doc| doc.operations[0].amount = 0, "operations"),
+            (
+                |doc| doc.operations[1].legs[0].category = Some("Fun".to_owned()),
+                "operations",
+            ),
+            (
+                |doc| doc.operations[1].legs[1].account = Some("Card".to_owned()),
+                "operations",
+            ),
+            (|doc| doc.rates[0].rate = 0, "rates"),
+            (
+                |doc| doc.securities[0].symbol = "vti".to_owned(),
+                "securities",
+            ),
+            (|doc| doc.trades[0].account = "Bank".to_owned(), "trades"),
+            (|doc| doc.trades[1].quantity = 40_000, "trades"),
+            (|doc| doc.trades[0].datetime = NOW + 1, "trades"),
+            (|doc| doc.trades[1].symbol = "VT".to_owned(), "trades"),
+            (|doc| doc.trades[1].amount = 300, "accounts"),
+            (|doc| doc.prices[0].symbol = "VT".to_owned(), "prices"),
+            (|doc| doc.prices.push(doc.prices[0].clone()), "prices"),
+            (
+                |doc| doc.credit_cards[0].account = "Card".to_owned(),
+                "credit_cards",
+            ),
+            (|doc| doc.credit_cards.push(card()), "credit_cards"),
+            (
+                |doc| doc.credit_cards[0].pay_from = Some("Savings".to_owned()),
+                "credit_cards",
+            ),
+            (
+                |doc| doc.credit_cards[0].pay_from = Some("Dollars".to_owned()),
+                "credit_cards",
+            ),
+            (
+                |doc| doc.installment_plans[0].from = "Card".to_owned(),
+                "installment_plans",
+            ),
+            (
+                |doc| doc.installment_plans[0].cost_account = Some("Dollars".to_owned()),
+                "installment_plans",
+            ),
+            (
+                |doc| doc.installment_plans.push(plan()),
+                "installment_plans",
+            ),
+            (
+                |doc| doc.shared_expenses[0].operation = 3,
+                "shared_expenses",
+            ),
+            (
+                |doc| doc.shared_expenses.push(doc.shared_expenses[0].clone()),
+                "shared_expenses",
+            ),
+            (|doc| doc.claims[1].operation = 3, "claims"),
+            (|doc| doc.claims[0].reimbursement = Some(3), "claims"),
+            (|doc| doc.claims[0].reimbursement = Some(1), "claims"),
+            (|doc| doc.claims[1].operation = 1, "claims"),
+            (|doc| doc.claims[1].status = ClaimStatus::Paid, "claims"),
+            (|doc| doc.goals[0].account = "Savings".to_owned(), "goals"),
+            (|doc| doc.goals[0].target = 0, "goals"),
+            (|doc| doc.goals.push(goal()), "goals"),
+            (
+                |doc| {
+                    doc.goals.push(Goal {
+                        name: "Car".to_owned(),
+                        ..goal()
+                    })
+                },
+                "goals",
+            ),
+            (
+                |doc| doc.recurring[0].to = "Dollars".to_owned(),
+                "recurring",
+            ),
+            (
+                |doc| doc.recurring[0].from = "Savings".to_owned(),
+                "recurring",
+            ),
+            (
+                |doc| doc.recurring[0].category = Some("Rent".to_owned()),
+                "recurring",
+            ),
+            (|doc| doc.recurring[0].amount = 0, "recurring"),
+            (|doc| doc.recurring.push(rent()), "recurring"),
+            (
+                |doc| doc.rates.push(rate("2020-03-31", 720_000_000)),
+                "rates",
+            ),
+        ];
+
+        for (mutate, field) in cases {
+            let mut doc = export();
+            mutate(&mut doc);
+            assert_eq!(doc.validate(NOW).unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_rate_rules() {
+        assert_eq!(rate("2024-02-29", 710_000_000).validate(), Ok(()));
+        let cases: Vec<(ExchangeRate, &str)> = vec![
+            (rate("2023-02-29", 710_000_000), "date"),
+            (rate("2024-13-01", 710_000_000), "date"),
+            (rate("2024-1-01", 710_000_000), "date"),
+            (rate("01/02/2024", 710_000_000), "date"),
+            (rate("2024-01-01", -100_000_000), "rate"),
+            (rate("2024-01-01", 0), "rate"),
+            (rate("2024-01-01", MAX_RATE + 1), "rate"),
+            (
+                ExchangeRate {
+                    quote: "USD".to_owned(),
+                    ..rate("2024-01-01", 100_000_000)
+                },
+                "quote",
+            ),
+        ];
+        for (rate, field) in cases {
+            assert_eq!(rate.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_credit_card_rules() {
+        assert_eq!(card().validate(), Ok(()));
+        let cases: Vec<(CreditCard, &str)> = vec![
+            (
+                CreditCard {
+                    statement_day: 0,
+                    ..card()
+                },
+                "statement_day",
+            ),
+            (
+                CreditCard {
+                    due_day: MAX_BILLING_DAY + 1,
+                    ..card()
+                },
+                "due_day",
+            ),
+            (
+                CreditCard {
+                    limit: -1,
+                    ..card()
+                },
+                "limit",
+            ),
+            (
+                CreditCard {
+                    account: " Card".to_owned(),
+                    ..card()
+                },
+                "account",
+            ),
+            (
+                CreditCard {
+                    pay_from: Some("Bank".to_owned()),
+                    ..card()
+                },
+                "pay_from",
+            ),
+        ];
+        for (card, field) in cases {
+            assert_eq!(card.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_installment_plan_rules() {
+        assert_eq!(plan().validate(), Ok(()));
+        let interest_free = InstallmentPlan {
+            cost_account: None,
+            fee: 0,
+            ..plan()
+        };
+        assert_eq!(interest_free.validate(), Ok(()));
+        let cases: Vec<(InstallmentPlan, &str)> = vec![
+            (
+                InstallmentPlan {
+                    name: String::new(),
+                    ..plan()
+                },
+                "name",
+            ),
+            (
+                InstallmentPlan {
+                    from: "Bank".to_owned(),
+                    ..plan()
+                },
+                "from",
+            ),
+            (
+                InstallmentPlan {
+                    principal: 0,
+                    ..plan()
+                },
+                "principal",
+            ),
+            (
+                InstallmentPlan {
+                    annual_rate: f64::NAN,
+                    ..plan()
+                },
+                "annual_rate",
+            ),
+            (InstallmentPlan { fee: -1, ..plan() }, "fee"),
+            (
+                InstallmentPlan {
+                    annual_rate: 4.5,
+                    ..interest_free.clone()
+                },
+                "cost_account",
+            ),
+            (
+                InstallmentPlan {
+                    term: MAX_TERM + 1,
+                    ..plan()
+                },
+                "term",
+            ),
+            (
+                InstallmentPlan {
+                    first_due: "2020-02-30".to_owned(),
+                    ..plan()
+                },
+                "first_due",
+            ),
+            (InstallmentPlan { paid: 13, ..plan() }, "paid"),
+        ];
+        for (plan, field) in cases {
+            assert_eq!(plan.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_goal_rules() {
+        assert_eq!(goal().validate(), Ok(()));
+        let cases: Vec<(Goal, &str)> = vec![
+            (
+                Goal {
+                    name: " ".to_owned(),
+                    ..goal()
+                },
+                "name",
+            ),
+            (
+                Goal {
+                    target: -1,
+                    ..goal()
+                },
+                "target",
+            ),
+            (
+                Goal {
+                    target: MAX_AMOUNT + 1,
+                    ..goal()
+                },
+                "target",
+            ),
+            (
+                Goal {
+                    target_date: "2020-02-30".to_owned(),
+                    ..goal()
+                },
+                "target_date",
+            ),
+        ];
+        for (goal, field) in cases {
+            assert_eq!(goal.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_recurring_rules() {
+        assert_eq!(rent().validate(), Ok(()));
+        let cases: Vec<(RecurringOperation, &str)> = vec![
+            (
+                RecurringOperation {
+                    name: String::new(),
+                    ..rent()
+                },
+                "name",
+            ),
+            (
+                RecurringOperation {
+                    to: "Bank".to_owned(),
+                    ..rent()
+                },
+                "to",
+            ),
+            (
+                RecurringOperation {
+                    amount: -1,
+                    ..rent()
+                },
+                "amount",
+            ),
+            (
+                RecurringOperation {
+                    start: "2020-02-30".to_owned(),
+                    ..rent()
+                },
+                "start",
+            ),
+            (
+                RecurringOperation {
+                    end: Some("2019-12-31".to_owned()),
+                    ..rent()
+                },
+                "end",
+            ),
+            (
+                RecurringOperation {
+                    category: Some(" ".to_owned()),
+                    ..rent()
+                },
+                "category",
+            ),
+        ];
+        for (item, field) in cases {
+            assert_eq!(item.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_claim_rules() {
+        let claim = |status: ClaimStatus, reimbursement: Option<i64>| Claim {
+            status,
+            reimbursement,
+        };
+        assert_eq!(claim(ClaimStatus::Pending, None).validate(), Ok(()));
+        assert_eq!(claim(ClaimStatus::Paid, Some(7)).validate(), Ok(()));
+        for invalid in &[
+            claim(ClaimStatus::Paid, None),
+            claim(ClaimStatus::Submitted, Some(7)),
+        ] {
+            assert_eq!(invalid.validate().unwrap_err().field, "reimbursement");
+        }
+
+        let reimbursement = |expenses: Vec<i64>| Reimbursement {
+            operation: 7,
+            expenses,
+        };
+        assert_eq!(reimbursement(vec![1, 2]).validate(), Ok(()));
+        for expenses in [vec![], vec![1, 1], vec![1, 7], vec![1; MAX_REIMBURSED + 1]] {
+            let e = reimbursement(expenses).validate().unwrap_err();
+            assert_eq!(e.field, "expenses");
+        }
+    }
+
+    #[test]
+    fn test_shared_expense_rules() {
+        assert_eq!(shared().validate(), Ok(()));
+        let percent = |shares: &[i64]| SharedExpense {
+            method: ShareMethod::Percent,
+            shares: shares
+                .iter()
+                .enumerate()
+                .map(|(i, share)| MemberShare {
+                    member: format!("Member {}", i + 1),
+                    share: *share,
+                })
+                .collect(),
+            ..shared()
+        };
+        assert_eq!(percent(&[3_333, 3_333, 3_334]).validate(), Ok(()));
+        let cases: Vec<(SharedExpense, &str)> = vec![
+            (percent(&[5_000, 4_000]), "shares"),
+            (percent(&[]), "shares"),
+            (percent(&[10_000, 0]), "shares"),
+            (
+                SharedExpense {
+                    paid_by: String::new(),
+                    ..shared()
+                },
+                "paid_by",
+            ),
+            (
+                {
+                    let mut twice = shared();
+                    twice.shares[1].member = "Alice".to_owned();
+                    twice
+                },
+                "shares",
+            ),
+        ];
+        for (expense, field) in cases {
+            assert_eq!(expense.validate().unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_investment_rules() {
+        assert_eq!(trade(TradeSide::Buy, 1, 0).validate(NOW), Ok(()));
+        let cases: Vec<(Trade, &str)> = vec![
+            (trade(TradeSide::Buy, 0, 100), "quantity"),
+            (trade(TradeSide::Sell, MAX_QUANTITY + 1, 100), "quantity"),
+            (trade(TradeSide::Buy, 1, -1), "amount"),
+            (
+                Trade {
+                    symbol: "BRK B".to_owned(),
+                    ..trade(TradeSide::Buy, 1, 100)
+                },
+                "symbol",
+            ),
+            (
+                Trade {
+                    datetime: 0,
+                    ..trade(TradeSide::Buy, 1, 100)
+                },
+                "datetime",
+            ),
+        ];
+        for (trade, field) in cases {
+            assert_eq!(trade.validate(NOW).unwrap_err().field, field);
+        }
+        assert_eq!(trade(TradeSide::Sell, 5, 7).signed_quantity(), -5);
+        assert_eq!(trade(TradeSide::Sell, 5, 7).signed_amount(), 7);
+
+        let price = |symbol: &str, price| Price {
+            symbol: symbol.to_owned(),
+            date: "2024-01-02".to_owned(),
+            price,
+        };
+        assert_eq!(price("BRK.B", 41_050_000_000).validate(), Ok(()));
+        assert_eq!(price("510300", 0).validate().unwrap_err().field, "price");
+        assert_eq!(
+            price("510300", MAX_PRICE + 1).validate().unwrap_err().field,
+            "price"
+        );
+        assert_eq!(
+            price(&"X".repeat(MAX_SYMBOL_LEN + 1), 100_000_000)
+                .validate()
+                .unwrap_err()
+                .field,
+            "symbol"
+        );
+    }
+
+    #[test]
+    fn test_account_rules() {
+        let account = |name: &str, balance| Account {
+            name: name.to_owned(),
+            balance,
+            currency: "CNY".to_owned(),
+        };
+
+        assert_eq!(account("招商银行", 0).validate(), Ok(()));
+        assert_eq!(account("", 0).validate().unwrap_err().field, "name");
+        assert_eq!(account(" Cash", 0).validate().unwrap_err().field, "name");
+        assert_eq!(account("Ca\tsh", 0).validate().unwrap_err().field, "name");
+        assert_eq!(
+            account("Cash", 100).validate().unwrap_err().field,
+            "balance"
+        );
+        let mut dollars = account("Dollars", 0);
+        dollars.currency = "US$".to_owned();
+        assert_eq!(dollars.validate().unwrap_err().field, "currency");
+    }
+
+    #[test]
+    fn test_ledger_rules() {
+        let ledger = |timezone: &str| NewLedger {
+            name: "Home".to_owned(),
+            currency: "CNY".to_owned(),
+            timezone: timezone.to_owned(),
+        };
+
+        assert_eq!(ledger("Asia/Shanghai").validate(), Ok(()));
+        assert_eq!(ledger("Europe/London").validate(), Ok(()));
+        assert_eq!(ledger("UTC+8").validate().unwrap_err().field, "timezone");
+        assert_eq!(ledger("").validate().unwrap_err().field, "timezone");
+    }
+
+    #[test]
+    fn test_forecast_months() {
+        let query = |months| ForecastQuery {
+            months,
+            history: false,
+        };
+
+        assert_eq!(query(1).validate(), Ok(()));
+        assert_eq!(query(MAX_FORECAST_MONTHS).validate(), Ok(()));
+        assert_eq!(query(0).validate().unwrap_err().field, "months");
+        assert_eq!(
+            query(MAX_FORECAST_MONTHS + 1).validate().unwrap_err().field,
+            "months"
+        );
+    }
+
+    #[test]
+    fn test_search_terms() {
+        let query = |q: &str| SearchQuery { q: q.to_owned() };
+
+        assert_eq!(query("IKEA, sofa!").terms(), vec!["ikea", "sofa"]);
+        assert_eq!(query("宜家 \"x*\"").terms(), vec!["宜家", "x"]);
+        assert_eq!(query(" -*- ").validate().unwrap_err().field, "q");
+        assert_eq!(
+            query(&"a".repeat(MAX_QUERY_LEN + 1))
+                .validate()
+                .unwrap_err()
+                .field,
+            "q"
+        );
+    }
+
+    #[test]
+    fn test_attachment_rules() {
+        let attachment = |name: &str, content_type: &str, size| NewAttachment {
+            name: name.to_owned(),
+            content_type: content_type.to_owned(),
+            size,
+        };
+
+        assert_eq!(
+            attachment("发票.pdf", "application/pdf", 1000).validate(),
+            Ok(())
+        );
+        let cases = vec![
+            (attachment(" ", "image/png", 1), "name"),
+            (attachment("../receipt.png", "image/png", 1), "name"),
+            (attachment("receipt.html", "text/html", 1), "content_type"),
+            (attachment("receipt.png", "image/png", 0), "size"),
+            (
+                attachment("receipt.png", "image/png", MAX_ATTACHMENT_SIZE + 1),
+                "size",
+            ),
+        ];
+        for (attachment, field) in cases {
+            assert_eq!(attachment.validate().unwrap_err().field, field);
+        }
+    }
+}
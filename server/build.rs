@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Generates a list of `(version, sql)` pairs for the SQLite migrations in `db/` and the
+/// PostgreSQL ones in `db/postgres/`, so the server doesn't need the files at runtime.
+fn main() -> io::Result<()> {
+    let db = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("../db");
+    generate(&db, "sqlite_migrations.rs")?;
+    generate(&db.join("postgres"), "postgres_migrations.rs")
+}
+
+fn generate(dir: &Path, out: &str) -> io::Result<()> {
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<_>>()?;
+    files.retain(|path| path.extension().and_then(|ext| ext.to_str()) == Some("sql"));
+    files.sort();
+
+    let mut generated = String::from("&[\n");
+    for path in files {
+        let version = path.file_stem().and_then(|stem| stem.to_str()).unwrap();
+        let path = path.canonicalize()?;
+        println!("cargo:rerun-if-changed={}", path.display());
+        generated.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            version,
+            path.display().to_string()
+        ));
+    }
+    generated.push(']');
+
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join(out),
+        generated,
+    )
+}
@@ -0,0 +1,218 @@
+use crate::data::Storage;
+use crate::error::ServerError;
+use chrono::Utc;
+use entities::{Attachment, NewAttachment};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+/// Stores attachment content in the database next to its details or, when a directory is
+/// configured, as files in it named by their SHA-256.
+#[derive(Clone)]
+pub struct Attachments {
+    dir: Option<PathBuf>,
+}
+
+impl Attachments {
+    pub fn new(dir: Option<String>) -> Self {
+        Attachments {
+            dir: dir.map(PathBuf::from),
+        }
+    }
+
+    /// Stores a validated upload for an operation of the ledger.
+    pub fn add(
+        &self,
+        db: &dyn Storage,
+        ledger: i64,
+        operation: i64,
+        attachment: &NewAttachment,
+        content: &[u8],
+    ) -> Result<Attachment, ServerError> {
+        let sha256 = hash(content);
+        let created_at = Utc::now().timestamp_millis();
+        if self.dir.is_none() {
+            return db.add_attachment(
+                ledger,
+                operation,
+                attachment,
+                &sha256,
+                created_at,
+                Some(content),
+            );
+        }
+
+        let path = self.path(&sha256)?;
+        let existed = path.exists();
+        if !existed {
+            fs::create_dir_all(path.parent().unwrap())?;
+            // Write under a temporary name so a crash never leaves a truncated file behind
+            // under a hash it doesn't match.
+            let partial = path.with_extension("partial");
+            fs::write(&partial, content)?;
+            fs::rename(&partial, &path)?;
+        }
+
+        db.add_attachment(ledger, operation, attachment, &sha256, created_at, None)
+            .inspect_err(|_| {
+                if !existed {
+                    let _ = fs::remove_file(&path);
+                }
+            })
+    }
+
+    /// Returns an attachment of the ledger with its content.
+    pub fn read(
+        &self,
+        db: &dyn Storage,
+        ledger: i64,
+        id: i64,
+    ) -> Result<(Attachment, Vec<u8>), ServerError> {
+        let (attachment, content) = db
+            .get_attachment(ledger, id)?
+            .ok_or_else(unknown_attachment)?;
+        let content = match content {
+            Some(content) => content,
+            None => fs::read(self.path(&attachment.sha256)?)?,
+        };
+
+        Ok((attachment, content))
+    }
+
+    /// Deletes an attachment of the ledger, and its file once no other attachment shares it.
+    pub fn delete(&self, db: &dyn Storage, ledger: i64, id: i64) -> Result<(), ServerError> {
+        let (attachment, shared) = db
+            .delete_attachment(ledger, id)?
+            .ok_or_else(unknown_attachment)?;
+        if !shared && self.dir.is_some() {
+            let path = self.path(&attachment.sha256)?;
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spreads files over subdirectories by the first two hex digits of their hash.
+    fn path(&self, sha256: &str) -> Result<PathBuf, ServerError> {
+        let dir = self.dir.as_ref().ok_or_else(|| {
+            ServerError::InternalError(format!(
+                "Content of attachment {} is not in the database and no directory is configured.",
+                sha256
+            ))
+        })?;
+
+        Ok(dir.join(&sha256[..2]).join(sha256))
+    }
+}
+
+fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn unknown_attachment() -> ServerError {
+    ServerError::NotFoundError("Attachment doesn't exist.".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::MemoryStorage;
+    use entities::{Account, Operation, DEFAULT_CURRENCY};
+    use tempfile::TempDir;
+
+    fn receipt() -> NewAttachment {
+        NewAttachment {
+            name: "receipt.png".to_owned(),
+            content_type: "image/png".to_owned(),
+            size: 3,
+        }
+    }
+
+    /// A store whose ledger 1 has one operation, returned with its id.
+    fn store() -> (MemoryStorage, i64) {
+        let db = MemoryStorage::new("123456");
+        for name in &["Bank", "Cash"] {
+            db.add_account(
+                1,
+                &Account {
+                    name: (*name).to_owned(),
+                    balance: 0,
+                    currency: DEFAULT_CURRENCY.to_owned(),
+                },
+            )
+            .unwrap();
+        }
+        let operation = db
+            .record_operation(
+                1,
+                &Operation {
+                    from: "Bank".to_owned(),
+                    to: "Cash".to_owned(),
+                    comment: String::new(),
+                    amount: 100,
+                    datetime: 1_585_699_200_000,
+                    legs: Vec::new(),
+                    currency: None,
+                    to_amount: None,
+                },
+            )
+            .unwrap();
+
+        (db, operation)
+    }
+
+    #[test]
+    fn test_files_are_shared_until_last_delete() {
+        let dir = TempDir::new().unwrap();
+        let attachments = Attachments::new(Some(dir.path().display().to_string()));
+        let (db, operation) = store();
+
+        let first = attachments
+            .add(&db, 1, operation, &receipt(), b"png")
+            .unwrap();
+        let second = attachments
+            .add(&db, 1, operation, &receipt(), b"png")
+            .unwrap();
+        assert_eq!(first.sha256, second.sha256);
+        let path = attachments.path(&first.sha256).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"png");
+
+        attachments.delete(&db, 1, first.id).unwrap();
+        assert_eq!(attachments.read(&db, 1, second.id).unwrap().1, b"png");
+        attachments.delete(&db, 1, second.id).unwrap();
+        assert!(!path.exists());
+        assert!(attachments.read(&db, 1, second.id).is_err());
+    }
+
+    #[test]
+    fn test_failed_add_leaves_no_file() {
+        let dir = TempDir::new().unwrap();
+        let attachments = Attachments::new(Some(dir.path().display().to_string()));
+        let (db, operation) = store();
+
+        assert!(matches!(
+            attachments.add(&db, 1, operation + 1, &receipt(), b"png"),
+            Err(ServerError::NotFoundError(_))
+        ));
+        assert!(!attachments.path(&hash(b"png")).unwrap().exists());
+    }
+
+    #[test]
+    fn test_content_in_database() {
+        let attachments = Attachments::new(None);
+        let (db, operation) = store();
+
+        let attachment = attachments
+            .add(&db, 1, operation, &receipt(), b"png")
+            .unwrap();
+        assert_eq!(attachments.read(&db, 1, attachment.id).unwrap().1, b"png");
+        // Ledgers don't see each other's attachments.
+        assert!(attachments.read(&db, 2, attachment.id).is_err());
+        assert!(attachments.delete(&db, 2, attachment.id).is_err());
+    }
+}
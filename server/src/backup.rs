@@ -0,0 +1,247 @@
+use crate::data::{SqliteStorage, Storage};
+use crate::error::ServerError;
+use crate::migration::Migration;
+use chrono::Utc;
+use entities::BackupFile;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "accounting-";
+const EXTENSION: &str = "db";
+/// How many snapshots to keep when `BACKUP_KEEP` isn't set.
+pub const DEFAULT_KEEP: usize = 7;
+
+/// Timestamped snapshots in a directory, of which only the newest `keep` are kept.
+#[derive(Clone)]
+pub struct Backups {
+    dir: Option<PathBuf>,
+    keep: usize,
+}
+
+impl Backups {
+    pub fn new(dir: Option<String>, keep: usize) -> Self {
+        Backups {
+            dir: dir.map(PathBuf::from),
+            keep: keep.max(1),
+        }
+    }
+
+    /// Takes a snapshot of the running database, then removes the oldest ones beyond `keep`.
+    pub fn create(&self, db: &SqliteStorage) -> Result<BackupFile, ServerError> {
+        let dir = self.dir()?;
+        fs::create_dir_all(dir)?;
+
+        let name = format!(
+            "{}{}.{}",
+            PREFIX,
+            Utc::now().format("%Y%m%dT%H%M%S%.6fZ"),
+            EXTENSION
+        );
+        // Write under a temporary name so a crash never leaves a truncated snapshot behind
+        // that rotation would count as a good one.
+        let partial = dir.join(format!("{}.partial", name));
+        db.backup_to(&partial)?;
+        let path = dir.join(&name);
+        fs::rename(&partial, &path)?;
+        info!("Backed up the database to {}.", path.display());
+
+        self.rotate()?;
+
+        Ok(BackupFile {
+            size: fs::metadata(&path)?.len(),
+            name,
+        })
+    }
+
+    /// Lists the snapshots, oldest first.
+    pub fn list(&self) -> Result<Vec<PathBuf>, ServerError> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(self.dir()?)? {
+            let path = entry?.path();
+            let is_backup = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(PREFIX))
+                && path.extension().and_then(|ext| ext.to_str()) == Some(EXTENSION);
+            if is_backup {
+                files.push(path);
+            }
+        }
+        // Timestamps in the names sort chronologically.
+        files.sort();
+
+        Ok(files)
+    }
+
+    fn rotate(&self) -> Result<(), ServerError> {
+        let files = self.list()?;
+        let excess = files.len().saturating_sub(self.keep);
+        for path in &files[..excess] {
+            info!("Removing old backup {}.", path.display());
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn dir(&self) -> Result<&Path, ServerError> {
+        self.dir
+            .as_deref()
+            .ok_or_else(|| ServerError::BadRequestError("Backups are not configured.".to_owned()))
+    }
+}
+
+/// Replaces the database at `db_addr` with the snapshot at `backup`. Must run while the server
+/// is stopped.
+///
+/// The snapshot has to pass SQLite's integrity check and carry migrations this build knows,
+/// unchanged, so the server can start on it and bring it up to date. The replaced file is kept
+/// next to the database with a `.before-restore` suffix.
+pub fn restore<P: AsRef<Path>, Q: AsRef<Path>>(
+    backup: P,
+    db_addr: Q,
+    migrations: &[Migration],
+) -> Result<(), ServerError> {
+    let backup = backup.as_ref();
+    let db_addr = db_addr.as_ref();
+    validate(backup, migrations)?;
+
+    let restoring = with_suffix(db_addr, ".restoring");
+    fs::copy(backup, &restoring)?;
+    if db_addr.exists() {
+        fs::rename(db_addr, with_suffix(db_addr, ".before-restore"))?;
+    }
+    fs::rename(&restoring, db_addr)?;
+    info!("Restored {} from {}.", db_addr.display(), backup.display());
+
+    Ok(())
+}
+
+fn validate(backup: &Path, migrations: &[Migration]) -> Result<(), ServerError> {
+    let invalid =
+        |reason: &str| ServerError::MigrationError(format!("{} {}", backup.display(), reason));
+    // Opening a missing file would create an empty database.
+    if !backup.is_file() {
+        return Err(invalid("doesn't exist."));
+    }
+
+    let snapshot = SqliteStorage::new(backup);
+    if !snapshot.integrity_check().unwrap_or(false) {
+        return Err(invalid("is not an intact SQLite database."));
+    }
+    let version = snapshot
+        .current_db_version()
+        .ok()
+        .flatten()
+        .ok_or_else(|| invalid("has no recorded migrations."))?;
+    // Fails if the snapshot comes from a newer build or a modified migration.
+    snapshot.pending_migrations(migrations)?;
+    info!("{} is at version {}.", backup.display(), version.version);
+
+    Ok(())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration::Dialect;
+    use entities::{Account, DEFAULT_CURRENCY};
+    use rusqlite::Connection;
+    use tempfile::TempDir;
+
+    fn database(dir: &TempDir) -> SqliteStorage {
+        let db = SqliteStorage::new(dir.path().join("ledger.db"));
+        db.migrate(&crate::migration::embedded(Dialect::Sqlite).unwrap())
+            .unwrap();
+        db
+    }
+
+    fn account(name: &str) -> Account {
+        Account {
+            name: name.to_owned(),
+            balance: 0,
+            currency: DEFAULT_CURRENCY.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_backups_are_rotated() {
+        let dir = TempDir::new().unwrap();
+        let db = database(&dir);
+        let backups = Backups::new(Some(dir.path().join("backups").display().to_string()), 2);
+
+        let first = backups.create(&db).unwrap();
+        assert!(first.size > 0);
+        backups.create(&db).unwrap();
+        let third = backups.create(&db).unwrap();
+
+        let files = backups.list().unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|path| !path.ends_with(&first.name)));
+        assert!(files[1].ends_with(&third.name));
+    }
+
+    #[test]
+    fn test_backup_requires_directory() {
+        let dir = TempDir::new().unwrap();
+        let db = database(&dir);
+
+        assert!(Backups::new(None, DEFAULT_KEEP).create(&db).is_err());
+    }
+
+    #[test]
+    fn test_restore_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let db = database(&dir);
+        let backups = Backups::new(Some(dir.path().join("backups").display().to_string()), 2);
+        db.add_account(1, &account("Cash")).unwrap();
+        let backup = backups.create(&db).unwrap();
+        db.add_account(1, &account("Bank")).unwrap();
+        drop(db);
+
+        let db_addr = dir.path().join("ledger.db");
+        restore(
+            dir.path().join("backups").join(&backup.name),
+            &db_addr,
+            &crate::migration::embedded(Dialect::Sqlite).unwrap(),
+        )
+        .unwrap();
+
+        let accounts = SqliteStorage::new(&db_addr).get_accounts(1).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, "Cash");
+        assert!(with_suffix(&db_addr, ".before-restore").exists());
+    }
+
+    #[test]
+    fn test_restore_rejects_unknown_schema() {
+        let dir = TempDir::new().unwrap();
+        let migrations = crate::migration::embedded(Dialect::Sqlite).unwrap();
+        let target = dir.path().join("target.db");
+
+        let garbage = dir.path().join("garbage.db");
+        fs::write(&garbage, "not a database").unwrap();
+        assert!(restore(&garbage, &target, &migrations).is_err());
+
+        let unrelated = dir.path().join("unrelated.db");
+        Connection::open(&unrelated)
+            .unwrap()
+            .execute_batch("CREATE TABLE A (X INTEGER);")
+            .unwrap();
+        assert!(restore(&unrelated, &target, &migrations).is_err());
+
+        // A snapshot taken by a build with a migration this one doesn't have.
+        database(&dir);
+        let older_build = &migrations[..migrations.len() - 1];
+        assert!(restore(dir.path().join("ledger.db"), &target, older_build).is_err());
+
+        assert!(!target.exists());
+    }
+}
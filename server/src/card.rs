@@ -0,0 +1,150 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use entities::{CardStatement, CreditCard};
+
+/// The share of the statement balance due by the due date, in percent.
+const MINIMUM_PAYMENT_PERCENT: i64 = 10;
+
+/// The last statement date before `today`. A statement closes at the end of its day, so on the
+/// statement day itself the previous month's is still the latest.
+pub fn statement_date(statement_day: u32, today: NaiveDate) -> NaiveDate {
+    let this_month = with_day(today, statement_day);
+    if this_month < today {
+        this_month
+    } else {
+        with_day(previous_month(today), statement_day)
+    }
+}
+
+/// The first due day after the statement date.
+pub fn due_date(due_day: u32, statement_date: NaiveDate) -> NaiveDate {
+    let this_month = with_day(statement_date, due_day);
+    if this_month > statement_date {
+        this_month
+    } else {
+        with_day(next_month(statement_date), due_day)
+    }
+}
+
+/// Where the card stands against the statement closed on `statement_date`. `balance` is the
+/// account's balance now and `changes` how it changed since the statement closed, negative
+/// when spent. Money paid into the card since counts towards the statement first.
+pub fn statement(
+    card: &CreditCard,
+    currency: &str,
+    balance: i64,
+    changes: &[(i64, i64)],
+    statement_date: NaiveDate,
+) -> CardStatement {
+    let since: i64 = changes.iter().map(|(_, amount)| amount).sum();
+    let payments: i64 = changes
+        .iter()
+        .map(|(_, amount)| amount)
+        .filter(|amount| **amount > 0)
+        .sum();
+    let statement_balance = since - balance;
+    let remaining = (statement_balance - payments).max(0);
+    // Rounded up, so paying the minimum never leaves a cent short.
+    let minimum = (statement_balance.max(0) * MINIMUM_PAYMENT_PERCENT + 99) / 100;
+
+    CardStatement {
+        account: card.account.clone(),
+        currency: currency.to_owned(),
+        statement_date: statement_date.format("%Y-%m-%d").to_string(),
+        due_date: due_date(card.due_day, statement_date)
+            .format("%Y-%m-%d")
+            .to_string(),
+        statement_balance,
+        remaining,
+        minimum_payment: (minimum - payments).clamp(0, remaining),
+        owed: -balance,
+        limit: card.limit,
+        available_credit: (card.limit + balance).max(0),
+    }
+}
+
+/// `date` moved to `day`, which billing days keep valid in every month.
+fn with_day(date: NaiveDate, day: u32) -> NaiveDate {
+    date.with_day(day)
+        .expect("billing days exist in every month")
+}
+
+fn previous_month(date: NaiveDate) -> NaiveDate {
+    with_day(date, 1) - Duration::days(1)
+}
+
+fn next_month(date: NaiveDate) -> NaiveDate {
+    with_day(date, 28) + Duration::days(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn card() -> CreditCard {
+        CreditCard {
+            account: "Visa".to_owned(),
+            statement_day: 5,
+            due_day: 25,
+            limit: 500_000,
+            pay_from: None,
+        }
+    }
+
+    #[test]
+    fn test_billing_dates() {
+        assert_eq!(statement_date(5, date("2024-03-10")), date("2024-03-05"));
+        // Still open on the day itself.
+        assert_eq!(statement_date(5, date("2024-03-05")), date("2024-02-05"));
+        assert_eq!(statement_date(28, date("2024-01-03")), date("2023-12-28"));
+
+        assert_eq!(due_date(25, date("2024-03-05")), date("2024-03-25"));
+        // Due the month after when the due day comes first.
+        assert_eq!(due_date(3, date("2024-03-20")), date("2024-04-03"));
+        assert_eq!(due_date(20, date("2024-12-20")), date("2025-01-20"));
+    }
+
+    #[test]
+    fn test_statement() {
+        // $1,200.00 owed at the statement, then $300.00 paid and $50.00 spent.
+        let changes = [(1, 30_000), (2, -5_000)];
+        let statement = statement(&card(), "USD", -95_000, &changes, date("2024-03-05"));
+        assert_eq!(statement.statement_date, "2024-03-05");
+        assert_eq!(statement.due_date, "2024-03-25");
+        assert_eq!(statement.statement_balance, 120_000);
+        assert_eq!(statement.remaining, 90_000);
+        // The $120.00 minimum is covered by the payment.
+        assert_eq!(statement.minimum_payment, 0);
+        assert_eq!(statement.owed, 95_000);
+        assert_eq!(statement.available_credit, 405_000);
+    }
+
+    #[test]
+    fn test_minimum_payment() {
+        let statement = statement_for(-12_345, &[]);
+        // 10% of $123.45, rounded up.
+        assert_eq!(statement.minimum_payment, 1_235);
+        assert_eq!(statement.remaining, 12_345);
+
+        // Never more than what's left.
+        let changes = [(1, 12_000)];
+        let statement = statement_for(-345, &changes);
+        assert_eq!(statement.remaining, 345);
+        assert_eq!(statement.minimum_payment, 0);
+
+        // Nothing owed on a card in credit, and going over the limit leaves none available.
+        let statement = statement_for(2_000, &[]);
+        assert_eq!(statement.statement_balance, -2_000);
+        assert_eq!(statement.remaining, 0);
+        assert_eq!(statement.minimum_payment, 0);
+        let statement = statement_for(-600_000, &[]);
+        assert_eq!(statement.available_credit, 0);
+    }
+
+    fn statement_for(balance: i64, changes: &[(i64, i64)]) -> CardStatement {
+        statement(&card(), "USD", balance, changes, date("2024-03-05"))
+    }
+}
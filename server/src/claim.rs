@@ -0,0 +1,156 @@
+use crate::error::ServerError;
+use csv::Writer;
+use entities::time::Tz;
+use entities::{ClaimStatus, ClaimedExpense, Money, Timestamp};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+const HEADER: [&str; 6] = [
+    "date",
+    "paid to",
+    "description",
+    "amount",
+    "currency",
+    "status",
+];
+
+/// The claims still to be paid back, in the order given.
+pub fn outstanding(claims: Vec<ClaimedExpense>) -> Vec<ClaimedExpense> {
+    claims
+        .into_iter()
+        .filter(|claim| claim.status != ClaimStatus::Paid)
+        .collect()
+}
+
+/// Writes the claims as CSV to copy onto a claim form: a row per expense, dated in the
+/// ledger's timezone, then a total row per currency. Text that a spreadsheet would read as a
+/// formula is quoted, see `text_cell`.
+pub fn to_csv(claims: &[ClaimedExpense], timezone: Tz) -> Result<String, ServerError> {
+    let mut writer = Writer::from_writer(Vec::new());
+    writer.write_record(HEADER).map_err(csv_error)?;
+    let mut totals: BTreeMap<&str, i64> = BTreeMap::new();
+    for claim in claims {
+        let date = Timestamp::from_millis(claim.datetime).local_date(timezone);
+        writer
+            .write_record([
+                &date.format("%Y-%m-%d").to_string(),
+                &*text_cell(&claim.to),
+                &*text_cell(&claim.comment),
+                &Money::new(claim.amount, &claim.currency).decimal(),
+                &claim.currency,
+                claim.status.as_str(),
+            ])
+            .map_err(csv_error)?;
+        *totals.entry(&claim.currency).or_default() += claim.amount;
+    }
+    for (currency, total) in totals {
+        writer
+            .write_record([
+                "",
+                "",
+                "Total",
+                &Money::new(total, currency).decimal(),
+                currency,
+                "",
+            ])
+            .map_err(csv_error)?;
+    }
+    let content = writer
+        .into_inner()
+        .map_err(|e| ServerError::InternalError(e.to_string()))?;
+
+    String::from_utf8(content).map_err(|e| ServerError::InternalError(e.to_string()))
+}
+
+/// Prefixes text starting like a spreadsheet formula with `'`, so opening the claim form
+/// shows it as entered instead of evaluating it.
+fn text_cell(text: &str) -> Cow<'_, str> {
+    match text.chars().next() {
+        Some('=' | '+' | '-' | '@' | '\t' | '\r') => Cow::Owned(format!("'{}", text)),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+fn csv_error(e: csv::Error) -> ServerError {
+    ServerError::InternalError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(datetime: i64, comment: &str, amount: i64, status: ClaimStatus) -> ClaimedExpense {
+        ClaimedExpense {
+            operation: 1,
+            datetime,
+            to: "Airline".to_owned(),
+            comment: comment.to_owned(),
+            amount,
+            currency: "USD".to_owned(),
+            status,
+            reimbursement: None,
+        }
+    }
+
+    #[test]
+    fn test_outstanding() {
+        let claims = vec![
+            claim(1, "Taxi", 2_000, ClaimStatus::Paid),
+            claim(2, "Hotel", 30_000, ClaimStatus::Submitted),
+            claim(3, "Lunch", 1_500, ClaimStatus::Pending),
+        ];
+        let comments: Vec<String> = outstanding(claims)
+            .into_iter()
+            .map(|claim| claim.comment)
+            .collect();
+        assert_eq!(comments, vec!["Hotel", "Lunch"]);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        // 2024-03-31T23:30:00Z, already April in Shanghai.
+        let mut flight = claim(
+            1_711_927_800_000,
+            "Flight, return",
+            45_050,
+            ClaimStatus::Pending,
+        );
+        flight.currency = "CNY".to_owned();
+        let claims = vec![
+            flight,
+            claim(1_711_927_800_000, "Hotel", 30_000, ClaimStatus::Submitted),
+            claim(1_711_927_800_000, "Taxi", 1_999, ClaimStatus::Pending),
+        ];
+        let timezone: Tz = "Asia/Shanghai".parse().unwrap();
+        assert_eq!(
+            to_csv(&claims, timezone).unwrap(),
+            "date,paid to,description,amount,currency,status\n\
+             2024-04-01,Airline,\"Flight, return\",450.50,CNY,pending\n\
+             2024-04-01,Airline,Hotel,300.00,USD,submitted\n\
+             2024-04-01,Airline,Taxi,19.99,USD,pending\n\
+             ,,Total,450.50,CNY,\n\
+             ,,Total,319.99,USD,\n"
+        );
+    }
+
+    #[test]
+    fn test_formulas_are_quoted() {
+        let mut claims = vec![
+            claim(0, "=HYPERLINK(\"http://x\")", 100, ClaimStatus::Pending),
+            claim(0, "+1", 100, ClaimStatus::Pending),
+            claim(0, "-1", 100, ClaimStatus::Pending),
+            claim(0, "Lunch - team", 100, ClaimStatus::Pending),
+        ];
+        claims[3].to = "@SUM(A1)".to_owned();
+        let timezone: Tz = "UTC".parse().unwrap();
+        assert_eq!(
+            to_csv(&claims, timezone).unwrap(),
+            "date,paid to,description,amount,currency,status\n\
+             1970-01-01,Airline,\"'=HYPERLINK(\"\"http://x\"\")\",1.00,USD,pending\n\
+             1970-01-01,Airline,'+1,1.00,USD,pending\n\
+             1970-01-01,Airline,'-1,1.00,USD,pending\n\
+             1970-01-01,'@SUM(A1),Lunch - team,1.00,USD,pending\n\
+             ,,Total,4.00,USD,\n"
+        );
+    }
+}
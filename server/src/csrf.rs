@@ -0,0 +1,197 @@
+use crate::error::ServerError;
+use actix_service::{Service, Transform};
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method};
+use actix_web::{Error, HttpMessage};
+use futures::future::{err, ok, LocalBoxFuture, Ready};
+use rand::RngCore;
+use std::task::{Context, Poll};
+
+pub const COOKIE_NAME: &str = "csrf";
+pub const HEADER_NAME: &str = "X-CSRF-Token";
+
+/// Double-submit cookie protection for every state-changing request.
+///
+/// Safe requests hand out a random token in a script-readable cookie, and unsafe ones must
+/// echo it back in a header and come from the same origin. Other sites can neither read the
+/// cookie nor set the header, so they can't forge a request riding on the `token` cookie.
+pub struct Csrf;
+
+impl<S, B> Transform<S> for Csrf
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CsrfMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CsrfMiddleware { service })
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for CsrfMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let cookie_token = req.cookie(COOKIE_NAME).map(|c| c.value().to_owned());
+
+        if !is_safe(req.method()) {
+            if let Err(reason) = check_request(&req, cookie_token.as_deref()) {
+                warn!("Rejected {} {}: {}", req.method(), req.path(), reason);
+                return Box::pin(err(ServerError::ForbiddenError.into()));
+            }
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if cookie_token.is_none() {
+                let cookie = Cookie::build(COOKIE_NAME, generate_token())
+                    .path("/")
+                    .same_site(SameSite::Strict)
+                    .http_only(false)
+                    .finish();
+                res.response_mut().add_cookie(&cookie)?;
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn check_request(req: &ServiceRequest, cookie_token: Option<&str>) -> Result<(), &'static str> {
+    let headers = req.headers();
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .ok_or("missing Host header")?;
+
+    // Browsers send Origin on cross-origin POSTs; fall back to Referer for older ones.
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        if !same_origin(origin.to_str().unwrap_or_default(), host) {
+            return Err("Origin doesn't match Host");
+        }
+    } else if let Some(referer) = headers.get(header::REFERER) {
+        if !same_origin(referer.to_str().unwrap_or_default(), host) {
+            return Err("Referer doesn't match Host");
+        }
+    }
+
+    let cookie_token = cookie_token.ok_or("missing CSRF cookie")?;
+    let header_token = headers
+        .get(HEADER_NAME)
+        .and_then(|token| token.to_str().ok())
+        .ok_or("missing CSRF header")?;
+
+    if constant_time_eq(cookie_token.as_bytes(), header_token.as_bytes()) {
+        Ok(())
+    } else {
+        Err("CSRF token mismatch")
+    }
+}
+
+/// Compares the authority of an `Origin` or `Referer` value against the `Host` header.
+fn same_origin(url: &str, host: &str) -> bool {
+    let authority = match url.find("://") {
+        Some(idx) => &url[idx + 3..],
+        None => return false,
+    };
+    let authority = authority.split('/').next().unwrap_or_default();
+
+    !authority.is_empty() && authority.eq_ignore_ascii_case(host)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn generate_token() -> String {
+    let mut token = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut token);
+
+    token.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[test]
+    fn test_same_origin() {
+        assert!(same_origin("http://127.0.0.1:7001", "127.0.0.1:7001"));
+        assert!(same_origin("https://Example.com/index.html", "example.com"));
+        assert!(!same_origin("https://evil.com", "example.com"));
+        assert!(!same_origin("https://example.com.evil.com", "example.com"));
+        assert!(!same_origin("null", "example.com"));
+    }
+
+    #[actix_rt::test]
+    async fn test_unsafe_requests_need_matching_token() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Csrf)
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/", web::post().to(HttpResponse::Created)),
+        )
+        .await;
+
+        let res = test::call_service(&mut app, test::TestRequest::get().to_request()).await;
+        let token = res
+            .response()
+            .cookies()
+            .find(|c| c.name() == COOKIE_NAME)
+            .map(|c| c.value().to_owned())
+            .expect("A CSRF cookie is issued on safe requests.");
+
+        let forged = test::TestRequest::post()
+            .header(header::HOST, "localhost")
+            .cookie(Cookie::new(COOKIE_NAME, token.clone()))
+            .to_request();
+        assert!(app.call(forged).await.is_err());
+
+        let cross_site = test::TestRequest::post()
+            .header(header::HOST, "localhost")
+            .header(header::ORIGIN, "http://evil.com")
+            .header(HEADER_NAME, token.clone())
+            .cookie(Cookie::new(COOKIE_NAME, token.clone()))
+            .to_request();
+        assert!(app.call(cross_site).await.is_err());
+
+        let genuine = test::TestRequest::post()
+            .header(header::HOST, "localhost")
+            .header(header::ORIGIN, "http://localhost")
+            .header(HEADER_NAME, token.clone())
+            .cookie(Cookie::new(COOKIE_NAME, token))
+            .to_request();
+        let res = test::call_service(&mut app, genuine).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::CREATED);
+    }
+}
@@ -0,0 +1,1722 @@
+use super::{
+    account_has_goal, category_exists, check_card, check_claim, check_owners, check_plan,
+    check_recurring, check_trade, export_claims, export_shares, highlight, in_field,
+    not_next_repayment, paid_by, plan_exists, security_exists, unknown_account, unknown_category,
+    unknown_operation, unknown_operation_at, unknown_plan, unknown_security, user_exists,
+    validate_currencies, AttachmentContent, SharedAmount, Storage, TotpSecret, User, MATCH_END,
+    MATCH_START,
+};
+use crate::error::ServerError;
+use crate::password;
+use entities::time::DEFAULT_TIMEZONE;
+use entities::{
+    Account, Attachment, Category, Claim, ClaimedExpense, Conversion, CreditCard, DbVersion,
+    ExchangeRate, Goal, InstallmentPlan, Ledger, LedgerExport, LedgerMember, LedgerSettings, Leg,
+    NewAttachment, NewLedger, Operation, Price, RecurringOperation, Reimbursement, Role, SearchHit,
+    Security, SharedExpense, Trade, TradeSide, DEFAULT_CURRENCY, EXPORT_VERSION,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// Keeps everything in process memory, for tests that shouldn't need a file on disk.
+///
+/// Starts out like a freshly migrated SQLite database: an `admin` user who owns a `Default`
+/// ledger, both with id 1.
+pub struct MemoryStorage {
+    state: Mutex<State>,
+}
+
+#[derive(Clone)]
+struct State {
+    next_id: i64,
+    users: Vec<User>,
+    ledgers: Vec<(i64, LedgerSettings)>,
+    members: Vec<(i64, i64, Role)>,
+    accounts: Vec<StoredAccount>,
+    categories: Vec<(i64, String)>,
+    operations: Vec<StoredOperation>,
+    /// Oldest first, so later entries win ties like higher ids do in SQL.
+    rates: Vec<(i64, ExchangeRate)>,
+    securities: Vec<StoredSecurity>,
+    trades: Vec<StoredTrade>,
+    /// By security id.
+    prices: Vec<(i64, Price)>,
+    /// By account id.
+    credit_cards: Vec<(i64, CreditCard)>,
+    installment_plans: Vec<StoredPlan>,
+    goals: Vec<StoredGoal>,
+    recurring: Vec<StoredRecurring>,
+    /// By operation id.
+    shared_expenses: Vec<(i64, SharedExpense)>,
+    /// By operation id.
+    claims: Vec<(i64, Claim)>,
+    totps: Vec<StoredTotp>,
+    recovery_codes: Vec<StoredRecoveryCode>,
+    attachments: Vec<StoredAttachment>,
+    /// Attachment content by hash.
+    contents: HashMap<String, Vec<u8>>,
+}
+
+#[derive(Clone)]
+struct StoredAccount {
+    id: i64,
+    ledger: i64,
+    name: String,
+    balance: i64,
+    currency: String,
+}
+
+#[derive(Clone)]
+struct StoredOperation {
+    id: i64,
+    ledger: i64,
+    from: i64,
+    to: i64,
+    comment: String,
+    amount: i64,
+    datetime: i64,
+    legs: Vec<StoredLeg>,
+    currency: String,
+    to_amount: Option<i64>,
+    import_key: Option<String>,
+}
+
+#[derive(Clone)]
+struct StoredLeg {
+    account: Option<i64>,
+    category: Option<String>,
+    amount: i64,
+    memo: String,
+}
+
+#[derive(Clone)]
+struct StoredSecurity {
+    id: i64,
+    ledger: i64,
+    symbol: String,
+    name: String,
+    currency: String,
+}
+
+#[derive(Clone)]
+struct StoredTrade {
+    id: i64,
+    ledger: i64,
+    account: i64,
+    security: i64,
+    side: TradeSide,
+    quantity: i64,
+    amount: i64,
+    datetime: i64,
+    comment: String,
+}
+
+#[derive(Clone)]
+struct StoredPlan {
+    ledger: i64,
+    account: i64,
+    from: i64,
+    cost_account: Option<i64>,
+    /// With account names as they were added.
+    plan: InstallmentPlan,
+}
+
+#[derive(Clone)]
+struct StoredGoal {
+    ledger: i64,
+    account: i64,
+    /// With the account name as it was set.
+    goal: Goal,
+}
+
+#[derive(Clone)]
+struct StoredRecurring {
+    ledger: i64,
+    from: i64,
+    to: i64,
+    /// With account names as they were set.
+    item: RecurringOperation,
+}
+
+#[derive(Clone)]
+struct StoredAttachment {
+    id: i64,
+    ledger: i64,
+    operation: i64,
+    name: String,
+    content_type: String,
+    size: i64,
+    sha256: String,
+    created_at: i64,
+}
+
+impl StoredAttachment {
+    fn to_attachment(&self) -> Attachment {
+        Attachment {
+            id: self.id,
+            operation: self.operation,
+            name: self.name.clone(),
+            content_type: self.content_type.clone(),
+            size: self.size,
+            sha256: self.sha256.clone(),
+            created_at: self.created_at,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct StoredTotp {
+    id: i64,
+    user: i64,
+    secret: String,
+    confirmed: bool,
+    last_step: i64,
+}
+
+#[derive(Clone)]
+struct StoredRecoveryCode {
+    totp: i64,
+    hash: String,
+    used: bool,
+}
+
+impl MemoryStorage {
+    pub fn new(admin_password: &str) -> Self {
+        let state = State {
+            next_id: 2,
+            users: vec![User {
+                id: 1,
+                name: "admin".to_owned(),
+                password_hash: password::hash(admin_password).unwrap(),
+                admin: true,
+            }],
+            ledgers: vec![(
+                1,
+                LedgerSettings {
+                    name: "Default".to_owned(),
+                    currency: DEFAULT_CURRENCY.to_owned(),
+                    timezone: DEFAULT_TIMEZONE.to_owned(),
+                },
+            )],
+            members: vec![(1, 1, Role::Owner)],
+            accounts: Vec::new(),
+            categories: Vec::new(),
+            operations: Vec::new(),
+            rates: Vec::new(),
+            securities: Vec::new(),
+            trades: Vec::new(),
+            prices: Vec::new(),
+            credit_cards: Vec::new(),
+            installment_plans: Vec::new(),
+            goals: Vec::new(),
+            recurring: Vec::new(),
+            shared_expenses: Vec::new(),
+            claims: Vec::new(),
+            totps: Vec::new(),
+            recovery_codes: Vec::new(),
+            attachments: Vec::new(),
+            contents: HashMap::new(),
+        };
+
+        MemoryStorage {
+            state: Mutex::new(state),
+        }
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, State>, ServerError> {
+        self.state
+            .lock()
+            .map_err(|_| ServerError::InternalError("Storage lock is poisoned.".to_owned()))
+    }
+}
+
+impl State {
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn find_account(&self, ledger: i64, name: &str) -> Option<&StoredAccount> {
+        self.accounts
+            .iter()
+            .find(|account| account.ledger == ledger && account.name == name)
+    }
+
+    fn account_id(&self, ledger: i64, name: &str) -> Option<i64> {
+        self.find_account(ledger, name).map(|account| account.id)
+    }
+
+    fn account_currency(&self, id: i64) -> String {
+        self.accounts
+            .iter()
+            .find(|account| account.id == id)
+            .map(|account| account.currency.clone())
+            .unwrap_or_default()
+    }
+
+    fn settings(&self, ledger: i64) -> Result<&LedgerSettings, ServerError> {
+        self.ledgers
+            .iter()
+            .find(|(id, _)| *id == ledger)
+            .map(|(_, settings)| settings)
+            .ok_or_else(|| ServerError::NotFoundError("Ledger doesn't exist.".to_owned()))
+    }
+
+    fn account_name(&self, id: i64) -> String {
+        self.accounts
+            .iter()
+            .find(|account| account.id == id)
+            .map(|account| account.name.clone())
+            .unwrap_or_default()
+    }
+
+    fn add_rates(&mut self, ledger: i64, rates: &[ExchangeRate]) {
+        for rate in rates {
+            self.rates.retain(|(l, r)| {
+                !(*l == ledger
+                    && r.base == rate.base
+                    && r.quote == rate.quote
+                    && r.date == rate.date)
+            });
+            self.rates.push((ledger, rate.clone()));
+        }
+    }
+
+    fn rates(&self, ledger: i64) -> Vec<ExchangeRate> {
+        let mut rates: Vec<ExchangeRate> = self
+            .rates
+            .iter()
+            .filter(|(l, _)| *l == ledger)
+            .map(|(_, rate)| rate.clone())
+            .collect();
+        rates.sort_by(|a, b| (&a.base, &a.quote, &a.date).cmp(&(&b.base, &b.quote, &b.date)));
+
+        rates
+    }
+
+    fn find_security(&self, ledger: i64, symbol: &str) -> Option<&StoredSecurity> {
+        self.securities
+            .iter()
+            .find(|security| security.ledger == ledger && security.symbol == symbol)
+    }
+
+    fn add_security(&mut self, ledger: i64, security: &Security) -> Result<(), ServerError> {
+        if self.find_security(ledger, &security.symbol).is_some() {
+            return Err(security_exists(&security.symbol));
+        }
+        let id = self.next_id();
+        self.securities.push(StoredSecurity {
+            id,
+            ledger,
+            symbol: security.symbol.clone(),
+            name: security.name.clone(),
+            currency: security.currency.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn securities(&self, ledger: i64) -> Vec<Security> {
+        self.securities
+            .iter()
+            .filter(|security| security.ledger == ledger)
+            .map(|security| Security {
+                symbol: security.symbol.clone(),
+                name: security.name.clone(),
+                currency: security.currency.clone(),
+            })
+            .collect()
+    }
+
+    fn symbol(&self, security: i64) -> String {
+        self.securities
+            .iter()
+            .find(|s| s.id == security)
+            .map(|s| s.symbol.clone())
+            .unwrap_or_default()
+    }
+
+    /// Records the trade after `check_trade`, resolving it like SQLite does.
+    fn record_trade(&mut self, ledger: i64, trade: &Trade) -> Result<(), ServerError> {
+        let account = self
+            .find_account(ledger, &trade.account)
+            .ok_or_else(|| unknown_account("account", &trade.account))?;
+        let security = self
+            .find_security(ledger, &trade.symbol)
+            .ok_or_else(|| unknown_security("symbol", &trade.symbol))?;
+        let (account, account_currency) = (account.id, account.currency.clone());
+        let (security, security_currency) = (security.id, security.currency.clone());
+        let earlier = self
+            .trades
+            .iter()
+            .filter(|t| t.account == account && t.security == security);
+        let held = earlier
+            .clone()
+            .map(|t| match t.side {
+                TradeSide::Buy => t.quantity,
+                TradeSide::Sell => -t.quantity,
+            })
+            .sum();
+        let latest = earlier.map(|t| t.datetime).max();
+        check_trade(trade, &account_currency, &security_currency, held, latest)?;
+
+        let id = self.next_id();
+        self.trades.push(StoredTrade {
+            id,
+            ledger,
+            account,
+            security,
+            side: trade.side,
+            quantity: trade.quantity,
+            amount: trade.amount,
+            datetime: trade.datetime,
+            comment: trade.comment.clone(),
+        });
+        for stored in self.accounts.iter_mut() {
+            if stored.id == account {
+                stored.balance += trade.signed_amount();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn trades(&self, ledger: i64) -> Vec<Trade> {
+        let mut trades: Vec<&StoredTrade> =
+            self.trades.iter().filter(|t| t.ledger == ledger).collect();
+        trades.sort_by_key(|t| (t.datetime, t.id));
+        trades
+            .into_iter()
+            .map(|t| Trade {
+                account: self.account_name(t.account),
+                symbol: self.symbol(t.security),
+                side: t.side,
+                quantity: t.quantity,
+                amount: t.amount,
+                datetime: t.datetime,
+                comment: t.comment.clone(),
+            })
+            .collect()
+    }
+
+    fn add_prices(&mut self, ledger: i64, prices: &[Price]) -> Result<(), ServerError> {
+        for price in prices {
+            let security = self
+                .find_security(ledger, &price.symbol)
+                .ok_or_else(|| unknown_security("symbol", &price.symbol))?
+                .id;
+            self.prices
+                .retain(|(s, p)| !(*s == security && p.date == price.date));
+            self.prices.push((security, price.clone()));
+        }
+
+        Ok(())
+    }
+
+    fn prices(&self, ledger: i64) -> Vec<Price> {
+        let mut prices: Vec<Price> = self
+            .prices
+            .iter()
+            .filter(|(security, _)| {
+                self.securities
+                    .iter()
+                    .any(|s| s.id == *security && s.ledger == ledger)
+            })
+            .map(|(_, price)| price.clone())
+            .collect();
+        prices.sort_by(|a, b| (&a.symbol, &a.date).cmp(&(&b.symbol, &b.date)));
+
+        prices
+    }
+
+    fn credit_cards(&self, ledger: i64) -> Vec<CreditCard> {
+        let mut cards: Vec<&(i64, CreditCard)> = self
+            .credit_cards
+            .iter()
+            .filter(|(account, _)| {
+                self.accounts
+                    .iter()
+                    .any(|a| a.id == *account && a.ledger == ledger)
+            })
+            .collect();
+        cards.sort_by_key(|(account, _)| *account);
+        cards
+            .into_iter()
+            .map(|(account, card)| CreditCard {
+                account: self.account_name(*account),
+                ..card.clone()
+            })
+            .collect()
+    }
+
+    fn set_credit_card(&mut self, ledger: i64, card: &CreditCard) -> Result<(), ServerError> {
+        let (account, currency) = self
+            .find_account(ledger, &card.account)
+            .map(|a| (a.id, a.currency.clone()))
+            .ok_or_else(|| unknown_account("account", &card.account))?;
+        let pay_from = match &card.pay_from {
+            Some(name) => Some(
+                self.find_account(ledger, name)
+                    .ok_or_else(|| unknown_account("pay_from", name))?,
+            ),
+            None => None,
+        };
+        check_card(card, &currency, pay_from.map(|a| a.currency.as_str()))?;
+        self.credit_cards.retain(|(a, _)| *a != account);
+        self.credit_cards.push((account, card.clone()));
+
+        Ok(())
+    }
+
+    fn goals(&self, ledger: i64) -> Vec<Goal> {
+        let mut goals: Vec<Goal> = self
+            .goals
+            .iter()
+            .filter(|stored| stored.ledger == ledger)
+            .map(|stored| Goal {
+                account: self.account_name(stored.account),
+                ..stored.goal.clone()
+            })
+            .collect();
+        goals.sort_by(|a, b| (&a.target_date, &a.name).cmp(&(&b.target_date, &b.name)));
+
+        goals
+    }
+
+    fn set_goal(&mut self, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+        let account = self
+            .account_id(ledger, &goal.account)
+            .ok_or_else(|| unknown_account("account", &goal.account))?;
+        if let Some(other) = self
+            .goals
+            .iter()
+            .find(|stored| stored.account == account && stored.goal.name != goal.name)
+        {
+            return Err(account_has_goal(&goal.account, &other.goal.name));
+        }
+        self.goals
+            .retain(|stored| stored.ledger != ledger || stored.goal.name != goal.name);
+        self.goals.push(StoredGoal {
+            ledger,
+            account,
+            goal: goal.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn recurring(&self, ledger: i64) -> Vec<RecurringOperation> {
+        let mut recurring: Vec<RecurringOperation> = self
+            .recurring
+            .iter()
+            .filter(|stored| stored.ledger == ledger)
+            .map(|stored| RecurringOperation {
+                from: self.account_name(stored.from),
+                to: self.account_name(stored.to),
+                ..stored.item.clone()
+            })
+            .collect();
+        recurring.sort_by(|a, b| a.name.cmp(&b.name));
+
+        recurring
+    }
+
+    fn set_recurring(&mut self, ledger: i64, item: &RecurringOperation) -> Result<(), ServerError> {
+        let from = self
+            .find_account(ledger, &item.from)
+            .ok_or_else(|| unknown_account("from", &item.from))?;
+        let to = self
+            .find_account(ledger, &item.to)
+            .ok_or_else(|| unknown_account("to", &item.to))?;
+        check_recurring(item, &from.currency, &to.currency)?;
+        let (from, to) = (from.id, to.id);
+        if let Some(category) = &item.category {
+            if !self.category_exists(ledger, category) {
+                return Err(unknown_category("category", category));
+            }
+        }
+        self.recurring
+            .retain(|stored| stored.ledger != ledger || stored.item.name != item.name);
+        self.recurring.push(StoredRecurring {
+            ledger,
+            from,
+            to,
+            item: item.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn installment_plans(&self, ledger: i64) -> Vec<InstallmentPlan> {
+        self.installment_plans
+            .iter()
+            .filter(|stored| stored.ledger == ledger)
+            .map(|stored| InstallmentPlan {
+                account: self.account_name(stored.account),
+                from: self.account_name(stored.from),
+                cost_account: stored.cost_account.map(|id| self.account_name(id)),
+                ..stored.plan.clone()
+            })
+            .collect()
+    }
+
+    fn add_installment_plan(
+        &mut self,
+        ledger: i64,
+        plan: &InstallmentPlan,
+    ) -> Result<(), ServerError> {
+        let account = self
+            .find_account(ledger, &plan.account)
+            .ok_or_else(|| unknown_account("account", &plan.account))?;
+        let (account, account_currency) = (account.id, account.currency.clone());
+        let from = self
+            .find_account(ledger, &plan.from)
+            .ok_or_else(|| unknown_account("from", &plan.from))?;
+        let (from, from_currency) = (from.id, from.currency.clone());
+        let cost = match &plan.cost_account {
+            Some(name) => {
+                let cost = self
+                    .find_account(ledger, name)
+                    .ok_or_else(|| unknown_account("cost_account", name))?;
+                Some((cost.id, cost.currency.clone()))
+            }
+            None => None,
+        };
+        check_plan(
+            plan,
+            &account_currency,
+            &from_currency,
+            cost.as_ref().map(|(_, currency)| currency.as_str()),
+        )?;
+        if self
+            .installment_plans
+            .iter()
+            .any(|stored| stored.ledger == ledger && stored.plan.name == plan.name)
+        {
+            return Err(plan_exists(&plan.name));
+        }
+
+        self.installment_plans.push(StoredPlan {
+            ledger,
+            account,
+            from,
+            cost_account: cost.map(|(id, _)| id),
+            plan: plan.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn share_operation(&mut self, operation: i64, expense: &SharedExpense) {
+        self.shared_expenses.retain(|(id, _)| *id != operation);
+        self.shared_expenses.push((operation, expense.clone()));
+    }
+
+    fn shared_expenses(&self, ledger: i64) -> Vec<SharedAmount> {
+        let mut shared: Vec<(i64, i64, SharedAmount)> = self
+            .shared_expenses
+            .iter()
+            .filter_map(|(id, expense)| {
+                let operation = self
+                    .operations
+                    .iter()
+                    .find(|o| o.id == *id && o.ledger == ledger)?;
+                let amount = SharedAmount {
+                    operation: *id,
+                    amount: operation.amount,
+                    currency: operation.currency.clone(),
+                    expense: expense.clone(),
+                };
+                Some((operation.datetime, *id, amount))
+            })
+            .collect();
+        shared.sort_by_key(|(datetime, id, _)| (*datetime, *id));
+
+        shared.into_iter().map(|(_, _, amount)| amount).collect()
+    }
+
+    fn set_claim(&mut self, operation: i64, claim: &Claim) {
+        self.claims.retain(|(id, _)| *id != operation);
+        self.claims.push((operation, claim.clone()));
+    }
+
+    fn claims(&self, ledger: i64) -> Vec<ClaimedExpense> {
+        let mut claims: Vec<ClaimedExpense> = self
+            .claims
+            .iter()
+            .filter_map(|(id, claim)| {
+                let operation = self
+                    .operations
+                    .iter()
+                    .find(|o| o.id == *id && o.ledger == ledger)?;
+                Some(ClaimedExpense {
+                    operation: *id,
+                    datetime: operation.datetime,
+                    to: self.account_name(operation.to),
+                    comment: operation.comment.clone(),
+                    amount: operation.amount,
+                    currency: operation.currency.clone(),
+                    status: claim.status,
+                    reimbursement: claim.reimbursement,
+                })
+            })
+            .collect();
+        claims.sort_by_key(|claim| (claim.datetime, claim.operation));
+
+        claims
+    }
+
+    fn category_exists(&self, ledger: i64, name: &str) -> bool {
+        self.categories
+            .iter()
+            .any(|(l, n)| *l == ledger && n == name)
+    }
+
+    /// Moves the amount from one account of the ledger to those it credits, resolving them
+    /// like SQLite does.
+    fn record_operation(
+        &mut self,
+        ledger: i64,
+        operation: &Operation,
+        field: (&str, &str, &str),
+    ) -> Result<i64, ServerError> {
+        let from = self
+            .find_account(ledger, &operation.from)
+            .ok_or_else(|| unknown_account(field.0, &operation.from))?;
+        let to = self
+            .find_account(ledger, &operation.to)
+            .ok_or_else(|| unknown_account(field.1, &operation.to))?;
+        let (from, from_currency) = (from.id, from.currency.clone());
+        let (to, to_currency) = (to.id, to.currency.clone());
+        let mut legs = Vec::new();
+        for leg in &operation.legs {
+            let account = match &leg.account {
+                Some(name) => Some(
+                    self.account_id(ledger, name)
+                        .ok_or_else(|| unknown_account(field.2, name))?,
+                ),
+                None => None,
+            };
+            if let Some(name) = &leg.category {
+                if !self.category_exists(ledger, name) {
+                    return Err(unknown_category(field.2, name));
+                }
+            }
+            legs.push(StoredLeg {
+                account,
+                category: leg.category.clone(),
+                amount: leg.amount,
+                memo: leg.memo.clone(),
+            });
+        }
+
+        let leg_currencies: Vec<String> = legs
+            .iter()
+            .map(|leg| {
+                leg.account
+                    .map(|id| self.account_currency(id))
+                    .unwrap_or_default()
+            })
+            .collect();
+        let leg_currencies: Vec<Option<&str>> = legs
+            .iter()
+            .zip(&leg_currencies)
+            .map(|(leg, currency)| leg.account.map(|_| currency.as_str()))
+            .collect();
+        let currency =
+            validate_currencies(operation, &from_currency, &to_currency, &leg_currencies)?;
+
+        let mut credits: Vec<(i64, i64)> = legs
+            .iter()
+            .map(|leg| (leg.account.unwrap_or(to), leg.amount))
+            .collect();
+        if legs.is_empty() {
+            credits.push((to, operation.to_amount.unwrap_or(operation.amount)));
+        }
+        let id = self.next_id();
+        self.operations.push(StoredOperation {
+            id,
+            ledger,
+            from,
+            to,
+            comment: operation.comment.clone(),
+            amount: operation.amount,
+            datetime: operation.datetime,
+            legs,
+            currency,
+            to_amount: operation.to_amount,
+            import_key: None,
+        });
+        for account in self.accounts.iter_mut() {
+            if account.id == from {
+                account.balance -= operation.amount;
+            }
+            for (credited, amount) in &credits {
+                if account.id == *credited {
+                    account.balance += amount;
+                }
+            }
+        }
+
+        Ok(id)
+    }
+
+    fn to_operation(&self, operation: &StoredOperation) -> Operation {
+        Operation {
+            from: self.account_name(operation.from),
+            to: self.account_name(operation.to),
+            comment: operation.comment.clone(),
+            amount: operation.amount,
+            datetime: operation.datetime,
+            legs: operation
+                .legs
+                .iter()
+                .map(|leg| Leg {
+                    account: leg.account.map(|id| self.account_name(id)),
+                    category: leg.category.clone(),
+                    amount: leg.amount,
+                    memo: leg.memo.clone(),
+                })
+                .collect(),
+            currency: Some(operation.currency.clone()),
+            to_amount: operation.to_amount,
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn record_operation(&self, ledger: i64, operation: &Operation) -> Result<i64, ServerError> {
+        self.state()?
+            .record_operation(ledger, operation, ("from", "to", "legs"))
+    }
+
+    fn import_operations(
+        &self,
+        ledger: i64,
+        operations: &[(String, Operation)],
+    ) -> Result<usize, ServerError> {
+        let mut state = self.state()?;
+        let mut next = state.clone();
+        let mut recorded = 0;
+        for (key, operation) in operations {
+            let imported = next
+                .operations
+                .iter()
+                .any(|o| o.ledger == ledger && o.import_key.as_ref() == Some(key));
+            if imported {
+                continue;
+            }
+            let fields = ("transactions", "transactions", "transactions");
+            let id = next.record_operation(ledger, operation, fields)?;
+            if let Some(stored) = next.operations.iter_mut().find(|o| o.id == id) {
+                stored.import_key = Some(key.clone());
+            }
+            recorded += 1;
+        }
+        *state = next;
+
+        Ok(recorded)
+    }
+
+    fn get_user(&self, name: &str) -> Result<Option<User>, ServerError> {
+        Ok(self
+            .state()?
+            .users
+            .iter()
+            .find(|user| user.name == name)
+            .cloned())
+    }
+
+    fn get_user_by_id(&self, id: i64) -> Result<Option<User>, ServerError> {
+        Ok(self
+            .state()?
+            .users
+            .iter()
+            .find(|user| user.id == id)
+            .cloned())
+    }
+
+    fn add_user(&self, name: &str, password_hash: &str) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if state.users.iter().any(|existing| existing.name == name) {
+            return Err(user_exists(name));
+        }
+
+        let id = state.next_id();
+        state.users.push(User {
+            id,
+            name: name.to_owned(),
+            password_hash: password_hash.to_owned(),
+            admin: false,
+        });
+
+        Ok(())
+    }
+
+    /// Users are hashed from the start here, there's no earlier version to migrate from.
+    fn plain_tokens(&self) -> Result<Vec<(i64, String)>, ServerError> {
+        Ok(Vec::new())
+    }
+
+    fn set_password_hash(&self, user: i64, password_hash: &str) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if let Some(stored) = state.users.iter_mut().find(|stored| stored.id == user) {
+            stored.password_hash = password_hash.to_owned();
+        }
+
+        Ok(())
+    }
+
+    fn get_ledgers(&self, user: i64) -> Result<Vec<Ledger>, ServerError> {
+        let state = self.state()?;
+        let mut ledgers: Vec<Ledger> = state
+            .members
+            .iter()
+            .filter(|(_, member, _)| *member == user)
+            .filter_map(|(ledger, _, role)| {
+                state
+                    .ledgers
+                    .iter()
+                    .find(|(id, _)| id == ledger)
+                    .map(|(id, settings)| Ledger {
+                        id: *id,
+                        name: settings.name.clone(),
+                        role: *role,
+                        currency: settings.currency.clone(),
+                        timezone: settings.timezone.clone(),
+                    })
+            })
+            .collect();
+        ledgers.sort_by_key(|ledger| ledger.id);
+
+        Ok(ledgers)
+    }
+
+    fn get_role(&self, user: i64, ledger: i64) -> Result<Option<Role>, ServerError> {
+        Ok(self
+            .state()?
+            .members
+            .iter()
+            .find(|(l, u, _)| *l == ledger && *u == user)
+            .map(|(_, _, role)| *role))
+    }
+
+    fn ledger_ids(&self) -> Result<Vec<i64>, ServerError> {
+        let mut ids: Vec<i64> = self.state()?.ledgers.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+
+        Ok(ids)
+    }
+
+    fn add_ledger(&self, user: i64, ledger: &NewLedger) -> Result<i64, ServerError> {
+        let mut state = self.state()?;
+        let id = state.next_id();
+        state.ledgers.push((
+            id,
+            LedgerSettings {
+                name: ledger.name.clone(),
+                currency: ledger.currency.clone(),
+                timezone: ledger.timezone.clone(),
+            },
+        ));
+        state.members.push((id, user, Role::Owner));
+
+        Ok(id)
+    }
+
+    fn set_member(&self, ledger: i64, member: &LedgerMember) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        let user = state
+            .users
+            .iter()
+            .find(|user| user.name == member.user)
+            .map(|user| user.id)
+            .ok_or_else(|| {
+                ServerError::NotFoundError(format!("User {} doesn't exist.", member.user))
+            })?;
+
+        let owners = state
+            .members
+            .iter()
+            .filter(|(l, u, role)| *l == ledger && *u != user && *role == Role::Owner)
+            .count()
+            + usize::from(member.role == Role::Owner);
+        check_owners(owners as i64)?;
+        state
+            .members
+            .retain(|(l, u, _)| !(*l == ledger && *u == user));
+        state.members.push((ledger, user, member.role));
+
+        Ok(())
+    }
+
+    fn get_totp(&self, user: i64, confirmed: bool) -> Result<Option<TotpSecret>, ServerError> {
+        Ok(self
+            .state()?
+            .totps
+            .iter()
+            .filter(|totp| totp.user == user && totp.confirmed == confirmed)
+            .max_by_key(|totp| totp.id)
+            .map(|totp| TotpSecret {
+                id: totp.id,
+                secret: totp.secret.clone(),
+                last_step: totp.last_step,
+            }))
+    }
+
+    fn enrol_totp(
+        &self,
+        user: i64,
+        secret: &str,
+        recovery_hashes: &[String],
+    ) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        state.remove_totps(user, false);
+
+        let id = state.next_id();
+        state.totps.push(StoredTotp {
+            id,
+            user,
+            secret: secret.to_owned(),
+            confirmed: false,
+            last_step: 0,
+        });
+        state
+            .recovery_codes
+            .extend(recovery_hashes.iter().map(|hash| StoredRecoveryCode {
+                totp: id,
+                hash: hash.clone(),
+                used: false,
+            }));
+
+        Ok(())
+    }
+
+    fn confirm_totp(&self, user: i64, totp_id: i64, step: i64) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if !state.totps.iter().any(|totp| totp.id == totp_id) {
+            return Err(ServerError::DBError(
+                "Wrong number of lines updated.".to_owned(),
+            ));
+        }
+
+        state.remove_totps(user, true);
+        for totp in state.totps.iter_mut().filter(|totp| totp.id == totp_id) {
+            totp.confirmed = true;
+            totp.last_step = step;
+        }
+
+        Ok(())
+    }
+
+    fn update_totp_step(&self, totp_id: i64, step: i64) -> Result<bool, ServerError> {
+        let mut state = self.state()?;
+        match state
+            .totps
+            .iter_mut()
+            .find(|totp| totp.id == totp_id && totp.last_step < step)
+        {
+            Some(totp) => {
+                totp.last_step = step;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn use_recovery_code(&self, totp_id: i64, hash: &str) -> Result<bool, ServerError> {
+        let mut state = self.state()?;
+        match state
+            .recovery_codes
+            .iter_mut()
+            .find(|code| code.totp == totp_id && code.hash == hash && !code.used)
+        {
+            Some(code) => {
+                code.used = true;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn get_accounts(&self, ledger: i64) -> Result<Vec<Account>, ServerError> {
+        Ok(self
+            .state()?
+            .accounts
+            .iter()
+            .filter(|account| account.ledger == ledger)
+            .map(|account| Account {
+                name: account.name.clone(),
+                balance: account.balance,
+                currency: account.currency.clone(),
+            })
+            .collect())
+    }
+
+    fn add_account(&self, ledger: i64, account: &Account) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if state.account_id(ledger, &account.name).is_some() {
+            return Err(ServerError::ConflictError(format!(
+                "Account {} already exists.",
+                account.name
+            )));
+        }
+
+        let id = state.next_id();
+        state.accounts.push(StoredAccount {
+            id,
+            ledger,
+            name: account.name.clone(),
+            balance: account.balance,
+            currency: account.currency.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn get_categories(&self, ledger: i64) -> Result<Vec<Category>, ServerError> {
+        Ok(self
+            .state()?
+            .categories
+            .iter()
+            .filter(|(l, _)| *l == ledger)
+            .map(|(_, name)| Category { name: name.clone() })
+            .collect())
+    }
+
+    fn add_category(&self, ledger: i64, category: &Category) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if state.category_exists(ledger, &category.name) {
+            return Err(category_exists(&category.name));
+        }
+        state.categories.push((ledger, category.name.clone()));
+
+        Ok(())
+    }
+
+    fn get_settings(&self, ledger: i64) -> Result<LedgerSettings, ServerError> {
+        Ok(self.state()?.settings(ledger)?.clone())
+    }
+
+    fn update_settings(&self, ledger: i64, settings: &LedgerSettings) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        state.settings(ledger)?;
+        for (id, stored) in state.ledgers.iter_mut() {
+            if *id == ledger {
+                *stored = settings.clone();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_conversions(&self, ledger: i64) -> Result<Vec<Conversion>, ServerError> {
+        let state = self.state()?;
+        let mut conversions: Vec<(i64, Conversion)> = state
+            .operations
+            .iter()
+            .filter(|operation| operation.ledger == ledger)
+            .filter_map(|operation| {
+                operation.to_amount.map(|to_amount| {
+                    let conversion = Conversion {
+                        from_currency: operation.currency.clone(),
+                        to_currency: state.account_currency(operation.to),
+                        amount: operation.amount,
+                        to_amount,
+                        datetime: operation.datetime,
+                    };
+                    (operation.id, conversion)
+                })
+            })
+            .collect();
+        conversions.sort_by_key(|(id, conversion)| (conversion.datetime, *id));
+
+        Ok(conversions
+            .into_iter()
+            .map(|(_, conversion)| conversion)
+            .collect())
+    }
+
+    fn get_rates(&self, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError> {
+        Ok(self.state()?.rates(ledger))
+    }
+
+    fn add_rates(&self, ledger: i64, rates: &[ExchangeRate]) -> Result<(), ServerError> {
+        self.state()?.add_rates(ledger, rates);
+
+        Ok(())
+    }
+
+    fn find_rate(
+        &self,
+        ledger: i64,
+        currency: &str,
+        other: &str,
+        date: &str,
+    ) -> Result<Option<ExchangeRate>, ServerError> {
+        let state = self.state()?;
+        let pair = |rate: &ExchangeRate| {
+            (rate.base == currency && rate.quote == other)
+                || (rate.base == other && rate.quote == currency)
+        };
+
+        Ok(state
+            .rates
+            .iter()
+            .enumerate()
+            .filter(|(_, (l, rate))| *l == ledger && pair(rate) && rate.date.as_str() <= date)
+            .max_by(|(i, (_, a)), (j, (_, b))| a.date.cmp(&b.date).then(i.cmp(j)))
+            .map(|(_, (_, rate))| rate.clone()))
+    }
+
+    fn get_securities(&self, ledger: i64) -> Result<Vec<Security>, ServerError> {
+        Ok(self.state()?.securities(ledger))
+    }
+
+    fn add_security(&self, ledger: i64, security: &Security) -> Result<(), ServerError> {
+        self.state()?.add_security(ledger, security)
+    }
+
+    fn record_trade(&self, ledger: i64, trade: &Trade) -> Result<(), ServerError> {
+        self.state()?.record_trade(ledger, trade)
+    }
+
+    fn get_trades(&self, ledger: i64) -> Result<Vec<Trade>, ServerError> {
+        Ok(self.state()?.trades(ledger))
+    }
+
+    fn get_prices(&self, ledger: i64) -> Result<Vec<Price>, ServerError> {
+        Ok(self.state()?.prices(ledger))
+    }
+
+    fn add_prices(&self, ledger: i64, prices: &[Price]) -> Result<(), ServerError> {
+        // Apply to a copy so an unknown symbol leaves nothing behind.
+        let mut state = self.state()?;
+        let mut next = state.clone();
+        next.add_prices(ledger, prices)?;
+        *state = next;
+
+        Ok(())
+    }
+
+    fn latest_prices(&self, ledger: i64, date: &str) -> Result<Vec<Price>, ServerError> {
+        let mut latest: Vec<Price> = Vec::new();
+        for price in self.state()?.prices(ledger) {
+            if price.date.as_str() > date {
+                continue;
+            }
+            match latest.last_mut() {
+                Some(last) if last.symbol == price.symbol => *last = price,
+                _ => latest.push(price),
+            }
+        }
+
+        Ok(latest)
+    }
+
+    fn get_credit_cards(&self, ledger: i64) -> Result<Vec<CreditCard>, ServerError> {
+        Ok(self.state()?.credit_cards(ledger))
+    }
+
+    fn set_credit_card(&self, ledger: i64, card: &CreditCard) -> Result<(), ServerError> {
+        self.state()?.set_credit_card(ledger, card)
+    }
+
+    fn balance_changes(
+        &self,
+        ledger: i64,
+        account: &str,
+        since: i64,
+    ) -> Result<Vec<(i64, i64)>, ServerError> {
+        let state = self.state()?;
+        let account = state
+            .account_id(ledger, account)
+            .ok_or_else(|| unknown_account("account", account))?;
+        let mut changes = Vec::new();
+        for operation in state.operations.iter() {
+            if operation.ledger != ledger || operation.datetime < since {
+                continue;
+            }
+            if operation.from == account {
+                changes.push((operation.datetime, -operation.amount));
+            }
+            if operation.legs.is_empty() {
+                if operation.to == account {
+                    let amount = operation.to_amount.unwrap_or(operation.amount);
+                    changes.push((operation.datetime, amount));
+                }
+            } else {
+                for leg in &operation.legs {
+                    if leg.account.unwrap_or(operation.to) == account {
+                        changes.push((operation.datetime, leg.amount));
+                    }
+                }
+            }
+        }
+        for trade in state.trades.iter() {
+            if trade.account == account && trade.datetime >= since {
+                let amount = match trade.side {
+                    TradeSide::Buy => -trade.amount,
+                    TradeSide::Sell => trade.amount,
+                };
+                changes.push((trade.datetime, amount));
+            }
+        }
+        changes.sort_by_key(|(datetime, _)| *datetime);
+
+        Ok(changes)
+    }
+
+    fn get_goals(&self, ledger: i64) -> Result<Vec<Goal>, ServerError> {
+        Ok(self.state()?.goals(ledger))
+    }
+
+    fn set_goal(&self, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+        self.state()?.set_goal(ledger, goal)
+    }
+
+    fn get_recurring(&self, ledger: i64) -> Result<Vec<RecurringOperation>, ServerError> {
+        Ok(self.state()?.recurring(ledger))
+    }
+
+    fn set_recurring(&self, ledger: i64, item: &RecurringOperation) -> Result<(), ServerError> {
+        self.state()?.set_recurring(ledger, item)
+    }
+
+    fn get_installment_plans(&self, ledger: i64) -> Result<Vec<InstallmentPlan>, ServerError> {
+        Ok(self.state()?.installment_plans(ledger))
+    }
+
+    fn add_installment_plan(&self, ledger: i64, plan: &InstallmentPlan) -> Result<(), ServerError> {
+        self.state()?.add_installment_plan(ledger, plan)
+    }
+
+    fn record_repayment(
+        &self,
+        ledger: i64,
+        plan: &str,
+        number: u32,
+        operation: &Operation,
+    ) -> Result<i64, ServerError> {
+        let mut state = self.state()?;
+        let stored = state
+            .installment_plans
+            .iter()
+            .position(|stored| stored.ledger == ledger && stored.plan.name == plan)
+            .ok_or_else(|| unknown_plan(plan))?;
+        let paid = state.installment_plans[stored].plan.paid;
+        if number == 0 || paid != number - 1 || number > state.installment_plans[stored].plan.term {
+            return Err(not_next_repayment(plan, number));
+        }
+        let id = state.record_operation(ledger, operation, ("from", "to", "legs"))?;
+        state.installment_plans[stored].plan.paid = number;
+
+        Ok(id)
+    }
+
+    fn share_operation(
+        &self,
+        ledger: i64,
+        operation: i64,
+        expense: &SharedExpense,
+    ) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if !state.operation_exists(ledger, operation) {
+            return Err(unknown_operation());
+        }
+        state.share_operation(operation, expense);
+
+        Ok(())
+    }
+
+    fn get_shared_expenses(&self, ledger: i64) -> Result<Vec<SharedAmount>, ServerError> {
+        Ok(self.state()?.shared_expenses(ledger))
+    }
+
+    fn set_claim(&self, ledger: i64, operation: i64, claim: &Claim) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if !state.operation_exists(ledger, operation) {
+            return Err(unknown_operation());
+        }
+        check_claim(operation, claim)?;
+        if let Some(reimbursement) = claim.reimbursement {
+            if !state.operation_exists(ledger, reimbursement) {
+                return Err(unknown_operation_at("reimbursement", reimbursement));
+            }
+        }
+        state.set_claim(operation, claim);
+
+        Ok(())
+    }
+
+    fn record_reimbursement(
+        &self,
+        ledger: i64,
+        reimbursement: &Reimbursement,
+    ) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        if !state.operation_exists(ledger, reimbursement.operation) {
+            return Err(unknown_operation_at("operation", reimbursement.operation));
+        }
+        if let Some(expense) = reimbursement
+            .expenses
+            .iter()
+            .find(|expense| !state.operation_exists(ledger, **expense))
+        {
+            return Err(unknown_operation_at("expenses", *expense));
+        }
+        for expense in &reimbursement.expenses {
+            state.set_claim(*expense, &paid_by(reimbursement.operation));
+        }
+
+        Ok(())
+    }
+
+    fn get_claims(&self, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError> {
+        Ok(self.state()?.claims(ledger))
+    }
+
+    fn operations_since(&self, ledger: i64, since: i64) -> Result<Vec<Operation>, ServerError> {
+        let state = self.state()?;
+        let mut operations: Vec<&StoredOperation> = state
+            .operations
+            .iter()
+            .filter(|operation| operation.ledger == ledger && operation.datetime >= since)
+            .collect();
+        operations.sort_by_key(|operation| (operation.datetime, operation.id));
+
+        Ok(operations
+            .into_iter()
+            .map(|operation| state.to_operation(operation))
+            .collect())
+    }
+
+    fn export_ledger(&self, ledger: i64, now: i64) -> Result<LedgerExport, ServerError> {
+        let state = self.state()?;
+        let settings = state.settings(ledger)?.clone();
+
+        let mut operations: Vec<&StoredOperation> = state
+            .operations
+            .iter()
+            .filter(|operation| operation.ledger == ledger)
+            .collect();
+        operations.sort_by_key(|operation| (operation.datetime, operation.id));
+        let ids: Vec<i64> = operations.iter().map(|operation| operation.id).collect();
+
+        Ok(LedgerExport {
+            version: EXPORT_VERSION,
+            exported_at: now,
+            settings,
+            accounts: state
+                .accounts
+                .iter()
+                .filter(|account| account.ledger == ledger)
+                .map(|account| Account {
+                    name: account.name.clone(),
+                    balance: account.balance,
+                    currency: account.currency.clone(),
+                })
+                .collect(),
+            categories: state
+                .categories
+                .iter()
+                .filter(|(l, _)| *l == ledger)
+                .map(|(_, name)| Category { name: name.clone() })
+                .collect(),
+            operations: operations
+                .into_iter()
+                .map(|operation| state.to_operation(operation))
+                .collect(),
+            rates: state.rates(ledger),
+            securities: state.securities(ledger),
+            trades: state.trades(ledger),
+            prices: state.prices(ledger),
+            credit_cards: state.credit_cards(ledger),
+            installment_plans: state.installment_plans(ledger),
+            shared_expenses: export_shares(&ids, state.shared_expenses(ledger)),
+            claims: export_claims(&ids, state.claims(ledger)),
+            goals: state.goals(ledger),
+            recurring: state.recurring(ledger),
+        })
+    }
+
+    fn import_ledger(&self, ledger: i64, export: &LedgerExport) -> Result<(), ServerError> {
+        let mut state = self.state()?;
+        let has_data = state.accounts.iter().any(|a| a.ledger == ledger)
+            || state.categories.iter().any(|(l, _)| *l == ledger)
+            || state.operations.iter().any(|o| o.ledger == ledger)
+            || state.securities.iter().any(|s| s.ledger == ledger);
+        if has_data {
+            return Err(ServerError::ConflictError(
+                "Only an empty ledger can be imported into.".to_owned(),
+            ));
+        }
+
+        // Apply to a copy so a failure halfway leaves nothing behind, like a rolled back
+        // transaction.
+        let mut next = state.clone();
+        for (id, settings) in next.ledgers.iter_mut() {
+            if *id == ledger {
+                *settings = export.settings.clone();
+            }
+        }
+        for account in &export.accounts {
+            let id = next.next_id();
+            next.accounts.push(StoredAccount {
+                id,
+                ledger,
+                name: account.name.clone(),
+                balance: 0,
+                currency: account.currency.clone(),
+            });
+        }
+        next.categories.extend(
+            export
+                .categories
+                .iter()
+                .map(|category| (ledger, category.name.clone())),
+        );
+        let mut ids = Vec::new();
+        for operation in &export.operations {
+            let fields = ("operations", "operations", "operations");
+            ids.push(next.record_operation(ledger, operation, fields)?);
+        }
+        next.add_rates(ledger, &export.rates);
+        for security in &export.securities {
+            next.add_security(ledger, security)?;
+        }
+        for trade in &export.trades {
+            next.record_trade(ledger, trade)
+                .map_err(|e| in_field(e, "trades"))?;
+        }
+        next.add_prices(ledger, &export.prices)
+            .map_err(|e| in_field(e, "prices"))?;
+        for card in &export.credit_cards {
+            next.set_credit_card(ledger, card)
+                .map_err(|e| in_field(e, "credit_cards"))?;
+        }
+        for goal in &export.goals {
+            next.set_goal(ledger, goal)
+                .map_err(|e| in_field(e, "goals"))?;
+        }
+        for item in &export.recurring {
+            next.set_recurring(ledger, item)
+                .map_err(|e| in_field(e, "recurring"))?;
+        }
+        for plan in &export.installment_plans {
+            next.add_installment_plan(ledger, plan)
+                .map_err(|e| in_field(e, "installment_plans"))?;
+        }
+        for share in &export.shared_expenses {
+            next.share_operation(ids[share.operation], &share.expense);
+        }
+        for claim in &export.claims {
+            let stored = Claim {
+                status: claim.status,
+                reimbursement: claim.reimbursement.map(|i| ids[i]),
+            };
+            next.set_claim(ids[claim.operation], &stored);
+        }
+        *state = next;
+
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        ledger: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<SearchHit>, ServerError> {
+        let state = self.state()?;
+        let mut hits = Vec::new();
+        for operation in state.operations.iter().filter(|o| o.ledger == ledger) {
+            let from = state.account_name(operation.from);
+            let to = state.account_name(operation.to);
+            let counterparties = format!("{} {}", from, to);
+            let found = |text: &str, term: &str| words(text).any(|word| starts_with(word, term));
+            if !terms
+                .iter()
+                .all(|term| found(&operation.comment, term) || found(&counterparties, term))
+            {
+                continue;
+            }
+
+            let in_comment = terms
+                .iter()
+                .filter(|term| found(&operation.comment, term))
+                .count();
+            let text = if in_comment > 0 {
+                &operation.comment
+            } else {
+                &counterparties
+            };
+            let hit = SearchHit {
+                id: operation.id,
+                operation: state.to_operation(operation),
+                snippet: highlight(&mark_words(text, terms)),
+            };
+            hits.push((in_comment, operation.datetime, hit));
+        }
+        // Matches in the comment count for more, as in the SQL implementations, then newer first.
+        hits.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+
+        Ok(hits
+            .into_iter()
+            .take(limit as usize)
+            .map(|(_, _, hit)| hit)
+            .collect())
+    }
+
+    fn add_attachment(
+        &self,
+        ledger: i64,
+        operation: i64,
+        attachment: &NewAttachment,
+        sha256: &str,
+        created_at: i64,
+        content: Option<&[u8]>,
+    ) -> Result<Attachment, ServerError> {
+        let mut state = self.state()?;
+        if !state.operation_exists(ledger, operation) {
+            return Err(unknown_operation());
+        }
+
+        if let Some(content) = content {
+            state
+                .contents
+                .entry(sha256.to_owned())
+                .or_insert_with(|| content.to_vec());
+        }
+        let stored = StoredAttachment {
+            id: state.next_id(),
+            ledger,
+            operation,
+            name: attachment.name.clone(),
+            content_type: attachment.content_type.clone(),
+            size: attachment.size,
+            sha256: sha256.to_owned(),
+            created_at,
+        };
+        let attachment = stored.to_attachment();
+        state.attachments.push(stored);
+
+        Ok(attachment)
+    }
+
+    fn get_attachments(&self, ledger: i64, operation: i64) -> Result<Vec<Attachment>, ServerError> {
+        let state = self.state()?;
+        if !state.operation_exists(ledger, operation) {
+            return Err(unknown_operation());
+        }
+
+        Ok(state
+            .attachments
+            .iter()
+            .filter(|a| a.ledger == ledger && a.operation == operation)
+            .map(StoredAttachment::to_attachment)
+            .collect())
+    }
+
+    fn get_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<AttachmentContent>, ServerError> {
+        let state = self.state()?;
+
+        Ok(state
+            .attachments
+            .iter()
+            .find(|a| a.id == id && a.ledger == ledger)
+            .map(|a| (a.to_attachment(), state.contents.get(&a.sha256).cloned())))
+    }
+
+    fn delete_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<(Attachment, bool)>, ServerError> {
+        let mut state = self.state()?;
+        let position = match state
+            .attachments
+            .iter()
+            .position(|a| a.id == id && a.ledger == ledger)
+        {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        let attachment = state.attachments.remove(position).to_attachment();
+        let shared = state
+            .attachments
+            .iter()
+            .any(|a| a.sha256 == attachment.sha256);
+        if !shared {
+            state.contents.remove(&attachment.sha256);
+        }
+
+        Ok(Some((attachment, shared)))
+    }
+
+    fn current_db_version(&self) -> Result<Option<DbVersion>, ServerError> {
+        Ok(None)
+    }
+}
+
+/// Splits text into words the way the full-text indexes tokenize it.
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+fn starts_with(word: &str, term: &str) -> bool {
+    word.to_lowercase().starts_with(term)
+}
+
+/// Puts match markers around the words of `text` that start with one of `terms`.
+fn mark_words(text: &str, terms: &[String]) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut word = String::new();
+    let push_word = |marked: &mut String, word: &mut String| {
+        if terms.iter().any(|term| starts_with(word, term)) {
+            marked.push(MATCH_START);
+            marked.push_str(word);
+            marked.push(MATCH_END);
+        } else {
+            marked.push_str(word);
+        }
+        word.clear();
+    };
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            push_word(&mut marked, &mut word);
+            marked.push(c);
+        }
+    }
+    push_word(&mut marked, &mut word);
+
+    marked
+}
+
+impl State {
+    fn operation_exists(&self, ledger: i64, operation: i64) -> bool {
+        self.operations
+            .iter()
+            .any(|o| o.id == operation && o.ledger == ledger)
+    }
+
+    fn remove_totps(&mut self, user: i64, confirmed: bool) {
+        let removed: Vec<i64> = self
+            .totps
+            .iter()
+            .filter(|totp| totp.user == user && totp.confirmed == confirmed)
+            .map(|totp| totp.id)
+            .collect();
+        self.totps.retain(|totp| !removed.contains(&totp.id));
+        self.recovery_codes
+            .retain(|code| !removed.contains(&code.totp));
+    }
+}
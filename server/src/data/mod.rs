@@ -0,0 +1,2004 @@
+#[cfg(test)]
+mod memory;
+mod postgres;
+mod sqlite;
+
+#[cfg(test)]
+pub use memory::MemoryStorage;
+pub use postgres::PostgresStorage;
+pub use sqlite::SqliteStorage;
+
+use crate::error::ServerError;
+use crate::migration::{Dialect, Migration};
+use crate::password;
+use entities::money::format_decimal;
+use entities::{
+    Account, Attachment, Category, Claim, ClaimStatus, ClaimedExpense, Conversion, CreditCard,
+    DbVersion, ExchangeRate, ExportedClaim, ExportedShare, Goal, InstallmentPlan, Ledger,
+    LedgerExport, LedgerMember, LedgerSettings, NewAttachment, NewLedger, Operation, Price,
+    RecurringOperation, Reimbursement, Role, SearchHit, Security, SharedExpense, Trade,
+    QUANTITY_DIGITS,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    /// The password as `password::hash` hashed it.
+    pub password_hash: String,
+    pub admin: bool,
+}
+
+pub struct TotpSecret {
+    pub id: i64,
+    pub secret: String,
+    pub last_step: i64,
+}
+
+/// A shared expense with the amount of the operation it shares, in the operation's currency.
+pub struct SharedAmount {
+    pub operation: i64,
+    pub amount: i64,
+    pub currency: String,
+    pub expense: SharedExpense,
+}
+
+/// An attachment with its content, or `None` if the content is kept outside the database.
+pub type AttachmentContent = (Attachment, Option<Vec<u8>>);
+
+/// The storage handlers share, cheap to clone into `web::block`.
+pub type Store = Arc<dyn Storage>;
+
+/// Everything the handlers persist. Methods block, so call them from `web::block`.
+///
+/// Implementations must agree on errors as well as data: a duplicate name is a
+/// `ConflictError`, an unknown account in an operation a `ValidationError` and so on, since
+/// handlers pass them straight to the client.
+pub trait Storage: Send + Sync {
+    /// Inserts the operation with its legs and moves its amount from `from` to the accounts
+    /// of `Operation::credits` atomically, returning its id. Leg accounts and categories must
+    /// exist in the ledger.
+    fn record_operation(&self, ledger: i64, operation: &Operation) -> Result<i64, ServerError>;
+
+    /// Records operations imported from a statement, each under a key that identifies its
+    /// transaction, all or none of them. Those whose key an earlier import recorded are skipped;
+    /// returns how many were recorded.
+    fn import_operations(
+        &self,
+        ledger: i64,
+        operations: &[(String, Operation)],
+    ) -> Result<usize, ServerError>;
+
+    fn get_user(&self, name: &str) -> Result<Option<User>, ServerError>;
+
+    fn get_user_by_id(&self, id: i64) -> Result<Option<User>, ServerError>;
+
+    fn add_user(&self, name: &str, password_hash: &str) -> Result<(), ServerError>;
+
+    /// Lists the users whose token is still stored as the password in plain text, as it was
+    /// before 1_19_0, with the token.
+    fn plain_tokens(&self) -> Result<Vec<(i64, String)>, ServerError>;
+
+    fn set_password_hash(&self, user: i64, password_hash: &str) -> Result<(), ServerError>;
+
+    fn get_ledgers(&self, user: i64) -> Result<Vec<Ledger>, ServerError>;
+
+    fn get_role(&self, user: i64, ledger: i64) -> Result<Option<Role>, ServerError>;
+
+    /// Lists the id of every ledger, for work the server does on all of them.
+    fn ledger_ids(&self) -> Result<Vec<i64>, ServerError>;
+
+    /// Creates a ledger owned by `user`, returning its id.
+    fn add_ledger(&self, user: i64, ledger: &NewLedger) -> Result<i64, ServerError>;
+
+    /// Adds the named user to the ledger, or changes their role if they're already a member.
+    /// Fails on `role` if the change would leave the ledger without an owner.
+    fn set_member(&self, ledger: i64, member: &LedgerMember) -> Result<(), ServerError>;
+
+    /// Returns the user's latest confirmed secret, or pending one if `confirmed` is false.
+    fn get_totp(&self, user: i64, confirmed: bool) -> Result<Option<TotpSecret>, ServerError>;
+
+    /// Stores a pending secret with its recovery codes, replacing any earlier pending one.
+    fn enrol_totp(
+        &self,
+        user: i64,
+        secret: &str,
+        recovery_hashes: &[String],
+    ) -> Result<(), ServerError>;
+
+    /// Promotes a pending enrolment and drops the previous secret with its recovery codes.
+    fn confirm_totp(&self, user: i64, totp_id: i64, step: i64) -> Result<(), ServerError>;
+
+    /// Records the step of an accepted code, returning false if it was used concurrently.
+    fn update_totp_step(&self, totp_id: i64, step: i64) -> Result<bool, ServerError>;
+
+    /// Marks a recovery code as used, returning whether an unused one matched.
+    fn use_recovery_code(&self, totp_id: i64, hash: &str) -> Result<bool, ServerError>;
+
+    fn get_accounts(&self, ledger: i64) -> Result<Vec<Account>, ServerError>;
+
+    fn add_account(&self, ledger: i64, account: &Account) -> Result<(), ServerError>;
+
+    /// Lists the ledger's categories, oldest first.
+    fn get_categories(&self, ledger: i64) -> Result<Vec<Category>, ServerError>;
+
+    fn add_category(&self, ledger: i64, category: &Category) -> Result<(), ServerError>;
+
+    fn get_settings(&self, ledger: i64) -> Result<LedgerSettings, ServerError>;
+
+    /// Renames the ledger and changes its base currency and home timezone. Recorded
+    /// timestamps are instants, so they keep their meaning in the new timezone.
+    fn update_settings(&self, ledger: i64, settings: &LedgerSettings) -> Result<(), ServerError>;
+
+    /// Lists the ledger's operations between accounts in different currencies, oldest first.
+    fn get_conversions(&self, ledger: i64) -> Result<Vec<Conversion>, ServerError>;
+
+    /// Lists the ledger's exchange rates by currency pair, then date.
+    fn get_rates(&self, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError>;
+
+    /// Stores the rates atomically, replacing any the ledger has for the same pair and date.
+    fn add_rates(&self, ledger: i64, rates: &[ExchangeRate]) -> Result<(), ServerError>;
+
+    /// Returns the latest rate between the two currencies dated on or before `date`, quoted
+    /// either way round.
+    fn find_rate(
+        &self,
+        ledger: i64,
+        currency: &str,
+        other: &str,
+        date: &str,
+    ) -> Result<Option<ExchangeRate>, ServerError>;
+
+    /// Lists the ledger's securities, oldest first.
+    fn get_securities(&self, ledger: i64) -> Result<Vec<Security>, ServerError>;
+
+    fn add_security(&self, ledger: i64, security: &Security) -> Result<(), ServerError>;
+
+    /// Inserts the trade and moves its amount into or out of the account's balance
+    /// atomically. The account and security must exist in the ledger and
+    /// pass `check_trade` against the account's earlier trades in the security.
+    fn record_trade(&self, ledger: i64, trade: &Trade) -> Result<(), ServerError>;
+
+    /// Lists the ledger's trades, oldest first.
+    fn get_trades(&self, ledger: i64) -> Result<Vec<Trade>, ServerError>;
+
+    /// Lists the ledger's prices by symbol, then date.
+    fn get_prices(&self, ledger: i64) -> Result<Vec<Price>, ServerError>;
+
+    /// Stores the prices atomically, replacing any the security has for the same date.
+    fn add_prices(&self, ledger: i64, prices: &[Price]) -> Result<(), ServerError>;
+
+    /// Returns each security's latest price dated on or before `date`, by symbol.
+    fn latest_prices(&self, ledger: i64, date: &str) -> Result<Vec<Price>, ServerError>;
+
+    /// Lists the ledger's credit cards in the order their accounts were added.
+    fn get_credit_cards(&self, ledger: i64) -> Result<Vec<CreditCard>, ServerError>;
+
+    /// Makes the account a credit card, or changes its billing cycle, limit and the account
+    /// paying it if it is one.
+    fn set_credit_card(&self, ledger: i64, card: &CreditCard) -> Result<(), ServerError>;
+
+    /// Lists the ledger's savings goals, soonest target first.
+    fn get_goals(&self, ledger: i64) -> Result<Vec<Goal>, ServerError>;
+
+    /// Adds the goal, or changes the one with its name. The account can't be one another goal
+    /// already saves in.
+    fn set_goal(&self, ledger: i64, goal: &Goal) -> Result<(), ServerError>;
+
+    /// Lists the ledger's recurring operations by name.
+    fn get_recurring(&self, ledger: i64) -> Result<Vec<RecurringOperation>, ServerError>;
+
+    /// Adds the recurring operation, or changes the one with its name. Both accounts must be in
+    /// the same currency.
+    fn set_recurring(&self, ledger: i64, item: &RecurringOperation) -> Result<(), ServerError>;
+
+    /// Lists how each operation and trade dated at or after `since` changed the account's
+    /// balance, oldest first, as `(datetime, amount)`.
+    fn balance_changes(
+        &self,
+        ledger: i64,
+        account: &str,
+        since: i64,
+    ) -> Result<Vec<(i64, i64)>, ServerError>;
+
+    /// Lists the operations dated at or after `since` with their legs, oldest first.
+    fn operations_since(&self, ledger: i64, since: i64) -> Result<Vec<Operation>, ServerError>;
+
+    /// Lists the ledger's installment plans in the order they were added.
+    fn get_installment_plans(&self, ledger: i64) -> Result<Vec<InstallmentPlan>, ServerError>;
+
+    /// Adds an installment plan over accounts of the ledger that share a currency.
+    fn add_installment_plan(&self, ledger: i64, plan: &InstallmentPlan) -> Result<(), ServerError>;
+
+    /// Records the operation as repayment `number` of the plan, which must be the one after
+    /// those recorded so far, returning the operation's id.
+    fn record_repayment(
+        &self,
+        ledger: i64,
+        plan: &str,
+        number: u32,
+        operation: &Operation,
+    ) -> Result<i64, ServerError>;
+
+    /// Shares the operation among household members, replacing how it was shared before.
+    fn share_operation(
+        &self,
+        ledger: i64,
+        operation: i64,
+        expense: &SharedExpense,
+    ) -> Result<(), ServerError>;
+
+    /// Lists the ledger's shared expenses, oldest operation first.
+    fn get_shared_expenses(&self, ledger: i64) -> Result<Vec<SharedAmount>, ServerError>;
+
+    /// Flags the operation as reimbursable, or updates where its claim stands.
+    fn set_claim(&self, ledger: i64, operation: i64, claim: &Claim) -> Result<(), ServerError>;
+
+    /// Marks every expense paid back by the reimbursement operation, all or none.
+    fn record_reimbursement(
+        &self,
+        ledger: i64,
+        reimbursement: &Reimbursement,
+    ) -> Result<(), ServerError>;
+
+    /// Lists the ledger's reimbursable expenses, oldest first.
+    fn get_claims(&self, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError>;
+
+    /// Reads the whole ledger into one document. `now` is recorded as `exported_at`.
+    fn export_ledger(&self, ledger: i64, now: i64) -> Result<LedgerExport, ServerError>;
+
+    /// Loads a validated export into a ledger that has no accounts, categories, operations or
+    /// securities. Balances are rebuilt from the operations and trades the same way
+    /// `record_operation` and `record_trade` do.
+    fn import_ledger(&self, ledger: i64, export: &LedgerExport) -> Result<(), ServerError>;
+
+    /// Finds the ledger's operations whose comment or accounts contain words starting with
+    /// every one of `terms`, best match first. `terms` come from `SearchQuery::terms`.
+    fn search(
+        &self,
+        ledger: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<SearchHit>, ServerError>;
+
+    /// Records a file attached to an operation of the ledger. `content` is stored with it
+    /// unless the caller keeps files elsewhere, in which case it's `None`; content shared by
+    /// several attachments is stored once.
+    fn add_attachment(
+        &self,
+        ledger: i64,
+        operation: i64,
+        attachment: &NewAttachment,
+        sha256: &str,
+        created_at: i64,
+        content: Option<&[u8]>,
+    ) -> Result<Attachment, ServerError>;
+
+    /// Lists an operation's attachments, oldest first.
+    fn get_attachments(&self, ledger: i64, operation: i64) -> Result<Vec<Attachment>, ServerError>;
+
+    /// Returns an attachment with its content, if that's stored here.
+    fn get_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<AttachmentContent>, ServerError>;
+
+    /// Deletes an attachment, returning it and whether another one still has the same content.
+    fn delete_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<(Attachment, bool)>, ServerError>;
+
+    /// Returns the newest applied migration, if any.
+    fn current_db_version(&self) -> Result<Option<DbVersion>, ServerError>;
+}
+
+/// The database `ServerConfig.db_addr` names: a `postgres://` or `postgresql://` URL, or
+/// otherwise the path of a SQLite file.
+#[derive(Clone)]
+pub enum Backend {
+    Sqlite(SqliteStorage),
+    Postgres(PostgresStorage),
+}
+
+impl Backend {
+    pub fn dialect_of(addr: &str) -> Dialect {
+        if addr.starts_with("postgres://") || addr.starts_with("postgresql://") {
+            Dialect::Postgres
+        } else {
+            Dialect::Sqlite
+        }
+    }
+
+    pub fn open(addr: &str) -> Result<Self, ServerError> {
+        match Self::dialect_of(addr) {
+            Dialect::Sqlite => Ok(Backend::Sqlite(SqliteStorage::new(addr))),
+            Dialect::Postgres => Ok(Backend::Postgres(PostgresStorage::new(addr)?)),
+        }
+    }
+
+    pub fn pending_migrations<'a>(
+        &self,
+        migrations: &'a [Migration],
+    ) -> Result<Vec<&'a Migration>, ServerError> {
+        match self {
+            Backend::Sqlite(db) => db.pending_migrations(migrations),
+            Backend::Postgres(db) => db.pending_migrations(migrations),
+        }
+    }
+
+    /// Applies the pending migrations, then hashes any passwords still stored in plain text.
+    pub fn migrate(&self, migrations: &[Migration]) -> Result<(), ServerError> {
+        match self {
+            Backend::Sqlite(db) => db.migrate(migrations)?,
+            Backend::Postgres(db) => db.migrate(migrations)?,
+        }
+
+        password::hash_stored_tokens(self.store().as_ref())
+    }
+
+    pub fn store(&self) -> Store {
+        match self {
+            Backend::Sqlite(db) => Arc::new(db.clone()),
+            Backend::Postgres(db) => Arc::new(db.clone()),
+        }
+    }
+}
+
+/// Put around matches in snippets by the database, which can't escape the text between them.
+/// `highlight` turns them into markup.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+/// Escapes a snippet for HTML and wraps what lies between the match markers in `<mark>`.
+fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len());
+    for c in snippet.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+/// Checks an operation against the currencies of the accounts it was resolved to: `from`'s,
+/// `to`'s and, in leg order, those of legs naming their own account. Returns the currency of
+/// its amount.
+fn validate_currencies(
+    operation: &Operation,
+    from: &str,
+    to: &str,
+    legs: &[Option<&str>],
+) -> Result<String, ServerError> {
+    let mut currencies = HashMap::new();
+    currencies.insert(operation.from.as_str(), from);
+    currencies.insert(operation.to.as_str(), to);
+    for (leg, currency) in operation.legs.iter().zip(legs) {
+        if let (Some(name), Some(currency)) = (&leg.account, currency) {
+            currencies.insert(name.as_str(), *currency);
+        }
+    }
+
+    Ok(operation.validate_currencies(&currencies)?)
+}
+
+/// Checks a trade against the currencies of its account and security, and against what the
+/// account held of the security before it with the time of its latest trade in it, if any.
+/// Trades can't be backdated, so that lots are always used up in the order they were bought.
+fn check_trade(
+    trade: &Trade,
+    account_currency: &str,
+    security_currency: &str,
+    held: i64,
+    latest: Option<i64>,
+) -> Result<(), ServerError> {
+    if account_currency != security_currency {
+        return Err(ServerError::validation(
+            "symbol",
+            format!(
+                "{} is in {} but {} holds {}.",
+                trade.symbol, security_currency, trade.account, account_currency
+            ),
+        ));
+    }
+    if held + trade.signed_quantity() < 0 {
+        return Err(ServerError::validation(
+            "quantity",
+            format!(
+                "{} holds only {} of {}.",
+                trade.account,
+                format_decimal(held, QUANTITY_DIGITS),
+                trade.symbol
+            ),
+        ));
+    }
+    if latest.is_some_and(|latest| trade.datetime < latest) {
+        return Err(ServerError::validation(
+            "datetime",
+            format!(
+                "{} already traded {} later than this.",
+                trade.account, trade.symbol
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Names the operations of shared expenses by their position in `ids`, as exports do.
+fn export_shares(ids: &[i64], shared: Vec<SharedAmount>) -> Vec<ExportedShare> {
+    shared
+        .into_iter()
+        .filter_map(|shared| {
+            let operation = ids.iter().position(|id| *id == shared.operation)?;
+            Some(ExportedShare {
+                operation,
+                expense: shared.expense,
+            })
+        })
+        .collect()
+}
+
+/// Names the operations of claims by their position in `ids`, as exports do.
+fn export_claims(ids: &[i64], claims: Vec<ClaimedExpense>) -> Vec<ExportedClaim> {
+    let index = |id: i64| ids.iter().position(|i| *i == id);
+    claims
+        .into_iter()
+        .filter_map(|claim| {
+            Some(ExportedClaim {
+                operation: index(claim.operation)?,
+                status: claim.status,
+                reimbursement: match claim.reimbursement {
+                    Some(id) => Some(index(id)?),
+                    None => None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Checks what the claim's validation can't: that the expense doesn't pay itself back.
+fn check_claim(operation: i64, claim: &Claim) -> Result<(), ServerError> {
+    if claim.reimbursement == Some(operation) {
+        return Err(ServerError::validation(
+            "reimbursement",
+            "A reimbursement can't pay itself back.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that a ledger still has someone who can manage it after its members changed.
+fn check_owners(owners: i64) -> Result<(), ServerError> {
+    if owners == 0 {
+        return Err(ServerError::validation(
+            "role",
+            "A ledger needs at least one owner.",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that the accounts of an installment plan share the currency of the one it's owed on.
+fn check_plan(
+    plan: &InstallmentPlan,
+    account_currency: &str,
+    from_currency: &str,
+    cost_currency: Option<&str>,
+) -> Result<(), ServerError> {
+    let others = [
+        ("from", Some(&plan.from), Some(from_currency)),
+        ("cost_account", plan.cost_account.as_ref(), cost_currency),
+    ];
+    for (field, name, currency) in others.iter() {
+        if let (Some(name), Some(currency)) = (name, currency) {
+            if *currency != account_currency {
+                return Err(ServerError::validation(
+                    field,
+                    format!(
+                        "{} is in {} but {} is in {}.",
+                        name, currency, plan.account, account_currency
+                    ),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that a credit card is paid from an account in its own currency.
+fn check_card(
+    card: &CreditCard,
+    account_currency: &str,
+    pay_from_currency: Option<&str>,
+) -> Result<(), ServerError> {
+    if let (Some(pay_from), Some(currency)) = (&card.pay_from, pay_from_currency) {
+        if currency != account_currency {
+            return Err(ServerError::validation(
+                "pay_from",
+                format!(
+                    "{} is in {} but {} is in {}.",
+                    pay_from, currency, card.account, account_currency
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Blames a validation error on `field`, for records checked as part of a larger document.
+fn in_field(e: ServerError, field: &str) -> ServerError {
+    match e {
+        ServerError::ValidationError { message, .. } => ServerError::validation(field, message),
+        e => e,
+    }
+}
+
+fn unknown_account(field: &str, name: &str) -> ServerError {
+    ServerError::validation(field, format!("Account {} doesn't exist.", name))
+}
+
+fn unknown_category(field: &str, name: &str) -> ServerError {
+    ServerError::validation(field, format!("Category {} doesn't exist.", name))
+}
+
+fn unknown_security(field: &str, symbol: &str) -> ServerError {
+    ServerError::validation(field, format!("Security {} doesn't exist.", symbol))
+}
+
+fn security_exists(symbol: &str) -> ServerError {
+    ServerError::ConflictError(format!("Security {} already exists.", symbol))
+}
+
+fn category_exists(name: &str) -> ServerError {
+    ServerError::ConflictError(format!("Category {} already exists.", name))
+}
+
+fn user_exists(name: &str) -> ServerError {
+    ServerError::ConflictError(format!("User {} already exists.", name))
+}
+
+fn account_has_goal(account: &str, goal: &str) -> ServerError {
+    ServerError::validation(
+        "account",
+        format!("{} already saves towards {}.", account, goal),
+    )
+}
+
+/// Checks that a recurring operation moves money within one currency.
+fn check_recurring(
+    item: &RecurringOperation,
+    from_currency: &str,
+    to_currency: &str,
+) -> Result<(), ServerError> {
+    if from_currency != to_currency {
+        return Err(ServerError::validation(
+            "to",
+            format!(
+                "{} is in {} but {} is in {}.",
+                item.from, from_currency, item.to, to_currency
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn plan_exists(name: &str) -> ServerError {
+    ServerError::ConflictError(format!("Installment plan {} already exists.", name))
+}
+
+fn unknown_plan(name: &str) -> ServerError {
+    ServerError::NotFoundError(format!("Installment plan {} doesn't exist.", name))
+}
+
+fn not_next_repayment(name: &str, number: u32) -> ServerError {
+    ServerError::ConflictError(format!(
+        "Repayment {} of {} isn't the next one due.",
+        number, name
+    ))
+}
+
+fn unknown_operation() -> ServerError {
+    ServerError::NotFoundError("Operation doesn't exist.".to_owned())
+}
+
+fn unknown_operation_at(field: &str, id: i64) -> ServerError {
+    ServerError::validation(field, format!("Operation {} doesn't exist.", id))
+}
+
+/// The claim recording that `reimbursement` paid the expense back.
+fn paid_by(reimbursement: i64) -> Claim {
+    Claim {
+        status: ClaimStatus::Paid,
+        reimbursement: Some(reimbursement),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migration;
+    use ::postgres::{Client, NoTls};
+    use entities::time::DEFAULT_TIMEZONE;
+    use entities::{Frequency, Leg, MemberShare, ShareMethod, TradeSide, DEFAULT_CURRENCY};
+    use std::env;
+    use tempfile::TempDir;
+
+    const NOW: i64 = 1_585_699_200_000;
+
+    /// Runs `check` against every implementation, each starting from a fresh store.
+    fn for_each_storage(check: impl Fn(&dyn Storage)) {
+        check(&MemoryStorage::new("123456"));
+
+        let dir = TempDir::new().unwrap();
+        let sqlite = SqliteStorage::new(dir.path().join("ledger.db"));
+        sqlite
+            .migrate(&migration::embedded(Dialect::Sqlite).unwrap())
+            .unwrap();
+        check(&sqlite);
+
+        // Runs only against a server named by `POSTGRES_TEST_ADDR`, in a throwaway schema.
+        if let Ok(addr) = env::var("POSTGRES_TEST_ADDR") {
+            let schema = format!("test_{}", rand::random::<u32>());
+            let mut client = Client::connect(&addr, NoTls).unwrap();
+            client
+                .batch_execute(&format!("CREATE SCHEMA {}", schema))
+                .unwrap();
+
+            let separator = if addr.contains('?') { '&' } else { '?' };
+            let url = format!("{}{}options=-csearch_path%3D{}", addr, separator, schema);
+            let postgres = PostgresStorage::new(&url).unwrap();
+            postgres
+                .migrate(&migration::embedded(Dialect::Postgres).unwrap())
+                .unwrap();
+            check(&postgres);
+
+            drop(postgres);
+            client
+                .batch_execute(&format!("DROP SCHEMA {} CASCADE", schema))
+                .unwrap();
+        }
+    }
+
+    fn account(name: &str) -> Account {
+        Account {
+            name: name.to_owned(),
+            balance: 0,
+            currency: DEFAULT_CURRENCY.to_owned(),
+        }
+    }
+
+    fn operation(from: &str, to: &str, amount: i64) -> Operation {
+        Operation {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            comment: String::new(),
+            amount,
+            datetime: NOW,
+            legs: Vec::new(),
+            currency: None,
+            to_amount: None,
+        }
+    }
+
+    fn rate(base: &str, quote: &str, date: &str, rate: i64) -> ExchangeRate {
+        ExchangeRate {
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+            date: date.to_owned(),
+            rate,
+        }
+    }
+
+    fn security(symbol: &str, currency: &str) -> Security {
+        Security {
+            symbol: symbol.to_owned(),
+            name: format!("{} fund", symbol),
+            currency: currency.to_owned(),
+        }
+    }
+
+    fn shared(paid_by: &str) -> SharedExpense {
+        SharedExpense {
+            paid_by: paid_by.to_owned(),
+            method: ShareMethod::Shares,
+            shares: vec![
+                member_share("alice", 1),
+                member_share("bob", 2),
+                member_share("carol", 1),
+            ],
+        }
+    }
+
+    fn member_share(member: &str, share: i64) -> MemberShare {
+        MemberShare {
+            member: member.to_owned(),
+            share,
+        }
+    }
+
+    fn trade(side: TradeSide, quantity: i64, amount: i64, datetime: i64) -> Trade {
+        Trade {
+            account: "Broker".to_owned(),
+            symbol: "VTI".to_owned(),
+            side,
+            quantity,
+            amount,
+            datetime,
+            comment: String::new(),
+        }
+    }
+
+    fn price(symbol: &str, date: &str, price: i64) -> Price {
+        Price {
+            symbol: symbol.to_owned(),
+            date: date.to_owned(),
+            price,
+        }
+    }
+
+    fn leg(account: Option<&str>, category: Option<&str>, amount: i64) -> Leg {
+        Leg {
+            account: account.map(str::to_owned),
+            category: category.map(str::to_owned),
+            amount,
+            memo: String::new(),
+        }
+    }
+
+    fn balances(store: &dyn Storage, ledger: i64) -> Vec<(String, i64)> {
+        store
+            .get_accounts(ledger)
+            .unwrap()
+            .into_iter()
+            .map(|account| (account.name, account.balance))
+            .collect()
+    }
+
+    #[test]
+    fn test_operations_move_balances() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Cash")).unwrap();
+            store
+                .record_operation(1, &operation("Bank", "Cash", 300))
+                .unwrap();
+
+            let balances: Vec<(String, i64)> = store
+                .get_accounts(1)
+                .unwrap()
+                .into_iter()
+                .map(|account| (account.name, account.balance))
+                .collect();
+            assert!(balances.contains(&("Bank".to_owned(), -300)));
+            assert!(balances.contains(&("Cash".to_owned(), 300)));
+
+            match store.add_account(1, &account("Bank")) {
+                Err(ServerError::ConflictError(message)) => {
+                    assert_eq!(message, "Account Bank already exists.")
+                }
+                _ => panic!("Duplicate accounts must conflict."),
+            }
+            match store.record_operation(1, &operation("Bank", "Card", 1)) {
+                Err(ServerError::ValidationError { field, .. }) => assert_eq!(field, "to"),
+                _ => panic!("Unknown accounts must fail validation."),
+            }
+        });
+    }
+
+    #[test]
+    fn test_imports_are_atomic_and_skip_known_keys() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Shop")).unwrap();
+            let imported = |keys: &[&str]| -> Vec<(String, Operation)> {
+                keys.iter()
+                    .map(|key| (key.to_string(), operation("Bank", "Shop", 100)))
+                    .collect()
+            };
+
+            let mut operations = imported(&["a", "b"]);
+            operations.push(("c".to_owned(), operation("Bank", "Card", 100)));
+            match store.import_operations(1, &operations) {
+                Err(ServerError::ValidationError { field, .. }) => {
+                    assert_eq!(field, "transactions")
+                }
+                _ => panic!("Unknown accounts must fail validation."),
+            }
+            assert_eq!(balances(store, 1)[0], ("Bank".to_owned(), 0));
+
+            assert_eq!(
+                store.import_operations(1, &imported(&["a", "b"])).unwrap(),
+                2
+            );
+            assert_eq!(
+                store.import_operations(1, &imported(&["b", "c"])).unwrap(),
+                1
+            );
+            assert_eq!(balances(store, 1)[0], ("Bank".to_owned(), -300));
+        });
+    }
+
+    #[test]
+    fn test_split_operations() {
+        for_each_storage(|store| {
+            for name in &["Bank", "Cash", "Shop"] {
+                store.add_account(1, &account(name)).unwrap();
+            }
+            for name in &["Food", "Household"] {
+                let category = Category {
+                    name: (*name).to_owned(),
+                };
+                store.add_category(1, &category).unwrap();
+            }
+            assert!(matches!(
+                store.add_category(
+                    1,
+                    &Category {
+                        name: "Food".to_owned()
+                    }
+                ),
+                Err(ServerError::ConflictError(_))
+            ));
+            assert_eq!(store.get_categories(1).unwrap()[1].name, "Household");
+            assert!(store.get_categories(2).unwrap().is_empty());
+
+            let mut receipt = operation("Bank", "Shop", 1000);
+            receipt.comment = "Supermarket".to_owned();
+            receipt.legs = vec![
+                leg(None, Some("Food"), 600),
+                leg(None, Some("Household"), 300),
+                leg(Some("Cash"), None, 100),
+            ];
+            receipt.legs[0].memo = "Bread, milk".to_owned();
+            receipt.currency = Some(DEFAULT_CURRENCY.to_owned());
+            store.record_operation(1, &receipt).unwrap();
+
+            let moved = balances(store, 1);
+            assert!(moved.contains(&("Bank".to_owned(), -1000)));
+            assert!(moved.contains(&("Shop".to_owned(), 900)));
+            assert!(moved.contains(&("Cash".to_owned(), 100)));
+
+            let stored = store.search(1, &["supermarket".to_owned()], 10).unwrap();
+            assert_eq!(
+                serde_json::to_value(&stored[0].operation).unwrap(),
+                serde_json::to_value(&receipt).unwrap()
+            );
+
+            // Nothing is recorded when a leg names something the ledger doesn't have.
+            for (account, category) in &[(Some("Card"), None), (None, Some("Fun"))] {
+                receipt.legs[2] = leg(*account, *category, 100);
+                match store.record_operation(1, &receipt) {
+                    Err(ServerError::ValidationError { field, .. }) => assert_eq!(field, "legs"),
+                    _ => panic!("Unknown leg accounts and categories must fail validation."),
+                }
+            }
+            assert_eq!(balances(store, 1), moved);
+            assert_eq!(store.export_ledger(1, NOW).unwrap().operations.len(), 1);
+        });
+    }
+
+    #[test]
+    fn test_operations_since() {
+        for_each_storage(|store| {
+            for name in &["Bank", "Shop"] {
+                store.add_account(1, &account(name)).unwrap();
+            }
+            let food = Category {
+                name: "Food".to_owned(),
+            };
+            store.add_category(1, &food).unwrap();
+            for (datetime, amount) in &[(NOW + 10, 300), (NOW - 10, 100), (NOW, 200)] {
+                let mut purchase = operation("Bank", "Shop", *amount);
+                purchase.datetime = *datetime;
+                purchase.legs = vec![leg(None, Some("Food"), *amount)];
+                store.record_operation(1, &purchase).unwrap();
+            }
+
+            let since = store.operations_since(1, NOW).unwrap();
+            let listed: Vec<(i64, i64)> = since.iter().map(|o| (o.datetime, o.amount)).collect();
+            assert_eq!(listed, vec![(NOW, 200), (NOW + 10, 300)]);
+            assert_eq!(since[1].legs[0].category.as_deref(), Some("Food"));
+            assert!(store.operations_since(1, NOW + 11).unwrap().is_empty());
+            assert!(store.operations_since(2, 0).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_operations_between_currencies() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            let mut dollars = account("Dollars");
+            dollars.currency = "USD".to_owned();
+            store.add_account(1, &dollars).unwrap();
+            assert_eq!(store.get_settings(1).unwrap().currency, DEFAULT_CURRENCY);
+
+            let mut exchange = operation("Bank", "Dollars", 7_250);
+            exchange.to_amount = Some(1_000);
+            store.record_operation(1, &exchange).unwrap();
+            let mut back = operation("Dollars", "Bank", 100);
+            back.datetime = NOW + 1;
+            back.to_amount = Some(700);
+            store.record_operation(1, &back).unwrap();
+
+            let moved = balances(store, 1);
+            assert_eq!(
+                moved,
+                vec![("Bank".to_owned(), -6_550), ("Dollars".to_owned(), 900)]
+            );
+            let conversions: Vec<(String, String, i64, i64)> = store
+                .get_conversions(1)
+                .unwrap()
+                .into_iter()
+                .map(|c| (c.from_currency, c.to_currency, c.amount, c.to_amount))
+                .collect();
+            assert_eq!(
+                conversions,
+                vec![
+                    ("CNY".to_owned(), "USD".to_owned(), 7_250, 1_000),
+                    ("USD".to_owned(), "CNY".to_owned(), 100, 700),
+                ]
+            );
+            assert_eq!(
+                store.export_ledger(1, NOW).unwrap().operations[1].currency,
+                Some("USD".to_owned())
+            );
+
+            // Without the received amount there's no telling what the transfer was worth.
+            for (currency, to_amount, field) in &[
+                (None, None, "to_amount"),
+                (Some("EUR"), Some(1_000), "currency"),
+            ] {
+                exchange.currency = currency.map(str::to_owned);
+                exchange.to_amount = *to_amount;
+                match store.record_operation(1, &exchange) {
+                    Err(ServerError::ValidationError { field: f, .. }) => assert_eq!(f, *field),
+                    _ => panic!("A transfer between currencies must state both amounts."),
+                }
+            }
+            assert_eq!(balances(store, 1), moved);
+            assert!(store.get_conversions(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_exchange_rates() {
+        for_each_storage(|store| {
+            store
+                .add_rates(
+                    1,
+                    &[
+                        rate("USD", "CNY", "2024-03-01", 710_000_000),
+                        rate("USD", "CNY", "2024-01-01", 700_000_000),
+                        rate("CNY", "USD", "2024-02-01", 14_000_000),
+                        rate("HKD", "CNY", "2024-02-15", 91_000_000),
+                    ],
+                )
+                .unwrap();
+            let find = |date: &str| {
+                store
+                    .find_rate(1, "USD", "CNY", date)
+                    .unwrap()
+                    .map(|rate| (rate.base, rate.date))
+            };
+
+            assert_eq!(find("2023-12-31"), None);
+            assert_eq!(
+                find("2024-01-31"),
+                Some(("USD".to_owned(), "2024-01-01".to_owned()))
+            );
+            // The nearest earlier rate wins whichever way round it's quoted.
+            assert_eq!(
+                find("2024-02-29"),
+                Some(("CNY".to_owned(), "2024-02-01".to_owned()))
+            );
+            assert_eq!(
+                find("2030-01-01"),
+                Some(("USD".to_owned(), "2024-03-01".to_owned()))
+            );
+            assert_eq!(
+                store.find_rate(2, "USD", "CNY", "2030-01-01").unwrap(),
+                None
+            );
+
+            // Adding a rate for a pair and date that has one replaces it.
+            store
+                .add_rates(1, &[rate("USD", "CNY", "2024-03-01", 720_000_000)])
+                .unwrap();
+            let rates = store.get_rates(1).unwrap();
+            let listed: Vec<(&str, &str, i64)> = rates
+                .iter()
+                .map(|r| (r.base.as_str(), r.date.as_str(), r.rate))
+                .collect();
+            assert_eq!(
+                listed,
+                vec![
+                    ("CNY", "2024-02-01", 14_000_000),
+                    ("HKD", "2024-02-15", 91_000_000),
+                    ("USD", "2024-01-01", 700_000_000),
+                    ("USD", "2024-03-01", 720_000_000),
+                ]
+            );
+            assert!(store.get_rates(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_trades_move_balances() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Broker")).unwrap();
+            store.add_security(1, &security("VTI", "CNY")).unwrap();
+            assert!(matches!(
+                store.add_security(1, &security("VTI", "CNY")),
+                Err(ServerError::ConflictError(message))
+                    if message == "Security VTI already exists."
+            ));
+
+            store
+                .record_trade(1, &trade(TradeSide::Buy, 30_000, 600, NOW))
+                .unwrap();
+            store
+                .record_trade(1, &trade(TradeSide::Sell, 10_000, 250, NOW + 1))
+                .unwrap();
+            assert_eq!(balances(store, 1), vec![("Broker".to_owned(), -350)]);
+            let trades = store.get_trades(1).unwrap();
+            let sides: Vec<(TradeSide, i64)> =
+                trades.iter().map(|t| (t.side, t.quantity)).collect();
+            assert_eq!(
+                sides,
+                vec![(TradeSide::Buy, 30_000), (TradeSide::Sell, 10_000)]
+            );
+
+            let rejected = |trade: Trade| match store.record_trade(1, &trade) {
+                Err(ServerError::ValidationError { field, .. }) => field,
+                result => panic!("{:?} was {:?}", trade, result.map_err(|e| e.to_string())),
+            };
+            assert_eq!(
+                rejected(trade(TradeSide::Sell, 20_001, 1, NOW + 2)),
+                "quantity"
+            );
+            assert_eq!(rejected(trade(TradeSide::Buy, 1, 1, NOW)), "datetime");
+            assert_eq!(
+                rejected(Trade {
+                    account: "Cash".to_owned(),
+                    ..trade(TradeSide::Buy, 1, 1, NOW + 2)
+                }),
+                "account"
+            );
+            assert_eq!(
+                rejected(Trade {
+                    symbol: "VT".to_owned(),
+                    ..trade(TradeSide::Buy, 1, 1, NOW + 2)
+                }),
+                "symbol"
+            );
+            store.add_security(1, &security("SPY", "USD")).unwrap();
+            assert_eq!(
+                rejected(Trade {
+                    symbol: "SPY".to_owned(),
+                    ..trade(TradeSide::Buy, 1, 1, NOW + 2)
+                }),
+                "symbol"
+            );
+            // Nothing moved for the rejected trades.
+            assert_eq!(balances(store, 1), vec![("Broker".to_owned(), -350)]);
+            assert_eq!(store.get_trades(1).unwrap().len(), 2);
+            assert!(store.get_trades(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_prices() {
+        for_each_storage(|store| {
+            store.add_security(1, &security("VTI", "USD")).unwrap();
+            store.add_security(1, &security("510300", "CNY")).unwrap();
+            store
+                .add_prices(
+                    1,
+                    &[
+                        price("VTI", "2024-03-01", 24_500_000_000),
+                        price("VTI", "2024-01-01", 23_000_000_000),
+                        price("510300", "2024-02-01", 350_000_000),
+                    ],
+                )
+                .unwrap();
+            let latest = |date: &str| -> Vec<(String, String)> {
+                store
+                    .latest_prices(1, date)
+                    .unwrap()
+                    .into_iter()
+                    .map(|price| (price.symbol, price.date))
+                    .collect()
+            };
+            assert!(latest("2023-12-31").is_empty());
+            assert_eq!(
+                latest("2024-02-15"),
+                vec![
+                    ("510300".to_owned(), "2024-02-01".to_owned()),
+                    ("VTI".to_owned(), "2024-01-01".to_owned()),
+                ]
+            );
+
+            // A price for a date that has one replaces it, and unknown symbols change nothing.
+            store
+                .add_prices(1, &[price("VTI", "2024-03-01", 24_650_000_000)])
+                .unwrap();
+            assert!(matches!(
+                store.add_prices(
+                    1,
+                    &[price("VTI", "2024-04-01", 25_000_000_000), price("VT", "2024-04-01", 100_000_000)]
+                ),
+                Err(ServerError::ValidationError { field, .. }) if field == "symbol"
+            ));
+            let prices = store.get_prices(1).unwrap();
+            let listed: Vec<(&str, &str, i64)> = prices
+                .iter()
+                .map(|p| (p.symbol.as_str(), p.date.as_str(), p.price))
+                .collect();
+            assert_eq!(
+                listed,
+                vec![
+                    ("510300", "2024-02-01", 350_000_000),
+                    ("VTI", "2024-01-01", 23_000_000_000),
+                    ("VTI", "2024-03-01", 24_650_000_000),
+                ]
+            );
+            assert!(store.get_prices(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_credit_cards() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Visa")).unwrap();
+            store.add_account(1, &account("Shop")).unwrap();
+            store
+                .add_account(
+                    1,
+                    &Account {
+                        currency: "USD".to_owned(),
+                        ..account("Dollars")
+                    },
+                )
+                .unwrap();
+            let mut card = CreditCard {
+                account: "Visa".to_owned(),
+                statement_day: 5,
+                due_day: 25,
+                limit: 500_000,
+                pay_from: None,
+            };
+            store.set_credit_card(1, &card).unwrap();
+            card.limit = 800_000;
+            card.pay_from = Some("Bank".to_owned());
+            store.set_credit_card(1, &card).unwrap();
+            let cards = store.get_credit_cards(1).unwrap();
+            assert_eq!(cards.len(), 1);
+            assert_eq!(
+                (
+                    cards[0].account.as_str(),
+                    cards[0].limit,
+                    cards[0].pay_from.as_deref()
+                ),
+                ("Visa", 800_000, Some("Bank"))
+            );
+            assert!(matches!(
+                store.set_credit_card(1, &CreditCard { account: "Amex".to_owned(), ..card.clone() }),
+                Err(ServerError::ValidationError { field, .. }) if field == "account"
+            ));
+            for pay_from in &["Chase", "Dollars"] {
+                let card = CreditCard {
+                    pay_from: Some((*pay_from).to_owned()),
+                    ..card.clone()
+                };
+                assert!(matches!(
+                    store.set_credit_card(1, &card),
+                    Err(ServerError::ValidationError { field, .. }) if field == "pay_from"
+                ));
+            }
+            card.pay_from = None;
+            store.set_credit_card(1, &card).unwrap();
+            assert_eq!(store.get_credit_cards(1).unwrap()[0].pay_from, None);
+            assert!(store.get_credit_cards(2).unwrap().is_empty());
+
+            let at = |operation: Operation, datetime: i64| Operation {
+                datetime,
+                ..operation
+            };
+            store
+                .record_operation(1, &at(operation("Visa", "Shop", 900), NOW - 10))
+                .unwrap();
+            store
+                .record_operation(1, &at(operation("Bank", "Visa", 500), NOW))
+                .unwrap();
+            let mut split = at(operation("Visa", "Shop", 300), NOW + 10);
+            split.legs = vec![leg(Some("Visa"), None, 100), leg(None, None, 200)];
+            store.record_operation(1, &split).unwrap();
+
+            // Only what changed the card's balance since, a refund leg included.
+            assert_eq!(
+                store.balance_changes(1, "Visa", NOW).unwrap(),
+                vec![(NOW, 500), (NOW + 10, -300), (NOW + 10, 100)]
+            );
+            let changes = store.balance_changes(1, "Visa", 0).unwrap();
+            let total: i64 = changes.iter().map(|(_, amount)| amount).sum();
+            assert_eq!(total, -600);
+            assert!(matches!(
+                store.balance_changes(1, "Amex", 0),
+                Err(ServerError::ValidationError { .. })
+            ));
+        });
+    }
+
+    #[test]
+    fn test_installment_plans() {
+        for_each_storage(|store| {
+            for name in &["Card", "Bank", "Fees"] {
+                store.add_account(1, &account(name)).unwrap();
+            }
+            store
+                .add_account(
+                    1,
+                    &Account {
+                        currency: "USD".to_owned(),
+                        ..account("Dollars")
+                    },
+                )
+                .unwrap();
+            let plan = InstallmentPlan {
+                name: "Phone".to_owned(),
+                account: "Card".to_owned(),
+                from: "Bank".to_owned(),
+                cost_account: Some("Fees".to_owned()),
+                principal: 600_000,
+                annual_rate: 0.0,
+                fee: 3_600,
+                term: 12,
+                first_due: "2024-01-25".to_owned(),
+                paid: 0,
+            };
+            store.add_installment_plan(1, &plan).unwrap();
+            assert!(matches!(
+                store.add_installment_plan(1, &plan),
+                Err(ServerError::ConflictError(_))
+            ));
+            let cases = vec![
+                (Some("Shop"), "Bank", "cost_account"),
+                (Some("Fees"), "Dollars", "from"),
+                (Some("Dollars"), "Bank", "cost_account"),
+            ];
+            for (cost_account, from, field) in cases {
+                let other = InstallmentPlan {
+                    name: "Other".to_owned(),
+                    cost_account: cost_account.map(str::to_owned),
+                    from: from.to_owned(),
+                    ..plan.clone()
+                };
+                assert!(matches!(
+                    store.add_installment_plan(1, &other),
+                    Err(ServerError::ValidationError { field: f, .. }) if f == field
+                ));
+            }
+
+            let mut repayment = operation("Bank", "Card", 53_600);
+            repayment.legs = vec![leg(None, None, 50_000), leg(Some("Fees"), None, 3_600)];
+            assert!(matches!(
+                store.record_repayment(1, "Phone", 2, &repayment),
+                Err(ServerError::ConflictError(_))
+            ));
+            store.record_repayment(1, "Phone", 1, &repayment).unwrap();
+            assert!(matches!(
+                store.record_repayment(1, "Phone", 1, &repayment),
+                Err(ServerError::ConflictError(_))
+            ));
+            assert!(matches!(
+                store.record_repayment(1, "Car", 1, &repayment),
+                Err(ServerError::NotFoundError(_))
+            ));
+            // A repayment that can't be recorded doesn't count as made.
+            assert!(store
+                .record_repayment(1, "Phone", 2, &operation("Bank", "Shop", 1))
+                .is_err());
+
+            let plans = store.get_installment_plans(1).unwrap();
+            assert_eq!(plans.len(), 1);
+            assert_eq!(plans[0].paid, 1);
+            assert_eq!(plans[0].cost_account.as_deref(), Some("Fees"));
+            assert_eq!(
+                balances(store, 1),
+                vec![
+                    ("Card".to_owned(), 50_000),
+                    ("Bank".to_owned(), -53_600),
+                    ("Fees".to_owned(), 3_600),
+                    ("Dollars".to_owned(), 0),
+                ]
+            );
+            assert!(store.get_installment_plans(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_shared_expenses() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Shop")).unwrap();
+            let later = Operation {
+                datetime: NOW + 10,
+                ..operation("Bank", "Shop", 900)
+            };
+            let dinner = store.record_operation(1, &later).unwrap();
+            let rent = store
+                .record_operation(1, &operation("Bank", "Shop", 3_000))
+                .unwrap();
+            store.share_operation(1, dinner, &shared("alice")).unwrap();
+            store.share_operation(1, rent, &shared("bob")).unwrap();
+            // Sharing again replaces how it was shared.
+            let mut even = shared("alice");
+            even.method = ShareMethod::Percent;
+            even.shares = vec![member_share("alice", 5_000), member_share("carol", 5_000)];
+            store.share_operation(1, dinner, &even).unwrap();
+
+            let expenses = store.get_shared_expenses(1).unwrap();
+            let expenses: Vec<(i64, i64, &str, ShareMethod, usize)> = expenses
+                .iter()
+                .map(|e| {
+                    let expense = &e.expense;
+                    (
+                        e.operation,
+                        e.amount,
+                        &*expense.paid_by,
+                        expense.method,
+                        expense.shares.len(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                expenses,
+                vec![
+                    (rent, 3_000, "bob", ShareMethod::Shares, 3),
+                    (dinner, 900, "alice", ShareMethod::Percent, 2),
+                ]
+            );
+            assert_eq!(
+                store.get_shared_expenses(1).unwrap()[0].expense.shares[2].member,
+                "carol"
+            );
+
+            assert!(matches!(
+                store.share_operation(1, rent + dinner + 1, &even),
+                Err(ServerError::NotFoundError(_))
+            ));
+            assert!(matches!(
+                store.share_operation(2, rent, &even),
+                Err(ServerError::NotFoundError(_))
+            ));
+            assert!(store.get_shared_expenses(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_claims() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Hotel")).unwrap();
+            store.add_account(1, &account("Employer")).unwrap();
+            let mut ids = Vec::new();
+            for (i, amount) in [30_000, 4_500, 1_000].iter().enumerate() {
+                let mut expense = operation("Bank", "Hotel", *amount);
+                expense.datetime = NOW - 10 * i as i64;
+                ids.push(store.record_operation(1, &expense).unwrap());
+            }
+            let pending = Claim {
+                status: ClaimStatus::Pending,
+                reimbursement: None,
+            };
+            for id in &ids {
+                store.set_claim(1, *id, &pending).unwrap();
+            }
+            let submitted = Claim {
+                status: ClaimStatus::Submitted,
+                ..pending.clone()
+            };
+            store.set_claim(1, ids[2], &submitted).unwrap();
+
+            let claims = store.get_claims(1).unwrap();
+            let listed: Vec<(i64, &str, ClaimStatus)> = claims
+                .iter()
+                .map(|c| (c.operation, c.to.as_str(), c.status))
+                .collect();
+            assert_eq!(
+                listed,
+                vec![
+                    (ids[2], "Hotel", ClaimStatus::Submitted),
+                    (ids[1], "Hotel", ClaimStatus::Pending),
+                    (ids[0], "Hotel", ClaimStatus::Pending),
+                ]
+            );
+            assert_eq!(claims[0].amount, 1_000);
+            assert_eq!(claims[0].currency, DEFAULT_CURRENCY);
+
+            let income = store
+                .record_operation(1, &operation("Employer", "Bank", 31_000))
+                .unwrap();
+            let reimbursement = Reimbursement {
+                operation: income,
+                expenses: vec![ids[0], ids[2]],
+            };
+            store.record_reimbursement(1, &reimbursement).unwrap();
+            let claims = store.get_claims(1).unwrap();
+            let paid: Vec<(i64, ClaimStatus, Option<i64>)> = claims
+                .iter()
+                .map(|c| (c.operation, c.status, c.reimbursement))
+                .collect();
+            assert_eq!(
+                paid,
+                vec![
+                    (ids[2], ClaimStatus::Paid, Some(income)),
+                    (ids[1], ClaimStatus::Pending, None),
+                    (ids[0], ClaimStatus::Paid, Some(income)),
+                ]
+            );
+
+            // An unknown expense leaves every claim as it was.
+            let unknown = income + 100;
+            let reimbursement = Reimbursement {
+                operation: income,
+                expenses: vec![ids[1], unknown],
+            };
+            assert!(matches!(
+                store.record_reimbursement(1, &reimbursement),
+                Err(ServerError::ValidationError { field, .. }) if field == "expenses"
+            ));
+            assert_eq!(store.get_claims(1).unwrap()[1].status, ClaimStatus::Pending);
+            assert!(matches!(
+                store.set_claim(1, ids[1], &paid_by(unknown)),
+                Err(ServerError::ValidationError { field, .. }) if field == "reimbursement"
+            ));
+            assert!(matches!(
+                store.set_claim(1, ids[1], &paid_by(ids[1])),
+                Err(ServerError::ValidationError { field, .. }) if field == "reimbursement"
+            ));
+            assert!(matches!(
+                store.set_claim(1, unknown, &pending),
+                Err(ServerError::NotFoundError(_))
+            ));
+            assert!(matches!(
+                store.set_claim(2, ids[1], &pending),
+                Err(ServerError::NotFoundError(_))
+            ));
+            assert!(store.get_claims(2).unwrap().is_empty());
+        });
+    }
+
+    fn goal(name: &str, account: &str, target_date: &str) -> Goal {
+        Goal {
+            name: name.to_owned(),
+            account: account.to_owned(),
+            target: 500_000,
+            target_date: target_date.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_goals() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Savings")).unwrap();
+            store.add_account(1, &account("Cash")).unwrap();
+            store.add_account(1, &account("Pension")).unwrap();
+            store
+                .set_goal(1, &goal("Holiday", "Cash", "2025-06-01"))
+                .unwrap();
+            store
+                .set_goal(1, &goal("Car", "Savings", "2025-06-01"))
+                .unwrap();
+            store
+                .set_goal(1, &goal("Emergency", "Pension", "2024-12-31"))
+                .unwrap();
+            let names = |goals: Vec<Goal>| -> Vec<String> {
+                goals.into_iter().map(|goal| goal.name).collect()
+            };
+            assert_eq!(
+                names(store.get_goals(1).unwrap()),
+                vec!["Emergency", "Car", "Holiday"]
+            );
+
+            // Setting a goal again by name replaces it.
+            let mut car = goal("Car", "Savings", "2024-01-31");
+            car.target = 1_200_000;
+            store.set_goal(1, &car).unwrap();
+            let goals = store.get_goals(1).unwrap();
+            assert_eq!(goals.len(), 3);
+            assert_eq!(
+                (&*goals[0].name, &*goals[0].account, goals[0].target),
+                ("Car", "Savings", 1_200_000)
+            );
+
+            // A goal counts its account's whole balance, so no two goals share one.
+            for (name, account) in &[("Car", "Cash"), ("House", "Savings")] {
+                assert!(matches!(
+                    store.set_goal(1, &goal(name, account, "2030-01-01")),
+                    Err(ServerError::ValidationError { field, .. }) if field == "account"
+                ));
+            }
+            assert_eq!(store.get_goals(1).unwrap()[0].account, "Savings");
+            assert_eq!(store.get_goals(1).unwrap().len(), 3);
+
+            assert!(matches!(
+                store.set_goal(1, &goal("House", "Bank", "2030-01-01")),
+                Err(ServerError::ValidationError { field, .. }) if field == "account"
+            ));
+            assert!(matches!(
+                store.set_goal(2, &goal("Car", "Savings", "2030-01-01")),
+                Err(ServerError::ValidationError { field, .. }) if field == "account"
+            ));
+            assert!(store.get_goals(2).unwrap().is_empty());
+        });
+    }
+
+    fn rent(from: &str, to: &str) -> RecurringOperation {
+        RecurringOperation {
+            name: "Rent".to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+            amount: 300_000,
+            frequency: Frequency::Monthly,
+            start: "2024-01-31".to_owned(),
+            end: None,
+            category: None,
+        }
+    }
+
+    #[test]
+    fn test_recurring_operations() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Landlord")).unwrap();
+            store
+                .add_account(
+                    1,
+                    &Account {
+                        currency: "USD".to_owned(),
+                        ..account("Dollars")
+                    },
+                )
+                .unwrap();
+            store
+                .add_category(
+                    1,
+                    &Category {
+                        name: "Housing".to_owned(),
+                    },
+                )
+                .unwrap();
+            store.set_recurring(1, &rent("Bank", "Landlord")).unwrap();
+            let salary = RecurringOperation {
+                name: "Salary".to_owned(),
+                amount: 1_000_000,
+                ..rent("Landlord", "Bank")
+            };
+            store.set_recurring(1, &salary).unwrap();
+
+            // Setting one again by name replaces it.
+            let mut changed = rent("Bank", "Landlord");
+            changed.frequency = Frequency::Weekly;
+            changed.end = Some("2024-12-31".to_owned());
+            changed.category = Some("Housing".to_owned());
+            store.set_recurring(1, &changed).unwrap();
+            let recurring = store.get_recurring(1).unwrap();
+            assert_eq!(recurring.len(), 2);
+            assert_eq!(recurring[0].name, "Rent");
+            assert_eq!(recurring[0].frequency, Frequency::Weekly);
+            assert_eq!(recurring[0].end.as_deref(), Some("2024-12-31"));
+            assert_eq!(recurring[0].category.as_deref(), Some("Housing"));
+            assert_eq!(recurring[1].amount, 1_000_000);
+
+            for (item, field) in [
+                (rent("Cash", "Landlord"), "from"),
+                (rent("Bank", "Cash"), "to"),
+                (rent("Bank", "Dollars"), "to"),
+                (
+                    RecurringOperation {
+                        category: Some("Food".to_owned()),
+                        ..rent("Bank", "Landlord")
+                    },
+                    "category",
+                ),
+            ] {
+                assert!(matches!(
+                    store.set_recurring(1, &item),
+                    Err(ServerError::ValidationError { field: f, .. }) if f == field
+                ));
+            }
+            assert_eq!(store.get_recurring(1).unwrap()[0].to, "Landlord");
+
+            assert!(matches!(
+                store.set_recurring(2, &rent("Bank", "Landlord")),
+                Err(ServerError::ValidationError { field, .. }) if field == "from"
+            ));
+            assert!(store.get_recurring(2).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_ledgers_are_isolated() {
+        for_each_storage(|store| {
+            store.add_user("bob", "secret").unwrap();
+            assert!(matches!(
+                store.add_user("bob", "other"),
+                Err(ServerError::ConflictError(_))
+            ));
+            let bob = store.get_user("bob").unwrap().unwrap();
+            assert!(!bob.admin);
+
+            let ledger = store
+                .add_ledger(
+                    bob.id,
+                    &NewLedger {
+                        name: "Bob's".to_owned(),
+                        currency: DEFAULT_CURRENCY.to_owned(),
+                        timezone: "Europe/Berlin".to_owned(),
+                    },
+                )
+                .unwrap();
+            let ledgers = store.get_ledgers(bob.id).unwrap();
+            assert_eq!(ledgers[0].timezone, "Europe/Berlin");
+            assert_eq!(store.ledger_ids().unwrap(), vec![1, ledger]);
+            store.add_account(ledger, &account("Cash")).unwrap();
+            assert!(store.get_accounts(1).unwrap().is_empty());
+            assert_eq!(store.get_role(1, ledger).unwrap(), None);
+
+            let member = LedgerMember {
+                user: "admin".to_owned(),
+                role: Role::Viewer,
+            };
+            store.set_member(ledger, &member).unwrap();
+            assert_eq!(store.get_role(1, ledger).unwrap(), Some(Role::Viewer));
+            assert_eq!(store.get_ledgers(1).unwrap().len(), 2);
+        });
+    }
+
+    #[test]
+    fn test_stored_tokens_are_hashed() {
+        for_each_storage(|store| {
+            store
+                .add_user("bob", &password::hash("secret").unwrap())
+                .unwrap();
+            let bob = store.get_user("bob").unwrap().unwrap();
+            let tokens = store.plain_tokens().unwrap();
+            assert!(tokens.iter().all(|(user, _)| *user != bob.id));
+
+            password::hash_stored_tokens(store).unwrap();
+            assert!(store.plain_tokens().unwrap().is_empty());
+            let admin = store.get_user("admin").unwrap().unwrap();
+            assert!(password::verify("123456", &admin.password_hash));
+            assert!(!password::verify("123456", "123456"));
+            let bob = store.get_user("bob").unwrap().unwrap();
+            assert!(password::verify("secret", &bob.password_hash));
+        });
+    }
+
+    #[test]
+    fn test_last_owner_stays() {
+        for_each_storage(|store| {
+            store.add_user("bob", "secret").unwrap();
+            let bob = store.get_user("bob").unwrap().unwrap();
+            let member = |user: &str, role| LedgerMember {
+                user: user.to_owned(),
+                role,
+            };
+
+            // The admin is the only owner of the default ledger.
+            assert!(matches!(
+                store.set_member(1, &member("admin", Role::Editor)),
+                Err(ServerError::ValidationError { field, .. }) if field == "role"
+            ));
+            assert_eq!(store.get_role(1, 1).unwrap(), Some(Role::Owner));
+
+            // Once there's another owner, either can step down.
+            store.set_member(1, &member("bob", Role::Owner)).unwrap();
+            store.set_member(1, &member("admin", Role::Editor)).unwrap();
+            assert_eq!(store.get_role(1, 1).unwrap(), Some(Role::Editor));
+            assert!(matches!(
+                store.set_member(1, &member("bob", Role::Viewer)),
+                Err(ServerError::ValidationError { field, .. }) if field == "role"
+            ));
+            assert_eq!(store.get_role(bob.id, 1).unwrap(), Some(Role::Owner));
+        });
+    }
+
+    #[test]
+    fn test_ledger_settings() {
+        for_each_storage(|store| {
+            let settings = store.get_settings(1).unwrap();
+            assert_eq!(settings.timezone, DEFAULT_TIMEZONE);
+
+            let renamed = LedgerSettings {
+                name: "Home".to_owned(),
+                currency: "EUR".to_owned(),
+                timezone: "Europe/Paris".to_owned(),
+            };
+            store.update_settings(1, &renamed).unwrap();
+            let settings = store.get_settings(1).unwrap();
+            assert_eq!(
+                (settings.name, settings.currency, settings.timezone),
+                (
+                    renamed.name.clone(),
+                    renamed.currency.clone(),
+                    renamed.timezone.clone()
+                )
+            );
+            let ledger = &store.get_ledgers(1).unwrap()[0];
+            assert_eq!(ledger.timezone, "Europe/Paris");
+
+            assert!(matches!(
+                store.update_settings(99, &renamed),
+                Err(ServerError::NotFoundError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_migrations_are_dated_in_millis() {
+        for_each_storage(|store| {
+            // Only the databases record when they were migrated.
+            if let Some(version) = store.current_db_version().unwrap() {
+                let now = chrono::Utc::now().timestamp_millis();
+                assert!((now - version.deploy_at).abs() < 60_000);
+            }
+        });
+    }
+
+    #[test]
+    fn test_totp_codes_are_single_use() {
+        for_each_storage(|store| {
+            let hashes = vec!["a".to_owned(), "b".to_owned()];
+            store.enrol_totp(1, "SECRET", &hashes).unwrap();
+            assert!(store.get_totp(1, true).unwrap().is_none());
+
+            let pending = store.get_totp(1, false).unwrap().unwrap();
+            store.confirm_totp(1, pending.id, 10).unwrap();
+            let totp = store.get_totp(1, true).unwrap().unwrap();
+            assert_eq!(totp.last_step, 10);
+
+            assert!(!store.update_totp_step(totp.id, 10).unwrap());
+            assert!(store.update_totp_step(totp.id, 11).unwrap());
+            assert!(store.use_recovery_code(totp.id, "a").unwrap());
+            assert!(!store.use_recovery_code(totp.id, "a").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Cash")).unwrap();
+            let lent = store
+                .record_operation(1, &operation("Bank", "Cash", 300))
+                .unwrap();
+            let repaid = store
+                .record_operation(1, &operation("Cash", "Bank", 100))
+                .unwrap();
+            store.share_operation(1, repaid, &shared("bob")).unwrap();
+            store.set_claim(1, lent, &paid_by(repaid)).unwrap();
+            store
+                .set_goal(1, &goal("Car", "Cash", "2025-06-01"))
+                .unwrap();
+            store
+                .add_category(
+                    1,
+                    &Category {
+                        name: "Fees".to_owned(),
+                    },
+                )
+                .unwrap();
+            store
+                .set_recurring(
+                    1,
+                    &RecurringOperation {
+                        category: Some("Fees".to_owned()),
+                        ..rent("Bank", "Cash")
+                    },
+                )
+                .unwrap();
+            let mut split = operation("Bank", "Cash", 50);
+            split.legs = vec![leg(None, Some("Fees"), 20), leg(None, None, 30)];
+            store.record_operation(1, &split).unwrap();
+            store
+                .add_rates(1, &[rate("USD", "CNY", "2024-03-01", 710_000_000)])
+                .unwrap();
+            store.add_account(1, &account("Broker")).unwrap();
+            store.add_security(1, &security("VTI", "CNY")).unwrap();
+            store
+                .record_trade(1, &trade(TradeSide::Buy, 30_000, 600, NOW))
+                .unwrap();
+            store
+                .record_trade(1, &trade(TradeSide::Sell, 10_000, 250, NOW))
+                .unwrap();
+            store
+                .add_prices(1, &[price("VTI", "2024-03-01", 21_000_000)])
+                .unwrap();
+            store
+                .set_credit_card(
+                    1,
+                    &CreditCard {
+                        account: "Cash".to_owned(),
+                        statement_day: 1,
+                        due_day: 20,
+                        limit: 1_000,
+                        pay_from: Some("Bank".to_owned()),
+                    },
+                )
+                .unwrap();
+            store
+                .add_installment_plan(
+                    1,
+                    &InstallmentPlan {
+                        name: "Laptop".to_owned(),
+                        account: "Cash".to_owned(),
+                        from: "Bank".to_owned(),
+                        cost_account: None,
+                        principal: 1_200,
+                        annual_rate: 0.0,
+                        fee: 0,
+                        term: 6,
+                        first_due: "2024-04-01".to_owned(),
+                        paid: 2,
+                    },
+                )
+                .unwrap();
+            let mut settings = store.get_settings(1).unwrap();
+            settings.timezone = "America/New_York".to_owned();
+            store.update_settings(1, &settings).unwrap();
+
+            let export = store.export_ledger(1, NOW).unwrap();
+            export.validate(NOW).unwrap();
+            let copy = store
+                .add_ledger(
+                    1,
+                    &NewLedger {
+                        name: "Copy".to_owned(),
+                        currency: DEFAULT_CURRENCY.to_owned(),
+                        timezone: DEFAULT_TIMEZONE.to_owned(),
+                    },
+                )
+                .unwrap();
+            store.import_ledger(copy, &export).unwrap();
+
+            let imported = store.export_ledger(copy, NOW).unwrap();
+            assert_eq!(
+                serde_json::to_value(&imported).unwrap(),
+                serde_json::to_value(&export).unwrap()
+            );
+            assert_eq!(imported.accounts[1].balance, 250);
+            assert_eq!(imported.operations[2].legs.len(), 2);
+            assert_eq!(imported.rates.len(), 1);
+            assert_eq!(imported.settings.timezone, "America/New_York");
+            assert_eq!(imported.accounts[2].balance, -350);
+            assert_eq!(imported.trades.len(), 2);
+            assert_eq!(imported.prices.len(), 1);
+            assert_eq!(imported.credit_cards[0].account, "Cash");
+            assert_eq!(imported.credit_cards[0].pay_from.as_deref(), Some("Bank"));
+            assert_eq!(imported.installment_plans[0].paid, 2);
+            assert_eq!(imported.shared_expenses[0].operation, 1);
+            assert_eq!(imported.shared_expenses[0].expense.shares.len(), 3);
+            assert_eq!(imported.claims[0].operation, 0);
+            assert_eq!(imported.claims[0].reimbursement, Some(1));
+            assert_eq!(imported.goals[0].account, "Cash");
+            assert_eq!(imported.recurring[0].category.as_deref(), Some("Fees"));
+
+            assert!(matches!(
+                store.import_ledger(copy, &export),
+                Err(ServerError::ConflictError(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_search_ranks_and_highlights() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Cash")).unwrap();
+            let comments = [
+                ("Cash withdrawal", NOW - 1000),
+                ("IKEA sofa & lamp", NOW),
+                ("Groceries", NOW),
+                ("Refund from ikea", NOW + 1000),
+            ];
+            for (comment, datetime) in &comments {
+                let mut operation = operation("Bank", "Cash", 100);
+                operation.comment = (*comment).to_owned();
+                operation.datetime = *datetime;
+                store.record_operation(1, &operation).unwrap();
+            }
+            let search = |terms: &[&str]| {
+                let terms: Vec<String> = terms.iter().map(|term| (*term).to_owned()).collect();
+                store.search(1, &terms, 10).unwrap()
+            };
+
+            assert_eq!(search(&["ikea"]).len(), 2);
+            let hits = search(&["ike", "sofa"]);
+            assert_eq!(hits.len(), 1);
+            assert_eq!(
+                hits[0].snippet,
+                "<mark>IKEA</mark> <mark>sofa</mark> &amp; lamp"
+            );
+            assert!(search(&["ikea", "groceries"]).is_empty());
+
+            // Every operation goes through Cash, but the one mentioning it comes first.
+            let hits = search(&["cash"]);
+            assert_eq!(hits.len(), 4);
+            assert_eq!(hits[0].operation.comment, "Cash withdrawal");
+            assert_eq!(hits[3].snippet, "Bank <mark>Cash</mark>");
+            assert_eq!(store.search(1, &["cash".to_owned()], 2).unwrap().len(), 2);
+
+            let other = store
+                .add_ledger(
+                    1,
+                    &NewLedger {
+                        name: "Other".to_owned(),
+                        currency: DEFAULT_CURRENCY.to_owned(),
+                        timezone: DEFAULT_TIMEZONE.to_owned(),
+                    },
+                )
+                .unwrap();
+            assert!(store
+                .search(other, &["cash".to_owned()], 10)
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_attachment_content_is_shared() {
+        for_each_storage(|store| {
+            store.add_account(1, &account("Bank")).unwrap();
+            store.add_account(1, &account("Cash")).unwrap();
+            let operation = store
+                .record_operation(1, &operation("Bank", "Cash", 300))
+                .unwrap();
+            let receipt = NewAttachment {
+                name: "receipt.pdf".to_owned(),
+                content_type: "application/pdf".to_owned(),
+                size: 3,
+            };
+            let add = |content| store.add_attachment(1, operation, &receipt, "abc", NOW, content);
+
+            let first = add(Some(b"pdf")).unwrap();
+            let second = add(Some(b"pdf")).unwrap();
+            let on_disk = add(None).unwrap();
+            assert_eq!(first.operation, operation);
+            assert_eq!(store.get_attachments(1, operation).unwrap().len(), 3);
+            let (_, content) = store.get_attachment(1, on_disk.id).unwrap().unwrap();
+            assert_eq!(content.as_deref(), Some(&b"pdf"[..]));
+            assert!(store.get_attachment(2, first.id).unwrap().is_none());
+            assert!(matches!(
+                store.get_attachments(1, operation + 1000),
+                Err(ServerError::NotFoundError(_))
+            ));
+
+            assert!(store.delete_attachment(1, first.id).unwrap().unwrap().1);
+            assert!(store.delete_attachment(1, second.id).unwrap().unwrap().1);
+            let (deleted, shared) = store.delete_attachment(1, on_disk.id).unwrap().unwrap();
+            assert_eq!(deleted.name, "receipt.pdf");
+            assert!(!shared);
+            assert!(store.delete_attachment(1, on_disk.id).unwrap().is_none());
+        });
+    }
+}
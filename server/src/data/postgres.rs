@@ -0,0 +1,1926 @@
+use super::{
+    account_has_goal, category_exists, check_card, check_claim, check_owners, check_plan,
+    check_recurring, check_trade, export_claims, export_shares, highlight, in_field,
+    not_next_repayment, paid_by, plan_exists, security_exists, unknown_account, unknown_category,
+    unknown_operation, unknown_operation_at, unknown_plan, unknown_security, user_exists,
+    validate_currencies, AttachmentContent, SharedAmount, Storage, TotpSecret, User, MATCH_END,
+    MATCH_START,
+};
+use crate::error::ServerError;
+use crate::migration::{self, Migration, Version};
+use entities::{
+    Account, Attachment, Category, Claim, ClaimedExpense, Conversion, CreditCard, DbVersion,
+    ExchangeRate, Goal, InstallmentPlan, Ledger, LedgerExport, LedgerMember, LedgerSettings, Leg,
+    MemberShare, NewAttachment, NewLedger, Operation, Price, RecurringOperation, Reimbursement,
+    Role, SearchHit, Security, SharedExpense, Trade, EXPORT_VERSION,
+};
+use postgres::{GenericClient, NoTls, Row};
+use r2d2_postgres::PostgresConnectionManager;
+use std::collections::HashMap;
+
+pub type Pool = r2d2::Pool<PostgresConnectionManager<NoTls>>;
+
+/// Held while migrating so that servers sharing a database don't run migrations twice.
+const MIGRATION_LOCK: i64 = 0x6d69_6772;
+
+#[derive(Clone)]
+pub struct PostgresStorage {
+    pool: Pool,
+}
+
+impl PostgresStorage {
+    /// Connects to a `postgres://` or `postgresql://` URL.
+    pub fn new(url: &str) -> Result<Self, ServerError> {
+        let config = url
+            .parse()
+            .map_err(|e| ServerError::InternalError(format!("Invalid PostgreSQL URL: {}", e)))?;
+        let pool = Pool::new(PostgresConnectionManager::new(config, NoTls))?;
+
+        Ok(PostgresStorage { pool })
+    }
+
+    pub fn pending_migrations<'a>(
+        &self,
+        migrations: &'a [Migration],
+    ) -> Result<Vec<&'a Migration>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let exists: bool = conn
+            .query_one("SELECT TO_REGCLASS('MIGRATION') IS NOT NULL", &[])?
+            .try_get(0)?;
+        let applied = if exists {
+            applied_migrations(&mut *conn)?
+                .into_iter()
+                .map(|(version, checksum, _)| (version, checksum))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        migration::pending(&applied, migrations)
+    }
+
+    /// Applies every migration not yet recorded in `MIGRATION`, each in its own transaction,
+    /// after checking the recorded ones with `migration::pending`.
+    pub fn migrate(&self, migrations: &[Migration]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute("SELECT PG_ADVISORY_LOCK($1)", &[&MIGRATION_LOCK])?;
+        let result: Result<(), ServerError> = (|| {
+            conn.batch_execute(
+                "CREATE TABLE IF NOT EXISTS MIGRATION
+                 (
+                     VERSION    TEXT PRIMARY KEY,
+                     CHECKSUM   TEXT   NOT NULL,
+                     APPLIED_AT BIGINT NOT NULL
+                 );",
+            )?;
+
+            for migration in self.pending_migrations(migrations)? {
+                info!("Applying database migration {}.", migration.version);
+                let mut tx = conn.transaction()?;
+                tx.batch_execute(&migration.sql)?;
+                tx.execute(
+                    "INSERT INTO MIGRATION (VERSION, CHECKSUM, APPLIED_AT)
+                     VALUES ($1, $2, (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT)",
+                    &[&migration.version.to_string(), &migration.checksum],
+                )?;
+                tx.commit()?;
+            }
+
+            Ok(())
+        })();
+        conn.execute("SELECT PG_ADVISORY_UNLOCK($1)", &[&MIGRATION_LOCK])?;
+        result?;
+
+        info!("The database is now the latest version.");
+
+        Ok(())
+    }
+}
+
+impl Storage for PostgresStorage {
+    fn record_operation(&self, ledger: i64, operation: &Operation) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let id = insert_operation(&mut tx, ledger, operation, ("from", "to", "legs"))?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn import_operations(
+        &self,
+        ledger: i64,
+        operations: &[(String, Operation)],
+    ) -> Result<usize, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let mut recorded = 0;
+        for (key, operation) in operations {
+            let imported = tx
+                .query_opt(
+                    "SELECT 1 FROM OPERATION WHERE LEDGER = $1 AND IMPORT_KEY = $2",
+                    &[&ledger, key],
+                )?
+                .is_some();
+            if imported {
+                continue;
+            }
+            let fields = ("transactions", "transactions", "transactions");
+            let id = insert_operation(&mut tx, ledger, operation, fields)?;
+            tx.execute(
+                "UPDATE OPERATION SET IMPORT_KEY = $1 WHERE ID = $2",
+                &[key, &id],
+            )?;
+            recorded += 1;
+        }
+        tx.commit()?;
+
+        Ok(recorded)
+    }
+
+    fn get_user(&self, name: &str) -> Result<Option<User>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT ID, NAME, TOKEN, ADMIN FROM \"USER\" WHERE NAME = $1",
+            &[&name],
+        )?
+        .map(|row| to_user(&row))
+        .transpose()
+    }
+
+    fn get_user_by_id(&self, id: i64) -> Result<Option<User>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT ID, NAME, TOKEN, ADMIN FROM \"USER\" WHERE ID = $1",
+            &[&id],
+        )?
+        .map(|row| to_user(&row))
+        .transpose()
+    }
+
+    fn add_user(&self, name: &str, password_hash: &str) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO \"USER\" (NAME, TOKEN, TOKEN_HASHED) VALUES ($1, $2, TRUE)",
+            &[&name, &password_hash],
+        )
+        .map_err(|e| match ServerError::from(e) {
+            ServerError::ConflictError(_) => user_exists(name),
+            e => e,
+        })?;
+
+        Ok(())
+    }
+
+    fn plain_tokens(&self) -> Result<Vec<(i64, String)>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            "SELECT ID, TOKEN FROM \"USER\" WHERE NOT TOKEN_HASHED ORDER BY ID",
+            &[],
+        )?
+        .iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+        .collect()
+    }
+
+    fn set_password_hash(&self, user: i64, password_hash: &str) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE \"USER\" SET TOKEN = $1, TOKEN_HASHED = TRUE WHERE ID = $2",
+            &[&password_hash, &user],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_ledgers(&self, user: i64) -> Result<Vec<Ledger>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            "SELECT L.ID, L.NAME, M.ROLE, L.CURRENCY, L.TIMEZONE
+             FROM LEDGER L JOIN LEDGER_MEMBER M ON M.LEDGER = L.ID
+             WHERE M.\"USER\" = $1 ORDER BY L.ID",
+            &[&user],
+        )?
+        .iter()
+        .map(|row| {
+            Ok(Ledger {
+                id: row.try_get(0)?,
+                name: row.try_get(1)?,
+                role: parse_role(row.try_get(2)?)?,
+                currency: row.try_get(3)?,
+                timezone: row.try_get(4)?,
+            })
+        })
+        .collect()
+    }
+
+    fn get_role(&self, user: i64, ledger: i64) -> Result<Option<Role>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT ROLE FROM LEDGER_MEMBER WHERE \"USER\" = $1 AND LEDGER = $2",
+            &[&user, &ledger],
+        )?
+        .map(|row| parse_role(row.try_get(0)?))
+        .transpose()
+    }
+
+    fn ledger_ids(&self) -> Result<Vec<i64>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query("SELECT ID FROM LEDGER ORDER BY ID", &[])?
+            .iter()
+            .map(|row| Ok(row.try_get(0)?))
+            .collect()
+    }
+
+    fn add_ledger(&self, user: i64, ledger: &NewLedger) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let ledger_id: i64 = tx
+            .query_one(
+                "INSERT INTO LEDGER (NAME, CURRENCY, TIMEZONE) VALUES ($1, $2, $3) RETURNING ID",
+                &[&ledger.name, &ledger.currency, &ledger.timezone],
+            )?
+            .try_get(0)?;
+        tx.execute(
+            "INSERT INTO LEDGER_MEMBER (LEDGER, \"USER\", ROLE) VALUES ($1, $2, $3)",
+            &[&ledger_id, &user, &Role::Owner.as_str()],
+        )?;
+        tx.commit()?;
+
+        Ok(ledger_id)
+    }
+
+    fn set_member(&self, ledger: i64, member: &LedgerMember) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let rows_updated = tx.execute(
+            "INSERT INTO LEDGER_MEMBER (LEDGER, \"USER\", ROLE)
+             SELECT $1, ID, $2 FROM \"USER\" WHERE NAME = $3
+             ON CONFLICT (LEDGER, \"USER\") DO UPDATE SET ROLE = EXCLUDED.ROLE",
+            &[&ledger, &member.role.as_str(), &member.user],
+        )?;
+
+        if rows_updated != 1 {
+            return Err(ServerError::NotFoundError(format!(
+                "User {} doesn't exist.",
+                member.user
+            )));
+        }
+        let owners: i64 = tx
+            .query_one(
+                "SELECT COUNT(*) FROM LEDGER_MEMBER WHERE LEDGER = $1 AND ROLE = $2",
+                &[&ledger, &Role::Owner.as_str()],
+            )?
+            .try_get(0)?;
+        check_owners(owners)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_totp(&self, user: i64, confirmed: bool) -> Result<Option<TotpSecret>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT ID, SECRET, LAST_STEP FROM TOTP WHERE \"USER\" = $1 AND CONFIRMED = $2
+             ORDER BY ID DESC LIMIT 1",
+            &[&user, &confirmed],
+        )?
+        .map(|row| {
+            Ok(TotpSecret {
+                id: row.try_get(0)?,
+                secret: row.try_get(1)?,
+                last_step: row.try_get(2)?,
+            })
+        })
+        .transpose()
+    }
+
+    fn enrol_totp(
+        &self,
+        user: i64,
+        secret: &str,
+        recovery_hashes: &[String],
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        delete_totps(&mut tx, user, false)?;
+        let totp_id: i64 = tx
+            .query_one(
+                "INSERT INTO TOTP (\"USER\", SECRET, CONFIRMED, LAST_STEP)
+                 VALUES ($1, $2, FALSE, 0) RETURNING ID",
+                &[&user, &secret],
+            )?
+            .try_get(0)?;
+        for hash in recovery_hashes {
+            tx.execute(
+                "INSERT INTO RECOVERY_CODE (TOTP, HASH) VALUES ($1, $2)",
+                &[&totp_id, hash],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn confirm_totp(&self, user: i64, totp_id: i64, step: i64) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        delete_totps(&mut tx, user, true)?;
+        let rows_updated = tx.execute(
+            "UPDATE TOTP SET CONFIRMED = TRUE, LAST_STEP = $1 WHERE ID = $2",
+            &[&step, &totp_id],
+        )?;
+
+        if rows_updated != 1 {
+            return Err(ServerError::DBError(
+                "Wrong number of lines updated.".to_owned(),
+            ));
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn update_totp_step(&self, totp_id: i64, step: i64) -> Result<bool, ServerError> {
+        let mut conn = self.pool.get()?;
+        let rows_updated = conn.execute(
+            "UPDATE TOTP SET LAST_STEP = $1 WHERE ID = $2 AND LAST_STEP < $1",
+            &[&step, &totp_id],
+        )?;
+
+        Ok(rows_updated == 1)
+    }
+
+    fn use_recovery_code(&self, totp_id: i64, hash: &str) -> Result<bool, ServerError> {
+        let mut conn = self.pool.get()?;
+        let rows_updated = conn.execute(
+            "UPDATE RECOVERY_CODE SET USED_AT = (EXTRACT(EPOCH FROM NOW()) * 1000)::BIGINT
+             WHERE TOTP = $1 AND HASH = $2 AND USED_AT IS NULL",
+            &[&totp_id, &hash],
+        )?;
+
+        Ok(rows_updated == 1)
+    }
+
+    fn get_accounts(&self, ledger: i64) -> Result<Vec<Account>, ServerError> {
+        let mut conn = self.pool.get()?;
+        accounts(&mut *conn, ledger)
+    }
+
+    fn add_account(&self, ledger: i64, account: &Account) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO ACCOUNT (LEDGER, NAME, BALANCE, CURRENCY) VALUES ($1, $2, $3, $4)",
+            &[&ledger, &account.name, &account.balance, &account.currency],
+        )
+        .map_err(|e| match ServerError::from(e) {
+            ServerError::ConflictError(_) => {
+                ServerError::ConflictError(format!("Account {} already exists.", account.name))
+            }
+            e => e,
+        })?;
+
+        Ok(())
+    }
+
+    fn get_categories(&self, ledger: i64) -> Result<Vec<Category>, ServerError> {
+        let mut conn = self.pool.get()?;
+        categories(&mut *conn, ledger)
+    }
+
+    fn add_category(&self, ledger: i64, category: &Category) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO CATEGORY (LEDGER, NAME) VALUES ($1, $2)",
+            &[&ledger, &category.name],
+        )
+        .map_err(|e| match ServerError::from(e) {
+            ServerError::ConflictError(_) => category_exists(&category.name),
+            e => e,
+        })?;
+
+        Ok(())
+    }
+
+    fn get_settings(&self, ledger: i64) -> Result<LedgerSettings, ServerError> {
+        let mut conn = self.pool.get()?;
+        settings(&mut *conn, ledger)
+    }
+
+    fn update_settings(&self, ledger: i64, settings: &LedgerSettings) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        update_settings(&mut *conn, ledger, settings)
+    }
+
+    fn get_conversions(&self, ledger: i64) -> Result<Vec<Conversion>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            "SELECT O.CURRENCY, T.CURRENCY, O.AMOUNT, O.TO_AMOUNT, O.DATETIME
+             FROM OPERATION O
+                      JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+             WHERE O.LEDGER = $1 AND O.TO_AMOUNT IS NOT NULL
+             ORDER BY O.DATETIME, O.ID",
+            &[&ledger],
+        )?
+        .iter()
+        .map(|row| {
+            Ok(Conversion {
+                from_currency: row.try_get(0)?,
+                to_currency: row.try_get(1)?,
+                amount: row.try_get(2)?,
+                to_amount: row.try_get(3)?,
+                datetime: row.try_get(4)?,
+            })
+        })
+        .collect()
+    }
+
+    fn get_rates(&self, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError> {
+        let mut conn = self.pool.get()?;
+        rates(&mut *conn, ledger)
+    }
+
+    fn add_rates(&self, ledger: i64, rates: &[ExchangeRate]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        insert_rates(&mut tx, ledger, rates)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn find_rate(
+        &self,
+        ledger: i64,
+        currency: &str,
+        other: &str,
+        date: &str,
+    ) -> Result<Option<ExchangeRate>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT BASE, QUOTE, DATE, RATE FROM EXCHANGE_RATE
+             WHERE LEDGER = $1 AND DATE <= $4
+               AND ((BASE = $2 AND QUOTE = $3) OR (BASE = $3 AND QUOTE = $2))
+             ORDER BY DATE DESC, ID DESC
+             LIMIT 1",
+            &[&ledger, &currency, &other, &date],
+        )?
+        .map(|row| to_rate(&row))
+        .transpose()
+    }
+
+    fn get_securities(&self, ledger: i64) -> Result<Vec<Security>, ServerError> {
+        let mut conn = self.pool.get()?;
+        securities(&mut *conn, ledger)
+    }
+
+    fn add_security(&self, ledger: i64, security: &Security) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        insert_security(&mut *conn, ledger, security)
+    }
+
+    fn record_trade(&self, ledger: i64, trade: &Trade) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        insert_trade(&mut tx, ledger, trade)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_trades(&self, ledger: i64) -> Result<Vec<Trade>, ServerError> {
+        let mut conn = self.pool.get()?;
+        trades(&mut *conn, ledger)
+    }
+
+    fn get_prices(&self, ledger: i64) -> Result<Vec<Price>, ServerError> {
+        let mut conn = self.pool.get()?;
+        prices(&mut *conn, ledger)
+    }
+
+    fn add_prices(&self, ledger: i64, prices: &[Price]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        insert_prices(&mut tx, ledger, prices)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn latest_prices(&self, ledger: i64, date: &str) -> Result<Vec<Price>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query(
+            "SELECT S.SYMBOL, P.DATE, P.PRICE
+             FROM PRICE P
+                      JOIN SECURITY S ON S.ID = P.SECURITY
+             WHERE S.LEDGER = $1
+               AND P.DATE = (SELECT MAX(DATE) FROM PRICE WHERE SECURITY = P.SECURITY AND DATE <= $2)
+             ORDER BY S.SYMBOL",
+            &[&ledger, &date],
+        )?
+        .iter()
+        .map(to_price)
+        .collect()
+    }
+
+    fn get_credit_cards(&self, ledger: i64) -> Result<Vec<CreditCard>, ServerError> {
+        let mut conn = self.pool.get()?;
+        credit_cards(&mut *conn, ledger)
+    }
+
+    fn set_credit_card(&self, ledger: i64, card: &CreditCard) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        insert_credit_card(&mut *conn, ledger, card)
+    }
+
+    fn balance_changes(
+        &self,
+        ledger: i64,
+        account: &str,
+        since: i64,
+    ) -> Result<Vec<(i64, i64)>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let (account, _) = find_account(&mut *conn, ledger, account)?
+            .ok_or_else(|| unknown_account("account", account))?;
+        conn.query(
+            "SELECT DATETIME, -AMOUNT FROM OPERATION
+             WHERE FROM_ACCOUNT = $1 AND DATETIME >= $2
+             UNION ALL
+             SELECT O.DATETIME, COALESCE(O.TO_AMOUNT, O.AMOUNT) FROM OPERATION O
+             WHERE O.TO_ACCOUNT = $1 AND O.DATETIME >= $2
+               AND NOT EXISTS (SELECT 1 FROM OPERATION_LEG L WHERE L.OPERATION = O.ID)
+             UNION ALL
+             SELECT O.DATETIME, L.AMOUNT FROM OPERATION_LEG L
+                      JOIN OPERATION O ON O.ID = L.OPERATION
+             WHERE COALESCE(L.ACCOUNT, O.TO_ACCOUNT) = $1 AND O.DATETIME >= $2
+             UNION ALL
+             SELECT DATETIME, CASE SIDE WHEN 'buy' THEN -AMOUNT ELSE AMOUNT END FROM TRADE
+             WHERE ACCOUNT = $1 AND DATETIME >= $2
+             ORDER BY 1",
+            &[&account, &since],
+        )?
+        .iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+        .collect()
+    }
+
+    fn operations_since(&self, ledger: i64, since: i64) -> Result<Vec<Operation>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            format!(
+                "SELECT {}
+                 FROM OPERATION O
+                          JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                          JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+                 WHERE O.LEDGER = $1 AND O.DATETIME >= $2
+                 ORDER BY O.DATETIME, O.ID",
+                OPERATION_COLUMNS
+            )
+            .as_str(),
+            &[&ledger, &since],
+        )?;
+        let ids = rows
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<Vec<i64>, _>>()?;
+        let mut legs = legs(&mut *conn, &ids)?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(Operation {
+                    legs: legs.remove(&row.try_get(0)?).unwrap_or_default(),
+                    ..to_operation(row)?
+                })
+            })
+            .collect()
+    }
+
+    fn get_goals(&self, ledger: i64) -> Result<Vec<Goal>, ServerError> {
+        let mut conn = self.pool.get()?;
+        goals(&mut *conn, ledger)
+    }
+
+    fn set_goal(&self, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        insert_goal(&mut *conn, ledger, goal)
+    }
+
+    fn get_recurring(&self, ledger: i64) -> Result<Vec<RecurringOperation>, ServerError> {
+        let mut conn = self.pool.get()?;
+        recurring(&mut *conn, ledger)
+    }
+
+    fn set_recurring(&self, ledger: i64, item: &RecurringOperation) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        insert_recurring(&mut *conn, ledger, item)
+    }
+
+    fn get_installment_plans(&self, ledger: i64) -> Result<Vec<InstallmentPlan>, ServerError> {
+        let mut conn = self.pool.get()?;
+        installment_plans(&mut *conn, ledger)
+    }
+
+    fn add_installment_plan(&self, ledger: i64, plan: &InstallmentPlan) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        insert_installment_plan(&mut *conn, ledger, plan)
+    }
+
+    fn record_repayment(
+        &self,
+        ledger: i64,
+        plan: &str,
+        number: u32,
+        operation: &Operation,
+    ) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let number = number as i32;
+        let updated = tx.execute(
+            "UPDATE INSTALLMENT_PLAN SET PAID = $1
+             WHERE LEDGER = $2 AND NAME = $3 AND PAID = $4 AND TERM >= $1",
+            &[&number, &ledger, &plan, &(number - 1)],
+        )?;
+        if updated == 0 {
+            let found = tx.query_opt(
+                "SELECT ID FROM INSTALLMENT_PLAN WHERE LEDGER = $1 AND NAME = $2",
+                &[&ledger, &plan],
+            )?;
+            return Err(match found {
+                Some(_) => not_next_repayment(plan, number as u32),
+                None => unknown_plan(plan),
+            });
+        }
+        let id = insert_operation(&mut tx, ledger, operation, ("from", "to", "legs"))?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn share_operation(
+        &self,
+        ledger: i64,
+        operation: i64,
+        expense: &SharedExpense,
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        if !operation_exists(&mut tx, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+        insert_shared_expense(&mut tx, operation, expense)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_shared_expenses(&self, ledger: i64) -> Result<Vec<SharedAmount>, ServerError> {
+        let mut conn = self.pool.get()?;
+        shared_expenses(&mut *conn, ledger)
+    }
+
+    fn set_claim(&self, ledger: i64, operation: i64, claim: &Claim) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        if !operation_exists(&mut *conn, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+        check_claim(operation, claim)?;
+        if let Some(reimbursement) = claim.reimbursement {
+            if !operation_exists(&mut *conn, ledger, reimbursement)? {
+                return Err(unknown_operation_at("reimbursement", reimbursement));
+            }
+        }
+        insert_claim(&mut *conn, operation, claim)
+    }
+
+    fn record_reimbursement(
+        &self,
+        ledger: i64,
+        reimbursement: &Reimbursement,
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        if !operation_exists(&mut tx, ledger, reimbursement.operation)? {
+            return Err(unknown_operation_at("operation", reimbursement.operation));
+        }
+        for expense in &reimbursement.expenses {
+            if !operation_exists(&mut tx, ledger, *expense)? {
+                return Err(unknown_operation_at("expenses", *expense));
+            }
+            insert_claim(&mut tx, *expense, &paid_by(reimbursement.operation))?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_claims(&self, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError> {
+        let mut conn = self.pool.get()?;
+        claims(&mut *conn, ledger)
+    }
+
+    fn export_ledger(&self, ledger: i64, now: i64) -> Result<LedgerExport, ServerError> {
+        let mut conn = self.pool.get()?;
+        let settings = settings(&mut *conn, ledger)?;
+        let accounts = accounts(&mut *conn, ledger)?;
+        let categories = categories(&mut *conn, ledger)?;
+        let rates = rates(&mut *conn, ledger)?;
+        let securities = securities(&mut *conn, ledger)?;
+        let trades = trades(&mut *conn, ledger)?;
+        let prices = prices(&mut *conn, ledger)?;
+        let credit_cards = credit_cards(&mut *conn, ledger)?;
+        let installment_plans = installment_plans(&mut *conn, ledger)?;
+        let goals = goals(&mut *conn, ledger)?;
+        let recurring = recurring(&mut *conn, ledger)?;
+        let rows = conn.query(
+            format!(
+                "SELECT {}
+                 FROM OPERATION O
+                          JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                          JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+                 WHERE O.LEDGER = $1
+                 ORDER BY O.DATETIME, O.ID",
+                OPERATION_COLUMNS
+            )
+            .as_str(),
+            &[&ledger],
+        )?;
+        let ids = rows
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<Vec<i64>, _>>()?;
+        let mut legs = legs(&mut *conn, &ids)?;
+        let shared_expenses = export_shares(&ids, shared_expenses(&mut *conn, ledger)?);
+        let claims = export_claims(&ids, claims(&mut *conn, ledger)?);
+        let operations = rows
+            .iter()
+            .map(|row| {
+                Ok(Operation {
+                    legs: legs.remove(&row.try_get(0)?).unwrap_or_default(),
+                    ..to_operation(row)?
+                })
+            })
+            .collect::<Result<_, ServerError>>()?;
+
+        Ok(LedgerExport {
+            version: EXPORT_VERSION,
+            exported_at: now,
+            settings,
+            accounts,
+            categories,
+            operations,
+            rates,
+            securities,
+            trades,
+            prices,
+            credit_cards,
+            installment_plans,
+            shared_expenses,
+            claims,
+            goals,
+            recurring,
+        })
+    }
+
+    fn import_ledger(&self, ledger: i64, export: &LedgerExport) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+
+        let existing: i64 = tx
+            .query_one(
+                "SELECT (SELECT COUNT(*) FROM ACCOUNT WHERE LEDGER = $1)
+                      + (SELECT COUNT(*) FROM CATEGORY WHERE LEDGER = $1)
+                      + (SELECT COUNT(*) FROM OPERATION WHERE LEDGER = $1)
+                      + (SELECT COUNT(*) FROM SECURITY WHERE LEDGER = $1)",
+                &[&ledger],
+            )?
+            .try_get(0)?;
+        if existing > 0 {
+            return Err(ServerError::ConflictError(
+                "Only an empty ledger can be imported into.".to_owned(),
+            ));
+        }
+
+        update_settings(&mut tx, ledger, &export.settings)?;
+        for account in &export.accounts {
+            tx.execute(
+                "INSERT INTO ACCOUNT (LEDGER, NAME, BALANCE, CURRENCY) VALUES ($1, $2, 0, $3)",
+                &[&ledger, &account.name, &account.currency],
+            )?;
+        }
+        for category in &export.categories {
+            tx.execute(
+                "INSERT INTO CATEGORY (LEDGER, NAME) VALUES ($1, $2)",
+                &[&ledger, &category.name],
+            )?;
+        }
+        let mut ids = Vec::new();
+        for operation in &export.operations {
+            let fields = ("operations", "operations", "operations");
+            ids.push(insert_operation(&mut tx, ledger, operation, fields)?);
+        }
+        insert_rates(&mut tx, ledger, &export.rates)?;
+        for security in &export.securities {
+            insert_security(&mut tx, ledger, security)?;
+        }
+        for trade in &export.trades {
+            insert_trade(&mut tx, ledger, trade).map_err(|e| in_field(e, "trades"))?;
+        }
+        insert_prices(&mut tx, ledger, &export.prices).map_err(|e| in_field(e, "prices"))?;
+        for card in &export.credit_cards {
+            insert_credit_card(&mut tx, ledger, card).map_err(|e| in_field(e, "credit_cards"))?;
+        }
+        for goal in &export.goals {
+            insert_goal(&mut tx, ledger, goal).map_err(|e| in_field(e, "goals"))?;
+        }
+        for item in &export.recurring {
+            insert_recurring(&mut tx, ledger, item).map_err(|e| in_field(e, "recurring"))?;
+        }
+        for plan in &export.installment_plans {
+            insert_installment_plan(&mut tx, ledger, plan)
+                .map_err(|e| in_field(e, "installment_plans"))?;
+        }
+        for share in &export.shared_expenses {
+            insert_shared_expense(&mut tx, ids[share.operation], &share.expense)?;
+        }
+        for claim in &export.claims {
+            let stored = Claim {
+                status: claim.status,
+                reimbursement: claim.reimbursement.map(|i| ids[i]),
+            };
+            insert_claim(&mut tx, ids[claim.operation], &stored)?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        ledger: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<SearchHit>, ServerError> {
+        // Each term as a prefix; they're alphanumeric so need no escaping.
+        let query: Vec<String> = terms.iter().map(|term| format!("{}:*", term)).collect();
+        let options = format!(
+            "StartSel={}, StopSel={}, MaxWords=12, MinWords=3",
+            MATCH_START, MATCH_END
+        );
+        let mut conn = self.pool.get()?;
+        let rows = conn.query(
+            format!(
+                "SELECT {},
+                        CASE
+                            WHEN TO_TSVECTOR('simple', S.COMMENT) @@ Q.QUERY
+                                THEN TS_HEADLINE('simple', S.COMMENT, Q.QUERY, $2)
+                            ELSE TS_HEADLINE('simple', S.COUNTERPARTIES, Q.QUERY, $2)
+                            END
+                 FROM OPERATION_SEARCH S
+                          JOIN OPERATION O ON O.ID = S.OPERATION
+                          JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                          JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT,
+                      TO_TSQUERY('simple', $1) Q(QUERY)
+                 WHERE S.DOCUMENT @@ Q.QUERY AND S.LEDGER = $3
+                 ORDER BY TS_RANK(S.DOCUMENT, Q.QUERY) DESC, O.DATETIME DESC
+                 LIMIT $4",
+                OPERATION_COLUMNS
+            )
+            .as_str(),
+            &[&query.join(" & "), &options, &ledger, &limit],
+        )?;
+        let ids = rows
+            .iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<Vec<i64>, _>>()?;
+        let mut legs = legs(&mut *conn, &ids)?;
+
+        rows.iter()
+            .map(|row| {
+                let id = row.try_get(0)?;
+                Ok(SearchHit {
+                    id,
+                    operation: Operation {
+                        legs: legs.remove(&id).unwrap_or_default(),
+                        ..to_operation(row)?
+                    },
+                    snippet: highlight(row.try_get(8)?),
+                })
+            })
+            .collect()
+    }
+
+    fn add_attachment(
+        &self,
+        ledger: i64,
+        operation: i64,
+        attachment: &NewAttachment,
+        sha256: &str,
+        created_at: i64,
+        content: Option<&[u8]>,
+    ) -> Result<Attachment, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        if !operation_exists(&mut tx, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+
+        if let Some(content) = content {
+            tx.execute(
+                "INSERT INTO ATTACHMENT_CONTENT (HASH, CONTENT) VALUES ($1, $2)
+                 ON CONFLICT (HASH) DO NOTHING",
+                &[&sha256, &content],
+            )?;
+        }
+        let id: i64 = tx
+            .query_one(
+                "INSERT INTO ATTACHMENT
+                     (LEDGER, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING ID",
+                &[
+                    &ledger,
+                    &operation,
+                    &attachment.name,
+                    &attachment.content_type,
+                    &attachment.size,
+                    &sha256,
+                    &created_at,
+                ],
+            )?
+            .try_get(0)?;
+        tx.commit()?;
+
+        Ok(Attachment {
+            id,
+            operation,
+            name: attachment.name.clone(),
+            content_type: attachment.content_type.clone(),
+            size: attachment.size,
+            sha256: sha256.to_owned(),
+            created_at,
+        })
+    }
+
+    fn get_attachments(&self, ledger: i64, operation: i64) -> Result<Vec<Attachment>, ServerError> {
+        let mut conn = self.pool.get()?;
+        if !operation_exists(&mut *conn, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+
+        conn.query(
+            "SELECT ID, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT
+             FROM ATTACHMENT
+             WHERE LEDGER = $1 AND OPERATION = $2
+             ORDER BY ID",
+            &[&ledger, &operation],
+        )?
+        .iter()
+        .map(to_attachment)
+        .collect()
+    }
+
+    fn get_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<AttachmentContent>, ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.query_opt(
+            "SELECT A.ID, A.OPERATION, A.NAME, A.CONTENT_TYPE, A.SIZE, A.HASH, A.CREATED_AT,
+                    C.CONTENT
+             FROM ATTACHMENT A
+                      LEFT JOIN ATTACHMENT_CONTENT C ON C.HASH = A.HASH
+             WHERE A.ID = $1 AND A.LEDGER = $2",
+            &[&id, &ledger],
+        )?
+        .map(|row| Ok((to_attachment(&row)?, row.try_get(7)?)))
+        .transpose()
+    }
+
+    fn delete_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<(Attachment, bool)>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let mut tx = conn.transaction()?;
+        let attachment = match tx.query_opt(
+            "DELETE FROM ATTACHMENT WHERE ID = $1 AND LEDGER = $2
+             RETURNING ID, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT",
+            &[&id, &ledger],
+        )? {
+            Some(row) => to_attachment(&row)?,
+            None => return Ok(None),
+        };
+
+        let shared = tx
+            .query_opt(
+                "SELECT 1 FROM ATTACHMENT WHERE HASH = $1 LIMIT 1",
+                &[&attachment.sha256],
+            )?
+            .is_some();
+        if !shared {
+            tx.execute(
+                "DELETE FROM ATTACHMENT_CONTENT WHERE HASH = $1",
+                &[&attachment.sha256],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(Some((attachment, shared)))
+    }
+
+    fn current_db_version(&self) -> Result<Option<DbVersion>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let latest = applied_migrations(&mut *conn)?
+            .into_iter()
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(version, _, deploy_at)| DbVersion {
+                version: version.to_string(),
+                deploy_at,
+            });
+
+        Ok(latest)
+    }
+}
+
+fn applied_migrations(
+    conn: &mut impl GenericClient,
+) -> Result<Vec<(Version, String, i64)>, ServerError> {
+    conn.query("SELECT VERSION, CHECKSUM, APPLIED_AT FROM MIGRATION", &[])?
+        .iter()
+        .map(|row| {
+            let version: String = row.try_get(0)?;
+            Ok((version.parse()?, row.try_get(1)?, row.try_get(2)?))
+        })
+        .collect()
+}
+
+fn accounts(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Account>, ServerError> {
+    conn.query(
+        "SELECT NAME, BALANCE, CURRENCY FROM ACCOUNT WHERE LEDGER = $1 ORDER BY ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(Account {
+            name: row.try_get(0)?,
+            balance: row.try_get(1)?,
+            currency: row.try_get(2)?,
+        })
+    })
+    .collect()
+}
+
+fn settings(conn: &mut impl GenericClient, ledger: i64) -> Result<LedgerSettings, ServerError> {
+    let row = conn
+        .query_opt(
+            "SELECT NAME, CURRENCY, TIMEZONE FROM LEDGER WHERE ID = $1",
+            &[&ledger],
+        )?
+        .ok_or_else(|| ServerError::NotFoundError("Ledger doesn't exist.".to_owned()))?;
+
+    Ok(LedgerSettings {
+        name: row.try_get(0)?,
+        currency: row.try_get(1)?,
+        timezone: row.try_get(2)?,
+    })
+}
+
+fn update_settings(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    settings: &LedgerSettings,
+) -> Result<(), ServerError> {
+    let updated = conn.execute(
+        "UPDATE LEDGER SET NAME = $1, CURRENCY = $2, TIMEZONE = $3 WHERE ID = $4",
+        &[
+            &settings.name,
+            &settings.currency,
+            &settings.timezone,
+            &ledger,
+        ],
+    )?;
+    if updated == 0 {
+        return Err(ServerError::NotFoundError(
+            "Ledger doesn't exist.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The columns `to_operation` reads, starting with the operation's id.
+const OPERATION_COLUMNS: &str = "O.ID, F.NAME, T.NAME, O.COMMENT, O.AMOUNT, O.DATETIME, \
+                                 O.CURRENCY, O.TO_AMOUNT";
+
+/// Reads an operation without its legs from a row starting with `OPERATION_COLUMNS`.
+fn to_operation(row: &Row) -> Result<Operation, ServerError> {
+    Ok(Operation {
+        from: row.try_get(1)?,
+        to: row.try_get(2)?,
+        comment: row.try_get::<_, Option<String>>(3)?.unwrap_or_default(),
+        amount: row.try_get(4)?,
+        datetime: row.try_get(5)?,
+        legs: Vec::new(),
+        currency: row.try_get(6)?,
+        to_amount: row.try_get(7)?,
+    })
+}
+
+fn categories(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Category>, ServerError> {
+    conn.query(
+        "SELECT NAME FROM CATEGORY WHERE LEDGER = $1 ORDER BY ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(Category {
+            name: row.try_get(0)?,
+        })
+    })
+    .collect()
+}
+
+fn rates(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError> {
+    conn.query(
+        "SELECT BASE, QUOTE, DATE, RATE FROM EXCHANGE_RATE
+         WHERE LEDGER = $1
+         ORDER BY BASE, QUOTE, DATE",
+        &[&ledger],
+    )?
+    .iter()
+    .map(to_rate)
+    .collect()
+}
+
+fn insert_rates(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    rates: &[ExchangeRate],
+) -> Result<(), ServerError> {
+    // Replaced rates get a new id like SQLite's INSERT OR REPLACE gives them, which
+    // `find_rate` relies on to prefer the latest of two rates quoted either way round.
+    for rate in rates {
+        conn.execute(
+            "DELETE FROM EXCHANGE_RATE
+             WHERE LEDGER = $1 AND BASE = $2 AND QUOTE = $3 AND DATE = $4",
+            &[&ledger, &rate.base, &rate.quote, &rate.date],
+        )?;
+        conn.execute(
+            "INSERT INTO EXCHANGE_RATE (LEDGER, BASE, QUOTE, DATE, RATE)
+             VALUES ($1, $2, $3, $4, $5)",
+            &[&ledger, &rate.base, &rate.quote, &rate.date, &rate.rate],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn to_rate(row: &Row) -> Result<ExchangeRate, ServerError> {
+    Ok(ExchangeRate {
+        base: row.try_get(0)?,
+        quote: row.try_get(1)?,
+        date: row.try_get(2)?,
+        rate: row.try_get(3)?,
+    })
+}
+
+fn securities(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Security>, ServerError> {
+    conn.query(
+        "SELECT SYMBOL, NAME, CURRENCY FROM SECURITY WHERE LEDGER = $1 ORDER BY ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(Security {
+            symbol: row.try_get(0)?,
+            name: row.try_get(1)?,
+            currency: row.try_get(2)?,
+        })
+    })
+    .collect()
+}
+
+fn insert_security(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    security: &Security,
+) -> Result<(), ServerError> {
+    conn.execute(
+        "INSERT INTO SECURITY (LEDGER, SYMBOL, NAME, CURRENCY) VALUES ($1, $2, $3, $4)",
+        &[
+            &ledger,
+            &security.symbol,
+            &security.name,
+            &security.currency,
+        ],
+    )
+    .map_err(|e| match ServerError::from(e) {
+        ServerError::ConflictError(_) => security_exists(&security.symbol),
+        e => e,
+    })?;
+
+    Ok(())
+}
+
+fn find_security(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    symbol: &str,
+) -> Result<Option<(i64, String)>, ServerError> {
+    conn.query_opt(
+        "SELECT ID, CURRENCY FROM SECURITY WHERE LEDGER = $1 AND SYMBOL = $2",
+        &[&ledger, &symbol],
+    )?
+    .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+    .transpose()
+}
+
+/// Inserts the trade after `check_trade` and moves its amount into or out of the account.
+fn insert_trade(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    trade: &Trade,
+) -> Result<(), ServerError> {
+    let (account, account_currency) = find_account(conn, ledger, &trade.account)?
+        .ok_or_else(|| unknown_account("account", &trade.account))?;
+    let (security, security_currency) = find_security(conn, ledger, &trade.symbol)?
+        .ok_or_else(|| unknown_security("symbol", &trade.symbol))?;
+    let row = conn.query_one(
+        "SELECT COALESCE(SUM(CASE SIDE WHEN 'buy' THEN QUANTITY ELSE -QUANTITY END), 0)::BIGINT,
+                MAX(DATETIME)
+         FROM TRADE
+         WHERE ACCOUNT = $1 AND SECURITY = $2",
+        &[&account, &security],
+    )?;
+    let (held, latest): (i64, Option<i64>) = (row.try_get(0)?, row.try_get(1)?);
+    check_trade(trade, &account_currency, &security_currency, held, latest)?;
+
+    conn.execute(
+        "INSERT INTO TRADE (LEDGER, ACCOUNT, SECURITY, SIDE, QUANTITY, AMOUNT, DATETIME, COMMENT)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        &[
+            &ledger,
+            &account,
+            &security,
+            &trade.side.as_str(),
+            &trade.quantity,
+            &trade.amount,
+            &trade.datetime,
+            &trade.comment,
+        ],
+    )?;
+    conn.execute(
+        "UPDATE ACCOUNT SET BALANCE = BALANCE + $1 WHERE ID = $2",
+        &[&trade.signed_amount(), &account],
+    )?;
+
+    Ok(())
+}
+
+fn trades(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Trade>, ServerError> {
+    conn.query(
+        "SELECT A.NAME, S.SYMBOL, T.SIDE, T.QUANTITY, T.AMOUNT, T.DATETIME, T.COMMENT
+         FROM TRADE T
+                  JOIN ACCOUNT A ON A.ID = T.ACCOUNT
+                  JOIN SECURITY S ON S.ID = T.SECURITY
+         WHERE T.LEDGER = $1
+         ORDER BY T.DATETIME, T.ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(Trade {
+            account: row.try_get(0)?,
+            symbol: row.try_get(1)?,
+            side: row
+                .try_get::<_, String>(2)?
+                .parse()
+                .map_err(ServerError::DBError)?,
+            quantity: row.try_get(3)?,
+            amount: row.try_get(4)?,
+            datetime: row.try_get(5)?,
+            comment: row.try_get(6)?,
+        })
+    })
+    .collect()
+}
+
+fn prices(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Price>, ServerError> {
+    conn.query(
+        "SELECT S.SYMBOL, P.DATE, P.PRICE
+         FROM PRICE P
+                  JOIN SECURITY S ON S.ID = P.SECURITY
+         WHERE S.LEDGER = $1
+         ORDER BY S.SYMBOL, P.DATE",
+        &[&ledger],
+    )?
+    .iter()
+    .map(to_price)
+    .collect()
+}
+
+fn insert_prices(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    prices: &[Price],
+) -> Result<(), ServerError> {
+    for price in prices {
+        let (security, _) = find_security(conn, ledger, &price.symbol)?
+            .ok_or_else(|| unknown_security("symbol", &price.symbol))?;
+        conn.execute(
+            "DELETE FROM PRICE WHERE SECURITY = $1 AND DATE = $2",
+            &[&security, &price.date],
+        )?;
+        conn.execute(
+            "INSERT INTO PRICE (SECURITY, DATE, PRICE) VALUES ($1, $2, $3)",
+            &[&security, &price.date, &price.price],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn to_price(row: &Row) -> Result<Price, ServerError> {
+    Ok(Price {
+        symbol: row.try_get(0)?,
+        date: row.try_get(1)?,
+        price: row.try_get(2)?,
+    })
+}
+
+fn credit_cards(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+) -> Result<Vec<CreditCard>, ServerError> {
+    conn.query(
+        "SELECT A.NAME, C.STATEMENT_DAY, C.DUE_DAY, C.CREDIT_LIMIT, P.NAME
+         FROM CREDIT_CARD C
+                  JOIN ACCOUNT A ON A.ID = C.ACCOUNT
+                  LEFT JOIN ACCOUNT P ON P.ID = C.PAY_FROM
+         WHERE A.LEDGER = $1
+         ORDER BY A.ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(CreditCard {
+            account: row.try_get(0)?,
+            statement_day: row.try_get::<_, i32>(1)? as u32,
+            due_day: row.try_get::<_, i32>(2)? as u32,
+            limit: row.try_get(3)?,
+            pay_from: row.try_get(4)?,
+        })
+    })
+    .collect()
+}
+
+fn insert_credit_card(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    card: &CreditCard,
+) -> Result<(), ServerError> {
+    let (account, currency) = find_account(conn, ledger, &card.account)?
+        .ok_or_else(|| unknown_account("account", &card.account))?;
+    let pay_from = match &card.pay_from {
+        Some(name) => Some(
+            find_account(conn, ledger, name)?.ok_or_else(|| unknown_account("pay_from", name))?,
+        ),
+        None => None,
+    };
+    check_card(
+        card,
+        &currency,
+        pay_from.as_ref().map(|(_, currency)| currency.as_str()),
+    )?;
+    conn.execute(
+        "INSERT INTO CREDIT_CARD (ACCOUNT, STATEMENT_DAY, DUE_DAY, CREDIT_LIMIT, PAY_FROM)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (ACCOUNT) DO UPDATE
+             SET STATEMENT_DAY = EXCLUDED.STATEMENT_DAY,
+                 DUE_DAY       = EXCLUDED.DUE_DAY,
+                 CREDIT_LIMIT  = EXCLUDED.CREDIT_LIMIT,
+                 PAY_FROM      = EXCLUDED.PAY_FROM",
+        &[
+            &account,
+            &(card.statement_day as i32),
+            &(card.due_day as i32),
+            &card.limit,
+            &pay_from.map(|(id, _)| id),
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn goals(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<Goal>, ServerError> {
+    conn.query(
+        "SELECT G.NAME, A.NAME, G.TARGET, G.TARGET_DATE
+         FROM GOAL G
+                  JOIN ACCOUNT A ON A.ID = G.ACCOUNT
+         WHERE G.LEDGER = $1
+         ORDER BY G.TARGET_DATE, G.NAME",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(Goal {
+            name: row.try_get(0)?,
+            account: row.try_get(1)?,
+            target: row.try_get(2)?,
+            target_date: row.try_get(3)?,
+        })
+    })
+    .collect()
+}
+
+fn insert_goal(conn: &mut impl GenericClient, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+    let (account, _) = find_account(conn, ledger, &goal.account)?
+        .ok_or_else(|| unknown_account("account", &goal.account))?;
+    if let Some(row) = conn.query_opt(
+        "SELECT NAME FROM GOAL WHERE ACCOUNT = $1 AND NAME <> $2 LIMIT 1",
+        &[&account, &goal.name],
+    )? {
+        return Err(account_has_goal(&goal.account, row.try_get(0)?));
+    }
+    conn.execute(
+        "INSERT INTO GOAL (LEDGER, NAME, ACCOUNT, TARGET, TARGET_DATE)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (LEDGER, NAME) DO UPDATE
+             SET ACCOUNT     = EXCLUDED.ACCOUNT,
+                 TARGET      = EXCLUDED.TARGET,
+                 TARGET_DATE = EXCLUDED.TARGET_DATE",
+        &[
+            &ledger,
+            &goal.name,
+            &account,
+            &goal.target,
+            &goal.target_date,
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn recurring(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+) -> Result<Vec<RecurringOperation>, ServerError> {
+    conn.query(
+        "SELECT R.NAME, F.NAME, T.NAME, R.AMOUNT, R.FREQUENCY, R.START_DATE, R.END_DATE, C.NAME
+         FROM RECURRING_OPERATION R
+                  JOIN ACCOUNT F ON F.ID = R.FROM_ACCOUNT
+                  JOIN ACCOUNT T ON T.ID = R.TO_ACCOUNT
+                  LEFT JOIN CATEGORY C ON C.ID = R.CATEGORY
+         WHERE R.LEDGER = $1
+         ORDER BY R.NAME",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(RecurringOperation {
+            name: row.try_get(0)?,
+            from: row.try_get(1)?,
+            to: row.try_get(2)?,
+            amount: row.try_get(3)?,
+            frequency: row
+                .try_get::<_, String>(4)?
+                .parse()
+                .map_err(ServerError::DBError)?,
+            start: row.try_get(5)?,
+            end: row.try_get(6)?,
+            category: row.try_get(7)?,
+        })
+    })
+    .collect()
+}
+
+fn insert_recurring(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    item: &RecurringOperation,
+) -> Result<(), ServerError> {
+    let (from, from_currency) = find_account(conn, ledger, &item.from)?
+        .ok_or_else(|| unknown_account("from", &item.from))?;
+    let (to, to_currency) =
+        find_account(conn, ledger, &item.to)?.ok_or_else(|| unknown_account("to", &item.to))?;
+    check_recurring(item, &from_currency, &to_currency)?;
+    let category = match &item.category {
+        Some(name) => Some(
+            category_id(conn, ledger, name)?.ok_or_else(|| unknown_category("category", name))?,
+        ),
+        None => None,
+    };
+    conn.execute(
+        "INSERT INTO RECURRING_OPERATION
+             (LEDGER, NAME, FROM_ACCOUNT, TO_ACCOUNT, CATEGORY, AMOUNT, FREQUENCY, START_DATE,
+              END_DATE)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         ON CONFLICT (LEDGER, NAME) DO UPDATE
+             SET FROM_ACCOUNT = EXCLUDED.FROM_ACCOUNT,
+                 TO_ACCOUNT   = EXCLUDED.TO_ACCOUNT,
+                 CATEGORY     = EXCLUDED.CATEGORY,
+                 AMOUNT       = EXCLUDED.AMOUNT,
+                 FREQUENCY    = EXCLUDED.FREQUENCY,
+                 START_DATE   = EXCLUDED.START_DATE,
+                 END_DATE     = EXCLUDED.END_DATE",
+        &[
+            &ledger,
+            &item.name,
+            &from,
+            &to,
+            &category,
+            &item.amount,
+            &item.frequency.as_str(),
+            &item.start,
+            &item.end,
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn installment_plans(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+) -> Result<Vec<InstallmentPlan>, ServerError> {
+    conn.query(
+        "SELECT P.NAME, A.NAME, F.NAME, C.NAME, P.PRINCIPAL, P.ANNUAL_RATE, P.FEE, P.TERM,
+                P.FIRST_DUE, P.PAID
+         FROM INSTALLMENT_PLAN P
+                  JOIN ACCOUNT A ON A.ID = P.ACCOUNT
+                  JOIN ACCOUNT F ON F.ID = P.FROM_ACCOUNT
+                  LEFT JOIN ACCOUNT C ON C.ID = P.COST_ACCOUNT
+         WHERE P.LEDGER = $1
+         ORDER BY P.ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(InstallmentPlan {
+            name: row.try_get(0)?,
+            account: row.try_get(1)?,
+            from: row.try_get(2)?,
+            cost_account: row.try_get(3)?,
+            principal: row.try_get(4)?,
+            annual_rate: row.try_get(5)?,
+            fee: row.try_get(6)?,
+            term: row.try_get::<_, i32>(7)? as u32,
+            first_due: row.try_get(8)?,
+            paid: row.try_get::<_, i32>(9)? as u32,
+        })
+    })
+    .collect()
+}
+
+/// Inserts the plan after `check_plan`.
+fn insert_installment_plan(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    plan: &InstallmentPlan,
+) -> Result<(), ServerError> {
+    let (account, account_currency) = find_account(conn, ledger, &plan.account)?
+        .ok_or_else(|| unknown_account("account", &plan.account))?;
+    let (from, from_currency) = find_account(conn, ledger, &plan.from)?
+        .ok_or_else(|| unknown_account("from", &plan.from))?;
+    let cost = match &plan.cost_account {
+        Some(name) => Some(
+            find_account(conn, ledger, name)?
+                .ok_or_else(|| unknown_account("cost_account", name))?,
+        ),
+        None => None,
+    };
+    check_plan(
+        plan,
+        &account_currency,
+        &from_currency,
+        cost.as_ref().map(|(_, currency)| currency.as_str()),
+    )?;
+
+    conn.execute(
+        "INSERT INTO INSTALLMENT_PLAN (LEDGER, NAME, ACCOUNT, FROM_ACCOUNT, COST_ACCOUNT,
+                                       PRINCIPAL, ANNUAL_RATE, FEE, TERM, FIRST_DUE, PAID)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        &[
+            &ledger,
+            &plan.name,
+            &account,
+            &from,
+            &cost.map(|(id, _)| id),
+            &plan.principal,
+            &plan.annual_rate,
+            &plan.fee,
+            &(plan.term as i32),
+            &plan.first_due,
+            &(plan.paid as i32),
+        ],
+    )
+    .map_err(|e| match ServerError::from(e) {
+        ServerError::ConflictError(_) => plan_exists(&plan.name),
+        e => e,
+    })?;
+
+    Ok(())
+}
+
+fn shared_expenses(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+) -> Result<Vec<SharedAmount>, ServerError> {
+    let rows = conn.query(
+        "SELECT O.ID, O.AMOUNT, O.CURRENCY, S.PAID_BY, S.METHOD
+         FROM SHARED_EXPENSE S
+                  JOIN OPERATION O ON O.ID = S.OPERATION
+         WHERE O.LEDGER = $1
+         ORDER BY O.DATETIME, O.ID",
+        &[&ledger],
+    )?;
+    let mut shared = Vec::new();
+    for row in rows {
+        let operation: i64 = row.try_get(0)?;
+        let shares = conn
+            .query(
+                "SELECT MEMBER, SHARE FROM EXPENSE_SHARE WHERE OPERATION = $1 ORDER BY ID",
+                &[&operation],
+            )?
+            .iter()
+            .map(|row| {
+                Ok(MemberShare {
+                    member: row.try_get(0)?,
+                    share: row.try_get(1)?,
+                })
+            })
+            .collect::<Result<_, ServerError>>()?;
+        shared.push(SharedAmount {
+            operation,
+            amount: row.try_get(1)?,
+            currency: row.try_get(2)?,
+            expense: SharedExpense {
+                paid_by: row.try_get(3)?,
+                method: row
+                    .try_get::<_, String>(4)?
+                    .parse()
+                    .map_err(ServerError::DBError)?,
+                shares,
+            },
+        });
+    }
+
+    Ok(shared)
+}
+
+fn claims(conn: &mut impl GenericClient, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError> {
+    conn.query(
+        "SELECT O.ID, O.DATETIME, T.NAME, O.COMMENT, O.AMOUNT, O.CURRENCY, C.STATUS,
+                C.REIMBURSEMENT
+         FROM CLAIM C
+                  JOIN OPERATION O ON O.ID = C.OPERATION
+                  JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+         WHERE O.LEDGER = $1
+         ORDER BY O.DATETIME, O.ID",
+        &[&ledger],
+    )?
+    .iter()
+    .map(|row| {
+        Ok(ClaimedExpense {
+            operation: row.try_get(0)?,
+            datetime: row.try_get(1)?,
+            to: row.try_get(2)?,
+            comment: row.try_get(3)?,
+            amount: row.try_get(4)?,
+            currency: row.try_get(5)?,
+            status: row
+                .try_get::<_, String>(6)?
+                .parse()
+                .map_err(ServerError::DBError)?,
+            reimbursement: row.try_get(7)?,
+        })
+    })
+    .collect()
+}
+
+fn insert_claim(
+    conn: &mut impl GenericClient,
+    operation: i64,
+    claim: &Claim,
+) -> Result<(), ServerError> {
+    conn.execute(
+        "INSERT INTO CLAIM (OPERATION, STATUS, REIMBURSEMENT) VALUES ($1, $2, $3)
+         ON CONFLICT (OPERATION) DO UPDATE
+             SET STATUS = EXCLUDED.STATUS, REIMBURSEMENT = EXCLUDED.REIMBURSEMENT",
+        &[&operation, &claim.status.as_str(), &claim.reimbursement],
+    )?;
+
+    Ok(())
+}
+
+/// Replaces how the operation is shared.
+fn insert_shared_expense(
+    conn: &mut impl GenericClient,
+    operation: i64,
+    expense: &SharedExpense,
+) -> Result<(), ServerError> {
+    conn.execute(
+        "DELETE FROM EXPENSE_SHARE WHERE OPERATION = $1",
+        &[&operation],
+    )?;
+    conn.execute(
+        "DELETE FROM SHARED_EXPENSE WHERE OPERATION = $1",
+        &[&operation],
+    )?;
+    conn.execute(
+        "INSERT INTO SHARED_EXPENSE (OPERATION, PAID_BY, METHOD) VALUES ($1, $2, $3)",
+        &[&operation, &expense.paid_by, &expense.method.as_str()],
+    )?;
+    for share in &expense.shares {
+        conn.execute(
+            "INSERT INTO EXPENSE_SHARE (OPERATION, MEMBER, SHARE) VALUES ($1, $2, $3)",
+            &[&operation, &share.member, &share.share],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Inserts the operation with its legs and moves its amount from `from` to the accounts it
+/// credits, all resolved within the ledger, returning its id. `fields` name the inputs to blame
+/// for an unknown `from`, `to` or leg account or category.
+fn insert_operation(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    operation: &Operation,
+    fields: (&str, &str, &str),
+) -> Result<i64, ServerError> {
+    let (from, from_currency) = find_account(conn, ledger, &operation.from)?
+        .ok_or_else(|| unknown_account(fields.0, &operation.from))?;
+    let (to, to_currency) = find_account(conn, ledger, &operation.to)?
+        .ok_or_else(|| unknown_account(fields.1, &operation.to))?;
+    let mut legs = Vec::new();
+    for leg in &operation.legs {
+        let account = match &leg.account {
+            Some(name) => Some(
+                find_account(conn, ledger, name)?.ok_or_else(|| unknown_account(fields.2, name))?,
+            ),
+            None => None,
+        };
+        let category = match &leg.category {
+            Some(name) => Some(
+                category_id(conn, ledger, name)?.ok_or_else(|| unknown_category(fields.2, name))?,
+            ),
+            None => None,
+        };
+        legs.push((leg, account, category));
+    }
+    let leg_currencies: Vec<Option<&str>> = legs
+        .iter()
+        .map(|(_, account, _)| account.as_ref().map(|(_, currency)| currency.as_str()))
+        .collect();
+    let currency = validate_currencies(operation, &from_currency, &to_currency, &leg_currencies)?;
+
+    let id: i64 = conn
+        .query_one(
+            "INSERT INTO OPERATION
+                 (LEDGER, FROM_ACCOUNT, TO_ACCOUNT, COMMENT, AMOUNT, DATETIME, CURRENCY, TO_AMOUNT)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING ID",
+            &[
+                &ledger,
+                &from,
+                &to,
+                &operation.comment,
+                &operation.amount,
+                &operation.datetime,
+                &currency,
+                &operation.to_amount,
+            ],
+        )?
+        .try_get(0)?;
+
+    let sql = "UPDATE ACCOUNT SET BALANCE = BALANCE + $1 WHERE ID = $2";
+    conn.execute(sql, &[&-operation.amount, &from])?;
+
+    if legs.is_empty() {
+        conn.execute(
+            sql,
+            &[&operation.to_amount.unwrap_or(operation.amount), &to],
+        )?;
+    }
+    for (leg, account, category) in legs {
+        let account = account.map(|(id, _)| id);
+        conn.execute(
+            "INSERT INTO OPERATION_LEG (OPERATION, ACCOUNT, CATEGORY, AMOUNT, MEMO)
+             VALUES ($1, $2, $3, $4, $5)",
+            &[&id, &account, &category, &leg.amount, &leg.memo],
+        )?;
+        conn.execute(sql, &[&leg.amount, &account.unwrap_or(to)])?;
+    }
+
+    Ok(id)
+}
+
+/// Reads the legs of the given operations, keyed by operation.
+fn legs(
+    conn: &mut impl GenericClient,
+    operations: &[i64],
+) -> Result<HashMap<i64, Vec<Leg>>, ServerError> {
+    let mut legs: HashMap<i64, Vec<Leg>> = HashMap::new();
+    for row in conn.query(
+        "SELECT L.OPERATION, A.NAME, C.NAME, L.AMOUNT, L.MEMO
+         FROM OPERATION_LEG L
+                  LEFT JOIN ACCOUNT A ON A.ID = L.ACCOUNT
+                  LEFT JOIN CATEGORY C ON C.ID = L.CATEGORY
+         WHERE L.OPERATION = ANY($1)
+         ORDER BY L.ID",
+        &[&operations],
+    )? {
+        legs.entry(row.try_get(0)?).or_default().push(Leg {
+            account: row.try_get(1)?,
+            category: row.try_get(2)?,
+            amount: row.try_get(3)?,
+            memo: row.try_get(4)?,
+        });
+    }
+
+    Ok(legs)
+}
+
+fn operation_exists(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    operation: i64,
+) -> Result<bool, ServerError> {
+    Ok(conn
+        .query_opt(
+            "SELECT 1 FROM OPERATION WHERE ID = $1 AND LEDGER = $2",
+            &[&operation, &ledger],
+        )?
+        .is_some())
+}
+
+fn to_attachment(row: &Row) -> Result<Attachment, ServerError> {
+    Ok(Attachment {
+        id: row.try_get(0)?,
+        operation: row.try_get(1)?,
+        name: row.try_get(2)?,
+        content_type: row.try_get(3)?,
+        size: row.try_get(4)?,
+        sha256: row.try_get(5)?,
+        created_at: row.try_get(6)?,
+    })
+}
+
+fn find_account(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    name: &str,
+) -> Result<Option<(i64, String)>, ServerError> {
+    conn.query_opt(
+        "SELECT ID, CURRENCY FROM ACCOUNT WHERE LEDGER = $1 AND NAME = $2",
+        &[&ledger, &name],
+    )?
+    .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+    .transpose()
+}
+
+fn category_id(
+    conn: &mut impl GenericClient,
+    ledger: i64,
+    name: &str,
+) -> Result<Option<i64>, ServerError> {
+    conn.query_opt(
+        "SELECT ID FROM CATEGORY WHERE LEDGER = $1 AND NAME = $2",
+        &[&ledger, &name],
+    )?
+    .map(|row| Ok(row.try_get(0)?))
+    .transpose()
+}
+
+fn delete_totps(
+    conn: &mut impl GenericClient,
+    user: i64,
+    confirmed: bool,
+) -> Result<(), ServerError> {
+    conn.execute(
+        "DELETE FROM RECOVERY_CODE
+         WHERE TOTP IN (SELECT ID FROM TOTP WHERE \"USER\" = $1 AND CONFIRMED = $2)",
+        &[&user, &confirmed],
+    )?;
+    conn.execute(
+        "DELETE FROM TOTP WHERE \"USER\" = $1 AND CONFIRMED = $2",
+        &[&user, &confirmed],
+    )?;
+
+    Ok(())
+}
+
+fn to_user(row: &Row) -> Result<User, ServerError> {
+    Ok(User {
+        id: row.try_get(0)?,
+        name: row.try_get(1)?,
+        password_hash: row.try_get(2)?,
+        admin: row.try_get(3)?,
+    })
+}
+
+fn parse_role(role: String) -> Result<Role, ServerError> {
+    role.parse().map_err(ServerError::DBError)
+}
@@ -0,0 +1,1960 @@
+use super::{
+    account_has_goal, category_exists, check_card, check_claim, check_owners, check_plan,
+    check_recurring, check_trade, export_claims, export_shares, highlight, in_field,
+    not_next_repayment, paid_by, plan_exists, security_exists, unknown_account, unknown_category,
+    unknown_operation, unknown_operation_at, unknown_plan, unknown_security, user_exists,
+    validate_currencies, AttachmentContent, SharedAmount, Storage, TotpSecret, User, MATCH_END,
+    MATCH_START,
+};
+use crate::error::ServerError;
+use crate::migration::{self, Migration, Version};
+use entities::{
+    Account, Attachment, Category, Claim, ClaimedExpense, Conversion, CreditCard, DbVersion,
+    ExchangeRate, Goal, InstallmentPlan, Ledger, LedgerExport, LedgerMember, LedgerSettings, Leg,
+    MemberShare, NewAttachment, NewLedger, Operation, Price, RecurringOperation, Reimbursement,
+    Role, SearchHit, Security, SharedExpense, Trade, EXPORT_VERSION,
+};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension, Row, NO_PARAMS};
+use std::path::Path;
+
+pub type Pool = r2d2::Pool<SqliteConnectionManager>;
+
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: Pool,
+}
+
+impl SqliteStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let manager = SqliteConnectionManager::file(path);
+        let pool = Pool::new(manager).unwrap();
+
+        SqliteStorage { pool }
+    }
+
+    /// Copies a consistent snapshot of the database to `path` with SQLite's online backup API,
+    /// so requests keep being served while it runs.
+    pub fn backup_to<P: AsRef<Path>>(&self, path: P) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        conn.backup(DatabaseName::Main, path, None)?;
+
+        Ok(())
+    }
+
+    pub fn integrity_check(&self) -> Result<bool, ServerError> {
+        let conn = self.pool.get()?;
+        let result: String =
+            conn.query_row("PRAGMA integrity_check", NO_PARAMS, |row| row.get(0))?;
+
+        Ok(result == "ok")
+    }
+
+    /// Lists the migrations `migrate` would apply, without writing to the database.
+    pub fn pending_migrations<'a>(
+        &self,
+        migrations: &'a [Migration],
+    ) -> Result<Vec<&'a Migration>, ServerError> {
+        let conn = self.pool.get()?;
+
+        pending_migrations(&conn, migrations)
+    }
+
+    /// Applies every migration not yet recorded in `MIGRATION`, each in its own transaction,
+    /// after checking the recorded ones with `migration::pending`.
+    pub fn migrate(&self, migrations: &[Migration]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS MIGRATION
+             (
+                 VERSION    TEXT PRIMARY KEY,
+                 CHECKSUM   TEXT    NOT NULL,
+                 APPLIED_AT INTEGER NOT NULL
+             );",
+        )?;
+        adopt_legacy_versions(&mut conn, migrations)?;
+
+        for migration in pending_migrations(&conn, migrations)? {
+            info!("Applying database migration {}.", migration.version);
+            let tx = conn.transaction()?;
+            tx.execute_batch(&migration.sql)?;
+            record_migration(&tx, migration)?;
+            tx.commit()?;
+        }
+
+        info!("The database is now the latest version.");
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn record_operation(&self, ledger: i64, operation: &Operation) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let id = insert_operation(&tx, ledger, operation, ("from", "to", "legs"))?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn import_operations(
+        &self,
+        ledger: i64,
+        operations: &[(String, Operation)],
+    ) -> Result<usize, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let mut recorded = 0;
+        for (key, operation) in operations {
+            let imported = tx
+                .query_row(
+                    "SELECT 1 FROM OPERATION WHERE LEDGER = ? AND IMPORT_KEY = ?",
+                    params![ledger, key],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if imported {
+                continue;
+            }
+            let fields = ("transactions", "transactions", "transactions");
+            let id = insert_operation(&tx, ledger, operation, fields)?;
+            tx.execute(
+                "UPDATE OPERATION SET IMPORT_KEY = ? WHERE ID = ?",
+                params![key, id],
+            )?;
+            recorded += 1;
+        }
+        tx.commit()?;
+
+        Ok(recorded)
+    }
+
+    fn get_user(&self, name: &str) -> Result<Option<User>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT ID, NAME, TOKEN, ADMIN FROM USER WHERE NAME = ?")?;
+        let mut rows = stmt.query_map(params![name], |row| {
+            Ok(User {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                password_hash: row.get(2)?,
+                admin: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.next().transpose()?)
+    }
+
+    fn get_user_by_id(&self, id: i64) -> Result<Option<User>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT ID, NAME, TOKEN, ADMIN FROM USER WHERE ID = ?")?;
+        let mut rows = stmt.query_map(params![id], |row| {
+            Ok(User {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                password_hash: row.get(2)?,
+                admin: row.get(3)?,
+            })
+        })?;
+
+        Ok(rows.next().transpose()?)
+    }
+
+    fn add_user(&self, name: &str, password_hash: &str) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        let rows_updated = conn
+            .execute(
+                "INSERT INTO USER (NAME, TOKEN, TOKEN_HASHED) VALUES (?, ?, 1)",
+                params![name, password_hash],
+            )
+            .map_err(|e| match ServerError::from(e) {
+                ServerError::ConflictError(_) => user_exists(name),
+                e => e,
+            })?;
+
+        if rows_updated != 1 {
+            Err(ServerError::DBError(
+                "Wrong number of lines inserted.".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn plain_tokens(&self) -> Result<Vec<(i64, String)>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT ID, TOKEN FROM USER WHERE TOKEN_HASHED = 0 ORDER BY ID")?;
+        let tokens = stmt
+            .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+
+        Ok(tokens)
+    }
+
+    fn set_password_hash(&self, user: i64, password_hash: &str) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "UPDATE USER SET TOKEN = ?, TOKEN_HASHED = 1 WHERE ID = ?",
+            params![password_hash, user],
+        )?;
+
+        Ok(())
+    }
+
+    fn get_ledgers(&self, user: i64) -> Result<Vec<Ledger>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT L.ID, L.NAME, M.ROLE, L.CURRENCY, L.TIMEZONE
+             FROM LEDGER L JOIN LEDGER_MEMBER M ON M.LEDGER = L.ID
+             WHERE M.USER = ? ORDER BY L.ID",
+        )?;
+        let ledgers = stmt
+            .query_map(params![user], |row| {
+                Ok(Ledger {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    role: parse_role(row.get(2)?)?,
+                    currency: row.get(3)?,
+                    timezone: row.get(4)?,
+                })
+            })
+            .and_then(Iterator::collect)?;
+
+        Ok(ledgers)
+    }
+
+    fn get_role(&self, user: i64, ledger: i64) -> Result<Option<Role>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt =
+            conn.prepare("SELECT ROLE FROM LEDGER_MEMBER WHERE USER = ? AND LEDGER = ?")?;
+        let mut rows = stmt.query_map(params![user, ledger], |row| parse_role(row.get(0)?))?;
+
+        Ok(rows.next().transpose()?)
+    }
+
+    fn ledger_ids(&self) -> Result<Vec<i64>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare("SELECT ID FROM LEDGER ORDER BY ID")?;
+        let ids = stmt
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .and_then(Iterator::collect)?;
+
+        Ok(ids)
+    }
+
+    fn add_ledger(&self, user: i64, ledger: &NewLedger) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO LEDGER (NAME, CURRENCY, TIMEZONE) VALUES (?, ?, ?)",
+            params![ledger.name, ledger.currency, ledger.timezone],
+        )?;
+        let ledger_id = tx.last_insert_rowid();
+        tx.execute(
+            "INSERT INTO LEDGER_MEMBER (LEDGER, USER, ROLE) VALUES (?, ?, ?)",
+            params![ledger_id, user, Role::Owner.as_str()],
+        )?;
+        tx.commit()?;
+
+        Ok(ledger_id)
+    }
+
+    fn set_member(&self, ledger: i64, member: &LedgerMember) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let rows_updated = tx.execute(
+            "INSERT OR REPLACE INTO LEDGER_MEMBER (LEDGER, USER, ROLE)
+             SELECT ?, ID, ? FROM USER WHERE NAME = ?",
+            params![ledger, member.role.as_str(), member.user],
+        )?;
+
+        if rows_updated != 1 {
+            return Err(ServerError::NotFoundError(format!(
+                "User {} doesn't exist.",
+                member.user
+            )));
+        }
+        let owners: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM LEDGER_MEMBER WHERE LEDGER = ? AND ROLE = ?",
+            params![ledger, Role::Owner.as_str()],
+            |row| row.get(0),
+        )?;
+        check_owners(owners)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_totp(&self, user: i64, confirmed: bool) -> Result<Option<TotpSecret>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT ID, SECRET, LAST_STEP FROM TOTP WHERE USER = ? AND CONFIRMED = ?
+             ORDER BY ID DESC LIMIT 1",
+        )?;
+        let mut rows = stmt.query_map(params![user, confirmed], |row| {
+            Ok(TotpSecret {
+                id: row.get(0)?,
+                secret: row.get(1)?,
+                last_step: row.get(2)?,
+            })
+        })?;
+
+        Ok(rows.next().transpose()?)
+    }
+
+    fn enrol_totp(
+        &self,
+        user: i64,
+        secret: &str,
+        recovery_hashes: &[String],
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM RECOVERY_CODE
+             WHERE TOTP IN (SELECT ID FROM TOTP WHERE USER = ? AND CONFIRMED = 0)",
+            params![user],
+        )?;
+        tx.execute(
+            "DELETE FROM TOTP WHERE USER = ? AND CONFIRMED = 0",
+            params![user],
+        )?;
+        tx.execute(
+            "INSERT INTO TOTP (USER, SECRET, CONFIRMED, LAST_STEP) VALUES (?, ?, 0, 0)",
+            params![user, secret],
+        )?;
+        let totp_id = tx.last_insert_rowid();
+        for hash in recovery_hashes {
+            tx.execute(
+                "INSERT INTO RECOVERY_CODE (TOTP, HASH) VALUES (?, ?)",
+                params![totp_id, hash],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn confirm_totp(&self, user: i64, totp_id: i64, step: i64) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM RECOVERY_CODE
+             WHERE TOTP IN (SELECT ID FROM TOTP WHERE USER = ? AND CONFIRMED = 1)",
+            params![user],
+        )?;
+        tx.execute(
+            "DELETE FROM TOTP WHERE USER = ? AND CONFIRMED = 1",
+            params![user],
+        )?;
+        let rows_updated = tx.execute(
+            "UPDATE TOTP SET CONFIRMED = 1, LAST_STEP = ? WHERE ID = ?",
+            params![step, totp_id],
+        )?;
+
+        if rows_updated != 1 {
+            return Err(ServerError::DBError(
+                "Wrong number of lines updated.".to_owned(),
+            ));
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn update_totp_step(&self, totp_id: i64, step: i64) -> Result<bool, ServerError> {
+        let conn = self.pool.get()?;
+        let rows_updated = conn.execute(
+            "UPDATE TOTP SET LAST_STEP = ? WHERE ID = ? AND LAST_STEP < ?",
+            params![step, totp_id, step],
+        )?;
+
+        Ok(rows_updated == 1)
+    }
+
+    fn use_recovery_code(&self, totp_id: i64, hash: &str) -> Result<bool, ServerError> {
+        let conn = self.pool.get()?;
+        let rows_updated = conn.execute(
+            "UPDATE RECOVERY_CODE SET USED_AT = CAST(STRFTIME('%s','now') AS INTEGER) * 1000 WHERE TOTP = ? AND HASH = ? AND USED_AT IS NULL",
+            params![totp_id, hash],
+        )?;
+
+        Ok(rows_updated == 1)
+    }
+
+    fn get_accounts(&self, ledger: i64) -> Result<Vec<Account>, ServerError> {
+        let conn = self.pool.get()?;
+        accounts(&conn, ledger)
+    }
+
+    fn add_account(&self, ledger: i64, account: &Account) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn
+            .prepare("INSERT INTO ACCOUNT (LEDGER, NAME, BALANCE, CURRENCY) VALUES (?, ?, ?, ?)")?;
+        let rows_updated = stmt
+            .execute(params![
+                ledger,
+                account.name,
+                account.balance,
+                account.currency
+            ])
+            .map_err(|e| match ServerError::from(e) {
+                ServerError::ConflictError(_) => {
+                    ServerError::ConflictError(format!("Account {} already exists.", account.name))
+                }
+                e => e,
+            })?;
+
+        if rows_updated != 1 {
+            Err(ServerError::DBError(
+                "Wrong number of lines inserted.".to_owned(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn get_categories(&self, ledger: i64) -> Result<Vec<Category>, ServerError> {
+        let conn = self.pool.get()?;
+        categories(&conn, ledger)
+    }
+
+    fn add_category(&self, ledger: i64, category: &Category) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        conn.execute(
+            "INSERT INTO CATEGORY (LEDGER, NAME) VALUES (?, ?)",
+            params![ledger, category.name],
+        )
+        .map_err(|e| match ServerError::from(e) {
+            ServerError::ConflictError(_) => category_exists(&category.name),
+            e => e,
+        })?;
+
+        Ok(())
+    }
+
+    fn get_settings(&self, ledger: i64) -> Result<LedgerSettings, ServerError> {
+        let conn = self.pool.get()?;
+        settings(&conn, ledger)
+    }
+
+    fn update_settings(&self, ledger: i64, settings: &LedgerSettings) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        update_settings(&conn, ledger, settings)
+    }
+
+    fn get_conversions(&self, ledger: i64) -> Result<Vec<Conversion>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT O.CURRENCY, T.CURRENCY, O.AMOUNT, O.TO_AMOUNT, O.DATETIME
+             FROM OPERATION O
+                      JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+             WHERE O.LEDGER = ? AND O.TO_AMOUNT IS NOT NULL
+             ORDER BY O.DATETIME, O.ID",
+        )?;
+        let conversions = stmt
+            .query_map(params![ledger], |row| {
+                Ok(Conversion {
+                    from_currency: row.get(0)?,
+                    to_currency: row.get(1)?,
+                    amount: row.get(2)?,
+                    to_amount: row.get(3)?,
+                    datetime: row.get(4)?,
+                })
+            })
+            .and_then(Iterator::collect)?;
+
+        Ok(conversions)
+    }
+
+    fn get_rates(&self, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError> {
+        let conn = self.pool.get()?;
+        rates(&conn, ledger)
+    }
+
+    fn add_rates(&self, ledger: i64, rates: &[ExchangeRate]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        insert_rates(&tx, ledger, rates)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn find_rate(
+        &self,
+        ledger: i64,
+        currency: &str,
+        other: &str,
+        date: &str,
+    ) -> Result<Option<ExchangeRate>, ServerError> {
+        let conn = self.pool.get()?;
+        Ok(conn
+            .query_row(
+                "SELECT BASE, QUOTE, DATE, RATE FROM EXCHANGE_RATE
+                 WHERE LEDGER = ?1 AND DATE <= ?4
+                   AND ((BASE = ?2 AND QUOTE = ?3) OR (BASE = ?3 AND QUOTE = ?2))
+                 ORDER BY DATE DESC, ID DESC
+                 LIMIT 1",
+                params![ledger, currency, other, date],
+                to_rate,
+            )
+            .optional()?)
+    }
+
+    fn get_securities(&self, ledger: i64) -> Result<Vec<Security>, ServerError> {
+        let conn = self.pool.get()?;
+        securities(&conn, ledger)
+    }
+
+    fn add_security(&self, ledger: i64, security: &Security) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        insert_security(&conn, ledger, security)
+    }
+
+    fn record_trade(&self, ledger: i64, trade: &Trade) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        insert_trade(&tx, ledger, trade)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_trades(&self, ledger: i64) -> Result<Vec<Trade>, ServerError> {
+        let conn = self.pool.get()?;
+        trades(&conn, ledger)
+    }
+
+    fn get_prices(&self, ledger: i64) -> Result<Vec<Price>, ServerError> {
+        let conn = self.pool.get()?;
+        prices(&conn, ledger)
+    }
+
+    fn add_prices(&self, ledger: i64, prices: &[Price]) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        insert_prices(&tx, ledger, prices)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn latest_prices(&self, ledger: i64, date: &str) -> Result<Vec<Price>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(
+            "SELECT S.SYMBOL, P.DATE, P.PRICE
+             FROM PRICE P
+                      JOIN SECURITY S ON S.ID = P.SECURITY
+             WHERE S.LEDGER = ?1
+               AND P.DATE = (SELECT MAX(DATE) FROM PRICE WHERE SECURITY = P.SECURITY AND DATE <= ?2)
+             ORDER BY S.SYMBOL",
+        )?;
+        let prices = stmt
+            .query_map(params![ledger, date], to_price)
+            .and_then(Iterator::collect)?;
+
+        Ok(prices)
+    }
+
+    fn get_credit_cards(&self, ledger: i64) -> Result<Vec<CreditCard>, ServerError> {
+        let conn = self.pool.get()?;
+        credit_cards(&conn, ledger)
+    }
+
+    fn set_credit_card(&self, ledger: i64, card: &CreditCard) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        insert_credit_card(&conn, ledger, card)
+    }
+
+    fn balance_changes(
+        &self,
+        ledger: i64,
+        account: &str,
+        since: i64,
+    ) -> Result<Vec<(i64, i64)>, ServerError> {
+        let conn = self.pool.get()?;
+        let (account, _) = find_account(&conn, ledger, account)?
+            .ok_or_else(|| unknown_account("account", account))?;
+        let mut stmt = conn.prepare(
+            "SELECT DATETIME, -AMOUNT FROM OPERATION
+             WHERE FROM_ACCOUNT = ?1 AND DATETIME >= ?2
+             UNION ALL
+             SELECT O.DATETIME, COALESCE(O.TO_AMOUNT, O.AMOUNT) FROM OPERATION O
+             WHERE O.TO_ACCOUNT = ?1 AND O.DATETIME >= ?2
+               AND NOT EXISTS (SELECT 1 FROM OPERATION_LEG L WHERE L.OPERATION = O.ID)
+             UNION ALL
+             SELECT O.DATETIME, L.AMOUNT FROM OPERATION_LEG L
+                      JOIN OPERATION O ON O.ID = L.OPERATION
+             WHERE COALESCE(L.ACCOUNT, O.TO_ACCOUNT) = ?1 AND O.DATETIME >= ?2
+             UNION ALL
+             SELECT DATETIME, CASE SIDE WHEN 'buy' THEN -AMOUNT ELSE AMOUNT END FROM TRADE
+             WHERE ACCOUNT = ?1 AND DATETIME >= ?2
+             ORDER BY 1",
+        )?;
+        let changes = stmt
+            .query_map(params![account, since], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .and_then(Iterator::collect)?;
+
+        Ok(changes)
+    }
+
+    fn operations_since(&self, ledger: i64, since: i64) -> Result<Vec<Operation>, ServerError> {
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM OPERATION O
+                      JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                      JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+             WHERE O.LEDGER = ? AND O.DATETIME >= ?
+             ORDER BY O.DATETIME, O.ID",
+            OPERATION_COLUMNS
+        ))?;
+        let rows = stmt
+            .query_map(params![ledger, since], |row| {
+                Ok((row.get(0)?, to_operation(row)?))
+            })
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<(i64, Operation)>>>)?;
+
+        rows.into_iter()
+            .map(|(id, operation)| with_legs(&conn, id, operation))
+            .collect()
+    }
+
+    fn get_goals(&self, ledger: i64) -> Result<Vec<Goal>, ServerError> {
+        let conn = self.pool.get()?;
+        goals(&conn, ledger)
+    }
+
+    fn set_goal(&self, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        insert_goal(&conn, ledger, goal)
+    }
+
+    fn get_recurring(&self, ledger: i64) -> Result<Vec<RecurringOperation>, ServerError> {
+        let conn = self.pool.get()?;
+        recurring(&conn, ledger)
+    }
+
+    fn set_recurring(&self, ledger: i64, item: &RecurringOperation) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        insert_recurring(&conn, ledger, item)
+    }
+
+    fn get_installment_plans(&self, ledger: i64) -> Result<Vec<InstallmentPlan>, ServerError> {
+        let conn = self.pool.get()?;
+        installment_plans(&conn, ledger)
+    }
+
+    fn add_installment_plan(&self, ledger: i64, plan: &InstallmentPlan) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        insert_installment_plan(&conn, ledger, plan)
+    }
+
+    fn record_repayment(
+        &self,
+        ledger: i64,
+        plan: &str,
+        number: u32,
+        operation: &Operation,
+    ) -> Result<i64, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let updated = tx.execute(
+            "UPDATE INSTALLMENT_PLAN SET PAID = ?
+             WHERE LEDGER = ? AND NAME = ? AND PAID = ? AND TERM >= ?",
+            params![number, ledger, plan, i64::from(number) - 1, number],
+        )?;
+        if updated == 0 {
+            let found: Option<i64> = tx
+                .query_row(
+                    "SELECT ID FROM INSTALLMENT_PLAN WHERE LEDGER = ? AND NAME = ?",
+                    params![ledger, plan],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            return Err(match found {
+                Some(_) => not_next_repayment(plan, number),
+                None => unknown_plan(plan),
+            });
+        }
+        let id = insert_operation(&tx, ledger, operation, ("from", "to", "legs"))?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn share_operation(
+        &self,
+        ledger: i64,
+        operation: i64,
+        expense: &SharedExpense,
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        if !operation_exists(&tx, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+        insert_shared_expense(&tx, operation, expense)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_shared_expenses(&self, ledger: i64) -> Result<Vec<SharedAmount>, ServerError> {
+        let conn = self.pool.get()?;
+        shared_expenses(&conn, ledger)
+    }
+
+    fn set_claim(&self, ledger: i64, operation: i64, claim: &Claim) -> Result<(), ServerError> {
+        let conn = self.pool.get()?;
+        if !operation_exists(&conn, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+        check_claim(operation, claim)?;
+        if let Some(reimbursement) = claim.reimbursement {
+            if !operation_exists(&conn, ledger, reimbursement)? {
+                return Err(unknown_operation_at("reimbursement", reimbursement));
+            }
+        }
+        insert_claim(&conn, operation, claim)
+    }
+
+    fn record_reimbursement(
+        &self,
+        ledger: i64,
+        reimbursement: &Reimbursement,
+    ) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        if !operation_exists(&tx, ledger, reimbursement.operation)? {
+            return Err(unknown_operation_at("operation", reimbursement.operation));
+        }
+        for expense in &reimbursement.expenses {
+            if !operation_exists(&tx, ledger, *expense)? {
+                return Err(unknown_operation_at("expenses", *expense));
+            }
+            insert_claim(&tx, *expense, &paid_by(reimbursement.operation))?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn get_claims(&self, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError> {
+        let conn = self.pool.get()?;
+        claims(&conn, ledger)
+    }
+
+    fn export_ledger(&self, ledger: i64, now: i64) -> Result<LedgerExport, ServerError> {
+        let conn = self.pool.get()?;
+        let settings = settings(&conn, ledger)?;
+        let accounts = accounts(&conn, ledger)?;
+        let categories = categories(&conn, ledger)?;
+        let rates = rates(&conn, ledger)?;
+        let securities = securities(&conn, ledger)?;
+        let trades = trades(&conn, ledger)?;
+        let prices = prices(&conn, ledger)?;
+        let credit_cards = credit_cards(&conn, ledger)?;
+        let installment_plans = installment_plans(&conn, ledger)?;
+        let goals = goals(&conn, ledger)?;
+        let recurring = recurring(&conn, ledger)?;
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}
+             FROM OPERATION O
+                      JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                      JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+             WHERE O.LEDGER = ?
+             ORDER BY O.DATETIME, O.ID",
+            OPERATION_COLUMNS
+        ))?;
+        let rows = stmt
+            .query_map(params![ledger], |row| Ok((row.get(0)?, to_operation(row)?)))
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<(i64, Operation)>>>)?;
+        let ids: Vec<i64> = rows.iter().map(|(id, _)| *id).collect();
+        let operations = rows
+            .into_iter()
+            .map(|(id, operation)| with_legs(&conn, id, operation))
+            .collect::<Result<_, _>>()?;
+        let shared_expenses = export_shares(&ids, shared_expenses(&conn, ledger)?);
+        let claims = export_claims(&ids, claims(&conn, ledger)?);
+
+        Ok(LedgerExport {
+            version: EXPORT_VERSION,
+            exported_at: now,
+            settings,
+            accounts,
+            categories,
+            operations,
+            rates,
+            securities,
+            trades,
+            prices,
+            credit_cards,
+            installment_plans,
+            shared_expenses,
+            claims,
+            goals,
+            recurring,
+        })
+    }
+
+    fn import_ledger(&self, ledger: i64, export: &LedgerExport) -> Result<(), ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+
+        let existing: i64 = tx.query_row(
+            "SELECT (SELECT COUNT(*) FROM ACCOUNT WHERE LEDGER = ?1)
+                  + (SELECT COUNT(*) FROM CATEGORY WHERE LEDGER = ?1)
+                  + (SELECT COUNT(*) FROM OPERATION WHERE LEDGER = ?1)
+                  + (SELECT COUNT(*) FROM SECURITY WHERE LEDGER = ?1)",
+            params![ledger],
+            |row| row.get(0),
+        )?;
+        if existing > 0 {
+            return Err(ServerError::ConflictError(
+                "Only an empty ledger can be imported into.".to_owned(),
+            ));
+        }
+
+        update_settings(&tx, ledger, &export.settings)?;
+        for account in &export.accounts {
+            tx.execute(
+                "INSERT INTO ACCOUNT (LEDGER, NAME, BALANCE, CURRENCY) VALUES (?, ?, 0, ?)",
+                params![ledger, account.name, account.currency],
+            )?;
+        }
+        for category in &export.categories {
+            tx.execute(
+                "INSERT INTO CATEGORY (LEDGER, NAME) VALUES (?, ?)",
+                params![ledger, category.name],
+            )?;
+        }
+        let mut ids = Vec::new();
+        for operation in &export.operations {
+            ids.push(insert_operation(
+                &tx,
+                ledger,
+                operation,
+                ("operations", "operations", "operations"),
+            )?);
+        }
+        insert_rates(&tx, ledger, &export.rates)?;
+        for security in &export.securities {
+            insert_security(&tx, ledger, security)?;
+        }
+        for trade in &export.trades {
+            insert_trade(&tx, ledger, trade).map_err(|e| in_field(e, "trades"))?;
+        }
+        insert_prices(&tx, ledger, &export.prices).map_err(|e| in_field(e, "prices"))?;
+        for card in &export.credit_cards {
+            insert_credit_card(&tx, ledger, card).map_err(|e| in_field(e, "credit_cards"))?;
+        }
+        for goal in &export.goals {
+            insert_goal(&tx, ledger, goal).map_err(|e| in_field(e, "goals"))?;
+        }
+        for item in &export.recurring {
+            insert_recurring(&tx, ledger, item).map_err(|e| in_field(e, "recurring"))?;
+        }
+        for plan in &export.installment_plans {
+            insert_installment_plan(&tx, ledger, plan)
+                .map_err(|e| in_field(e, "installment_plans"))?;
+        }
+        for share in &export.shared_expenses {
+            insert_shared_expense(&tx, ids[share.operation], &share.expense)?;
+        }
+        for claim in &export.claims {
+            let stored = Claim {
+                status: claim.status,
+                reimbursement: claim.reimbursement.map(|i| ids[i]),
+            };
+            insert_claim(&tx, ids[claim.operation], &stored)?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        ledger: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<SearchHit>, ServerError> {
+        // Each term as a quoted prefix query; they're alphanumeric so need no escaping.
+        let query: Vec<String> = terms.iter().map(|term| format!("\"{}\"*", term)).collect();
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, SNIPPET(OPERATION_SEARCH, -1, ?, ?, '…', 12)
+             FROM OPERATION_SEARCH S
+                      JOIN OPERATION O ON O.ID = S.ROWID
+                      JOIN ACCOUNT F ON F.ID = O.FROM_ACCOUNT
+                      JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+             WHERE OPERATION_SEARCH MATCH ? AND S.LEDGER = ?
+             ORDER BY BM25(OPERATION_SEARCH, 2.0, 1.0, 0.0), O.DATETIME DESC
+             LIMIT ?",
+            OPERATION_COLUMNS
+        ))?;
+        let hits = stmt
+            .query_map(
+                params![
+                    MATCH_START.to_string(),
+                    MATCH_END.to_string(),
+                    query.join(" "),
+                    ledger,
+                    limit
+                ],
+                |row| {
+                    Ok(SearchHit {
+                        id: row.get(0)?,
+                        operation: to_operation(row)?,
+                        snippet: highlight(&row.get::<_, String>(8)?),
+                    })
+                },
+            )
+            .and_then(Iterator::collect::<rusqlite::Result<Vec<SearchHit>>>)?
+            .into_iter()
+            .map(|hit| {
+                Ok(SearchHit {
+                    operation: with_legs(&conn, hit.id, hit.operation)?,
+                    ..hit
+                })
+            })
+            .collect::<Result<_, ServerError>>()?;
+
+        Ok(hits)
+    }
+
+    fn add_attachment(
+        &self,
+        ledger: i64,
+        operation: i64,
+        attachment: &NewAttachment,
+        sha256: &str,
+        created_at: i64,
+        content: Option<&[u8]>,
+    ) -> Result<Attachment, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        if !operation_exists(&tx, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+
+        if let Some(content) = content {
+            tx.execute(
+                "INSERT OR IGNORE INTO ATTACHMENT_CONTENT (HASH, CONTENT) VALUES (?, ?)",
+                params![sha256, content],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO ATTACHMENT (LEDGER, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                ledger,
+                operation,
+                attachment.name,
+                attachment.content_type,
+                attachment.size,
+                sha256,
+                created_at
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(Attachment {
+            id,
+            operation,
+            name: attachment.name.clone(),
+            content_type: attachment.content_type.clone(),
+            size: attachment.size,
+            sha256: sha256.to_owned(),
+            created_at,
+        })
+    }
+
+    fn get_attachments(&self, ledger: i64, operation: i64) -> Result<Vec<Attachment>, ServerError> {
+        let conn = self.pool.get()?;
+        if !operation_exists(&conn, ledger, operation)? {
+            return Err(unknown_operation());
+        }
+
+        let mut stmt = conn.prepare(
+            "SELECT ID, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT
+             FROM ATTACHMENT
+             WHERE LEDGER = ? AND OPERATION = ?
+             ORDER BY ID",
+        )?;
+        let attachments = stmt
+            .query_map(params![ledger, operation], to_attachment)
+            .and_then(Iterator::collect)?;
+
+        Ok(attachments)
+    }
+
+    fn get_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<AttachmentContent>, ServerError> {
+        let conn = self.pool.get()?;
+        let attachment = conn
+            .query_row(
+                "SELECT A.ID, A.OPERATION, A.NAME, A.CONTENT_TYPE, A.SIZE, A.HASH, A.CREATED_AT,
+                        C.CONTENT
+                 FROM ATTACHMENT A
+                          LEFT JOIN ATTACHMENT_CONTENT C ON C.HASH = A.HASH
+                 WHERE A.ID = ? AND A.LEDGER = ?",
+                params![id, ledger],
+                |row| Ok((to_attachment(row)?, row.get(7)?)),
+            )
+            .optional()?;
+
+        Ok(attachment)
+    }
+
+    fn delete_attachment(
+        &self,
+        ledger: i64,
+        id: i64,
+    ) -> Result<Option<(Attachment, bool)>, ServerError> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        let attachment = match tx
+            .query_row(
+                "SELECT ID, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT
+                 FROM ATTACHMENT
+                 WHERE ID = ? AND LEDGER = ?",
+                params![id, ledger],
+                to_attachment,
+            )
+            .optional()?
+        {
+            Some(attachment) => attachment,
+            None => return Ok(None),
+        };
+
+        tx.execute("DELETE FROM ATTACHMENT WHERE ID = ?", params![id])?;
+        let shared: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM ATTACHMENT WHERE HASH = ?",
+            params![attachment.sha256],
+            |row| row.get(0),
+        )?;
+        if shared == 0 {
+            tx.execute(
+                "DELETE FROM ATTACHMENT_CONTENT WHERE HASH = ?",
+                params![attachment.sha256],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(Some((attachment, shared > 0)))
+    }
+
+    fn current_db_version(&self) -> Result<Option<DbVersion>, ServerError> {
+        let conn = self.pool.get()?;
+        let latest = applied_migrations(&conn)?
+            .into_iter()
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(version, _, deploy_at)| DbVersion {
+                version: version.to_string(),
+                deploy_at,
+            });
+
+        Ok(latest)
+    }
+}
+
+fn pending_migrations<'a>(
+    conn: &Connection,
+    migrations: &'a [Migration],
+) -> Result<Vec<&'a Migration>, ServerError> {
+    let applied = if table_exists(conn, "MIGRATION")? {
+        applied_migrations(conn)?
+    } else {
+        Vec::new()
+    };
+    // A legacy database is adopted on the first `migrate`, so treat it as already done.
+    let applied: Vec<(Version, String)> = match (applied.is_empty(), legacy_version(conn)?) {
+        (true, Some(legacy)) => migrations
+            .iter()
+            .filter(|m| m.version <= legacy)
+            .map(|m| (m.version.clone(), m.checksum.clone()))
+            .collect(),
+        _ => applied
+            .into_iter()
+            .map(|(version, checksum, _)| (version, checksum))
+            .collect(),
+    };
+
+    migration::pending(&applied, migrations)
+}
+
+fn applied_migrations(conn: &Connection) -> Result<Vec<(Version, String, i64)>, ServerError> {
+    let mut stmt = conn.prepare("SELECT VERSION, CHECKSUM, APPLIED_AT FROM MIGRATION")?;
+    let rows: Vec<(String, String, i64)> = stmt
+        .query_map(NO_PARAMS, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .and_then(Iterator::collect)?;
+
+    rows.into_iter()
+        .map(|(version, checksum, applied_at)| Ok((version.parse()?, checksum, applied_at)))
+        .collect()
+}
+
+fn record_migration(conn: &Connection, migration: &Migration) -> Result<(), ServerError> {
+    conn.execute(
+        "INSERT INTO MIGRATION (VERSION, CHECKSUM, APPLIED_AT)
+         VALUES (?, ?, CAST(STRFTIME('%s','now') AS INTEGER) * 1000)",
+        params![migration.version.to_string(), migration.checksum],
+    )?;
+
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, name: &str) -> Result<bool, ServerError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+        params![name],
+        |row| row.get(0),
+    )?;
+
+    Ok(count > 0)
+}
+
+/// The newest version in `DB_VERSION`, which each migration file used to insert into itself
+/// before `MIGRATION` existed.
+fn legacy_version(conn: &Connection) -> Result<Option<Version>, ServerError> {
+    if !table_exists(conn, "DB_VERSION")? {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare("SELECT VERSION FROM DB_VERSION")?;
+    let versions: Vec<String> = stmt
+        .query_map(NO_PARAMS, |row| row.get(0))
+        .and_then(Iterator::collect)?;
+
+    Ok(versions
+        .iter()
+        .map(|version| version.parse::<Version>())
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .max())
+}
+
+/// Databases created before `MIGRATION` existed only know their versions from `DB_VERSION`.
+/// Those migrations are recorded with the checksums of the files as they are now.
+fn adopt_legacy_versions(
+    conn: &mut Connection,
+    migrations: &[Migration],
+) -> Result<(), ServerError> {
+    let recorded: i64 = conn.query_row("SELECT COUNT(*) FROM MIGRATION", NO_PARAMS, |row| {
+        row.get(0)
+    })?;
+    if recorded > 0 {
+        return Ok(());
+    }
+    let legacy = match legacy_version(conn)? {
+        Some(legacy) => legacy,
+        None => return Ok(()),
+    };
+
+    info!("Adopting legacy database version {}.", legacy);
+    let tx = conn.transaction()?;
+    for migration in migrations.iter().filter(|m| m.version <= legacy) {
+        record_migration(&tx, migration)?;
+    }
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Finds an account of the ledger by name, returning its id and currency.
+fn find_account(
+    conn: &Connection,
+    ledger: i64,
+    name: &str,
+) -> Result<Option<(i64, String)>, ServerError> {
+    Ok(conn
+        .query_row(
+            "SELECT ID, CURRENCY FROM ACCOUNT WHERE LEDGER = ? AND NAME = ?",
+            params![ledger, name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+/// The columns `to_operation` reads, starting with the operation's id.
+const OPERATION_COLUMNS: &str = "O.ID, F.NAME, T.NAME, O.COMMENT, O.AMOUNT, O.DATETIME, \
+                                 O.CURRENCY, O.TO_AMOUNT";
+
+/// Reads an operation without its legs from a row starting with `OPERATION_COLUMNS`.
+fn to_operation(row: &Row) -> rusqlite::Result<Operation> {
+    Ok(Operation {
+        from: row.get(1)?,
+        to: row.get(2)?,
+        comment: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        amount: row.get(4)?,
+        datetime: row.get(5)?,
+        legs: Vec::new(),
+        currency: row.get(6)?,
+        to_amount: row.get(7)?,
+    })
+}
+
+fn settings(conn: &Connection, ledger: i64) -> Result<LedgerSettings, ServerError> {
+    conn.query_row(
+        "SELECT NAME, CURRENCY, TIMEZONE FROM LEDGER WHERE ID = ?",
+        params![ledger],
+        |row| {
+            Ok(LedgerSettings {
+                name: row.get(0)?,
+                currency: row.get(1)?,
+                timezone: row.get(2)?,
+            })
+        },
+    )
+    .optional()?
+    .ok_or_else(|| ServerError::NotFoundError("Ledger doesn't exist.".to_owned()))
+}
+
+fn update_settings(
+    conn: &Connection,
+    ledger: i64,
+    settings: &LedgerSettings,
+) -> Result<(), ServerError> {
+    let updated = conn.execute(
+        "UPDATE LEDGER SET NAME = ?, CURRENCY = ?, TIMEZONE = ? WHERE ID = ?",
+        params![settings.name, settings.currency, settings.timezone, ledger],
+    )?;
+    if updated == 0 {
+        return Err(ServerError::NotFoundError(
+            "Ledger doesn't exist.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn accounts(conn: &Connection, ledger: i64) -> Result<Vec<Account>, ServerError> {
+    let mut stmt =
+        conn.prepare("SELECT NAME, BALANCE, CURRENCY FROM ACCOUNT WHERE LEDGER = ? ORDER BY ID")?;
+    let accounts = stmt
+        .query_map(params![ledger], |row| {
+            Ok(Account {
+                name: row.get(0)?,
+                balance: row.get(1)?,
+                currency: row.get(2)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(accounts)
+}
+
+fn category_id(conn: &Connection, ledger: i64, name: &str) -> Result<Option<i64>, ServerError> {
+    Ok(conn
+        .query_row(
+            "SELECT ID FROM CATEGORY WHERE LEDGER = ? AND NAME = ?",
+            params![ledger, name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn categories(conn: &Connection, ledger: i64) -> Result<Vec<Category>, ServerError> {
+    let mut stmt = conn.prepare("SELECT NAME FROM CATEGORY WHERE LEDGER = ? ORDER BY ID")?;
+    let categories = stmt
+        .query_map(params![ledger], |row| Ok(Category { name: row.get(0)? }))
+        .and_then(Iterator::collect)?;
+
+    Ok(categories)
+}
+
+fn rates(conn: &Connection, ledger: i64) -> Result<Vec<ExchangeRate>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT BASE, QUOTE, DATE, RATE FROM EXCHANGE_RATE
+         WHERE LEDGER = ?
+         ORDER BY BASE, QUOTE, DATE",
+    )?;
+    let rates = stmt
+        .query_map(params![ledger], to_rate)
+        .and_then(Iterator::collect)?;
+
+    Ok(rates)
+}
+
+fn insert_rates(conn: &Connection, ledger: i64, rates: &[ExchangeRate]) -> Result<(), ServerError> {
+    let mut stmt = conn.prepare(
+        "INSERT OR REPLACE INTO EXCHANGE_RATE (LEDGER, BASE, QUOTE, DATE, RATE)
+         VALUES (?, ?, ?, ?, ?)",
+    )?;
+    for rate in rates {
+        stmt.execute(params![ledger, rate.base, rate.quote, rate.date, rate.rate])?;
+    }
+
+    Ok(())
+}
+
+fn to_rate(row: &Row) -> rusqlite::Result<ExchangeRate> {
+    Ok(ExchangeRate {
+        base: row.get(0)?,
+        quote: row.get(1)?,
+        date: row.get(2)?,
+        rate: row.get(3)?,
+    })
+}
+
+fn securities(conn: &Connection, ledger: i64) -> Result<Vec<Security>, ServerError> {
+    let mut stmt =
+        conn.prepare("SELECT SYMBOL, NAME, CURRENCY FROM SECURITY WHERE LEDGER = ? ORDER BY ID")?;
+    let securities = stmt
+        .query_map(params![ledger], |row| {
+            Ok(Security {
+                symbol: row.get(0)?,
+                name: row.get(1)?,
+                currency: row.get(2)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(securities)
+}
+
+fn insert_security(conn: &Connection, ledger: i64, security: &Security) -> Result<(), ServerError> {
+    conn.execute(
+        "INSERT INTO SECURITY (LEDGER, SYMBOL, NAME, CURRENCY) VALUES (?, ?, ?, ?)",
+        params![ledger, security.symbol, security.name, security.currency],
+    )
+    .map_err(|e| match ServerError::from(e) {
+        ServerError::ConflictError(_) => security_exists(&security.symbol),
+        e => e,
+    })?;
+
+    Ok(())
+}
+
+/// Finds a security of the ledger by symbol, returning its id and currency.
+fn find_security(
+    conn: &Connection,
+    ledger: i64,
+    symbol: &str,
+) -> Result<Option<(i64, String)>, ServerError> {
+    Ok(conn
+        .query_row(
+            "SELECT ID, CURRENCY FROM SECURITY WHERE LEDGER = ? AND SYMBOL = ?",
+            params![ledger, symbol],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+/// Inserts the trade after `check_trade` and moves its amount into or out of the account.
+fn insert_trade(conn: &Connection, ledger: i64, trade: &Trade) -> Result<(), ServerError> {
+    let (account, account_currency) = find_account(conn, ledger, &trade.account)?
+        .ok_or_else(|| unknown_account("account", &trade.account))?;
+    let (security, security_currency) = find_security(conn, ledger, &trade.symbol)?
+        .ok_or_else(|| unknown_security("symbol", &trade.symbol))?;
+    let (held, latest): (i64, Option<i64>) = conn.query_row(
+        "SELECT COALESCE(SUM(CASE SIDE WHEN 'buy' THEN QUANTITY ELSE -QUANTITY END), 0),
+                MAX(DATETIME)
+         FROM TRADE
+         WHERE ACCOUNT = ? AND SECURITY = ?",
+        params![account, security],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    check_trade(trade, &account_currency, &security_currency, held, latest)?;
+
+    conn.execute(
+        "INSERT INTO TRADE (LEDGER, ACCOUNT, SECURITY, SIDE, QUANTITY, AMOUNT, DATETIME, COMMENT)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ledger,
+            account,
+            security,
+            trade.side.as_str(),
+            trade.quantity,
+            trade.amount,
+            trade.datetime,
+            trade.comment
+        ],
+    )?;
+    update_balance(conn, account, trade.signed_amount())
+}
+
+fn trades(conn: &Connection, ledger: i64) -> Result<Vec<Trade>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT A.NAME, S.SYMBOL, T.SIDE, T.QUANTITY, T.AMOUNT, T.DATETIME, T.COMMENT
+         FROM TRADE T
+                  JOIN ACCOUNT A ON A.ID = T.ACCOUNT
+                  JOIN SECURITY S ON S.ID = T.SECURITY
+         WHERE T.LEDGER = ?
+         ORDER BY T.DATETIME, T.ID",
+    )?;
+    let trades = stmt
+        .query_map(params![ledger], |row| {
+            Ok(Trade {
+                account: row.get(0)?,
+                symbol: row.get(1)?,
+                side: row.get::<_, String>(2)?.parse().map_err(|e: String| {
+                    rusqlite::Error::FromSqlConversionFailure(2, Type::Text, e.into())
+                })?,
+                quantity: row.get(3)?,
+                amount: row.get(4)?,
+                datetime: row.get(5)?,
+                comment: row.get(6)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(trades)
+}
+
+fn prices(conn: &Connection, ledger: i64) -> Result<Vec<Price>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT S.SYMBOL, P.DATE, P.PRICE
+         FROM PRICE P
+                  JOIN SECURITY S ON S.ID = P.SECURITY
+         WHERE S.LEDGER = ?
+         ORDER BY S.SYMBOL, P.DATE",
+    )?;
+    let prices = stmt
+        .query_map(params![ledger], to_price)
+        .and_then(Iterator::collect)?;
+
+    Ok(prices)
+}
+
+fn insert_prices(conn: &Connection, ledger: i64, prices: &[Price]) -> Result<(), ServerError> {
+    let mut stmt =
+        conn.prepare("INSERT OR REPLACE INTO PRICE (SECURITY, DATE, PRICE) VALUES (?, ?, ?)")?;
+    for price in prices {
+        let (security, _) = find_security(conn, ledger, &price.symbol)?
+            .ok_or_else(|| unknown_security("symbol", &price.symbol))?;
+        stmt.execute(params![security, price.date, price.price])?;
+    }
+
+    Ok(())
+}
+
+fn to_price(row: &Row) -> rusqlite::Result<Price> {
+    Ok(Price {
+        symbol: row.get(0)?,
+        date: row.get(1)?,
+        price: row.get(2)?,
+    })
+}
+
+fn credit_cards(conn: &Connection, ledger: i64) -> Result<Vec<CreditCard>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT A.NAME, C.STATEMENT_DAY, C.DUE_DAY, C.CREDIT_LIMIT, P.NAME
+         FROM CREDIT_CARD C
+                  JOIN ACCOUNT A ON A.ID = C.ACCOUNT
+                  LEFT JOIN ACCOUNT P ON P.ID = C.PAY_FROM
+         WHERE A.LEDGER = ?
+         ORDER BY A.ID",
+    )?;
+    let cards = stmt
+        .query_map(params![ledger], |row| {
+            Ok(CreditCard {
+                account: row.get(0)?,
+                statement_day: row.get(1)?,
+                due_day: row.get(2)?,
+                limit: row.get(3)?,
+                pay_from: row.get(4)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(cards)
+}
+
+fn insert_credit_card(
+    conn: &Connection,
+    ledger: i64,
+    card: &CreditCard,
+) -> Result<(), ServerError> {
+    let (account, currency) = find_account(conn, ledger, &card.account)?
+        .ok_or_else(|| unknown_account("account", &card.account))?;
+    let pay_from = match &card.pay_from {
+        Some(name) => Some(
+            find_account(conn, ledger, name)?.ok_or_else(|| unknown_account("pay_from", name))?,
+        ),
+        None => None,
+    };
+    check_card(
+        card,
+        &currency,
+        pay_from.as_ref().map(|(_, currency)| currency.as_str()),
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO CREDIT_CARD (ACCOUNT, STATEMENT_DAY, DUE_DAY, CREDIT_LIMIT, PAY_FROM)
+         VALUES (?, ?, ?, ?, ?)",
+        params![
+            account,
+            card.statement_day,
+            card.due_day,
+            card.limit,
+            pay_from.map(|(id, _)| id)
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn goals(conn: &Connection, ledger: i64) -> Result<Vec<Goal>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT G.NAME, A.NAME, G.TARGET, G.TARGET_DATE
+         FROM GOAL G
+                  JOIN ACCOUNT A ON A.ID = G.ACCOUNT
+         WHERE G.LEDGER = ?
+         ORDER BY G.TARGET_DATE, G.NAME",
+    )?;
+    let goals = stmt
+        .query_map(params![ledger], |row| {
+            Ok(Goal {
+                name: row.get(0)?,
+                account: row.get(1)?,
+                target: row.get(2)?,
+                target_date: row.get(3)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(goals)
+}
+
+fn insert_goal(conn: &Connection, ledger: i64, goal: &Goal) -> Result<(), ServerError> {
+    let (account, _) = find_account(conn, ledger, &goal.account)?
+        .ok_or_else(|| unknown_account("account", &goal.account))?;
+    let other: Option<String> = conn
+        .query_row(
+            "SELECT NAME FROM GOAL WHERE ACCOUNT = ? AND NAME <> ?",
+            params![account, goal.name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(other) = other {
+        return Err(account_has_goal(&goal.account, &other));
+    }
+    conn.execute(
+        "INSERT INTO GOAL (LEDGER, NAME, ACCOUNT, TARGET, TARGET_DATE) VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT (LEDGER, NAME) DO UPDATE
+             SET ACCOUNT = EXCLUDED.ACCOUNT,
+                 TARGET = EXCLUDED.TARGET,
+                 TARGET_DATE = EXCLUDED.TARGET_DATE",
+        params![ledger, goal.name, account, goal.target, goal.target_date],
+    )?;
+
+    Ok(())
+}
+
+fn recurring(conn: &Connection, ledger: i64) -> Result<Vec<RecurringOperation>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT R.NAME, F.NAME, T.NAME, R.AMOUNT, R.FREQUENCY, R.START_DATE, R.END_DATE, C.NAME
+         FROM RECURRING_OPERATION R
+                  JOIN ACCOUNT F ON F.ID = R.FROM_ACCOUNT
+                  JOIN ACCOUNT T ON T.ID = R.TO_ACCOUNT
+                  LEFT JOIN CATEGORY C ON C.ID = R.CATEGORY
+         WHERE R.LEDGER = ?
+         ORDER BY R.NAME",
+    )?;
+    let recurring = stmt
+        .query_map(params![ledger], |row| {
+            Ok(RecurringOperation {
+                name: row.get(0)?,
+                from: row.get(1)?,
+                to: row.get(2)?,
+                amount: row.get(3)?,
+                frequency: row.get::<_, String>(4)?.parse().map_err(|e: String| {
+                    rusqlite::Error::FromSqlConversionFailure(4, Type::Text, e.into())
+                })?,
+                start: row.get(5)?,
+                end: row.get(6)?,
+                category: row.get(7)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(recurring)
+}
+
+fn insert_recurring(
+    conn: &Connection,
+    ledger: i64,
+    item: &RecurringOperation,
+) -> Result<(), ServerError> {
+    let (from, from_currency) = find_account(conn, ledger, &item.from)?
+        .ok_or_else(|| unknown_account("from", &item.from))?;
+    let (to, to_currency) =
+        find_account(conn, ledger, &item.to)?.ok_or_else(|| unknown_account("to", &item.to))?;
+    check_recurring(item, &from_currency, &to_currency)?;
+    let category = match &item.category {
+        Some(name) => Some(
+            category_id(conn, ledger, name)?.ok_or_else(|| unknown_category("category", name))?,
+        ),
+        None => None,
+    };
+    conn.execute(
+        "INSERT INTO RECURRING_OPERATION
+             (LEDGER, NAME, FROM_ACCOUNT, TO_ACCOUNT, CATEGORY, AMOUNT, FREQUENCY, START_DATE,
+              END_DATE)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT (LEDGER, NAME) DO UPDATE
+             SET FROM_ACCOUNT = EXCLUDED.FROM_ACCOUNT,
+                 TO_ACCOUNT = EXCLUDED.TO_ACCOUNT,
+                 CATEGORY = EXCLUDED.CATEGORY,
+                 AMOUNT = EXCLUDED.AMOUNT,
+                 FREQUENCY = EXCLUDED.FREQUENCY,
+                 START_DATE = EXCLUDED.START_DATE,
+                 END_DATE = EXCLUDED.END_DATE",
+        params![
+            ledger,
+            item.name,
+            from,
+            to,
+            category,
+            item.amount,
+            item.frequency.as_str(),
+            item.start,
+            item.end
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn installment_plans(conn: &Connection, ledger: i64) -> Result<Vec<InstallmentPlan>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT P.NAME, A.NAME, F.NAME, C.NAME, P.PRINCIPAL, P.ANNUAL_RATE, P.FEE, P.TERM,
+                P.FIRST_DUE, P.PAID
+         FROM INSTALLMENT_PLAN P
+                  JOIN ACCOUNT A ON A.ID = P.ACCOUNT
+                  JOIN ACCOUNT F ON F.ID = P.FROM_ACCOUNT
+                  LEFT JOIN ACCOUNT C ON C.ID = P.COST_ACCOUNT
+         WHERE P.LEDGER = ?
+         ORDER BY P.ID",
+    )?;
+    let plans = stmt
+        .query_map(params![ledger], |row| {
+            Ok(InstallmentPlan {
+                name: row.get(0)?,
+                account: row.get(1)?,
+                from: row.get(2)?,
+                cost_account: row.get(3)?,
+                principal: row.get(4)?,
+                annual_rate: row.get(5)?,
+                fee: row.get(6)?,
+                term: row.get(7)?,
+                first_due: row.get(8)?,
+                paid: row.get(9)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(plans)
+}
+
+/// Inserts the plan after `check_plan`.
+fn insert_installment_plan(
+    conn: &Connection,
+    ledger: i64,
+    plan: &InstallmentPlan,
+) -> Result<(), ServerError> {
+    let (account, account_currency) = find_account(conn, ledger, &plan.account)?
+        .ok_or_else(|| unknown_account("account", &plan.account))?;
+    let (from, from_currency) = find_account(conn, ledger, &plan.from)?
+        .ok_or_else(|| unknown_account("from", &plan.from))?;
+    let cost = match &plan.cost_account {
+        Some(name) => Some(
+            find_account(conn, ledger, name)?
+                .ok_or_else(|| unknown_account("cost_account", name))?,
+        ),
+        None => None,
+    };
+    check_plan(
+        plan,
+        &account_currency,
+        &from_currency,
+        cost.as_ref().map(|(_, currency)| currency.as_str()),
+    )?;
+
+    conn.execute(
+        "INSERT INTO INSTALLMENT_PLAN (LEDGER, NAME, ACCOUNT, FROM_ACCOUNT, COST_ACCOUNT,
+                                       PRINCIPAL, ANNUAL_RATE, FEE, TERM, FIRST_DUE, PAID)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ledger,
+            plan.name,
+            account,
+            from,
+            cost.map(|(id, _)| id),
+            plan.principal,
+            plan.annual_rate,
+            plan.fee,
+            plan.term,
+            plan.first_due,
+            plan.paid
+        ],
+    )
+    .map_err(|e| match ServerError::from(e) {
+        ServerError::ConflictError(_) => plan_exists(&plan.name),
+        e => e,
+    })?;
+
+    Ok(())
+}
+
+fn shared_expenses(conn: &Connection, ledger: i64) -> Result<Vec<SharedAmount>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT O.ID, O.AMOUNT, O.CURRENCY, S.PAID_BY, S.METHOD
+         FROM SHARED_EXPENSE S
+                  JOIN OPERATION O ON O.ID = S.OPERATION
+         WHERE O.LEDGER = ?
+         ORDER BY O.DATETIME, O.ID",
+    )?;
+    let mut shares =
+        conn.prepare("SELECT MEMBER, SHARE FROM EXPENSE_SHARE WHERE OPERATION = ? ORDER BY ID")?;
+    let rows = stmt
+        .query_map(params![ledger], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get::<_, String>(4)?.parse().map_err(|e: String| {
+                    rusqlite::Error::FromSqlConversionFailure(4, Type::Text, e.into())
+                })?,
+            ))
+        })
+        .and_then(Iterator::collect::<rusqlite::Result<Vec<_>>>)?;
+    rows.into_iter()
+        .map(|(operation, amount, currency, paid_by, method)| {
+            let shares = shares
+                .query_map(params![operation], |row| {
+                    Ok(MemberShare {
+                        member: row.get(0)?,
+                        share: row.get(1)?,
+                    })
+                })
+                .and_then(Iterator::collect)?;
+            Ok(SharedAmount {
+                operation,
+                amount,
+                currency,
+                expense: SharedExpense {
+                    paid_by,
+                    method,
+                    shares,
+                },
+            })
+        })
+        .collect()
+}
+
+fn claims(conn: &Connection, ledger: i64) -> Result<Vec<ClaimedExpense>, ServerError> {
+    let mut stmt = conn.prepare(
+        "SELECT O.ID, O.DATETIME, T.NAME, O.COMMENT, O.AMOUNT, O.CURRENCY, C.STATUS,
+                C.REIMBURSEMENT
+         FROM CLAIM C
+                  JOIN OPERATION O ON O.ID = C.OPERATION
+                  JOIN ACCOUNT T ON T.ID = O.TO_ACCOUNT
+         WHERE O.LEDGER = ?
+         ORDER BY O.DATETIME, O.ID",
+    )?;
+    let claims = stmt
+        .query_map(params![ledger], |row| {
+            Ok(ClaimedExpense {
+                operation: row.get(0)?,
+                datetime: row.get(1)?,
+                to: row.get(2)?,
+                comment: row.get(3)?,
+                amount: row.get(4)?,
+                currency: row.get(5)?,
+                status: row.get::<_, String>(6)?.parse().map_err(|e: String| {
+                    rusqlite::Error::FromSqlConversionFailure(6, Type::Text, e.into())
+                })?,
+                reimbursement: row.get(7)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(claims)
+}
+
+fn insert_claim(conn: &Connection, operation: i64, claim: &Claim) -> Result<(), ServerError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO CLAIM (OPERATION, STATUS, REIMBURSEMENT) VALUES (?, ?, ?)",
+        params![operation, claim.status.as_str(), claim.reimbursement],
+    )?;
+
+    Ok(())
+}
+
+/// Replaces how the operation is shared.
+fn insert_shared_expense(
+    conn: &Connection,
+    operation: i64,
+    expense: &SharedExpense,
+) -> Result<(), ServerError> {
+    conn.execute(
+        "DELETE FROM EXPENSE_SHARE WHERE OPERATION = ?",
+        params![operation],
+    )?;
+    conn.execute(
+        "DELETE FROM SHARED_EXPENSE WHERE OPERATION = ?",
+        params![operation],
+    )?;
+    conn.execute(
+        "INSERT INTO SHARED_EXPENSE (OPERATION, PAID_BY, METHOD) VALUES (?, ?, ?)",
+        params![operation, expense.paid_by, expense.method.as_str()],
+    )?;
+    for share in &expense.shares {
+        conn.execute(
+            "INSERT INTO EXPENSE_SHARE (OPERATION, MEMBER, SHARE) VALUES (?, ?, ?)",
+            params![operation, share.member, share.share],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Inserts the operation with its legs and moves its amount from `from` to the accounts it
+/// credits, all resolved within the ledger, returning its id. `fields` name the inputs to blame
+/// for an unknown `from`, `to` or leg account or category.
+fn insert_operation(
+    conn: &Connection,
+    ledger: i64,
+    operation: &Operation,
+    fields: (&str, &str, &str),
+) -> Result<i64, ServerError> {
+    let (from, from_currency) = find_account(conn, ledger, &operation.from)?
+        .ok_or_else(|| unknown_account(fields.0, &operation.from))?;
+    let (to, to_currency) = find_account(conn, ledger, &operation.to)?
+        .ok_or_else(|| unknown_account(fields.1, &operation.to))?;
+    let mut legs = Vec::new();
+    for leg in &operation.legs {
+        let account = match &leg.account {
+            Some(name) => Some(
+                find_account(conn, ledger, name)?.ok_or_else(|| unknown_account(fields.2, name))?,
+            ),
+            None => None,
+        };
+        let category = match &leg.category {
+            Some(name) => Some(
+                category_id(conn, ledger, name)?.ok_or_else(|| unknown_category(fields.2, name))?,
+            ),
+            None => None,
+        };
+        legs.push((leg, account, category));
+    }
+    let leg_currencies: Vec<Option<&str>> = legs
+        .iter()
+        .map(|(_, account, _)| account.as_ref().map(|(_, currency)| currency.as_str()))
+        .collect();
+    let currency = validate_currencies(operation, &from_currency, &to_currency, &leg_currencies)?;
+
+    conn.execute(
+        "INSERT INTO OPERATION
+             (LEDGER, FROM_ACCOUNT, TO_ACCOUNT, COMMENT, AMOUNT, DATETIME, CURRENCY, TO_AMOUNT)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            ledger,
+            from,
+            to,
+            operation.comment,
+            operation.amount,
+            operation.datetime,
+            currency,
+            operation.to_amount
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    update_balance(conn, from, -operation.amount)?;
+
+    if legs.is_empty() {
+        update_balance(conn, to, operation.to_amount.unwrap_or(operation.amount))?;
+    }
+    for (leg, account, category) in legs {
+        let account = account.map(|(id, _)| id);
+        conn.execute(
+            "INSERT INTO OPERATION_LEG (OPERATION, ACCOUNT, CATEGORY, AMOUNT, MEMO)
+             VALUES (?, ?, ?, ?, ?)",
+            params![id, account, category, leg.amount, leg.memo],
+        )?;
+        update_balance(conn, account.unwrap_or(to), leg.amount)?;
+    }
+
+    Ok(id)
+}
+
+/// Fills in the legs of the operation stored under `id`.
+fn with_legs(conn: &Connection, id: i64, operation: Operation) -> Result<Operation, ServerError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT A.NAME, C.NAME, L.AMOUNT, L.MEMO
+         FROM OPERATION_LEG L
+                  LEFT JOIN ACCOUNT A ON A.ID = L.ACCOUNT
+                  LEFT JOIN CATEGORY C ON C.ID = L.CATEGORY
+         WHERE L.OPERATION = ?
+         ORDER BY L.ID",
+    )?;
+    let legs = stmt
+        .query_map(params![id], |row| {
+            Ok(Leg {
+                account: row.get(0)?,
+                category: row.get(1)?,
+                amount: row.get(2)?,
+                memo: row.get(3)?,
+            })
+        })
+        .and_then(Iterator::collect)?;
+
+    Ok(Operation { legs, ..operation })
+}
+
+fn operation_exists(conn: &Connection, ledger: i64, operation: i64) -> Result<bool, ServerError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM OPERATION WHERE ID = ? AND LEDGER = ?",
+        params![operation, ledger],
+        |row| row.get(0),
+    )?;
+
+    Ok(count > 0)
+}
+
+fn to_attachment(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        operation: row.get(1)?,
+        name: row.get(2)?,
+        content_type: row.get(3)?,
+        size: row.get(4)?,
+        sha256: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+fn update_balance(conn: &Connection, account: i64, delta: i64) -> Result<(), ServerError> {
+    let rows_updated = conn.execute(
+        "UPDATE ACCOUNT SET BALANCE = BALANCE + ? WHERE ID = ?",
+        params![delta, account],
+    )?;
+
+    if rows_updated != 1 {
+        return Err(ServerError::DBError(
+            "Wrong number of lines updated.".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn parse_role(role: String) -> rusqlite::Result<Role> {
+    role.parse()
+        .map_err(|e: String| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.into()))
+}
@@ -1,28 +1,127 @@
-use actix_web::error;
+use actix_web::error::{self, BlockingError, JsonPayloadError};
 use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use entities::validation::ValidationError;
+use entities::ErrorResponse;
+use postgres::error::SqlState;
+use rusqlite::ErrorCode;
 use std::fmt::Formatter;
 use std::{ffi, fmt, io};
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum ServerError {
     DBError(String),
     IOError(io::Error),
     InternalError(String),
+    MigrationError(String),
     UnauthorizedError,
+    ForbiddenError,
+    BadRequestError(String),
+    ValidationError { field: String, message: String },
+    NotFoundError(String),
+    ConflictError(String),
+}
+
+impl ServerError {
+    pub fn validation(field: &str, message: impl Into<String>) -> Self {
+        ServerError::ValidationError {
+            field: field.to_owned(),
+            message: message.into(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            ServerError::UnauthorizedError => "unauthorized",
+            ServerError::ForbiddenError => "forbidden",
+            ServerError::BadRequestError(_) => "bad_request",
+            ServerError::ValidationError { .. } => "validation",
+            ServerError::NotFoundError(_) => "not_found",
+            ServerError::ConflictError(_) => "conflict",
+            ServerError::DBError(_)
+            | ServerError::IOError(_)
+            | ServerError::InternalError(_)
+            | ServerError::MigrationError(_) => "internal",
+        }
+    }
 }
 
 impl error::ResponseError for ServerError {
     fn status_code(&self) -> StatusCode {
         match self {
             ServerError::UnauthorizedError => StatusCode::UNAUTHORIZED,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            ServerError::ForbiddenError => StatusCode::FORBIDDEN,
+            ServerError::BadRequestError(_) => StatusCode::BAD_REQUEST,
+            ServerError::ValidationError { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ServerError::NotFoundError(_) => StatusCode::NOT_FOUND,
+            ServerError::ConflictError(_) => StatusCode::CONFLICT,
+            ServerError::DBError(_)
+            | ServerError::IOError(_)
+            | ServerError::InternalError(_)
+            | ServerError::MigrationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        // Internal details stay in the log rather than going out to the browser.
+        let message = if status.is_server_error() {
+            error!("{}", self);
+            "Internal server error.".to_owned()
+        } else {
+            self.to_string()
+        };
+        let field = match self {
+            ServerError::ValidationError { field, .. } => Some(field.clone()),
+            _ => None,
+        };
+
+        HttpResponse::build(status).json(ErrorResponse {
+            code: self.code().to_owned(),
+            message,
+            field,
+        })
+    }
 }
 
 impl fmt::Display for ServerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            ServerError::DBError(e) => write!(f, "Database error: {}", e),
+            ServerError::IOError(e) => write!(f, "IO error: {}", e),
+            ServerError::InternalError(e) => write!(f, "Internal error: {}", e),
+            ServerError::MigrationError(e) => write!(f, "Migration error: {}", e),
+            ServerError::UnauthorizedError => write!(f, "Authentication required."),
+            ServerError::ForbiddenError => write!(f, "You're not allowed to do this."),
+            ServerError::BadRequestError(message)
+            | ServerError::ValidationError { message, .. }
+            | ServerError::NotFoundError(message)
+            | ServerError::ConflictError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<BlockingError<ServerError>> for ServerError {
+    fn from(e: BlockingError<ServerError>) -> Self {
+        match e {
+            BlockingError::Error(e) => e,
+            BlockingError::Canceled => {
+                ServerError::InternalError("Blocking task was canceled.".to_owned())
+            }
+        }
+    }
+}
+
+impl From<ValidationError> for ServerError {
+    fn from(e: ValidationError) -> Self {
+        ServerError::validation(e.field, e.message)
+    }
+}
+
+impl From<JsonPayloadError> for ServerError {
+    fn from(e: JsonPayloadError) -> Self {
+        ServerError::BadRequestError(e.to_string())
     }
 }
 
@@ -40,7 +139,29 @@ impl From<r2d2::Error> for ServerError {
 
 impl From<rusqlite::Error> for ServerError {
     fn from(e: rusqlite::Error) -> Self {
-        ServerError::DBError(e.to_string())
+        match e {
+            rusqlite::Error::SqliteFailure(failure, message)
+                if failure.code == ErrorCode::ConstraintViolation =>
+            {
+                ServerError::ConflictError(
+                    message.unwrap_or_else(|| "Conflicts with existing data.".to_owned()),
+                )
+            }
+            e => ServerError::DBError(e.to_string()),
+        }
+    }
+}
+
+impl From<postgres::Error> for ServerError {
+    fn from(e: postgres::Error) -> Self {
+        match e.code() {
+            Some(code) if *code == SqlState::UNIQUE_VIOLATION => ServerError::ConflictError(
+                e.as_db_error()
+                    .map(|e| e.message().to_owned())
+                    .unwrap_or_else(|| "Conflicts with existing data.".to_owned()),
+            ),
+            _ => ServerError::DBError(e.to_string()),
+        }
     }
 }
 
@@ -62,3 +183,39 @@ impl From<ffi::OsString> for ServerError {
         ServerError::InternalError(format!("{:?}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::Body;
+    use actix_web::ResponseError;
+    use rusqlite::{Connection, NO_PARAMS};
+
+    #[test]
+    fn test_constraint_violation_is_conflict() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE T (NAME TEXT UNIQUE); INSERT INTO T VALUES ('a');")
+            .unwrap();
+        let e = ServerError::from(
+            conn.execute("INSERT INTO T VALUES ('a')", NO_PARAMS)
+                .unwrap_err(),
+        );
+
+        assert_eq!(e.status_code(), StatusCode::CONFLICT);
+        assert_eq!(e.code(), "conflict");
+    }
+
+    #[test]
+    fn test_internal_errors_are_not_exposed() {
+        let e = ServerError::DBError("no such table: SECRET".to_owned());
+        let resp = e.error_response();
+        let body = match resp.body().as_ref() {
+            Some(Body::Bytes(bytes)) => String::from_utf8_lossy(bytes).into_owned(),
+            _ => String::new(),
+        };
+
+        assert_eq!(e.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("\"code\":\"internal\""));
+        assert!(!body.contains("SECRET"));
+    }
+}
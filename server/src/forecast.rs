@@ -0,0 +1,614 @@
+use crate::card;
+use crate::installment::add_months;
+use chrono::{Datelike, Duration, NaiveDate};
+use entities::time::Tz;
+use entities::{
+    Account, AccountForecast, AverageSpending, CardStatement, CreditCard, Frequency, Installment,
+    InstallmentPlan, Operation, RecurringOperation, Timestamp,
+};
+use std::collections::{BTreeMap, HashSet};
+
+/// How many months back spending is averaged over.
+const HISTORY_MONTHS: i32 = 3;
+
+/// A change to an account's balance expected on a day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flow {
+    pub date: NaiveDate,
+    pub account: String,
+    pub amount: i64,
+}
+
+/// The first day spending is averaged from.
+pub fn history_since(today: NaiveDate) -> NaiveDate {
+    add_months(today, -HISTORY_MONTHS)
+}
+
+/// How the operations move balances on the days they're dated in `timezone`.
+pub fn scheduled(operations: &[Operation], timezone: Tz) -> Vec<Flow> {
+    let mut flows = Vec::new();
+    for operation in operations {
+        let date = Timestamp::from_millis(operation.datetime).local_date(timezone);
+        flows.push(Flow {
+            date,
+            account: operation.from.clone(),
+            amount: -operation.amount,
+        });
+        for (account, amount) in operation.credits() {
+            flows.push(Flow {
+                date,
+                account: account.to_owned(),
+                amount,
+            });
+        }
+    }
+
+    flows
+}
+
+/// The days from `first` through `last` the item falls on, counted from its start date so a
+/// monthly item started on the 31st stays at the end of every month.
+pub fn occurrences(item: &RecurringOperation, first: NaiveDate, last: NaiveDate) -> Vec<NaiveDate> {
+    let start =
+        NaiveDate::parse_from_str(&item.start, "%Y-%m-%d").expect("start dates are validated");
+    let end = item
+        .end
+        .as_ref()
+        .map(|end| NaiveDate::parse_from_str(end, "%Y-%m-%d").expect("end dates are validated"))
+        .map_or(last, |end| end.min(last));
+    // Skip whole periods before `first` rather than stepping through years of history.
+    let skipped = match item.frequency {
+        Frequency::Weekly => (first - start).num_days() / 7,
+        Frequency::Monthly => {
+            i64::from(first.year() - start.year()) * 12 + i64::from(first.month())
+                - i64::from(start.month())
+                - 1
+        }
+        Frequency::Yearly => i64::from(first.year() - start.year()) - 1,
+    }
+    .max(0) as i32;
+    let nth = |n: i32| match item.frequency {
+        Frequency::Weekly => start + Duration::weeks(i64::from(n)),
+        Frequency::Monthly => add_months(start, n),
+        Frequency::Yearly => add_months(start, n * 12),
+    };
+
+    (skipped..)
+        .map(nth)
+        .take_while(|date| *date <= end)
+        .filter(|date| *date >= first)
+        .collect()
+}
+
+/// How the recurring operations move balances on each of their occurrences from `first`
+/// through `last`.
+pub fn recurring(items: &[RecurringOperation], first: NaiveDate, last: NaiveDate) -> Vec<Flow> {
+    let mut flows = Vec::new();
+    for item in items {
+        for date in occurrences(item, first, last) {
+            flows.push(Flow {
+                date,
+                account: item.from.clone(),
+                amount: -item.amount,
+            });
+            flows.push(Flow {
+                date,
+                account: item.to.clone(),
+                amount: item.amount,
+            });
+        }
+    }
+
+    flows
+}
+
+/// The repayments of the plan still to be recorded, with those overdue falling on `first`.
+pub fn repayments(
+    plan: &InstallmentPlan,
+    installments: &[Installment],
+    first: NaiveDate,
+) -> Vec<Flow> {
+    let mut flows = Vec::new();
+    for installment in installments.iter().filter(|i| !i.paid) {
+        let due = NaiveDate::parse_from_str(&installment.due_date, "%Y-%m-%d")
+            .expect("due dates are formatted by schedule");
+        let date = due.max(first);
+        let cost = installment.interest + installment.fee;
+        let mut flow = |account: &str, amount: i64| {
+            flows.push(Flow {
+                date,
+                account: account.to_owned(),
+                amount,
+            })
+        };
+        flow(&plan.from, -(installment.principal + cost));
+        flow(&plan.account, installment.principal);
+        if let (Some(cost_account), true) = (&plan.cost_account, cost > 0) {
+            flow(cost_account, cost);
+        }
+    }
+
+    flows
+}
+
+/// What each account spent a month on each category in `history`, the operations since
+/// `history_since`. Categories that `scheduled` operations or `recurring` ones already pay for
+/// are left out, so they aren't counted twice.
+pub fn average_spending(
+    history: &[Operation],
+    scheduled: &[Operation],
+    recurring: &[RecurringOperation],
+    accounts: &[Account],
+) -> Vec<AverageSpending> {
+    let planned: HashSet<&str> = scheduled
+        .iter()
+        .flat_map(|operation| &operation.legs)
+        .filter_map(|leg| leg.category.as_deref())
+        .chain(recurring.iter().filter_map(|item| item.category.as_deref()))
+        .collect();
+    let mut totals: BTreeMap<(&str, &str), i64> = BTreeMap::new();
+    for operation in history {
+        for leg in &operation.legs {
+            if let Some(category) = leg.category.as_deref() {
+                if !planned.contains(category) {
+                    *totals.entry((&operation.from, category)).or_default() += leg.amount;
+                }
+            }
+        }
+    }
+
+    totals
+        .into_iter()
+        .filter(|(_, total)| *total > 0)
+        .filter_map(|((account, category), total)| {
+            let currency = &accounts.iter().find(|a| a.name == account)?.currency;
+            Some(AverageSpending {
+                account: account.to_owned(),
+                category: category.to_owned(),
+                currency: currency.clone(),
+                monthly: total / i64::from(HISTORY_MONTHS),
+            })
+        })
+        .collect()
+}
+
+/// Spends each average from its account at the pace it was spent at, a day at a time from
+/// tomorrow through `last`. What doesn't divide evenly is carried to the next day.
+pub fn spending_flows(
+    spending: &[AverageSpending],
+    today: NaiveDate,
+    last: NaiveDate,
+) -> Vec<Flow> {
+    let tomorrow = today + Duration::days(1);
+    let history_days = i128::from((tomorrow - history_since(today)).num_days());
+    let days = (last - today).num_days();
+    let mut flows = Vec::new();
+    for average in spending {
+        let total = i128::from(average.monthly) * i128::from(HISTORY_MONTHS);
+        let spent_by = |day: i64| (total * i128::from(day) / history_days) as i64;
+        for day in 1..=days {
+            let amount = spent_by(day) - spent_by(day - 1);
+            if amount != 0 {
+                flows.push(Flow {
+                    date: today + Duration::days(day),
+                    account: average.account.clone(),
+                    amount: -amount,
+                });
+            }
+        }
+    }
+
+    flows
+}
+
+/// A card statement to be paid from `from` on `due`.
+struct Payment {
+    card: usize,
+    from: usize,
+    due: NaiveDate,
+    amount: i64,
+}
+
+/// Projects each account's balance at the end of every day from tomorrow through `last`.
+///
+/// `accounts` hold today's balances, which already include the `scheduled` operations dated
+/// after today; those are taken back out and moved in on their days, like the `expected`
+/// flows. `cards` are those with a `CreditCard::pay_from`, each with its latest statement:
+/// what's left of it is paid in full on its due date, or tomorrow if that has passed, and
+/// every statement closing from today on is paid the same way when it falls due.
+pub fn forecast(
+    today: NaiveDate,
+    last: NaiveDate,
+    accounts: &[Account],
+    scheduled: &[Flow],
+    expected: &[Flow],
+    cards: &[(CreditCard, CardStatement)],
+) -> Vec<AccountForecast> {
+    let index = |name: &str| accounts.iter().position(|a| a.name == name);
+    let tomorrow = today + Duration::days(1);
+    let mut balances: Vec<i64> = accounts.iter().map(|a| a.balance).collect();
+    for flow in scheduled {
+        if let Some(i) = index(&flow.account) {
+            balances[i] -= flow.amount;
+        }
+    }
+    let mut changes: BTreeMap<NaiveDate, Vec<(usize, i64)>> = BTreeMap::new();
+    for flow in scheduled.iter().chain(expected) {
+        if let (Some(i), true) = (index(&flow.account), flow.date > today) {
+            changes.entry(flow.date).or_default().push((i, flow.amount));
+        }
+    }
+
+    let cards: Vec<(&CreditCard, &CardStatement, usize, usize)> = cards
+        .iter()
+        .filter_map(|(card, statement)| {
+            let pay_from = card.pay_from.as_deref()?;
+            Some((card, statement, index(&card.account)?, index(pay_from)?))
+        })
+        .collect();
+    let mut payments: Vec<Payment> = cards
+        .iter()
+        .filter(|(_, statement, _, _)| statement.remaining > 0)
+        .map(|(_, statement, card, from)| {
+            let due = NaiveDate::parse_from_str(&statement.due_date, "%Y-%m-%d")
+                .expect("due dates are formatted by card::statement");
+            Payment {
+                card: *card,
+                from: *from,
+                due: due.max(tomorrow),
+                amount: statement.remaining,
+            }
+        })
+        .collect();
+
+    let mut days: Vec<Vec<i64>> = vec![Vec::new(); accounts.len()];
+    let mut date = today;
+    while date <= last {
+        if date > today {
+            for (i, amount) in changes.remove(&date).unwrap_or_default() {
+                balances[i] += amount;
+            }
+            for payment in payments.iter().filter(|p| p.due == date) {
+                balances[payment.from] -= payment.amount;
+                balances[payment.card] += payment.amount;
+            }
+            payments.retain(|p| p.due != date);
+            for (day, balance) in days.iter_mut().zip(&balances) {
+                day.push(*balance);
+            }
+        }
+        // Statements close at the end of the day, after that day's payments.
+        let closing = cards
+            .iter()
+            .filter(|(card, _, _, _)| card.statement_day == date.day());
+        for (card, _, account, from) in closing {
+            let due = card::due_date(card.due_day, date);
+            let pending: i64 = payments
+                .iter()
+                .filter(|p| p.card == *account)
+                .map(|p| p.amount)
+                .sum();
+            let paid_before_due: i64 = changes
+                .range(date + Duration::days(1)..=due)
+                .flat_map(|(_, changes)| changes)
+                .filter(|(i, amount)| i == account && *amount > 0)
+                .map(|(_, amount)| amount)
+                .sum();
+            let owed = -balances[*account] - pending - paid_before_due;
+            if owed > 0 {
+                payments.push(Payment {
+                    card: *account,
+                    from: *from,
+                    due,
+                    amount: owed,
+                });
+            }
+        }
+        date += Duration::days(1);
+    }
+
+    accounts
+        .iter()
+        .zip(days)
+        .map(|(account, balances)| summarise(account, balances, tomorrow))
+        .collect()
+}
+
+fn summarise(account: &Account, balances: Vec<i64>, first: NaiveDate) -> AccountForecast {
+    let date = |day: usize| {
+        (first + Duration::days(day as i64))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let mut lowest_day = 0;
+    for (day, balance) in balances.iter().enumerate() {
+        if *balance < balances[lowest_day] {
+            lowest_day = day;
+        }
+    }
+
+    AccountForecast {
+        account: account.name.clone(),
+        currency: account.currency.clone(),
+        lowest: balances[lowest_day],
+        lowest_date: date(lowest_day),
+        below_zero_date: balances.iter().position(|b| *b < 0).map(date),
+        balances,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::installment::schedule;
+    use entities::Leg;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn account(name: &str, balance: i64) -> Account {
+        Account {
+            name: name.to_owned(),
+            balance,
+            currency: "CNY".to_owned(),
+        }
+    }
+
+    fn flow(day: &str, account: &str, amount: i64) -> Flow {
+        Flow {
+            date: date(day),
+            account: account.to_owned(),
+            amount,
+        }
+    }
+
+    fn operation(from: &str, to: &str, datetime: i64, legs: &[(&str, i64)]) -> Operation {
+        Operation {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            comment: String::new(),
+            amount: legs.iter().map(|(_, amount)| amount).sum(),
+            datetime,
+            legs: legs
+                .iter()
+                .map(|(category, amount)| Leg {
+                    account: None,
+                    category: Some((*category).to_owned()),
+                    amount: *amount,
+                    memo: String::new(),
+                })
+                .collect(),
+            currency: None,
+            to_amount: None,
+        }
+    }
+
+    fn card() -> CreditCard {
+        CreditCard {
+            account: "Visa".to_owned(),
+            statement_day: 5,
+            due_day: 15,
+            limit: 500_000,
+            pay_from: Some("Checking".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_scheduled() {
+        // 2024-03-31T23:30:00Z, already April in Shanghai.
+        let shopping = operation("Bank", "Shop", 1_711_927_800_000, &[("Food", 600)]);
+        let timezone: Tz = "Asia/Shanghai".parse().unwrap();
+        assert_eq!(
+            scheduled(&[shopping], timezone),
+            vec![
+                flow("2024-04-01", "Bank", -600),
+                flow("2024-04-01", "Shop", 600),
+            ]
+        );
+    }
+
+    fn salary(frequency: Frequency, start: &str) -> RecurringOperation {
+        RecurringOperation {
+            name: "Salary".to_owned(),
+            from: "Employer".to_owned(),
+            to: "Checking".to_owned(),
+            amount: 100_000,
+            frequency,
+            start: start.to_owned(),
+            end: None,
+            category: None,
+        }
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let dates = |item: &RecurringOperation, first: &str, last: &str| -> Vec<String> {
+            occurrences(item, date(first), date(last))
+                .iter()
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .collect()
+        };
+        // A monthly item started years ago falls once in each month of a 12-month horizon,
+        // clamped to the end of the shorter months.
+        let monthly = salary(Frequency::Monthly, "2020-01-31");
+        let year = dates(&monthly, "2024-03-11", "2025-03-10");
+        assert_eq!(year.len(), 12);
+        assert_eq!(year[0], "2024-03-31");
+        assert_eq!(year[1], "2024-04-30");
+        assert_eq!(year[11], "2025-02-28");
+        for months in 1..=24 {
+            let first = date("2024-03-11");
+            let last = add_months(first, months) - Duration::days(1);
+            assert_eq!(
+                occurrences(&monthly, first, last).len(),
+                months as usize,
+                "{} months",
+                months
+            );
+        }
+
+        let weekly = salary(Frequency::Weekly, "2024-03-01");
+        assert_eq!(
+            dates(&weekly, "2024-03-10", "2024-03-31"),
+            vec!["2024-03-15", "2024-03-22", "2024-03-29"]
+        );
+        let yearly = salary(Frequency::Yearly, "2020-02-29");
+        assert_eq!(
+            dates(&yearly, "2023-01-01", "2024-12-31"),
+            vec!["2023-02-28", "2024-02-29"]
+        );
+
+        // Nothing before the start or after the end.
+        let mut ending = salary(Frequency::Monthly, "2024-04-15");
+        ending.end = Some("2024-06-15".to_owned());
+        assert_eq!(
+            dates(&ending, "2024-03-11", "2024-09-10"),
+            vec!["2024-04-15", "2024-05-15", "2024-06-15"]
+        );
+        assert!(dates(&ending, "2024-06-16", "2024-09-10").is_empty());
+
+        assert_eq!(
+            recurring(&[ending], date("2024-04-01"), date("2024-04-30")),
+            vec![
+                flow("2024-04-15", "Employer", -100_000),
+                flow("2024-04-15", "Checking", 100_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repayments() {
+        let plan = InstallmentPlan {
+            name: "Phone".to_owned(),
+            account: "Card".to_owned(),
+            from: "Bank".to_owned(),
+            cost_account: Some("Fees".to_owned()),
+            principal: 600_001,
+            annual_rate: 0.0,
+            fee: 3_600,
+            term: 12,
+            first_due: "2024-01-31".to_owned(),
+            paid: 2,
+        };
+        let flows = repayments(&plan, &schedule(&plan), date("2024-04-05"));
+        assert_eq!(flows.len(), 30);
+        // The third repayment, due on 2024-03-31, is overdue.
+        assert_eq!(
+            flows[..4],
+            [
+                flow("2024-04-05", "Bank", -53_600),
+                flow("2024-04-05", "Card", 50_000),
+                flow("2024-04-05", "Fees", 3_600),
+                flow("2024-04-30", "Bank", -53_600),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_average_spending() {
+        let history = vec![
+            operation("Bank", "Shop", 1, &[("Food", 30_000), ("Rent", 300_000)]),
+            operation("Bank", "Shop", 2, &[("Food", 15_001)]),
+            operation("Cash", "Shop", 3, &[("Food", 9_000), ("Fun", -100)]),
+        ];
+        // Rent is already scheduled, and fun is paid for by a recurring operation.
+        let rent = operation("Bank", "Landlord", 4, &[("Rent", 100_000)]);
+        let cinema = RecurringOperation {
+            category: Some("Fun".to_owned()),
+            ..salary(Frequency::Monthly, "2024-01-01")
+        };
+        let accounts = vec![account("Bank", 0), account("Cash", 0)];
+        let spending = average_spending(&history, &[rent], &[cinema], &accounts);
+        let averages: Vec<(&str, &str, i64)> = spending
+            .iter()
+            .map(|s| (&*s.account, &*s.category, s.monthly))
+            .collect();
+        assert_eq!(
+            averages,
+            vec![("Bank", "Food", 15_000), ("Cash", "Food", 3_000)]
+        );
+
+        // 2023-12-10 through 2024-03-10 is 92 days.
+        let flows = spending_flows(&spending[..1], date("2024-03-10"), date("2024-06-10"));
+        let spent: i64 = flows.iter().map(|f| f.amount).sum();
+        assert_eq!(spent, -45_000);
+        assert_eq!(flows[0], flow("2024-03-11", "Bank", -489));
+        // The cents left over add up to one more on the eighth day.
+        assert_eq!(flows[6].amount, -489);
+        assert_eq!(flows[7].amount, -490);
+    }
+
+    #[test]
+    fn test_forecast() {
+        // ¥600.00 on the statement closed on the 5th and ¥200.00 spent since.
+        let statement =
+            card::statement(&card(), "CNY", -80_000, &[(1, -20_000)], date("2024-03-05"));
+        // Salary is already entered for the 25th, so it's in today's balances.
+        let accounts = vec![
+            account("Checking", 150_000),
+            account("Visa", -80_000),
+            account("Employer", -100_000),
+        ];
+        let scheduled = vec![
+            flow("2024-03-25", "Employer", -100_000),
+            flow("2024-03-25", "Checking", 100_000),
+        ];
+        let rent = vec![flow("2024-03-12", "Checking", -70_000)];
+        let forecast = forecast(
+            date("2024-03-10"),
+            date("2024-04-10"),
+            &accounts,
+            &scheduled,
+            &rent,
+            &[(card(), statement)],
+        );
+
+        let checking = &forecast[0];
+        assert_eq!(checking.balances.len(), 31);
+        assert_eq!(checking.balances[0], 50_000);
+        assert_eq!(checking.below_zero_date.as_deref(), Some("2024-03-12"));
+        // The statement is paid on the 15th; the next one isn't due until the 15th of April.
+        assert_eq!(
+            (checking.lowest, checking.lowest_date.as_str()),
+            (-80_000, "2024-03-15")
+        );
+        assert_eq!(checking.balances[30], 20_000);
+        let visa = &forecast[1];
+        assert_eq!(
+            (visa.lowest, visa.lowest_date.as_str()),
+            (-80_000, "2024-03-11")
+        );
+        assert_eq!(visa.balances[30], -20_000);
+        assert_eq!(forecast[2].balances[30], -100_000);
+    }
+
+    #[test]
+    fn test_statement_closing_today() {
+        // The March statement is paid off and ¥50.00 more is entered for the 10th.
+        let changes = [(1, 60_000), (2, -20_000), (3, 5_000)];
+        let statement = card::statement(&card(), "CNY", -15_000, &changes, date("2024-03-05"));
+        assert_eq!(statement.remaining, 0);
+        let accounts = vec![account("Checking", 95_000), account("Visa", -15_000)];
+        let scheduled = vec![
+            flow("2024-04-10", "Checking", -5_000),
+            flow("2024-04-10", "Visa", 5_000),
+        ];
+        let forecast = forecast(
+            date("2024-04-05"),
+            date("2024-05-05"),
+            &accounts,
+            &scheduled,
+            &[],
+            &[(card(), statement)],
+        );
+
+        // Today's statement is ¥200.00, less what's already entered to pay it.
+        let on = |day: &str| (date(day) - date("2024-04-06")).num_days() as usize;
+        assert_eq!(forecast[0].balances[on("2024-04-14")], 95_000);
+        assert_eq!(forecast[0].balances[on("2024-04-15")], 80_000);
+        assert_eq!(forecast[1].balances[on("2024-04-15")], 0);
+        assert_eq!(forecast[0].below_zero_date, None);
+        assert_eq!(forecast[1].below_zero_date.as_deref(), Some("2024-04-06"));
+    }
+}
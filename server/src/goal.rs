@@ -0,0 +1,143 @@
+use crate::installment::add_months;
+use chrono::{Datelike, NaiveDate};
+use entities::{Goal, GoalProgress};
+
+/// How many months back the recent contribution rate is averaged over.
+const RECENT_MONTHS: i32 = 3;
+
+/// Projections further out than a century aren't worth a date.
+const MAX_PROJECTED_MONTHS: i64 = 1200;
+
+/// The first day the recent contribution rate counts changes from.
+pub fn recent_since(today: NaiveDate) -> NaiveDate {
+    add_months(today, -RECENT_MONTHS)
+}
+
+/// Where the goal stands today with `balance` in its account, given that the balance changed
+/// by `recent` since `recent_since(today)`.
+pub fn progress(
+    goal: &Goal,
+    currency: &str,
+    balance: i64,
+    recent: i64,
+    today: NaiveDate,
+) -> GoalProgress {
+    let target_date = NaiveDate::parse_from_str(&goal.target_date, "%Y-%m-%d")
+        .expect("goals are validated before they're stored");
+    let saved = balance.max(0);
+    let remaining = (goal.target - saved).max(0);
+    let months_left = months_between(today, target_date);
+    let monthly_required = match months_left {
+        _ if remaining == 0 => 0,
+        // Past the target date, everything left is due now.
+        0 => remaining,
+        months => div_ceil(remaining, i64::from(months)),
+    };
+    let monthly_contribution = recent / i64::from(RECENT_MONTHS);
+    let projected = if remaining == 0 {
+        Some(today)
+    } else if monthly_contribution > 0 {
+        let months = div_ceil(remaining, monthly_contribution);
+        Some(months)
+            .filter(|months| *months <= MAX_PROJECTED_MONTHS)
+            .map(|months| add_months(today, months as i32))
+    } else {
+        None
+    };
+
+    GoalProgress {
+        goal: goal.clone(),
+        currency: currency.to_owned(),
+        saved,
+        remaining,
+        months_left,
+        monthly_required,
+        monthly_contribution,
+        projected_date: projected.map(|date| date.format("%Y-%m-%d").to_string()),
+        on_track: projected.is_some_and(|date| date <= target_date),
+    }
+}
+
+/// Whole months from `from` to `to`, or none if `to` has passed.
+fn months_between(from: NaiveDate, to: NaiveDate) -> u32 {
+    let months = (to.year() - from.year()) * 12 + to.month() as i32 - from.month() as i32;
+    let months = if to.day() < from.day() {
+        months - 1
+    } else {
+        months
+    };
+
+    months.max(0) as u32
+}
+
+fn div_ceil(amount: i64, by: i64) -> i64 {
+    (amount + by - 1) / by
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    fn goal() -> Goal {
+        Goal {
+            name: "Car".to_owned(),
+            account: "Savings".to_owned(),
+            target: 600_000,
+            target_date: "2024-09-10".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_recent_since() {
+        assert_eq!(recent_since(date("2024-03-10")), date("2023-12-10"));
+        assert_eq!(recent_since(date("2024-05-31")), date("2024-02-29"));
+    }
+
+    #[test]
+    fn test_progress() {
+        // $1,500.00 saved, $900.00 of it over the last three months.
+        let progress = progress(&goal(), "USD", 150_000, 90_000, date("2024-03-10"));
+        assert_eq!(progress.saved, 150_000);
+        assert_eq!(progress.remaining, 450_000);
+        assert_eq!(progress.months_left, 6);
+        assert_eq!(progress.monthly_required, 75_000);
+        assert_eq!(progress.monthly_contribution, 30_000);
+        // Fifteen more months at $300.00.
+        assert_eq!(progress.projected_date.as_deref(), Some("2025-06-10"));
+        assert!(!progress.on_track);
+    }
+
+    #[test]
+    fn test_on_track_and_reached() {
+        let on_track = progress(&goal(), "USD", 450_000, 450_000, date("2024-03-10"));
+        assert_eq!(on_track.projected_date.as_deref(), Some("2024-04-10"));
+        assert!(on_track.on_track);
+
+        let reached = progress(&goal(), "USD", 700_000, 0, date("2024-03-10"));
+        assert_eq!((reached.remaining, reached.monthly_required), (0, 0));
+        assert_eq!(reached.projected_date.as_deref(), Some("2024-03-10"));
+        assert!(reached.on_track);
+    }
+
+    #[test]
+    fn test_behind() {
+        // A day short of a whole month, then past the target date.
+        let progress_on = |today: &str| progress(&goal(), "USD", -5_000, -3_000, date(today));
+        let late = progress_on("2024-08-11");
+        assert_eq!(late.saved, 0);
+        assert_eq!(late.months_left, 0);
+        assert_eq!(late.monthly_required, 600_000);
+        assert_eq!(late.monthly_contribution, -1_000);
+        assert_eq!(late.projected_date, None);
+        assert!(!late.on_track);
+        assert_eq!(progress_on("2025-01-01").months_left, 0);
+
+        // Too slow to get there within a century.
+        let slow = progress(&goal(), "USD", 0, 3, date("2024-03-10"));
+        assert_eq!(slow.projected_date, None);
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{Datelike, NaiveDate};
+use entities::time::Tz;
+use entities::{Installment, InstallmentPlan, Leg, Operation, Timestamp};
+
+/// Works out every repayment of the plan. With interest, each repays the same amount, most of
+/// it interest at first; without, the principal is split evenly, with what doesn't divide
+/// evenly repaid first. The last repayment clears whatever rounding left over.
+pub fn schedule(plan: &InstallmentPlan) -> Vec<Installment> {
+    let first_due = NaiveDate::parse_from_str(&plan.first_due, "%Y-%m-%d")
+        .expect("plans are validated before they're stored");
+    let term = i64::from(plan.term);
+    let monthly_rate = plan.annual_rate / 1200.0;
+    let payment = if monthly_rate > 0.0 {
+        let factor = (1.0 + monthly_rate).powi(-(plan.term as i32));
+        (plan.principal as f64 * monthly_rate / (1.0 - factor)).round() as i64
+    } else {
+        plan.principal / term
+    };
+
+    let mut remaining = plan.principal;
+    (1..=plan.term)
+        .map(|number| {
+            let interest = (remaining as f64 * monthly_rate).round() as i64;
+            let principal = if number == plan.term {
+                remaining
+            } else if monthly_rate > 0.0 {
+                (payment - interest).clamp(0, remaining)
+            } else if number == 1 {
+                payment + plan.principal % term
+            } else {
+                payment
+            };
+            remaining -= principal;
+            Installment {
+                number,
+                due_date: add_months(first_due, number as i32 - 1)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+                principal,
+                interest,
+                fee: plan.fee,
+                remaining,
+                paid: number <= plan.paid,
+            }
+        })
+        .collect()
+}
+
+/// The operation that records the repayment on its due date: the principal goes to the
+/// account it's owed on, and interest and fees to the plan's cost account.
+pub fn repayment(plan: &InstallmentPlan, installment: &Installment, timezone: Tz) -> Operation {
+    let due_date = NaiveDate::parse_from_str(&installment.due_date, "%Y-%m-%d")
+        .expect("due dates are formatted by schedule");
+    let cost = installment.interest + installment.fee;
+    let legs = match &plan.cost_account {
+        Some(cost_account) if cost > 0 => vec![
+            Leg {
+                account: None,
+                category: None,
+                amount: installment.principal,
+                memo: "Principal".to_owned(),
+            },
+            Leg {
+                account: Some(cost_account.clone()),
+                category: None,
+                amount: cost,
+                memo: "Interest and fees".to_owned(),
+            },
+        ],
+        _ => Vec::new(),
+    };
+
+    Operation {
+        from: plan.from.clone(),
+        to: plan.account.clone(),
+        comment: format!("{} {}/{}", plan.name, installment.number, plan.term),
+        amount: installment.principal + cost,
+        datetime: Timestamp::from_local_date(due_date, timezone).millis(),
+        legs,
+        currency: None,
+        to_amount: None,
+    }
+}
+
+/// The same day `months` later, or earlier if negative, or the last day of a shorter month.
+pub fn add_months(date: NaiveDate, months: i32) -> NaiveDate {
+    let index = date.month0() as i32 + months;
+    let (year, month) = (
+        date.year() + index.div_euclid(12),
+        index.rem_euclid(12) as u32 + 1,
+    );
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .expect("every month has a first day")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan() -> InstallmentPlan {
+        InstallmentPlan {
+            name: "Phone".to_owned(),
+            account: "Card".to_owned(),
+            from: "Bank".to_owned(),
+            cost_account: Some("Fees".to_owned()),
+            principal: 600_001,
+            annual_rate: 0.0,
+            fee: 3_600,
+            term: 12,
+            first_due: "2024-01-31".to_owned(),
+            paid: 2,
+        }
+    }
+
+    #[test]
+    fn test_even_principal_with_fees() {
+        let schedule = schedule(&plan());
+        assert_eq!(schedule.len(), 12);
+        assert_eq!(
+            schedule[0],
+            Installment {
+                number: 1,
+                due_date: "2024-01-31".to_owned(),
+                principal: 50_001,
+                interest: 0,
+                fee: 3_600,
+                remaining: 550_000,
+                paid: true,
+            }
+        );
+        // Due at the end of shorter months.
+        let due: Vec<&str> = schedule[1..4].iter().map(|i| &*i.due_date).collect();
+        assert_eq!(due, vec!["2024-02-29", "2024-03-31", "2024-04-30"]);
+        assert!(schedule[1].paid && !schedule[2].paid);
+        assert!(schedule[1..].iter().all(|i| i.principal == 50_000));
+        assert_eq!(schedule[11].due_date, "2024-12-31");
+        assert_eq!(schedule[11].remaining, 0);
+    }
+
+    #[test]
+    fn test_annuity() {
+        // ¥10,000.00 over a year at 12%, or 1% a month: ¥888.49 a month.
+        let plan = InstallmentPlan {
+            principal: 1_000_000,
+            annual_rate: 12.0,
+            fee: 0,
+            first_due: "2024-11-15".to_owned(),
+            ..plan()
+        };
+        let schedule = schedule(&plan);
+        assert_eq!(
+            (schedule[0].principal, schedule[0].interest),
+            (78_849, 10_000)
+        );
+        assert_eq!(schedule[2].due_date, "2025-01-15");
+        assert!(schedule[..11]
+            .iter()
+            .all(|i| i.principal + i.interest == 88_849));
+        let last = &schedule[11];
+        assert_eq!(last.remaining, 0);
+        // Rounding the payment up left a little less for the last one.
+        assert_eq!(last.principal + last.interest, 88_847);
+        let repaid: i64 = schedule.iter().map(|i| i.principal).sum();
+        assert_eq!(repaid, plan.principal);
+    }
+
+    #[test]
+    fn test_repayment() {
+        let plan = plan();
+        let installments = schedule(&plan);
+        let operation = repayment(&plan, &installments[2], Tz::UTC);
+        assert_eq!((&*operation.from, &*operation.to), ("Bank", "Card"));
+        assert_eq!(operation.comment, "Phone 3/12");
+        assert_eq!(operation.amount, 53_600);
+        // 2024-03-31T00:00:00Z.
+        assert_eq!(operation.datetime, 1_711_843_200_000);
+        let legs: Vec<(Option<&str>, i64)> = operation
+            .legs
+            .iter()
+            .map(|leg| (leg.account.as_deref(), leg.amount))
+            .collect();
+        assert_eq!(legs, vec![(None, 50_000), (Some("Fees"), 3_600)]);
+
+        // Interest-free plans repay the principal alone.
+        let free = InstallmentPlan {
+            cost_account: None,
+            fee: 0,
+            ..plan
+        };
+        let operation = repayment(&free, &schedule(&free)[0], Tz::UTC);
+        assert_eq!(operation.amount, 50_001);
+        assert!(operation.legs.is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use entities::money::minor_digits;
+use entities::{
+    Holding, Lot, Money, Price, Security, Trade, TradeSide, PRICE_DIGITS, QUANTITY_DIGITS,
+};
+
+/// Replays the trades into what each account holds of each security, in the order they were
+/// first bought. Sales use up the oldest lots first, and what they bring in over the cost of
+/// those lots is realised. Holdings are valued at `prices`, which hold at most the latest
+/// price of each symbol, or at cost without one. `trades` must be oldest first.
+pub fn holdings(securities: &[Security], trades: &[Trade], prices: &[Price]) -> Vec<Holding> {
+    let mut holdings: Vec<Holding> = Vec::new();
+    for trade in trades {
+        let index = match holdings
+            .iter()
+            .position(|h| h.account == trade.account && h.symbol == trade.symbol)
+        {
+            Some(index) => index,
+            None => {
+                let currency = securities
+                    .iter()
+                    .find(|security| security.symbol == trade.symbol)
+                    .map(|security| security.currency.clone())
+                    .unwrap_or_default();
+                holdings.push(Holding {
+                    account: trade.account.clone(),
+                    symbol: trade.symbol.clone(),
+                    currency,
+                    quantity: 0,
+                    cost: 0,
+                    lots: Vec::new(),
+                    realised: 0,
+                    price: None,
+                    price_date: None,
+                    value: 0,
+                    unrealised: None,
+                });
+                holdings.len() - 1
+            }
+        };
+        let holding = &mut holdings[index];
+        match trade.side {
+            TradeSide::Buy => holding.lots.push(Lot {
+                datetime: trade.datetime,
+                quantity: trade.quantity,
+                cost: trade.amount,
+            }),
+            TradeSide::Sell => {
+                let cost = sell(&mut holding.lots, trade.quantity, &holding.currency);
+                holding.realised += trade.amount - cost;
+            }
+        }
+        holding.quantity = holding.lots.iter().map(|lot| lot.quantity).sum();
+        holding.cost = holding.lots.iter().map(|lot| lot.cost).sum();
+    }
+
+    for holding in holdings.iter_mut() {
+        let price = prices.iter().find(|price| price.symbol == holding.symbol);
+        let value = price.and_then(|price| value(holding.quantity, price.price, &holding.currency));
+        match (price, value) {
+            (Some(price), Some(value)) => {
+                holding.price = Some(price.price);
+                holding.price_date = Some(price.date.clone());
+                holding.value = value;
+                holding.unrealised = Some(value - holding.cost);
+            }
+            _ => holding.value = holding.cost,
+        }
+    }
+
+    holdings
+}
+
+/// Takes `quantity` out of the oldest lots, returning what the units taken cost. A lot sold
+/// in part keeps the rest of its cost.
+fn sell(lots: &mut Vec<Lot>, mut quantity: i64, currency: &str) -> i64 {
+    let mut cost = 0;
+    while quantity > 0 && !lots.is_empty() {
+        let lot = &mut lots[0];
+        if lot.quantity <= quantity {
+            quantity -= lot.quantity;
+            cost += lot.cost;
+            lots.remove(0);
+        } else {
+            let taken = Money::new(lot.cost, currency)
+                .convert(i128::from(quantity), i128::from(lot.quantity), currency)
+                .map_or(0, |taken| taken.minor());
+            lot.quantity -= quantity;
+            lot.cost -= taken;
+            cost += taken;
+            quantity = 0;
+        }
+    }
+
+    cost
+}
+
+/// What `quantity` units are worth at `price` per unit, in minor units of `currency`, or
+/// `None` if that's too large.
+fn value(quantity: i64, price: i64, currency: &str) -> Option<i64> {
+    // Quantities are counted like amounts with QUANTITY_DIGITS decimal places, so converting
+    // one from `currency` to itself at the price leaves the scale to make up.
+    let scale = i128::pow(10, PRICE_DIGITS) * i128::pow(10, QUANTITY_DIGITS);
+    let unit = i128::pow(10, minor_digits(currency));
+    Money::new(quantity, currency)
+        .convert(i128::from(price).checked_mul(unit)?, scale, currency)
+        .map(|value| value.minor())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn security(symbol: &str, currency: &str) -> Security {
+        Security {
+            symbol: symbol.to_owned(),
+            name: symbol.to_owned(),
+            currency: currency.to_owned(),
+        }
+    }
+
+    fn trade(account: &str, side: TradeSide, quantity: i64, amount: i64, datetime: i64) -> Trade {
+        Trade {
+            account: account.to_owned(),
+            symbol: "VTI".to_owned(),
+            side,
+            quantity,
+            amount,
+            datetime,
+            comment: String::new(),
+        }
+    }
+
+    fn price(symbol: &str, price: i64) -> Price {
+        Price {
+            symbol: symbol.to_owned(),
+            date: "2024-03-01".to_owned(),
+            price,
+        }
+    }
+
+    #[test]
+    fn test_sales_use_up_the_oldest_lots() {
+        let securities = [security("VTI", "USD")];
+        let trades = [
+            // 10 units for $2,000.00, then 10 for $2,300.00.
+            trade("Broker", TradeSide::Buy, 100_000, 200_000, 1),
+            trade("Broker", TradeSide::Buy, 100_000, 230_000, 2),
+            // 15 units for $3,600.00 use up the first lot and half the second.
+            trade("Broker", TradeSide::Sell, 150_000, 360_000, 3),
+        ];
+
+        let holdings = holdings(&securities, &trades, &[]);
+        assert_eq!(holdings.len(), 1);
+        let holding = &holdings[0];
+        assert_eq!(holding.currency, "USD");
+        assert_eq!(holding.quantity, 50_000);
+        assert_eq!(
+            holding.lots,
+            vec![Lot {
+                datetime: 2,
+                quantity: 50_000,
+                cost: 115_000,
+            }]
+        );
+        assert_eq!(holding.cost, 115_000);
+        assert_eq!(holding.realised, 360_000 - 200_000 - 115_000);
+        // Without a price it's valued at cost.
+        assert_eq!(holding.value, 115_000);
+        assert_eq!(holding.price, None);
+        assert_eq!(holding.unrealised, None);
+    }
+
+    #[test]
+    fn test_holdings_are_valued_at_prices() {
+        let securities = [security("VTI", "USD"), security("510300", "CNY")];
+        let trades = [
+            trade("Broker", TradeSide::Buy, 30_000, 60_000, 1),
+            // Kept apart from what another account holds.
+            trade("IRA", TradeSide::Buy, 10_000, 25_000, 2),
+            Trade {
+                symbol: "510300".to_owned(),
+                ..trade("Funds", TradeSide::Buy, 1_000_000, 400_000, 3)
+            },
+            // Sold off entirely, keeping its realised gain.
+            Trade {
+                symbol: "510300".to_owned(),
+                ..trade("Funds", TradeSide::Sell, 1_000_000, 390_000, 4)
+            },
+        ];
+        let prices = [price("VTI", 24_512_500_000)];
+
+        let holdings = holdings(&securities, &trades, &prices);
+        let summary: Vec<(&str, &str, i64, i64, Option<i64>)> = holdings
+            .iter()
+            .map(|h| (&*h.account, &*h.symbol, h.quantity, h.value, h.unrealised))
+            .collect();
+        // 3 units at $245.125 are $735.375, rounded to $735.38.
+        assert_eq!(
+            summary,
+            vec![
+                ("Broker", "VTI", 30_000, 73_538, Some(73_538 - 60_000)),
+                ("IRA", "VTI", 10_000, 24_513, Some(24_513 - 25_000)),
+                ("Funds", "510300", 0, 0, None),
+            ]
+        );
+        assert_eq!(holdings[0].price_date.as_deref(), Some("2024-03-01"));
+        assert_eq!(holdings[2].realised, -10_000);
+    }
+}
@@ -1,24 +1,73 @@
 #[macro_use]
 extern crate log;
 
+mod attachment;
+mod backup;
+mod card;
+mod claim;
+mod csrf;
 mod data;
 mod error;
+mod forecast;
+mod goal;
+mod installment;
+mod investment;
+mod migration;
+mod password;
+mod rates;
+mod report;
+mod session;
+mod shared;
+mod totp;
 
 use crate::error::ServerError;
 use actix_identity::{CookieIdentityPolicy, Identity, IdentityService};
+use actix_multipart::Multipart;
+use actix_web::cookie::SameSite;
+use actix_web::http::header::{self, Charset, ContentDisposition, DispositionParam};
+use actix_web::http::header::{DispositionType, ExtendedValue};
 use actix_web::{middleware, web, App, Error as AWError, HttpResponse, HttpServer};
-use chrono::Duration;
+use attachment::Attachments;
+use backup::Backups;
+use chrono::{Duration, NaiveDate, Utc};
 use config::{Config, ConfigError, Environment};
-use data::Database;
-use entities::{Account, LoginChallenge, Operation};
+use data::{Backend, SqliteStorage, Storage, Store, TotpSecret};
+use entities::time::{parse_timezone, Tz};
+use entities::validation::MAX_ATTACHMENT_SIZE;
+use entities::{
+    Account, BackupFile, CardStatement, Category, Claim, CreditCard, ExchangeRate, Forecast,
+    ForecastQuery, Goal, GoalProgress, Holding, InstallmentPlan, InstallmentSchedule, Ledger,
+    LedgerExport, LedgerMember, LedgerSettings, LoginChallenge, LoginResponse, NewAttachment,
+    NewLedger, NewUser, Operation, OperationCreated, Price, RatesImported, RecurringOperation,
+    Reimbursement, RepaymentsRecorded, ReportQuery, Role, SearchQuery, Security, SharedExpense,
+    Timestamp, TotpConfirmation, TotpEnrolment, Trade, Transaction, TransactionImport,
+    TransactionsImported,
+};
+use futures::StreamExt;
+use migration::Dialect;
 use rand::RngCore;
 use serde::Deserialize;
+use session::Session;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+const JSON_LIMIT: usize = 4096;
+const IMPORT_LIMIT: usize = 16 * 1024 * 1024;
+/// How many operations a search returns at most.
+const SEARCH_LIMIT: i64 = 50;
 
 #[derive(Deserialize)]
 struct ServerConfig {
     bind_addr: String,
     db_addr: String,
-    migration_addr: String,
+    /// Reads migrations from this directory instead of the ones built into the binary.
+    migration_addr: Option<String>,
+    /// Directory for timestamped backups, taken through `POST /backup` or `--backup`.
+    backup_addr: Option<String>,
+    backup_keep: Option<usize>,
+    /// Directory for attachment files. Without it they're stored in the database, and so are
+    /// part of its backups.
+    attachment_addr: Option<String>,
 }
 
 impl ServerConfig {
@@ -29,76 +78,1370 @@ impl ServerConfig {
     }
 }
 
+/// What to do after loading the configuration, chosen by command line flags.
+enum Mode {
+    Serve,
+    /// `--migrate-only`: bring the database up to date and exit.
+    MigrateOnly,
+    /// `--dry-run`: print the pending migrations and exit without applying them.
+    DryRun,
+    /// `--backup`: write a snapshot to the backup directory and exit.
+    Backup,
+    /// `--restore <file>`: replace the database with a snapshot. The server must be stopped.
+    Restore(String),
+}
+
+impl Mode {
+    fn from_args() -> Result<Self, ServerError> {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        match args
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [] => Ok(Mode::Serve),
+            ["--migrate-only"] => Ok(Mode::MigrateOnly),
+            ["--dry-run"] | ["--migrate-only", "--dry-run"] | ["--dry-run", "--migrate-only"] => {
+                Ok(Mode::DryRun)
+            }
+            ["--backup"] => Ok(Mode::Backup),
+            ["--restore", file] => Ok(Mode::Restore((*file).to_owned())),
+            _ => Err(ServerError::InternalError(format!(
+                "Unknown arguments {:?}, expected --migrate-only, --dry-run, --backup or \
+                 --restore <file>.",
+                args
+            ))),
+        }
+    }
+}
+
+enum LoginOutcome {
+    Accepted(i64),
+    SecondFactorRequired,
+    Rejected,
+}
+
 async fn login(
     id: Identity,
-    db: web::Data<Database>,
+    db: web::Data<Store>,
     challenge: web::Json<LoginChallenge>,
 ) -> Result<HttpResponse, AWError> {
-    let secret = web::block(move || -> Result<String, ServerError> {
-        let secret = db.get_secret()?;
-        Ok(secret)
+    let outcome = web::block(move || -> Result<LoginOutcome, ServerError> {
+        let user = match db.get_user(&challenge.user)? {
+            Some(user) if password::verify(&challenge.token, &user.password_hash) => user,
+            _ => return Ok(LoginOutcome::Rejected),
+        };
+
+        let totp = match db.get_totp(user.id, true)? {
+            Some(totp) => totp,
+            None => return Ok(LoginOutcome::Accepted(user.id)),
+        };
+
+        match &challenge.code {
+            Some(code) if verify_second_factor(db.get_ref().as_ref(), &totp, code)? => {
+                Ok(LoginOutcome::Accepted(user.id))
+            }
+            Some(_) => Ok(LoginOutcome::Rejected),
+            None => Ok(LoginOutcome::SecondFactorRequired),
+        }
     })
-    .await?;
+    .await
+    .map_err(ServerError::from)?;
+
+    match outcome {
+        LoginOutcome::Accepted(user) => {
+            id.remember(user.to_string());
+            Ok(HttpResponse::Created().finish())
+        }
+        LoginOutcome::SecondFactorRequired => {
+            Ok(HttpResponse::Unauthorized().json(LoginResponse {
+                second_factor: true,
+            }))
+        }
+        LoginOutcome::Rejected => Err(ServerError::UnauthorizedError.into()),
+    }
+}
+
+/// Accepts either a TOTP code or one of the unused recovery codes.
+fn verify_second_factor(
+    db: &dyn Storage,
+    totp: &TotpSecret,
+    code: &str,
+) -> Result<bool, ServerError> {
+    match totp::verify(&totp.secret, code, Utc::now().timestamp(), totp.last_step) {
+        Some(step) => db.update_totp_step(totp.id, step),
+        None => db.use_recovery_code(totp.id, &totp::hash_recovery_code(code)),
+    }
+}
+
+async fn enrol_totp(id: Identity, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let user = session::user_id(&id)?;
+    let enrolment = web::block(move || -> Result<TotpEnrolment, ServerError> {
+        let account = db
+            .get_user_by_id(user)?
+            .ok_or(ServerError::UnauthorizedError)?;
+        let secret = totp::generate_secret();
+        let recovery_codes = totp::generate_recovery_codes();
+        let recovery_hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| totp::hash_recovery_code(code))
+            .collect();
+        db.enrol_totp(user, &secret, &recovery_hashes)?;
+
+        Ok(TotpEnrolment {
+            uri: totp::provisioning_uri(&secret, &account.name),
+            recovery_codes,
+        })
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().json(enrolment))
+}
+
+async fn confirm_totp(
+    id: Identity,
+    db: web::Data<Store>,
+    confirmation: web::Json<TotpConfirmation>,
+) -> Result<HttpResponse, AWError> {
+    let user = session::user_id(&id)?;
+    let confirmed = web::block(move || -> Result<bool, ServerError> {
+        let pending = match db.get_totp(user, false)? {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+        let now = Utc::now().timestamp();
+
+        match totp::verify(&pending.secret, &confirmation.code, now, pending.last_step) {
+            Some(step) => {
+                db.confirm_totp(user, pending.id, step)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    })
+    .await
+    .map_err(ServerError::from)?;
 
-    if secret == challenge.token {
-        id.remember("admin".to_owned());
-        Ok(HttpResponse::Created().finish())
+    if confirmed {
+        Ok(HttpResponse::NoContent().finish())
     } else {
-        Ok(HttpResponse::Unauthorized().finish())
+        Err(ServerError::validation("code", "The code is wrong or has expired.").into())
     }
 }
 
 async fn operation(
-    id: Identity,
-    db: web::Data<Database>,
+    session: Session,
+    db: web::Data<Store>,
     item: web::Json<Operation>,
 ) -> Result<HttpResponse, AWError> {
-    id.identity().ok_or(ServerError::UnauthorizedError)?;
-    web::block(move || -> Result<(), ServerError> {
-        db.insert_operation(&item)?;
-        db.update_balance(&item.from, -item.amount)?;
-        db.update_balance(&item.to, item.amount)?;
+    session.require(Role::Editor)?;
+    item.validate(Utc::now().timestamp_millis())
+        .map_err(ServerError::from)?;
+    let id = web::block(move || db.record_operation(session.ledger, &item))
+        .await
+        .map_err(ServerError::from)?;
 
-        Ok(())
+    Ok(HttpResponse::Created().json(OperationCreated { id }))
+}
+
+/// Records transactions parsed from a bank statement as operations, all of them or none. Every
+/// account they name has to exist. Transactions an earlier import recorded are skipped, so a
+/// statement can be imported again, or one that overlaps it.
+async fn import_transactions(
+    session: Session,
+    db: web::Data<Store>,
+    import: web::Json<TransactionImport>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    let now = Utc::now().timestamp_millis();
+    let operations = import.to_operations().ok_or_else(|| {
+        ServerError::validation(
+            "transactions",
+            format!("Every transaction has to be to or from {}.", import.account),
+        )
+    })?;
+    for operation in &operations {
+        operation.validate(now).map_err(ServerError::from)?;
+    }
+    let operations: Vec<(String, Operation)> = import_keys(&import.account, &import.transactions)
+        .into_iter()
+        .zip(operations)
+        .collect();
+    let result = web::block(move || -> Result<TransactionsImported, ServerError> {
+        let accounts = db.get_accounts(session.ledger)?;
+        let exists = |name: &str| accounts.iter().any(|account| account.name == name);
+        for (_, operation) in &operations {
+            for name in [&operation.from, &operation.to] {
+                if !exists(name) {
+                    return Err(ServerError::validation(
+                        "transactions",
+                        format!("Account {} doesn't exist.", name),
+                    ));
+                }
+            }
+        }
+        let count = db.import_operations(session.ledger, &operations)?;
+
+        Ok(TransactionsImported {
+            count,
+            skipped: operations.len() - count,
+        })
     })
-    .await?;
+    .await
+    .map_err(ServerError::from)?;
 
-    Ok(HttpResponse::Created().finish())
+    Ok(HttpResponse::Created().json(result))
+}
+
+/// Keys the transactions of a statement of `account` by what the statement says of them, so
+/// the same transaction gets the same key whenever it's imported. Statements can list identical
+/// transactions, like two coffees on a day, which the count of those before it tells apart.
+fn import_keys(account: &str, transactions: &[Transaction]) -> Vec<String> {
+    let mut seen = HashMap::new();
+    transactions
+        .iter()
+        .map(|transaction| {
+            let fields = (
+                transaction.datetime,
+                transaction.amount,
+                &transaction.from,
+                &transaction.to,
+                &transaction.description,
+            );
+            let occurrence = seen.entry(fields).or_insert(0);
+            *occurrence += 1;
+            let key = format!(
+                "{}\n{}\n{}\n{}\n{}\n{}\n{}",
+                account, fields.0, fields.1, fields.2, fields.3, fields.4, occurrence
+            );
+            Sha256::digest(key.as_bytes())
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        })
+        .collect()
+}
+
+async fn add_attachment(
+    session: Session,
+    db: web::Data<Store>,
+    attachments: web::Data<Attachments>,
+    operation: web::Path<i64>,
+    payload: Multipart,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    let (attachment, content) = read_upload(payload).await?;
+    attachment.validate().map_err(ServerError::from)?;
+    let result = web::block(move || {
+        attachments.add(
+            db.get_ref().as_ref(),
+            session.ledger,
+            *operation,
+            &attachment,
+            &content,
+        )
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().json(result))
+}
+
+/// Reads the `file` part of a multipart upload, giving up once it's larger than any
+/// attachment may be.
+async fn read_upload(mut payload: Multipart) -> Result<(NewAttachment, Vec<u8>), ServerError> {
+    let upload_error =
+        |e: actix_multipart::MultipartError| ServerError::BadRequestError(e.to_string());
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(upload_error)?;
+        let disposition = field.content_disposition();
+        if disposition.as_ref().and_then(|d| d.get_name()) != Some("file") {
+            continue;
+        }
+
+        // Some browsers send the full path of the file.
+        let name = disposition
+            .as_ref()
+            .and_then(|d| d.get_filename())
+            .and_then(|path| path.rsplit(['/', '\\']).next())
+            .unwrap_or_default()
+            .to_owned();
+        let mut attachment = NewAttachment {
+            name,
+            content_type: field.content_type().essence_str().to_owned(),
+            size: 0,
+        };
+        let mut content = Vec::new();
+        while let Some(chunk) = field.next().await {
+            content.extend_from_slice(&chunk.map_err(upload_error)?);
+            attachment.size = content.len() as i64;
+            if attachment.size > MAX_ATTACHMENT_SIZE {
+                attachment.validate()?;
+            }
+        }
+
+        return Ok((attachment, content));
+    }
+
+    Err(ServerError::validation("file", "Choose a file to attach."))
+}
+
+async fn attachments(
+    session: Session,
+    db: web::Data<Store>,
+    operation: web::Path<i64>,
+) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_attachments(session.ledger, *operation))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn download_attachment(
+    session: Session,
+    db: web::Data<Store>,
+    attachments: web::Data<Attachments>,
+    id: web::Path<i64>,
+) -> Result<HttpResponse, AWError> {
+    let (attachment, content) =
+        web::block(move || attachments.read(db.get_ref().as_ref(), session.ledger, *id))
+            .await
+            .map_err(ServerError::from)?;
+
+    let filename = if attachment.name.is_ascii() {
+        DispositionParam::Filename(attachment.name)
+    } else {
+        DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_owned()),
+            language_tag: None,
+            value: attachment.name.into_bytes(),
+        })
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(attachment.content_type)
+        .set(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![filename],
+        })
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .body(content))
+}
+
+async fn delete_attachment(
+    session: Session,
+    db: web::Data<Store>,
+    attachments: web::Data<Attachments>,
+    id: web::Path<i64>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    web::block(move || attachments.delete(db.get_ref().as_ref(), session.ledger, *id))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
 async fn add_account(
-    id: Identity,
-    db: web::Data<Database>,
+    session: Session,
+    db: web::Data<Store>,
     account: web::Json<Account>,
 ) -> Result<HttpResponse, AWError> {
-    id.identity().ok_or(ServerError::UnauthorizedError)?;
+    session.require(Role::Editor)?;
+    account.validate().map_err(ServerError::from)?;
     web::block(move || -> Result<(), ServerError> {
-        db.add_account(&account)?;
+        db.add_account(session.ledger, &account)?;
 
         Ok(())
     })
-    .await?;
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn account(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_accounts(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn rates(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_rates(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_rate(
+    session: Session,
+    db: web::Data<Store>,
+    rate: web::Json<ExchangeRate>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    rate.validate().map_err(ServerError::from)?;
+    web::block(move || db.add_rates(session.ledger, &[rate.into_inner()]))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn import_rates(
+    session: Session,
+    db: web::Data<Store>,
+    content: String,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    let rates = rates::parse_csv(&content)?;
+    let result = web::block(move || -> Result<RatesImported, ServerError> {
+        db.add_rates(session.ledger, &rates)?;
+
+        Ok(RatesImported { count: rates.len() })
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().json(result))
+}
+
+async fn report(
+    session: Session,
+    db: web::Data<Store>,
+    query: web::Query<ReportQuery>,
+) -> Result<HttpResponse, AWError> {
+    query.validate().map_err(ServerError::from)?;
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let result = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let today = local_today(&settings, now)?;
+        let base = query.into_inner().base.unwrap_or(settings.currency);
+        let accounts = db.get_accounts(session.ledger)?;
+        let holdings = holdings(db.get_ref().as_ref(), session.ledger, &today)?;
+        let mut rates = Vec::new();
+        for currency in report::foreign_currencies(&base, &accounts, &holdings) {
+            rates.extend(db.find_rate(session.ledger, &currency, &base, &today)?);
+        }
+        let conversions = db.get_conversions(session.ledger)?;
+
+        Ok(report::balance_report(
+            &base,
+            accounts,
+            &holdings,
+            &rates,
+            &conversions,
+        ))
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// The date `now` falls on in the ledger's timezone, which rates and prices are dated in like
+/// everything entered in it.
+fn local_today(settings: &LedgerSettings, now: Timestamp) -> Result<String, ServerError> {
+    let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+        ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+    })?;
+
+    Ok(now.local_date(timezone).format("%Y-%m-%d").to_string())
+}
+
+/// What the ledger's accounts hold, valued at the latest prices on or before `date`.
+fn holdings(db: &dyn Storage, ledger: i64, date: &str) -> Result<Vec<Holding>, ServerError> {
+    let securities = db.get_securities(ledger)?;
+    let trades = db.get_trades(ledger)?;
+    let prices = db.latest_prices(ledger, date)?;
+
+    Ok(investment::holdings(&securities, &trades, &prices))
+}
+
+async fn securities(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_securities(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_security(
+    session: Session,
+    db: web::Data<Store>,
+    security: web::Json<Security>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    security.validate().map_err(ServerError::from)?;
+    web::block(move || db.add_security(session.ledger, &security))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn trades(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_trades(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_trade(
+    session: Session,
+    db: web::Data<Store>,
+    trade: web::Json<Trade>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    trade
+        .validate(Utc::now().timestamp_millis())
+        .map_err(ServerError::from)?;
+    web::block(move || db.record_trade(session.ledger, &trade))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn prices(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_prices(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_price(
+    session: Session,
+    db: web::Data<Store>,
+    price: web::Json<Price>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    price.validate().map_err(ServerError::from)?;
+    web::block(move || db.add_prices(session.ledger, &[price.into_inner()]))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn investments(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let result = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let today = local_today(&settings, now)?;
+        holdings(db.get_ref().as_ref(), session.ledger, &today)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn cards(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let result = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+            ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+        })?;
+        let today = now.local_date(timezone);
+        let accounts = db.get_accounts(session.ledger)?;
+        let mut statements: Vec<CardStatement> = Vec::new();
+        for card in db.get_credit_cards(session.ledger)? {
+            let account = match accounts.iter().find(|a| a.name == card.account) {
+                Some(account) => account,
+                None => continue,
+            };
+            statements.push(current_statement(
+                db.get_ref().as_ref(),
+                session.ledger,
+                &card,
+                account,
+                today,
+                timezone,
+            )?);
+        }
+        statements.sort_by(|a, b| a.due_date.cmp(&b.due_date));
+
+        Ok(statements)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// The card's latest statement as of `today`, with the account's balance now.
+fn current_statement(
+    db: &dyn Storage,
+    ledger: i64,
+    card: &CreditCard,
+    account: &Account,
+    today: NaiveDate,
+    timezone: Tz,
+) -> Result<CardStatement, ServerError> {
+    let statement_date = card::statement_date(card.statement_day, today);
+    let closed = Timestamp::from_local_date(statement_date + Duration::days(1), timezone);
+    let changes = db.balance_changes(ledger, &card.account, closed.millis())?;
+
+    Ok(card::statement(
+        card,
+        &account.currency,
+        account.balance,
+        &changes,
+        statement_date,
+    ))
+}
+
+async fn set_card(
+    session: Session,
+    db: web::Data<Store>,
+    card: web::Json<CreditCard>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    card.validate().map_err(ServerError::from)?;
+    web::block(move || db.set_credit_card(session.ledger, &card))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn share_operation(
+    session: Session,
+    db: web::Data<Store>,
+    operation: web::Path<i64>,
+    expense: web::Json<SharedExpense>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    expense.validate().map_err(ServerError::from)?;
+    web::block(move || db.share_operation(session.ledger, *operation, &expense))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn shared_balances(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_shared_expenses(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(shared::balances(&result)))
+}
+
+async fn settle_up(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_shared_expenses(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(shared::settle(&shared::balances(&result))))
+}
+
+async fn set_claim(
+    session: Session,
+    db: web::Data<Store>,
+    operation: web::Path<i64>,
+    claim: web::Json<Claim>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    claim.validate().map_err(ServerError::from)?;
+    web::block(move || db.set_claim(session.ledger, *operation, &claim))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn add_reimbursement(
+    session: Session,
+    db: web::Data<Store>,
+    reimbursement: web::Json<Reimbursement>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    reimbursement.validate().map_err(ServerError::from)?;
+    web::block(move || db.record_reimbursement(session.ledger, &reimbursement))
+        .await
+        .map_err(ServerError::from)?;
 
     Ok(HttpResponse::Created().finish())
 }
 
-async fn account(id: Identity, db: web::Data<Database>) -> Result<HttpResponse, AWError> {
-    id.identity().ok_or(ServerError::UnauthorizedError)?;
-    let result = web::block(move || db.get_accounts()).await?;
+async fn claims(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_claims(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(claim::outstanding(result)))
+}
+
+async fn claims_csv(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let now = Utc::now();
+    let content = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+            ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+        })?;
+        let claims = claim::outstanding(db.get_claims(session.ledger)?);
+
+        claim::to_csv(&claims, timezone)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"claims-{}.csv\"",
+                now.format("%Y%m%d")
+            ),
+        )
+        .body(content))
+}
+
+async fn goals(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let result = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+            ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+        })?;
+        let today = now.local_date(timezone);
+        let since = Timestamp::from_local_date(goal::recent_since(today), timezone);
+        let accounts = db.get_accounts(session.ledger)?;
+        let mut goals: Vec<GoalProgress> = Vec::new();
+        for saving in db.get_goals(session.ledger)? {
+            let account = match accounts.iter().find(|a| a.name == saving.account) {
+                Some(account) => account,
+                None => continue,
+            };
+            let recent: i64 = db
+                .balance_changes(session.ledger, &saving.account, since.millis())?
+                .iter()
+                .map(|(_, amount)| amount)
+                .sum();
+            goals.push(goal::progress(
+                &saving,
+                &account.currency,
+                account.balance,
+                recent,
+                today,
+            ));
+        }
+
+        Ok(goals)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn set_goal(
+    session: Session,
+    db: web::Data<Store>,
+    goal: web::Json<Goal>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    goal.validate().map_err(ServerError::from)?;
+    web::block(move || db.set_goal(session.ledger, &goal))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn recurring(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_recurring(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
 
     Ok(HttpResponse::Ok().json(result))
 }
 
+async fn set_recurring(
+    session: Session,
+    db: web::Data<Store>,
+    item: web::Json<RecurringOperation>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    item.validate().map_err(ServerError::from)?;
+    web::block(move || db.set_recurring(session.ledger, &item))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn forecast(
+    session: Session,
+    db: web::Data<Store>,
+    query: web::Query<ForecastQuery>,
+) -> Result<HttpResponse, AWError> {
+    query.validate().map_err(ServerError::from)?;
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let result = web::block(move || -> Result<_, ServerError> {
+        let settings = db.get_settings(session.ledger)?;
+        let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+            ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+        })?;
+        let today = now.local_date(timezone);
+        let tomorrow = today + Duration::days(1);
+        let last = installment::add_months(today, query.months as i32);
+        let scheduled_from = Timestamp::from_local_date(tomorrow, timezone).millis();
+        let since = match query.history {
+            true => Timestamp::from_local_date(forecast::history_since(today), timezone).millis(),
+            false => scheduled_from,
+        };
+        let (history, scheduled): (Vec<Operation>, Vec<Operation>) = db
+            .operations_since(session.ledger, since)?
+            .into_iter()
+            .partition(|operation| operation.datetime < scheduled_from);
+        let accounts = db.get_accounts(session.ledger)?;
+        let recurring = db.get_recurring(session.ledger)?;
+
+        let mut expected = forecast::recurring(&recurring, tomorrow, last);
+        for plan in db.get_installment_plans(session.ledger)? {
+            let installments = installment::schedule(&plan);
+            expected.extend(forecast::repayments(&plan, &installments, tomorrow));
+        }
+        let spending = match query.history {
+            true => forecast::average_spending(&history, &scheduled, &recurring, &accounts),
+            false => Vec::new(),
+        };
+        expected.extend(forecast::spending_flows(&spending, today, last));
+        let mut cards = Vec::new();
+        for card in db.get_credit_cards(session.ledger)? {
+            let account = match accounts.iter().find(|a| a.name == card.account) {
+                Some(account) if card.pay_from.is_some() => account,
+                _ => continue,
+            };
+            let statement = current_statement(
+                db.get_ref().as_ref(),
+                session.ledger,
+                &card,
+                account,
+                today,
+                timezone,
+            )?;
+            cards.push((card, statement));
+        }
+
+        Ok(Forecast {
+            first_date: tomorrow.format("%Y-%m-%d").to_string(),
+            last_date: last.format("%Y-%m-%d").to_string(),
+            accounts: forecast::forecast(
+                today,
+                last,
+                &accounts,
+                &forecast::scheduled(&scheduled, timezone),
+                &expected,
+                &cards,
+            ),
+            spending,
+        })
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn installments(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || -> Result<_, ServerError> {
+        let accounts = db.get_accounts(session.ledger)?;
+        let schedules: Vec<InstallmentSchedule> = db
+            .get_installment_plans(session.ledger)?
+            .into_iter()
+            .map(|plan| {
+                let installments = installment::schedule(&plan);
+                let repaid: i64 = installments
+                    .iter()
+                    .filter(|installment| installment.paid)
+                    .map(|installment| installment.principal)
+                    .sum();
+                let currency = accounts
+                    .iter()
+                    .find(|account| account.name == plan.account)
+                    .map(|account| account.currency.clone())
+                    .unwrap_or_default();
+                InstallmentSchedule {
+                    remaining: plan.principal - repaid,
+                    plan,
+                    currency,
+                    installments,
+                }
+            })
+            .collect();
+
+        Ok(schedules)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_installment_plan(
+    session: Session,
+    db: web::Data<Store>,
+    plan: web::Json<InstallmentPlan>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    plan.validate().map_err(ServerError::from)?;
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    web::block(move || -> Result<_, ServerError> {
+        db.add_installment_plan(session.ledger, &plan)?;
+        record_due_repayments(
+            db.get_ref().as_ref(),
+            session.ledger,
+            &[plan.into_inner()],
+            now,
+        )
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn repay_installments(
+    session: Session,
+    db: web::Data<Store>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+    let count = web::block(move || -> Result<_, ServerError> {
+        let plans = db.get_installment_plans(session.ledger)?;
+        record_due_repayments(db.get_ref().as_ref(), session.ledger, &plans, now)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(RepaymentsRecorded { count }))
+}
+
+/// Records every repayment of the plans due by today in the ledger's timezone that isn't yet,
+/// returning how many there were. Later ones are left to the forecast until they fall due.
+fn record_due_repayments(
+    db: &dyn Storage,
+    ledger: i64,
+    plans: &[InstallmentPlan],
+    now: Timestamp,
+) -> Result<usize, ServerError> {
+    let settings = db.get_settings(ledger)?;
+    let timezone = parse_timezone(&settings.timezone).ok_or_else(|| {
+        ServerError::InternalError(format!("Unknown timezone {}.", settings.timezone))
+    })?;
+    let today = local_today(&settings, now)?;
+    let mut count = 0;
+    for plan in plans {
+        for due in installment::schedule(plan)
+            .iter()
+            .filter(|installment| !installment.paid && installment.due_date <= today)
+        {
+            let operation = installment::repayment(plan, due, timezone);
+            operation.validate(now.millis())?;
+            db.record_repayment(ledger, &plan.name, due.number, &operation)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Records repayments in every ledger as they fall due, checking daily.
+async fn record_all_due_repayments(store: Store) {
+    let mut daily = actix_rt::time::interval(std::time::Duration::from_secs(24 * 60 * 60));
+    loop {
+        daily.tick().await;
+        let db = store.clone();
+        let result = web::block(move || -> Result<usize, ServerError> {
+            let now = Timestamp::from_millis(Utc::now().timestamp_millis());
+            let mut count = 0;
+            for ledger in db.ledger_ids()? {
+                let plans = db.get_installment_plans(ledger)?;
+                count += record_due_repayments(db.as_ref(), ledger, &plans, now)?;
+            }
+
+            Ok(count)
+        })
+        .await;
+        match result.map_err(ServerError::from) {
+            Ok(0) => (),
+            Ok(count) => info!("Recorded {} installment repayments.", count),
+            Err(e) => error!("Failed to record installment repayments: {}", e),
+        }
+    }
+}
+
+async fn add_category(
+    session: Session,
+    db: web::Data<Store>,
+    category: web::Json<Category>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Editor)?;
+    category.validate().map_err(ServerError::from)?;
+    web::block(move || db.add_category(session.ledger, &category))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn categories(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let result = web::block(move || db.get_categories(session.ledger))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn export_ledger(session: Session, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let now = Utc::now();
+    let export = web::block(move || db.export_ledger(session.ledger, now.timestamp_millis()))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok()
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"ledger-{}.json\"",
+                now.format("%Y%m%d")
+            ),
+        )
+        .json(export))
+}
+
+async fn import_ledger(
+    session: Session,
+    db: web::Data<Store>,
+    export: web::Json<LedgerExport>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Owner)?;
+    export
+        .validate(Utc::now().timestamp_millis())
+        .map_err(ServerError::from)?;
+    web::block(move || db.import_ledger(session.ledger, &export))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+async fn search(
+    session: Session,
+    db: web::Data<Store>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, AWError> {
+    query.validate().map_err(ServerError::from)?;
+    let result = web::block(move || db.search(session.ledger, &query.terms(), SEARCH_LIMIT))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn ledgers(id: Identity, db: web::Data<Store>) -> Result<HttpResponse, AWError> {
+    let user = session::user_id(&id)?;
+    let result = web::block(move || db.get_ledgers(user))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Ok().json(result))
+}
+
+async fn add_ledger(
+    id: Identity,
+    db: web::Data<Store>,
+    ledger: web::Json<NewLedger>,
+) -> Result<HttpResponse, AWError> {
+    let user = session::user_id(&id)?;
+    ledger.validate().map_err(ServerError::from)?;
+    let result = web::block(move || -> Result<Ledger, ServerError> {
+        let ledger_id = db.add_ledger(user, &ledger)?;
+        let NewLedger {
+            name,
+            currency,
+            timezone,
+        } = ledger.into_inner();
+
+        Ok(Ledger {
+            id: ledger_id,
+            name,
+            role: Role::Owner,
+            currency,
+            timezone,
+        })
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().json(result))
+}
+
+async fn update_settings(
+    session: Session,
+    db: web::Data<Store>,
+    settings: web::Json<LedgerSettings>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Owner)?;
+    settings.validate().map_err(ServerError::from)?;
+    web::block(move || db.update_settings(session.ledger, &settings))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn set_member(
+    session: Session,
+    db: web::Data<Store>,
+    member: web::Json<LedgerMember>,
+) -> Result<HttpResponse, AWError> {
+    session.require(Role::Owner)?;
+    web::block(move || db.set_member(session.ledger, &member))
+        .await
+        .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn add_user(
+    id: Identity,
+    db: web::Data<Store>,
+    user: web::Json<NewUser>,
+) -> Result<HttpResponse, AWError> {
+    let admin = session::user_id(&id)?;
+    web::block(move || -> Result<(), ServerError> {
+        require_admin(db.get_ref().as_ref(), admin)?;
+        db.add_user(&user.name, &password::hash(&user.token)?)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// Only registered with SQLite storage, which is what `Backups` snapshots.
+async fn create_backup(
+    id: Identity,
+    db: web::Data<SqliteStorage>,
+    backups: web::Data<Backups>,
+) -> Result<HttpResponse, AWError> {
+    let admin = session::user_id(&id)?;
+    let file = web::block(move || -> Result<BackupFile, ServerError> {
+        require_admin(db.get_ref(), admin)?;
+        backups.create(&db)
+    })
+    .await
+    .map_err(ServerError::from)?;
+
+    Ok(HttpResponse::Created().json(file))
+}
+
+fn require_admin(db: &dyn Storage, user: i64) -> Result<(), ServerError> {
+    match db.get_user_by_id(user)? {
+        Some(user) if user.admin => Ok(()),
+        _ => Err(ServerError::ForbiddenError),
+    }
+}
+
+/// Registers the handlers that only need a `Store` and `Attachments`, shared by `main` and the
+/// handler tests.
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/login").route(web::post().to(login)))
+        .service(
+            web::resource("/totp")
+                .route(web::post().to(enrol_totp))
+                .route(web::put().to(confirm_totp)),
+        )
+        .service(web::resource("/operation").route(web::post().to(operation)))
+        .service(
+            web::resource("/transaction/import")
+                // Statements run to thousands of transactions.
+                .app_data(json_config(IMPORT_LIMIT))
+                .route(web::post().to(import_transactions)),
+        )
+        .service(
+            web::resource("/operation/{id}/attachment")
+                .route(web::get().to(attachments))
+                .route(web::post().to(add_attachment)),
+        )
+        .service(web::resource("/operation/{id}/share").route(web::put().to(share_operation)))
+        .service(web::resource("/operation/{id}/claim").route(web::put().to(set_claim)))
+        .service(web::resource("/reimbursement").route(web::post().to(add_reimbursement)))
+        .service(web::resource("/claim").route(web::get().to(claims)))
+        .service(web::resource("/claim/csv").route(web::get().to(claims_csv)))
+        .service(
+            web::resource("/attachment/{id}")
+                .route(web::get().to(download_attachment))
+                .route(web::delete().to(delete_attachment)),
+        )
+        .service(
+            web::resource("/ledger")
+                .route(web::get().to(ledgers))
+                .route(web::post().to(add_ledger)),
+        )
+        .service(web::resource("/export").route(web::get().to(export_ledger)))
+        .service(
+            web::resource("/import")
+                // Exports are far bigger than any other request body.
+                .app_data(json_config(IMPORT_LIMIT))
+                .route(web::post().to(import_ledger)),
+        )
+        .service(web::resource("/search").route(web::get().to(search)))
+        .service(web::resource("/report").route(web::get().to(report)))
+        .service(
+            web::resource("/rate")
+                .route(web::get().to(rates))
+                .route(web::post().to(add_rate)),
+        )
+        .service(
+            web::resource("/rate/import")
+                .app_data(web::PayloadConfig::new(IMPORT_LIMIT))
+                .route(web::post().to(import_rates)),
+        )
+        .service(
+            web::resource("/security")
+                .route(web::get().to(securities))
+                .route(web::post().to(add_security)),
+        )
+        .service(
+            web::resource("/trade")
+                .route(web::get().to(trades))
+                .route(web::post().to(add_trade)),
+        )
+        .service(
+            web::resource("/price")
+                .route(web::get().to(prices))
+                .route(web::post().to(add_price)),
+        )
+        .service(web::resource("/investment").route(web::get().to(investments)))
+        .service(
+            web::resource("/installment")
+                .route(web::get().to(installments))
+                .route(web::post().to(add_installment_plan)),
+        )
+        .service(web::resource("/installment/repay").route(web::post().to(repay_installments)))
+        .service(
+            web::resource("/card")
+                .route(web::get().to(cards))
+                .route(web::put().to(set_card)),
+        )
+        .service(
+            web::resource("/recurring")
+                .route(web::get().to(recurring))
+                .route(web::put().to(set_recurring)),
+        )
+        .service(web::resource("/forecast").route(web::get().to(forecast)))
+        .service(
+            web::resource("/goal")
+                .route(web::get().to(goals))
+                .route(web::put().to(set_goal)),
+        )
+        .service(web::resource("/shared").route(web::get().to(shared_balances)))
+        .service(web::resource("/shared/settle").route(web::get().to(settle_up)))
+        .service(web::resource("/ledger/member").route(web::put().to(set_member)))
+        .service(web::resource("/ledger/settings").route(web::put().to(update_settings)))
+        .service(web::resource("/user").route(web::post().to(add_user)))
+        .service(
+            web::resource("/account")
+                .route(web::get().to(account))
+                .route(web::post().to(add_account)),
+        )
+        .service(
+            web::resource("/category")
+                .route(web::get().to(categories))
+                .route(web::post().to(add_category)),
+        );
+}
+
+fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(|e, _| ServerError::from(e).into())
+}
+
+fn sqlite_only(action: &str) -> ServerError {
+    ServerError::InternalError(format!(
+        "{} is only supported for SQLite databases, use pg_dump and pg_restore for PostgreSQL.",
+        action
+    ))
+}
+
 #[actix_rt::main]
 async fn main() -> Result<(), ServerError> {
     dotenv::dotenv()?;
     env_logger::init();
 
+    let mode = Mode::from_args()?;
     let cfg = ServerConfig::new()?;
     info!("Configuration is load successfully.");
 
-    let db = Database::new(cfg.db_addr);
-    db.migrate(cfg.migration_addr)?;
+    let dialect = Backend::dialect_of(&cfg.db_addr);
+    let migrations = match &cfg.migration_addr {
+        Some(dir) => migration::load_dir(dir)?,
+        None => migration::embedded(dialect)?,
+    };
+    if let Mode::Restore(file) = &mode {
+        if dialect != Dialect::Sqlite {
+            return Err(sqlite_only("Restoring"));
+        }
+        return backup::restore(file, &cfg.db_addr, &migrations);
+    }
+    let backend = Backend::open(&cfg.db_addr)?;
+    let backups = Backups::new(
+        cfg.backup_addr,
+        cfg.backup_keep.unwrap_or(backup::DEFAULT_KEEP),
+    );
+    let attachments = Attachments::new(cfg.attachment_addr);
+    if let Mode::DryRun = mode {
+        let pending = backend.pending_migrations(&migrations)?;
+        if pending.is_empty() {
+            println!("The database is up to date.");
+        }
+        for migration in pending {
+            println!(
+                "Pending migration {}:\n{}",
+                migration.version, migration.sql
+            );
+        }
+        return Ok(());
+    }
+
+    backend.migrate(&migrations)?;
+    let store = backend.store();
+    if let Some(version) = store.current_db_version()? {
+        info!("The database is at version {}.", version.version);
+    }
+    // Snapshots are SQLite files, PostgreSQL has pg_dump for that.
+    let sqlite = match backend {
+        Backend::Sqlite(db) => Some(db),
+        Backend::Postgres(_) => None,
+    };
+    match mode {
+        Mode::MigrateOnly => return Ok(()),
+        Mode::Backup => {
+            let db = sqlite.ok_or_else(|| sqlite_only("Backing up"))?;
+            let file = backups.create(&db)?;
+            println!("Backed up to {} ({} bytes).", file.name, file.size);
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    actix_rt::spawn(record_all_due_repayments(store.clone()));
 
     let mut key = [0u8; 128];
     rand::thread_rng().fill_bytes(&mut key);
@@ -109,18 +1452,22 @@ async fn main() -> Result<(), ServerError> {
                 CookieIdentityPolicy::new(&key)
                     .name("token")
                     .max_age(Duration::days(365).num_seconds())
+                    .same_site(SameSite::Strict)
                     .secure(false),
             ))
-            .data(db.clone())
-            .data(web::JsonConfig::default().limit(4096))
+            .wrap(csrf::Csrf)
+            .data(store.clone())
+            .data(attachments.clone())
+            .app_data(json_config(JSON_LIMIT))
             .wrap(middleware::Logger::default())
-            .service(web::resource("/login").route(web::post().to(login)))
-            .service(web::resource("/operation").route(web::post().to(operation)))
-            .service(
-                web::resource("/account")
-                    .route(web::get().to(account))
-                    .route(web::post().to(add_account)),
-            )
+            .configure(routes)
+            .configure(|cfg| {
+                if let Some(db) = &sqlite {
+                    cfg.data(db.clone())
+                        .data(backups.clone())
+                        .service(web::resource("/backup").route(web::post().to(create_backup)));
+                }
+            })
             .service(actix_files::Files::new("/", "./static/").index_file("index.html"))
     })
     .bind(cfg.bind_addr)?
@@ -129,3 +1476,1368 @@ async fn main() -> Result<(), ServerError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::cookie::Cookie;
+    use actix_web::http::StatusCode;
+    use actix_web::test;
+    use data::MemoryStorage;
+    use entities::{
+        Attachment, BalanceReport, CardStatement, ClaimedExpense, ErrorResponse,
+        InstallmentSchedule, MemberBalance, RepaymentsRecorded, SearchHit, Settlement,
+    };
+    use serde_json::json;
+    use std::sync::Arc;
+
+    /// The app as `main` builds it, minus CSRF checks, over a fresh in-memory store.
+    macro_rules! app {
+        () => {
+            test::init_service(
+                App::new()
+                    .wrap(IdentityService::new(
+                        CookieIdentityPolicy::new(&[0; 32])
+                            .name("token")
+                            .secure(false),
+                    ))
+                    .data(Arc::new(MemoryStorage::new("123456")) as Store)
+                    .data(Attachments::new(None))
+                    .app_data(json_config(JSON_LIMIT))
+                    .configure(routes),
+            )
+            .await
+        };
+    }
+
+    macro_rules! call {
+        ($app:expr, $req:expr) => {
+            test::call_service(&mut $app, $req.to_request()).await
+        };
+    }
+
+    macro_rules! login {
+        ($app:expr, $user:expr, $token:expr) => {{
+            let resp = call!(
+                $app,
+                test::TestRequest::post().uri("/login").set_json(&json!({
+                    "user": $user,
+                    "token": $token,
+                    "code": null,
+                }))
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+            resp.response()
+                .cookies()
+                .find(|cookie| cookie.name() == "token")
+                .map(Cookie::into_owned)
+                .unwrap()
+        }};
+    }
+
+    macro_rules! error {
+        ($resp:expr) => {{
+            let body = test::read_body($resp).await;
+            serde_json::from_slice::<ErrorResponse>(&body).unwrap()
+        }};
+    }
+
+    #[actix_rt::test]
+    async fn test_login() {
+        let mut app = app!();
+
+        let resp = call!(
+            app,
+            test::TestRequest::post().uri("/login").set_json(&json!({
+                "user": "admin",
+                "token": "wrong",
+                "code": null,
+            }))
+        );
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(error!(resp).code, "unauthorized");
+
+        let resp = call!(app, test::TestRequest::get().uri("/account"));
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let token = login!(app, "admin", "123456");
+        let resp = call!(app, test::TestRequest::get().uri("/account").cookie(token));
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_operations_update_balances() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Cash"] {
+            let resp = call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        let transfer = |to: &str| {
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Bank",
+                    "to": to,
+                    "comment": "ATM",
+                    "amount": 500,
+                    "datetime": Utc::now().timestamp_millis(),
+                }))
+        };
+        let resp = call!(app, transfer("Card"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("to"));
+        let resp = call!(app, transfer("Cash"));
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let accounts: Vec<Account> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/account")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let cash = accounts.iter().find(|a| a.name == "Cash").unwrap();
+        assert_eq!(cash.balance, 500);
+    }
+
+    #[actix_rt::test]
+    async fn test_split_operation() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Shop"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/category")
+                .cookie(token.clone())
+                .set_json(&json!({ "name": "Groceries" }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let categories: Vec<Category> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/category")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(categories[0].name, "Groceries");
+
+        let receipt = |second: i64| {
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Bank",
+                    "to": "Shop",
+                    "comment": "Supermarket",
+                    "amount": 1000,
+                    "datetime": Utc::now().timestamp_millis(),
+                    "legs": [
+                        { "category": "Groceries", "amount": 800, "memo": "Bread" },
+                        { "amount": second },
+                    ],
+                }))
+        };
+        let resp = call!(app, receipt(100));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("legs"));
+        let resp = call!(app, receipt(200));
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let accounts: Vec<Account> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/account")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let shop = accounts.iter().find(|a| a.name == "Shop").unwrap();
+        assert_eq!(shop.balance, 1000);
+    }
+
+    #[actix_rt::test]
+    async fn test_report() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for (name, currency) in &[("Bank", "CNY"), ("Dollars", "USD"), ("Pounds", "GBP")] {
+            let resp = call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0, "currency": currency }))
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        let exchange = |to_amount: Option<i64>| {
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Bank",
+                    "to": "Dollars",
+                    "comment": "",
+                    "amount": 7250,
+                    "to_amount": to_amount,
+                    "datetime": Utc::now().timestamp_millis(),
+                }))
+        };
+        let resp = call!(app, exchange(None));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("to_amount"));
+        let resp = call!(app, exchange(Some(1000)));
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let report = |query: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/report{}", query))
+                .cookie(token.clone())
+        };
+        let base: BalanceReport = test::read_response_json(&mut app, report("").to_request()).await;
+        assert_eq!(base.base, "CNY");
+        assert_eq!(base.total, 0);
+        assert_eq!(base.unconverted, vec!["GBP".to_owned()]);
+        let usd: BalanceReport =
+            test::read_response_json(&mut app, report("?base=USD").to_request()).await;
+        assert_eq!(usd.accounts[1].converted, Some(1000));
+        assert_eq!(usd.unconverted, vec!["GBP".to_owned()]);
+
+        let resp = call!(app, report("?base=usd"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("base"));
+    }
+
+    #[actix_rt::test]
+    async fn test_ledger_settings() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        let update = |timezone: &str| {
+            test::TestRequest::put()
+                .uri("/ledger/settings")
+                .cookie(token.clone())
+                .set_json(&json!({ "name": "Home", "currency": "CNY", "timezone": timezone }))
+        };
+        let resp = call!(app, update("GMT+8"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("timezone"));
+        let resp = call!(app, update("Europe/Lisbon"));
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let ledgers: Vec<Ledger> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/ledger")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(ledgers[0].name, "Home");
+        assert_eq!(ledgers[0].timezone, "Europe/Lisbon");
+
+        let created: Ledger = test::read_response_json(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/ledger")
+                .cookie(token.clone())
+                .set_json(&json!({ "name": "Travel" }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(created.timezone, "Asia/Shanghai");
+    }
+
+    #[actix_rt::test]
+    async fn test_exchange_rates() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/account")
+                .cookie(token.clone())
+                .set_json(&json!({ "name": "Dollars", "balance": 0, "currency": "USD" }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let add = |rate: i64| {
+            test::TestRequest::post()
+                .uri("/rate")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "base": "USD",
+                    "quote": "CNY",
+                    "date": "2024-01-02",
+                    "rate": rate,
+                }))
+        };
+        let resp = call!(app, add(0));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("rate"));
+        let resp = call!(app, add(705_000_000));
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let import = |csv: &str| {
+            test::TestRequest::post()
+                .uri("/rate/import")
+                .cookie(token.clone())
+                .header(header::CONTENT_TYPE, "text/csv")
+                .set_payload(csv.to_owned())
+        };
+        let resp = call!(
+            app,
+            import("base,quote,date,rate\nUSD,CNY,2024-03-01,7.1\nUSD,CNY,x,1")
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(error!(resp).message.starts_with("Line 3:"));
+        let imported: RatesImported = test::read_response_json(
+            &mut app,
+            import("base,quote,date,rate\nUSD,CNY,2024-03-01,7.1\nHKD,CNY,2024-03-01,0.91")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(imported.count, 2);
+
+        let rates: Vec<ExchangeRate> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/rate")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(rates.len(), 3);
+
+        let report: BalanceReport = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/report")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(report.accounts[0].rate, Some(7.1));
+        assert_eq!(report.accounts[0].rate_date.as_deref(), Some("2024-03-01"));
+    }
+
+    #[actix_rt::test]
+    async fn test_investments() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        let post = |uri: &str, body: serde_json::Value| {
+            test::TestRequest::post()
+                .uri(uri)
+                .cookie(token.clone())
+                .set_json(&body)
+        };
+        let requests = vec![
+            post(
+                "/account",
+                json!({ "name": "Broker", "balance": 0, "currency": "USD" }),
+            ),
+            post(
+                "/security",
+                json!({ "symbol": "VTI", "name": "Vanguard Total Stock Market", "currency": "USD" }),
+            ),
+            post(
+                "/rate",
+                json!({ "base": "USD", "quote": "CNY", "date": "2024-01-01", "rate": 700_000_000 }),
+            ),
+            post(
+                "/price",
+                json!({ "symbol": "VTI", "date": "2024-01-02", "price": 24_500_000_000i64 }),
+            ),
+        ];
+        for request in requests {
+            assert_eq!(call!(app, request).status(), StatusCode::CREATED);
+        }
+        let trade = |side: &str, quantity: i64, amount: i64| {
+            post(
+                "/trade",
+                json!({
+                    "account": "Broker",
+                    "symbol": "VTI",
+                    "side": side,
+                    "quantity": quantity,
+                    "amount": amount,
+                    "datetime": Utc::now().timestamp_millis(),
+                }),
+            )
+        };
+        let resp = call!(app, trade("buy", 30_000, 60_000));
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = call!(app, trade("sell", 40_000, 90_000));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("quantity"));
+
+        let get = |uri: &str| test::TestRequest::get().uri(uri).cookie(token.clone());
+        let holdings: Vec<Holding> =
+            test::read_response_json(&mut app, get("/investment").to_request()).await;
+        assert_eq!(holdings.len(), 1);
+        assert_eq!(holdings[0].cost, 60_000);
+        assert_eq!(holdings[0].value, 73_500);
+        assert_eq!(holdings[0].unrealised, Some(13_500));
+
+        // The account paid $600.00 for what's now worth $735.00, at 7 CNY to the dollar.
+        let report: BalanceReport =
+            test::read_response_json(&mut app, get("/report").to_request()).await;
+        assert_eq!(report.accounts[0].converted, Some(-420_000));
+        assert_eq!(report.holdings[0].name, "Broker VTI");
+        assert_eq!(report.holdings[0].converted, Some(514_500));
+        assert_eq!(report.total, 94_500);
+    }
+
+    #[actix_rt::test]
+    async fn test_credit_cards() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Visa", "Shop"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        // Spent well before the latest statement.
+        let spent = Utc::now() - Duration::days(70);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Visa",
+                    "to": "Shop",
+                    "comment": "",
+                    "amount": 12_345,
+                    "datetime": spent.timestamp_millis(),
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let put = |body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri("/card")
+                .cookie(token.clone())
+                .set_json(&body)
+        };
+        let card = json!({ "account": "Visa", "statement_day": 5, "due_day": 25, "limit": 50_000 });
+        assert_eq!(call!(app, put(card)).status(), StatusCode::NO_CONTENT);
+        let resp = call!(
+            app,
+            put(json!({ "account": "Amex", "statement_day": 5, "due_day": 25, "limit": 0 }))
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("account"));
+        let resp = call!(
+            app,
+            put(json!({ "account": "Visa", "statement_day": 31, "due_day": 25, "limit": 0 }))
+        );
+        assert_eq!(error!(resp).field.as_deref(), Some("statement_day"));
+
+        let statements: Vec<CardStatement> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/card")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(statements.len(), 1);
+        let statement = &statements[0];
+        assert_eq!(statement.account, "Visa");
+        assert!(statement.statement_date.ends_with("-05"));
+        assert!(statement.due_date.ends_with("-25"));
+        assert_eq!(statement.statement_balance, 12_345);
+        assert_eq!(statement.minimum_payment, 1_235);
+        assert_eq!(statement.owed, 12_345);
+        assert_eq!(statement.available_credit, 50_000 - 12_345);
+    }
+
+    #[actix_rt::test]
+    async fn test_shared_expenses() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Shop"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        let mut ids = Vec::new();
+        for amount in &[9_000, 3_000] {
+            let created: OperationCreated = test::read_response_json(
+                &mut app,
+                test::TestRequest::post()
+                    .uri("/operation")
+                    .cookie(token.clone())
+                    .set_json(&json!({
+                        "from": "Bank",
+                        "to": "Shop",
+                        "comment": "",
+                        "amount": amount,
+                        "datetime": Utc::now().timestamp_millis(),
+                    }))
+                    .to_request(),
+            )
+            .await;
+            ids.push(created.id);
+        }
+
+        let share = |id: i64, body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri(&format!("/operation/{}/share", id))
+                .cookie(token.clone())
+                .set_json(&body)
+        };
+        let groceries = json!({
+            "paid_by": "alice",
+            "method": "percent",
+            "shares": [
+                { "member": "alice", "share": 3_334 },
+                { "member": "bob", "share": 3_333 },
+                { "member": "carol", "share": 3_333 },
+            ],
+        });
+        let resp = call!(app, share(ids[0], groceries));
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let dinner = json!({
+            "paid_by": "bob",
+            "method": "shares",
+            "shares": [{ "member": "alice", "share": 1 }, { "member": "bob", "share": 2 }],
+        });
+        assert_eq!(
+            call!(app, share(ids[1], dinner.clone())).status(),
+            StatusCode::NO_CONTENT
+        );
+        let resp = call!(app, share(ids[1] + 100, dinner));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let short = json!({
+            "paid_by": "alice",
+            "method": "percent",
+            "shares": [{ "member": "alice", "share": 5_000 }],
+        });
+        let resp = call!(app, share(ids[0], short));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("shares"));
+
+        let balances: Vec<MemberBalance> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/shared")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let balances: Vec<(&str, i64)> = balances.iter().map(|b| (&*b.member, b.balance)).collect();
+        // 33.34% and 33.33% of $90.00 both round to $30.00.
+        assert_eq!(
+            balances,
+            vec![("alice", 5_000), ("bob", -2_000), ("carol", -3_000)]
+        );
+
+        let settlements: Vec<Settlement> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/shared/settle")
+                .cookie(token)
+                .to_request(),
+        )
+        .await;
+        let settlements: Vec<(&str, &str, i64)> = settlements
+            .iter()
+            .map(|s| (&*s.from, &*s.to, s.amount))
+            .collect();
+        assert_eq!(
+            settlements,
+            vec![("carol", "alice", 3_000), ("bob", "alice", 2_000)]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_claims() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Hotel", "Employer"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        let mut ids = Vec::new();
+        for (from, to, comment, amount) in &[
+            ("Bank", "Hotel", "Conference hotel", 30_000),
+            ("Bank", "Hotel", "Late checkout", 4_550),
+            ("Employer", "Bank", "Expenses", 30_000),
+        ] {
+            let created: OperationCreated = test::read_response_json(
+                &mut app,
+                test::TestRequest::post()
+                    .uri("/operation")
+                    .cookie(token.clone())
+                    .set_json(&json!({
+                        "from": from,
+                        "to": to,
+                        "comment": comment,
+                        "amount": amount,
+                        "datetime": Utc::now().timestamp_millis(),
+                    }))
+                    .to_request(),
+            )
+            .await;
+            ids.push(created.id);
+        }
+
+        let claim = |id: i64, body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri(&format!("/operation/{}/claim", id))
+                .cookie(token.clone())
+                .set_json(&body)
+        };
+        for id in &ids[..2] {
+            let resp = call!(app, claim(*id, json!({ "status": "submitted" })));
+            assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        }
+        let resp = call!(app, claim(ids[0], json!({ "status": "paid" })));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("reimbursement"));
+        let resp = call!(app, claim(ids[2] + 100, json!({ "status": "pending" })));
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/reimbursement")
+                .cookie(token.clone())
+                .set_json(&json!({ "operation": ids[2], "expenses": [ids[0]] }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let claims: Vec<ClaimedExpense> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/claim")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let outstanding: Vec<(i64, &str)> = claims
+            .iter()
+            .map(|c| (c.operation, c.comment.as_str()))
+            .collect();
+        assert_eq!(outstanding, vec![(ids[1], "Late checkout")]);
+
+        let resp = call!(
+            app,
+            test::TestRequest::get().uri("/claim/csv").cookie(token)
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        let body = test::read_body(resp).await;
+        let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with(",Hotel,Late checkout,45.50,CNY,submitted"));
+        assert_eq!(lines[2], ",,Total,45.50,CNY,");
+    }
+
+    #[actix_rt::test]
+    async fn test_forecast() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Checking", "Employer", "Shop"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        call!(
+            app,
+            test::TestRequest::post()
+                .uri("/category")
+                .cookie(token.clone())
+                .set_json(&json!({ "name": "Food" }))
+        );
+        let now = Utc::now().timestamp_millis();
+        let payday = (Utc::now() + Duration::days(10)).timestamp_millis();
+        for operation in &[
+            json!({ "from": "Employer", "to": "Checking", "amount": 100_000, "datetime": now }),
+            json!({
+                "from": "Checking",
+                "to": "Shop",
+                "amount": 30_000,
+                "datetime": now,
+                "legs": [{ "category": "Food", "amount": 30_000 }],
+            }),
+            json!({ "from": "Employer", "to": "Checking", "amount": 50_000, "datetime": payday }),
+        ] {
+            let mut operation = operation.clone();
+            operation["comment"] = json!("");
+            let resp = call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/operation")
+                    .cookie(token.clone())
+                    .set_json(&operation)
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+
+        let get = |uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .cookie(token.clone())
+                .to_request()
+        };
+        let forecast: Forecast =
+            test::read_response_json(&mut app, get("/forecast?months=1")).await;
+        assert!(forecast.spending.is_empty());
+        let checking = &forecast.accounts[0];
+        assert_eq!(checking.account, "Checking");
+        assert!(checking.balances.len() >= 28);
+        // Payday is taken out of today's balance and paid on its day.
+        assert_eq!(checking.balances[0], 70_000);
+        assert_eq!(checking.balances.last(), Some(&120_000));
+        assert_eq!(checking.below_zero_date, None);
+
+        let forecast: Forecast =
+            test::read_response_json(&mut app, get("/forecast?months=1&history=true")).await;
+        let spending: Vec<(&str, &str, i64)> = forecast
+            .spending
+            .iter()
+            .map(|s| (&*s.account, &*s.category, s.monthly))
+            .collect();
+        assert_eq!(spending, vec![("Checking", "Food", 10_000)]);
+        assert!(forecast.accounts[0].balances[0] < 70_000);
+
+        // A monthly item starting tomorrow is paid once in each month of the horizon.
+        let rent = json!({
+            "name": "Rent",
+            "from": "Checking",
+            "to": "Shop",
+            "amount": 1_000,
+            "frequency": "monthly",
+            "start": forecast.first_date,
+        });
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/recurring")
+                .cookie(token.clone())
+                .set_json(&rent)
+        );
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let recurring: Vec<RecurringOperation> =
+            test::read_response_json(&mut app, get("/recurring")).await;
+        assert_eq!(recurring[0].name, "Rent");
+        let forecast: Forecast =
+            test::read_response_json(&mut app, get("/forecast?months=6")).await;
+        let shop = &forecast.accounts[2];
+        let paid = shop
+            .balances
+            .iter()
+            .zip(shop.balances.iter().skip(1))
+            .filter(|(before, after)| after > before)
+            .count();
+        assert_eq!(shop.balances[0], 31_000);
+        assert_eq!(paid + 1, 6);
+        assert_eq!(shop.balances.last(), Some(&36_000));
+
+        let mut unknown = rent.clone();
+        unknown["to"] = json!("Landlord");
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/recurring")
+                .cookie(token.clone())
+                .set_json(&unknown)
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("to"));
+
+        let resp = call!(
+            app,
+            test::TestRequest::get()
+                .uri("/forecast?months=13")
+                .cookie(token)
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("months"));
+    }
+
+    #[actix_rt::test]
+    async fn test_goals() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Savings"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Bank",
+                    "to": "Savings",
+                    "comment": "Monthly saving",
+                    "amount": 90_000,
+                    "datetime": Utc::now().timestamp_millis(),
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let set_goal = |body: serde_json::Value| {
+            test::TestRequest::put()
+                .uri("/goal")
+                .cookie(token.clone())
+                .set_json(&body)
+        };
+        let resp = call!(
+            app,
+            set_goal(json!({
+                "name": "Car",
+                "account": "Savings",
+                "target": 600_000,
+                "target_date": "2099-12-31",
+            }))
+        );
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = call!(
+            app,
+            set_goal(json!({
+                "name": "House",
+                "account": "Mortgage",
+                "target": 600_000,
+                "target_date": "2099-12-31",
+            }))
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("account"));
+        let resp = call!(
+            app,
+            set_goal(json!({
+                "name": "House",
+                "account": "Savings",
+                "target": 0,
+                "target_date": "2099-12-31",
+            }))
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("target"));
+
+        let goals: Vec<GoalProgress> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/goal")
+                .cookie(token)
+                .to_request(),
+        )
+        .await;
+        assert_eq!(goals.len(), 1);
+        let car = &goals[0];
+        assert_eq!(car.goal.name, "Car");
+        assert_eq!((car.saved, car.remaining), (90_000, 510_000));
+        // The $900.00 saved today averaged over three months.
+        assert_eq!(car.monthly_contribution, 30_000);
+        assert!(car.projected_date.is_some());
+        assert!(car.on_track);
+    }
+
+    #[actix_rt::test]
+    async fn test_installments() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Card", "Bank", "Fees"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        // Due two days ago wherever the ledger is, with the next a month later.
+        let first_due = (Utc::now() - Duration::days(2))
+            .format("%Y-%m-%d")
+            .to_string();
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/installment")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "name": "Phone",
+                    "account": "Card",
+                    "from": "Bank",
+                    "cost_account": "Fees",
+                    "principal": 1_200_000,
+                    "fee": 3_600,
+                    "term": 24,
+                    "first_due": first_due,
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/installment")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "name": "Loan",
+                    "account": "Card",
+                    "from": "Bank",
+                    "principal": 100_000,
+                    "annual_rate": 5.0,
+                    "term": 12,
+                    "first_due": first_due,
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("cost_account"));
+
+        let get = || {
+            test::TestRequest::get()
+                .uri("/installment")
+                .cookie(token.clone())
+                .to_request()
+        };
+        let schedules: Vec<InstallmentSchedule> = test::read_response_json(&mut app, get()).await;
+        assert_eq!(schedules.len(), 1);
+        let schedule = &schedules[0];
+        assert_eq!(schedule.currency, "CNY");
+        // Only the repayment already due was recorded when the plan was added.
+        assert_eq!(schedule.plan.paid, 1);
+        assert_eq!(schedule.installments.len(), 24);
+        assert_eq!(schedule.remaining, 1_150_000);
+        let hits: Vec<SearchHit> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/search?q=phone")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].operation.datetime < Utc::now().timestamp_millis());
+
+        // Nothing more is due yet.
+        let recorded: RepaymentsRecorded = test::read_response_json(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/installment/repay")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(recorded.count, 0);
+        let accounts: Vec<Account> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/account")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let balances: Vec<i64> = accounts.iter().map(|account| account.balance).collect();
+        assert_eq!(balances, vec![50_000, -53_600, 3_600]);
+
+        // The next two repayments fall due within two months, which the forecast expects.
+        let forecast: Forecast = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/forecast?months=2")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(forecast.accounts[1].balances[0], -53_600);
+        assert_eq!(forecast.accounts[1].balances.last(), Some(&-160_800));
+        assert_eq!(forecast.accounts[0].balances.last(), Some(&150_000));
+    }
+
+    #[actix_rt::test]
+    async fn test_attachments() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["Bank", "Cash"] {
+            call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+        }
+        let created: OperationCreated = test::read_response_json(
+            &mut app,
+            test::TestRequest::post()
+                .uri("/operation")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "from": "Bank",
+                    "to": "Cash",
+                    "comment": "Sofa",
+                    "amount": 500,
+                    "datetime": Utc::now().timestamp_millis(),
+                }))
+                .to_request(),
+        )
+        .await;
+
+        let upload = |name: &str, content_type: &str| {
+            let body = format!(
+                "--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: {}\r\n\r\nPNG DATA\r\n--XYZ--\r\n",
+                name, content_type
+            );
+            test::TestRequest::post()
+                .uri(&format!("/operation/{}/attachment", created.id))
+                .cookie(token.clone())
+                .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XYZ")
+                .set_payload(body)
+        };
+        let resp = call!(app, upload("page.html", "text/html"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("content_type"));
+        let attachment: Attachment = test::read_response_json(
+            &mut app,
+            upload("photos/receipt.png", "image/png").to_request(),
+        )
+        .await;
+        assert_eq!(attachment.name, "receipt.png");
+        assert_eq!(attachment.size, 8);
+
+        let listed: Vec<Attachment> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri(&format!("/operation/{}/attachment", created.id))
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        assert_eq!(listed.len(), 1);
+
+        let download = || {
+            test::TestRequest::get()
+                .uri(&format!("/attachment/{}", attachment.id))
+                .cookie(token.clone())
+        };
+        let resp = call!(app, download());
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        assert_eq!(test::read_body(resp).await, "PNG DATA");
+
+        let resp = call!(
+            app,
+            test::TestRequest::delete()
+                .uri(&format!("/attachment/{}", attachment.id))
+                .cookie(token.clone())
+        );
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(call!(app, download()).status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_search() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        let search = |q: &str| {
+            test::TestRequest::get()
+                .uri(&format!("/search?q={}", q))
+                .cookie(token.clone())
+        };
+        let resp = call!(app, search("%20*"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("q"));
+
+        let hits: Vec<SearchHit> =
+            test::read_response_json(&mut app, search("ikea").to_request()).await;
+        assert!(hits.is_empty());
+
+        for name in &["Alipay", "IKEA Store"] {
+            let resp = call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        let transaction = |to: &str, description: &str| {
+            json!({
+                "datetime": 1_585_699_200_000i64,
+                "from": "Alipay",
+                "to": to,
+                "amount": -129_900,
+                "description": description,
+            })
+        };
+        let import = |transactions: serde_json::Value| {
+            test::TestRequest::post()
+                .uri("/transaction/import")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "account": "Alipay",
+                    "counterpart": "IKEA Store",
+                    "counterparts": { "Lamps Ltd": "Unknown" },
+                    "transactions": transactions,
+                }))
+        };
+        // Nothing is recorded when one of the accounts is missing.
+        let resp = call!(
+            app,
+            import(json!([
+                transaction("IKEA", "Sofa"),
+                transaction("Lamps Ltd", "Lamp")
+            ]))
+        );
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("transactions"));
+        let hits: Vec<SearchHit> =
+            test::read_response_json(&mut app, search("sofa").to_request()).await;
+        assert!(hits.is_empty());
+
+        let resp = call!(
+            app,
+            import(json!([transaction("IKEA", "KIVIK sofa, grey")]))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let hits: Vec<SearchHit> =
+            test::read_response_json(&mut app, search("kivik").to_request()).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].operation.comment, "KIVIK sofa, grey");
+        assert_eq!(hits[0].operation.amount, 129_900);
+        assert_eq!(hits[0].snippet, "<mark>KIVIK</mark> sofa, grey");
+    }
+
+    #[actix_rt::test]
+    async fn test_import_transactions() {
+        let mut app = app!();
+        let token = login!(app, "admin", "123456");
+        for name in &["CGB", "Coffee", "Unsorted"] {
+            let resp = call!(
+                app,
+                test::TestRequest::post()
+                    .uri("/account")
+                    .cookie(token.clone())
+                    .set_json(&json!({ "name": name, "balance": 0 }))
+            );
+            assert_eq!(resp.status(), StatusCode::CREATED);
+        }
+        let statement = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../transaction/processor/1.csv"
+        );
+        let transactions = transaction_processor::parse(
+            statement,
+            transaction_processor::ParserConfig::new(
+                transaction_processor::TransactionSource::CgbCredit,
+                None,
+                "CGB".to_owned(),
+                Tz::Asia__Shanghai,
+            ),
+        )
+        .unwrap();
+        let import = |account: &str| {
+            test::TestRequest::post()
+                .uri("/transaction/import")
+                .cookie(token.clone())
+                .set_json(&json!({
+                    "account": account,
+                    "counterpart": "Unsorted",
+                    "counterparts": { "星巴克咖啡": "Coffee" },
+                    "transactions": transactions,
+                }))
+        };
+        let resp = call!(app, import("Alipay"));
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error!(resp).field.as_deref(), Some("transactions"));
+
+        let imported: TransactionsImported =
+            test::read_response_json(&mut app, import("CGB").to_request()).await;
+        assert_eq!((imported.count, imported.skipped), (3, 0));
+        // Importing the statement again records nothing new.
+        let imported: TransactionsImported =
+            test::read_response_json(&mut app, import("CGB").to_request()).await;
+        assert_eq!((imported.count, imported.skipped), (0, 3));
+
+        let accounts: Vec<Account> = test::read_response_json(
+            &mut app,
+            test::TestRequest::get()
+                .uri("/account")
+                .cookie(token.clone())
+                .to_request(),
+        )
+        .await;
+        let balances: Vec<(&str, i64)> = accounts
+            .iter()
+            .map(|account| (&*account.name, account.balance))
+            .collect();
+        assert_eq!(
+            balances,
+            vec![("CGB", 86_560), ("Coffee", 3_500), ("Unsorted", -90_060)]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_viewers_cannot_edit() {
+        let mut app = app!();
+        let admin = login!(app, "admin", "123456");
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/user")
+                .cookie(admin.clone())
+                .set_json(&json!({ "name": "bob", "token": "secret" }))
+        );
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/ledger/member")
+                .cookie(admin)
+                .set_json(&json!({ "user": "bob", "role": "viewer" }))
+        );
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let bob = login!(app, "bob", "secret");
+        let resp = call!(
+            app,
+            test::TestRequest::get().uri("/account").cookie(bob.clone())
+        );
+        assert_eq!(resp.status(), StatusCode::OK);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/account")
+                .cookie(bob.clone())
+                .set_json(&json!({ "name": "Cash", "balance": 0 }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/security")
+                .cookie(bob.clone())
+                .set_json(&json!({ "symbol": "VTI", "name": "VTI", "currency": "USD" }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/card")
+                .cookie(bob.clone())
+                .set_json(
+                    &json!({ "account": "Cash", "statement_day": 1, "due_day": 20, "limit": 0 })
+                )
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/installment/repay")
+                .cookie(bob.clone())
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/operation/1/share")
+                .cookie(bob.clone())
+                .set_json(&json!({
+                    "paid_by": "bob",
+                    "method": "shares",
+                    "shares": [{ "member": "bob", "share": 1 }],
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/operation/1/claim")
+                .cookie(bob.clone())
+                .set_json(&json!({ "status": "pending" }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/reimbursement")
+                .cookie(bob.clone())
+                .set_json(&json!({ "operation": 2, "expenses": [1] }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/ledger/settings")
+                .cookie(bob.clone())
+                .set_json(&json!({ "name": "Bob's", "timezone": "UTC" }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::put()
+                .uri("/goal")
+                .cookie(bob.clone())
+                .set_json(&json!({
+                    "name": "Car",
+                    "account": "Cash",
+                    "target": 100,
+                    "target_date": "2099-12-31",
+                }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = call!(
+            app,
+            test::TestRequest::post()
+                .uri("/user")
+                .cookie(bob)
+                .set_json(&json!({ "name": "eve", "token": "x" }))
+        );
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,454 @@
+use crate::error::ServerError;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A migration version such as `1_10_0`, compared component by component so that it sorts
+/// after `1_9_0`.
+#[derive(Debug, Clone)]
+pub struct Version(Vec<u32>);
+
+impl FromStr for Version {
+    type Err = ServerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('_')
+            .map(|part| part.parse::<u32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Version)
+            .map_err(|_| ServerError::MigrationError(format!("Invalid migration version {}.", s)))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Missing trailing components count as zero, so `1_1` equals `1_1_0`.
+        let len = self.0.len().max(other.0.len());
+        (0..len)
+            .map(|i| {
+                let left = self.0.get(i).copied().unwrap_or(0);
+                let right = other.0.get(i).copied().unwrap_or(0);
+                left.cmp(&right)
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(u32::to_string).collect();
+        write!(f, "{}", parts.join("_"))
+    }
+}
+
+pub struct Migration {
+    pub version: Version,
+    pub sql: String,
+    pub checksum: String,
+}
+
+impl Migration {
+    pub fn new(version: Version, sql: String) -> Self {
+        let checksum = checksum(&sql);
+
+        Migration {
+            version,
+            sql,
+            checksum,
+        }
+    }
+}
+
+/// The files of `db/` and `db/postgres/` as they were when the server was built, see
+/// `build.rs`.
+const SQLITE: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/sqlite_migrations.rs"));
+const POSTGRES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/postgres_migrations.rs"));
+
+/// The SQL flavour a set of migrations is written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+/// Returns the migrations compiled into the binary for `dialect`, sorted by version.
+pub fn embedded(dialect: Dialect) -> Result<Vec<Migration>, ServerError> {
+    let files = match dialect {
+        Dialect::Sqlite => SQLITE,
+        Dialect::Postgres => POSTGRES,
+    };
+    let mut migrations = files
+        .iter()
+        .map(|(version, sql)| Ok(Migration::new(version.parse()?, (*sql).to_owned())))
+        .collect::<Result<Vec<_>, ServerError>>()?;
+    sort(&mut migrations)?;
+
+    Ok(migrations)
+}
+
+/// Reads every `<version>.sql` file in `path`, sorted by version.
+pub fn load_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Migration>, ServerError> {
+    let mut migrations = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("sql") {
+            continue;
+        }
+
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| ServerError::MigrationError(format!("Invalid file {:?}.", path)))?;
+        migrations.push(Migration::new(stem.parse()?, fs::read_to_string(&path)?));
+    }
+
+    sort(&mut migrations)?;
+
+    Ok(migrations)
+}
+
+/// Orders migrations by version and rejects two files claiming the same one.
+pub fn sort(migrations: &mut [Migration]) -> Result<(), ServerError> {
+    migrations.sort_by(|a, b| a.version.cmp(&b.version));
+
+    match migrations
+        .windows(2)
+        .find(|pair| pair[0].version == pair[1].version)
+    {
+        Some(pair) => Err(ServerError::MigrationError(format!(
+            "Migration {} is defined twice.",
+            pair[0].version
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checksums migrations were first released with before they were corrected, for databases
+/// that applied the earlier text to keep starting rather than be refused as modified.
+const SUPERSEDED: &[(&str, &str)] = &[
+    (
+        "1_8_0",
+        "a637db566e37c45725ff28a1d08f3fb6b8326b1add6b0ff88033983eb0b36e88",
+    ),
+    (
+        "1_8_0",
+        "207aa755b9bd4a3796c3b6f63a69f6f8969a3079bdbd55457fa5da31e7d3827c",
+    ),
+];
+
+fn superseded(version: &Version, checksum: &str) -> bool {
+    SUPERSEDED
+        .iter()
+        .any(|(v, c)| v.parse().ok().as_ref() == Some(version) && *c == checksum)
+}
+
+/// Returns the migrations still to apply after the `(version, checksum)` pairs in `applied`.
+///
+/// Refuses to go on if an applied migration was changed or removed since, or if a new one
+/// sorts before one that's already applied.
+pub fn pending<'a>(
+    applied: &[(Version, String)],
+    migrations: &'a [Migration],
+) -> Result<Vec<&'a Migration>, ServerError> {
+    for (version, checksum) in applied {
+        match migrations.iter().find(|m| &m.version == version) {
+            Some(migration) if &migration.checksum == checksum || superseded(version, checksum) => {
+            }
+            Some(_) => {
+                return Err(ServerError::MigrationError(format!(
+                    "Migration {} was modified after it was applied.",
+                    version
+                )))
+            }
+            None => {
+                return Err(ServerError::MigrationError(format!(
+                    "Migration {} was applied but is missing.",
+                    version
+                )))
+            }
+        }
+    }
+
+    let latest = applied.iter().map(|(version, _)| version).max();
+    let mut pending = Vec::new();
+    for migration in migrations {
+        if applied
+            .iter()
+            .any(|(version, _)| version == &migration.version)
+        {
+            continue;
+        }
+        if let Some(latest) = latest {
+            if &migration.version < latest {
+                return Err(ServerError::MigrationError(format!(
+                    "Migration {} is older than the applied {}.",
+                    migration.version, latest
+                )));
+            }
+        }
+        pending.push(migration);
+    }
+
+    Ok(pending)
+}
+
+pub fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{SqliteStorage, Storage};
+    use rusqlite::{Connection, NO_PARAMS};
+    use tempfile::TempDir;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        assert!(version("1_10_0") > version("1_9_0"));
+        assert!(version("2_0_0") > version("1_99_99"));
+        assert!(version("1_1") == version("1_1_0"));
+        assert!("1_a_0".parse::<Version>().is_err());
+        assert_eq!(version("1_10_0").to_string(), "1_10_0");
+    }
+
+    #[test]
+    fn test_sort_rejects_duplicates() {
+        let mut migrations = vec![
+            Migration::new(version("1_10_0"), String::new()),
+            Migration::new(version("1_9_0"), String::new()),
+        ];
+        sort(&mut migrations).unwrap();
+        assert_eq!(migrations[0].version, version("1_9_0"));
+
+        migrations.push(Migration::new(version("1_9"), String::new()));
+        assert!(sort(&mut migrations).is_err());
+    }
+
+    #[test]
+    fn test_embedded_matches_db_dir() {
+        let db = concat!(env!("CARGO_MANIFEST_DIR"), "/../db");
+        for (dialect, dir) in &[
+            (Dialect::Sqlite, db.to_owned()),
+            (Dialect::Postgres, format!("{}/postgres", db)),
+        ] {
+            let embedded = embedded(*dialect).unwrap();
+            let loaded = load_dir(dir).unwrap();
+
+            assert!(!embedded.is_empty());
+            assert_eq!(
+                embedded.iter().map(|m| &m.checksum).collect::<Vec<_>>(),
+                loaded.iter().map(|m| &m.checksum).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_superseded_checksums_are_accepted() {
+        let migrations = embedded(Dialect::Sqlite).unwrap();
+        let released = |checksum: &str| {
+            migrations
+                .iter()
+                .take_while(|m| m.version <= version("1_8_0"))
+                .map(|m| match m.version == version("1_8_0") {
+                    true => (m.version.clone(), checksum.to_owned()),
+                    false => (m.version.clone(), m.checksum.clone()),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (_, checksum) in SUPERSEDED {
+            let pending = pending(&released(checksum), &migrations).unwrap();
+            assert!(pending.iter().all(|m| m.version > version("1_8_0")));
+        }
+        assert!(pending(&released("0"), &migrations).is_err());
+    }
+
+    fn migration(v: &str, sql: &str) -> Migration {
+        Migration::new(version(v), sql.to_owned())
+    }
+
+    fn tables(dir: &TempDir) -> Vec<String> {
+        let conn = Connection::open(dir.path().join("test.db")).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap();
+        let tables = stmt
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .and_then(Iterator::collect)
+            .unwrap();
+        tables
+    }
+
+    #[test]
+    fn test_migrate_records_and_verifies() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStorage::new(dir.path().join("test.db"));
+        let mut migrations = vec![
+            migration("1_10_0", "ALTER TABLE A ADD COLUMN Y INTEGER;"),
+            migration("1_9_0", "CREATE TABLE A (X INTEGER);"),
+        ];
+        sort(&mut migrations).unwrap();
+
+        db.migrate(&migrations).unwrap();
+        assert_eq!(db.current_db_version().unwrap().unwrap().version, "1_10_0");
+        // Running again is a no-op.
+        db.migrate(&migrations).unwrap();
+
+        migrations[0] = migration("1_9_0", "CREATE TABLE A (X TEXT);");
+        assert!(db.migrate(&migrations).is_err());
+        assert!(db.migrate(&migrations[..0]).is_err());
+    }
+
+    #[test]
+    fn test_failed_migration_is_rolled_back() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStorage::new(dir.path().join("test.db"));
+        let migrations = vec![migration(
+            "1_0_0",
+            "CREATE TABLE A (X INTEGER); INSERT INTO MISSING VALUES (1);",
+        )];
+
+        assert!(db.migrate(&migrations).is_err());
+        assert_eq!(tables(&dir), vec!["MIGRATION"]);
+        assert!(db.current_db_version().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_legacy_versions_are_adopted() {
+        let dir = TempDir::new().unwrap();
+        Connection::open(dir.path().join("test.db"))
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE A (X INTEGER);
+                 CREATE TABLE DB_VERSION (VERSION TEXT, DEPLOY_AT DATETIME);
+                 INSERT INTO DB_VERSION VALUES ('1_0_0', 0);",
+            )
+            .unwrap();
+        let db = SqliteStorage::new(dir.path().join("test.db"));
+        let migrations = vec![
+            migration("1_0_0", "CREATE TABLE A (X INTEGER);"),
+            migration("1_1_0", "CREATE TABLE B (X INTEGER);"),
+        ];
+
+        // A dry run sees only the new migration and leaves the database alone.
+        let pending = db.pending_migrations(&migrations).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].version, version("1_1_0"));
+        assert_eq!(tables(&dir), vec!["A", "DB_VERSION"]);
+
+        db.migrate(&migrations).unwrap();
+        assert_eq!(tables(&dir), vec!["A", "B", "DB_VERSION", "MIGRATION"]);
+        assert!(db.pending_migrations(&migrations).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_timestamps_are_normalised_to_millis() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStorage::new(dir.path().join("test.db"));
+        let migrations = embedded(Dialect::Sqlite).unwrap();
+        let (before, after): (Vec<_>, Vec<_>) = migrations
+            .into_iter()
+            .partition(|m| m.version < version("1_8_0"));
+        db.migrate(&before).unwrap();
+        Connection::open(dir.path().join("test.db"))
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO ACCOUNT (LEDGER, NAME) VALUES (1, 'Bank'), (1, 'Cash');
+                 INSERT INTO OPERATION (LEDGER, FROM_ACCOUNT, TO_ACCOUNT, AMOUNT, DATETIME)
+                 VALUES (1, 1, 2, 100, 1585699200),
+                        (1, 1, 2, 100, 1585699200000),
+                        (1, 1, 2, 100, '2020-04-01 00:00:00');
+                 INSERT INTO ATTACHMENT
+                     (LEDGER, OPERATION, NAME, CONTENT_TYPE, SIZE, HASH, CREATED_AT)
+                 VALUES (1, 1, 'a.png', 'image/png', 1, 'h', 1585699200);",
+            )
+            .unwrap();
+
+        let mut migrations = before;
+        migrations.extend(after);
+        db.migrate(&migrations).unwrap();
+        let conn = Connection::open(dir.path().join("test.db")).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT DATETIME FROM OPERATION ORDER BY ID")
+            .unwrap();
+        let datetimes: Vec<i64> = stmt
+            .query_map(NO_PARAMS, |row| row.get(0))
+            .and_then(Iterator::collect)
+            .unwrap();
+        assert_eq!(
+            datetimes,
+            vec![1_585_670_400_000, 1_585_699_200_000, 1_585_699_200_000]
+        );
+        let created_at: i64 = conn
+            .query_row("SELECT CREATED_AT FROM ATTACHMENT", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(created_at, 1_585_699_200_000);
+        assert_eq!(
+            db.get_settings(1).unwrap().timezone,
+            entities::time::DEFAULT_TIMEZONE
+        );
+    }
+
+    #[test]
+    fn test_rates_and_prices_become_exact() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStorage::new(dir.path().join("test.db"));
+        let migrations = embedded(Dialect::Sqlite).unwrap();
+        let (before, after): (Vec<_>, Vec<_>) = migrations
+            .into_iter()
+            .partition(|m| m.version < version("1_20_0"));
+        db.migrate(&before).unwrap();
+        Connection::open(dir.path().join("test.db"))
+            .unwrap()
+            .execute_batch(
+                "INSERT INTO EXCHANGE_RATE (LEDGER, BASE, QUOTE, DATE, RATE)
+                 VALUES (1, 'USD', 'CNY', '2024-03-01', 7.1234),
+                        (1, 'JPY', 'CNY', '2024-03-01', 0.04812345);
+                 INSERT INTO SECURITY (LEDGER, SYMBOL, NAME, CURRENCY)
+                 VALUES (1, 'VTI', 'Vanguard Total Stock Market', 'USD');
+                 INSERT INTO PRICE (SECURITY, DATE, PRICE) VALUES (1, '2024-03-01', 245.125);",
+            )
+            .unwrap();
+
+        let mut migrations = before;
+        migrations.extend(after);
+        db.migrate(&migrations).unwrap();
+        let rates: Vec<i64> = db
+            .get_rates(1)
+            .unwrap()
+            .into_iter()
+            .map(|rate| rate.rate)
+            .collect();
+        assert_eq!(rates, vec![4_812_345, 712_340_000]);
+        assert_eq!(db.get_prices(1).unwrap()[0].price, 24_512_500_000);
+    }
+}
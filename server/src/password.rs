@@ -0,0 +1,56 @@
+use crate::data::Storage;
+use crate::error::ServerError;
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+
+/// Hashes the password with a fresh salt, as a PHC string carrying the salt and parameters.
+pub fn hash(password: &str) -> Result<String, ServerError> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| ServerError::InternalError(format!("Can't hash the password: {}", e)))
+}
+
+/// Checks the password against a hash from `hash`, comparing in constant time.
+pub fn verify(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Hashes the tokens users were stored with in plain text before 1_19_0, which SQL can't do.
+pub fn hash_stored_tokens(db: &dyn Storage) -> Result<(), ServerError> {
+    let tokens = db.plain_tokens()?;
+    for (user, token) in &tokens {
+        db.set_password_hash(*user, &hash(token)?)?;
+    }
+    if !tokens.is_empty() {
+        info!("Hashed the passwords of {} users.", tokens.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_are_salted_and_verified() {
+        let first = hash("secret").unwrap();
+        let second = hash("secret").unwrap();
+        assert_ne!(first, second);
+        assert!(first.starts_with("$argon2"));
+
+        assert!(verify("secret", &first));
+        assert!(verify("secret", &second));
+        assert!(!verify("Secret", &first));
+        assert!(!verify("secret", "secret"));
+    }
+}
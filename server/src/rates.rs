@@ -0,0 +1,97 @@
+use crate::error::ServerError;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use entities::money::{parse_decimal, MoneyError};
+use entities::{ExchangeRate, RATE_DIGITS};
+
+const HEADER: [&str; 4] = ["base", "quote", "date", "rate"];
+
+/// Reads exchange rates from CSV rows of base, quote, date and rate, like
+/// `USD,CNY,2024-03-01,7.1234`. A header row naming the columns is skipped. Every rate is
+/// validated, and the first bad row fails the whole file.
+pub fn parse_csv(content: &str) -> Result<Vec<ExchangeRate>, ServerError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(Trim::All)
+        .from_reader(content.as_bytes());
+    let mut rates = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let invalid = |message: &str| {
+            ServerError::validation("rates", format!("Line {}: {}", i + 1, message))
+        };
+        let record = record.map_err(|e| invalid(&e.to_string()))?;
+        if i == 0 && is_header(&record) {
+            continue;
+        }
+        if record.len() != HEADER.len() {
+            return Err(invalid("Expected base, quote, date and rate."));
+        }
+
+        let rate = ExchangeRate {
+            base: record[0].to_owned(),
+            quote: record[1].to_owned(),
+            date: record[2].to_owned(),
+            rate: parse_decimal(&record[3], RATE_DIGITS).map_err(|e| match e {
+                MoneyError::TooPrecise { .. } => invalid(&e.to_string()),
+                _ => invalid(&format!("{} is not a number.", &record[3])),
+            })?,
+        };
+        rate.validate().map_err(|e| invalid(&e.message))?;
+        rates.push(rate);
+    }
+    if rates.is_empty() {
+        return Err(ServerError::validation("rates", "The file has no rates."));
+    }
+
+    Ok(rates)
+}
+
+fn is_header(record: &StringRecord) -> bool {
+    record
+        .iter()
+        .zip(HEADER.iter())
+        .all(|(field, name)| field.eq_ignore_ascii_case(name))
+        && record.len() == HEADER.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_error(content: &str) -> String {
+        match parse_csv(content) {
+            Err(ServerError::ValidationError { field, message }) => {
+                assert_eq!(field, "rates");
+                message
+            }
+            _ => panic!("{:?} should be rejected.", content),
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let rates = parse_csv(
+            "Base,Quote,Date,Rate\nUSD, CNY, 2024-03-01, 7.1234\n\nHKD,CNY,2024-03-01,0.91\n",
+        )
+        .unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(
+            rates[0],
+            ExchangeRate {
+                base: "USD".to_owned(),
+                quote: "CNY".to_owned(),
+                date: "2024-03-01".to_owned(),
+                rate: 712_340_000,
+            }
+        );
+
+        assert!(line_error("USD,CNY,2024-03-01,7.1\nUSD,CNY,2024-03-01").starts_with("Line 2:"));
+        assert!(line_error("USD,CNY,2024-03-01,seven").contains("seven"));
+        assert!(line_error("USD,CNY,2024-03-01,7.123456789").contains("8 decimal places"));
+        assert!(line_error("USD,CNY,2024/03/01,7.1").starts_with("Line 1:"));
+        assert_eq!(
+            line_error("base,quote,date,rate\n"),
+            "The file has no rates."
+        );
+    }
+}
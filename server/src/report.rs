@@ -0,0 +1,306 @@
+use entities::money::minor_digits;
+use entities::{
+    Account, BalanceReport, Conversion, ConvertedBalance, ExchangeRate, Holding, Money, RATE_DIGITS,
+};
+
+/// Values every account and every security still held in `base`. Rates from the
+/// exchange-rate table come first, `rates` holding at most the one to use for each currency;
+/// failing that, the latest conversion between the account's currency and `base` is used, in
+/// either direction. `conversions` must be oldest first.
+pub fn balance_report(
+    base: &str,
+    accounts: Vec<Account>,
+    holdings: &[Holding],
+    rates: &[ExchangeRate],
+    conversions: &[Conversion],
+) -> BalanceReport {
+    let mut total = Money::new(0, base);
+    let mut unconverted: Vec<String> = Vec::new();
+    let mut convert = |accounts: Vec<Account>| -> Vec<ConvertedBalance> {
+        accounts
+            .into_iter()
+            .map(|account| {
+                let (rate, rate_date) = match table_rate(&account.currency, base, rates) {
+                    Some((rate, date)) => (Some(rate), Some(date.to_owned())),
+                    None => (rate(&account.currency, base, conversions), None),
+                };
+                // Amounts too large to convert or add up are treated like those without a rate.
+                let converted = rate
+                    .and_then(|(numerator, denominator)| {
+                        Money::new(account.balance, &account.currency).convert(
+                            numerator,
+                            denominator,
+                            base,
+                        )
+                    })
+                    .and_then(|converted| Some((total.checked_add(&converted)?, converted)));
+                let converted = match converted {
+                    Some((sum, converted)) => {
+                        total = sum;
+                        Some(converted.minor())
+                    }
+                    None => {
+                        if !unconverted.contains(&account.currency) {
+                            unconverted.push(account.currency.clone());
+                        }
+                        None
+                    }
+                };
+
+                ConvertedBalance {
+                    rate: rate
+                        .map(|(numerator, denominator)| numerator as f64 / denominator as f64),
+                    rate_date,
+                    converted,
+                    name: account.name,
+                    currency: account.currency,
+                    balance: account.balance,
+                }
+            })
+            .collect()
+    };
+    let accounts = convert(accounts);
+    // Named like `Broker VTI`, valued in the security's currency.
+    let holdings = convert(
+        holdings
+            .iter()
+            .filter(|holding| holding.quantity > 0)
+            .map(|holding| Account {
+                name: format!("{} {}", holding.account, holding.symbol),
+                balance: holding.value,
+                currency: holding.currency.clone(),
+            })
+            .collect(),
+    );
+
+    BalanceReport {
+        base: base.to_owned(),
+        accounts,
+        total: total.minor(),
+        unconverted,
+        holdings,
+    }
+}
+
+/// The currencies other than `base` that the accounts and the securities still held are in,
+/// each once: those `balance_report` needs a rate for. A security can be priced in a currency
+/// none of the accounts use.
+pub fn foreign_currencies(base: &str, accounts: &[Account], holdings: &[Holding]) -> Vec<String> {
+    let held = holdings
+        .iter()
+        .filter(|holding| holding.quantity > 0)
+        .map(|holding| &holding.currency);
+    let mut currencies: Vec<String> = Vec::new();
+    for currency in accounts.iter().map(|account| &account.currency).chain(held) {
+        if currency != base && !currencies.contains(currency) {
+            currencies.push(currency.clone());
+        }
+    }
+
+    currencies
+}
+
+/// The rate from `currency` to `base` in `rates` as a fraction, with its date.
+fn table_rate<'a>(
+    currency: &str,
+    base: &str,
+    rates: &'a [ExchangeRate],
+) -> Option<((i128, i128), &'a str)> {
+    rates.iter().find_map(|rate| {
+        let scaled = i128::from(rate.rate);
+        let scale = i128::pow(10, RATE_DIGITS);
+        if rate.base == currency && rate.quote == base {
+            Some(((scaled, scale), rate.date.as_str()))
+        } else if rate.base == base && rate.quote == currency {
+            Some(((scale, scaled), rate.date.as_str()))
+        } else {
+            None
+        }
+    })
+}
+
+/// The rate from `currency` to `base` as a fraction, from transfers.
+fn rate(currency: &str, base: &str, conversions: &[Conversion]) -> Option<(i128, i128)> {
+    if currency == base {
+        return Some((1, 1));
+    }
+
+    conversions.iter().rev().find_map(|conversion| {
+        // Amounts are in minor units, rates per unit.
+        let unit = |currency: &str| i128::pow(10, minor_digits(currency));
+        let amount = i128::from(conversion.amount) * unit(&conversion.to_currency);
+        let to_amount = i128::from(conversion.to_amount) * unit(&conversion.from_currency);
+        if conversion.from_currency == currency && conversion.to_currency == base {
+            Some((to_amount, amount))
+        } else if conversion.from_currency == base && conversion.to_currency == currency {
+            Some((amount, to_amount))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(name: &str, currency: &str, balance: i64) -> Account {
+        Account {
+            name: name.to_owned(),
+            balance,
+            currency: currency.to_owned(),
+        }
+    }
+
+    fn exchange_rate(base: &str, quote: &str, date: &str, rate: i64) -> ExchangeRate {
+        ExchangeRate {
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+            date: date.to_owned(),
+            rate,
+        }
+    }
+
+    fn conversion(from: &str, to: &str, amount: i64, to_amount: i64) -> Conversion {
+        Conversion {
+            from_currency: from.to_owned(),
+            to_currency: to.to_owned(),
+            amount,
+            to_amount,
+            datetime: 0,
+        }
+    }
+
+    #[test]
+    fn test_balance_report() {
+        let accounts = vec![
+            account("Cash", "CNY", 1_000),
+            account("Dollars", "USD", 300),
+            account("Euros", "EUR", -200),
+            account("Pounds", "GBP", 50),
+        ];
+        let conversions = [
+            conversion("CNY", "USD", 700, 100),
+            // The latest rate wins, whichever way the money went.
+            conversion("USD", "CNY", 100, 720),
+            conversion("EUR", "CNY", 300, 2_345),
+        ];
+
+        let report = balance_report("CNY", accounts, &[], &[], &conversions);
+        assert_eq!(report.base, "CNY");
+        let converted: Vec<Option<i64>> = report.accounts.iter().map(|a| a.converted).collect();
+        // -200 * 2345 / 300 = -1563.33…
+        assert_eq!(
+            converted,
+            vec![Some(1_000), Some(2_160), Some(-1_563), None]
+        );
+        assert_eq!(report.accounts[1].rate, Some(7.2));
+        assert_eq!(report.total, 1_000 + 2_160 - 1_563);
+        assert_eq!(report.unconverted, vec!["GBP".to_owned()]);
+
+        let report = balance_report(
+            "USD",
+            vec![account("Cash", "CNY", 1_000)],
+            &[],
+            &[],
+            &conversions,
+        );
+        assert_eq!(report.accounts[0].converted, Some(139));
+        assert_eq!(report.total, 139);
+    }
+
+    #[test]
+    fn test_table_rates_come_first() {
+        let accounts = vec![
+            account("Dollars", "USD", 300),
+            account("Euros", "EUR", 1_000),
+            account("Yen", "JPY", 5_000),
+        ];
+        let rates = [
+            exchange_rate("USD", "CNY", "2024-03-01", 712_340_000),
+            // Quoted the other way round.
+            exchange_rate("CNY", "EUR", "2024-02-01", 12_800_000),
+        ];
+        let conversions = [
+            conversion("USD", "CNY", 100, 720),
+            conversion("JPY", "CNY", 10_000, 480),
+        ];
+
+        let report = balance_report("CNY", accounts, &[], &rates, &conversions);
+        let converted: Vec<Option<i64>> = report.accounts.iter().map(|a| a.converted).collect();
+        // 300 * 7.1234 = 2137.02, 1000 / 0.128 = 7812.5 and 5000 * 0.048 = 240.
+        assert_eq!(converted, vec![Some(2_137), Some(7_813), Some(240)]);
+        let dates: Vec<Option<&str>> = report
+            .accounts
+            .iter()
+            .map(|a| a.rate_date.as_deref())
+            .collect();
+        assert_eq!(dates, vec![Some("2024-03-01"), Some("2024-02-01"), None]);
+        assert_eq!(report.total, 2_137 + 7_813 + 240);
+    }
+
+    fn holding(account: &str, currency: &str, quantity: i64, value: i64) -> Holding {
+        Holding {
+            account: account.to_owned(),
+            symbol: "VTI".to_owned(),
+            currency: currency.to_owned(),
+            quantity,
+            cost: 0,
+            lots: Vec::new(),
+            realised: 0,
+            price: None,
+            price_date: None,
+            value,
+            unrealised: None,
+        }
+    }
+
+    #[test]
+    fn test_holdings_count_towards_the_total() {
+        let holdings = [
+            holding("Broker", "USD", 30_000, 73_500),
+            holding("IRA", "USD", 0, 0),
+        ];
+        let rates = [exchange_rate("USD", "CNY", "2024-03-01", 700_000_000)];
+
+        let report = balance_report(
+            "CNY",
+            vec![account("Broker", "USD", -60_000)],
+            &holdings,
+            &rates,
+            &[],
+        );
+        // Holdings sold off entirely are left out.
+        assert_eq!(report.holdings.len(), 1);
+        assert_eq!(report.holdings[0].name, "Broker VTI");
+        assert_eq!(report.holdings[0].converted, Some(514_500));
+        assert_eq!(report.total, -420_000 + 514_500);
+    }
+
+    #[test]
+    fn test_foreign_priced_holding() {
+        // Every account is in yuan, but the brokerage holds a security priced in dollars.
+        let accounts = vec![
+            account("Broker", "CNY", 10_000),
+            account("Cash", "CNY", 5_000),
+        ];
+        let holdings = [
+            holding("Broker", "USD", 30_000, 73_500),
+            holding("Broker", "HKD", 0, 0),
+        ];
+        assert_eq!(
+            foreign_currencies("CNY", &accounts, &holdings),
+            vec!["USD".to_owned()]
+        );
+        assert_eq!(
+            foreign_currencies("USD", &accounts, &holdings),
+            vec!["CNY".to_owned()]
+        );
+
+        let rates = [exchange_rate("USD", "CNY", "2024-03-01", 700_000_000)];
+        let report = balance_report("CNY", accounts, &holdings, &rates, &[]);
+        assert!(report.unconverted.is_empty());
+        assert_eq!(report.holdings[0].converted, Some(514_500));
+        assert_eq!(report.total, 15_000 + 514_500);
+    }
+}
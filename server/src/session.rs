@@ -0,0 +1,75 @@
+use crate::data::Store;
+use crate::error::ServerError;
+use actix_identity::{Identity, RequestIdentity};
+use actix_web::dev::Payload;
+use actix_web::{web, Error as AWError, FromRequest, HttpMessage, HttpRequest};
+use entities::Role;
+use futures::future::LocalBoxFuture;
+
+/// Cookie the client sets to pick its active ledger.
+pub const LEDGER_COOKIE: &str = "ledger";
+
+/// The ledger a signed-in user's request operates on, with their role in it.
+///
+/// Extracting it fails unless the user is a member of that ledger, so handlers taking a
+/// `Session` can scope every query by `ledger` without further checks.
+pub struct Session {
+    pub ledger: i64,
+    pub role: Role,
+}
+
+impl Session {
+    pub fn require(&self, role: Role) -> Result<(), ServerError> {
+        if self.role >= role {
+            Ok(())
+        } else {
+            Err(ServerError::ForbiddenError)
+        }
+    }
+}
+
+impl FromRequest for Session {
+    type Error = AWError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user = req.get_identity().and_then(|id| id.parse::<i64>().ok());
+        let ledger = req
+            .cookie(LEDGER_COOKIE)
+            .and_then(|cookie| cookie.value().parse::<i64>().ok());
+        let db = req.app_data::<web::Data<Store>>().cloned();
+
+        Box::pin(async move {
+            let user = user.ok_or(ServerError::UnauthorizedError)?;
+            let db = db.ok_or_else(|| {
+                ServerError::InternalError("Storage is not configured.".to_owned())
+            })?;
+
+            let membership = web::block(move || -> Result<Option<(i64, Role)>, ServerError> {
+                match ledger {
+                    Some(ledger) => Ok(db.get_role(user, ledger)?.map(|role| (ledger, role))),
+                    // Without a choice fall back to the user's first ledger.
+                    None => Ok(db
+                        .get_ledgers(user)?
+                        .into_iter()
+                        .next()
+                        .map(|ledger| (ledger.id, ledger.role))),
+                }
+            })
+            .await
+            .map_err(ServerError::from)?;
+
+            let (ledger, role) = membership.ok_or(ServerError::ForbiddenError)?;
+
+            Ok(Session { ledger, role })
+        })
+    }
+}
+
+/// Returns the id of the signed-in user, whatever ledger they're working on.
+pub fn user_id(id: &Identity) -> Result<i64, ServerError> {
+    id.identity()
+        .and_then(|id| id.parse().ok())
+        .ok_or(ServerError::UnauthorizedError)
+}
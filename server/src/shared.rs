@@ -0,0 +1,195 @@
+use crate::data::SharedAmount;
+use entities::{MemberBalance, Settlement};
+use std::collections::BTreeMap;
+
+/// Splits `amount` in proportion to `weights`. What doesn't divide evenly goes a cent at a
+/// time to the largest remainders, earliest first, so the parts always add up to `amount`.
+pub fn allocate(amount: i64, weights: &[i64]) -> Vec<i64> {
+    let total: i128 = weights.iter().map(|w| i128::from(*w)).sum();
+    if total == 0 {
+        return vec![0; weights.len()];
+    }
+    let exact: Vec<(i64, i128)> = weights
+        .iter()
+        .map(|weight| {
+            let share = i128::from(amount) * i128::from(*weight);
+            ((share / total) as i64, share % total)
+        })
+        .collect();
+    let mut parts: Vec<i64> = exact.iter().map(|(part, _)| *part).collect();
+    let left = amount - parts.iter().sum::<i64>();
+
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(exact[*i].1.abs()));
+    for i in order.into_iter().take(left.unsigned_abs() as usize) {
+        parts[i] += left.signum();
+    }
+
+    parts
+}
+
+/// What each member fronted and owes across the shared expenses, by member then currency.
+pub fn balances(shared: &[SharedAmount]) -> Vec<MemberBalance> {
+    let mut totals: BTreeMap<(&str, &str), (i64, i64)> = BTreeMap::new();
+    for expense in shared {
+        let currency = &*expense.currency;
+        totals
+            .entry((&expense.expense.paid_by, currency))
+            .or_default()
+            .0 += expense.amount;
+        let weights: Vec<i64> = expense.expense.shares.iter().map(|s| s.share).collect();
+        let parts = allocate(expense.amount, &weights);
+        for (share, part) in expense.expense.shares.iter().zip(parts) {
+            totals.entry((&share.member, currency)).or_default().1 += part;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|((member, currency), (paid, owed))| MemberBalance {
+            member: member.to_owned(),
+            currency: currency.to_owned(),
+            paid,
+            owed,
+            balance: paid - owed,
+        })
+        .collect()
+}
+
+/// The transfers that even out the balances: in each currency, whoever owes the most pays
+/// whoever is owed the most until one of them is square. Each transfer squares at least one
+/// member, so there are fewer transfers than members.
+pub fn settle(balances: &[MemberBalance]) -> Vec<Settlement> {
+    let mut by_currency: BTreeMap<&str, Vec<(&str, i64)>> = BTreeMap::new();
+    for balance in balances.iter().filter(|b| b.balance != 0) {
+        by_currency
+            .entry(&balance.currency)
+            .or_default()
+            .push((&balance.member, balance.balance));
+    }
+
+    let mut settlements = Vec::new();
+    for (currency, mut members) in by_currency {
+        loop {
+            // Ties go to the first name, so the suggestion doesn't change between requests.
+            let creditor = members
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, balance))| *balance > 0)
+                .min_by_key(|(_, (name, balance))| (-balance, *name))
+                .map(|(i, _)| i);
+            let debtor = members
+                .iter()
+                .enumerate()
+                .filter(|(_, (_, balance))| *balance < 0)
+                .min_by_key(|(_, (name, balance))| (*balance, *name))
+                .map(|(i, _)| i);
+            let (creditor, debtor) = match (creditor, debtor) {
+                (Some(creditor), Some(debtor)) => (creditor, debtor),
+                _ => break,
+            };
+            let amount = members[creditor].1.min(-members[debtor].1);
+            members[creditor].1 -= amount;
+            members[debtor].1 += amount;
+            settlements.push(Settlement {
+                from: members[debtor].0.to_owned(),
+                to: members[creditor].0.to_owned(),
+                currency: currency.to_owned(),
+                amount,
+            });
+        }
+    }
+
+    settlements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::{MemberShare, ShareMethod, SharedExpense};
+
+    fn shared(paid_by: &str, amount: i64, shares: &[(&str, i64)]) -> SharedAmount {
+        SharedAmount {
+            operation: 1,
+            amount,
+            currency: "USD".to_owned(),
+            expense: SharedExpense {
+                paid_by: paid_by.to_owned(),
+                method: ShareMethod::Shares,
+                shares: shares
+                    .iter()
+                    .map(|(member, share)| MemberShare {
+                        member: (*member).to_owned(),
+                        share: *share,
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn settlement(from: &str, to: &str, amount: i64) -> Settlement {
+        Settlement {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            currency: "USD".to_owned(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_allocate() {
+        assert_eq!(allocate(1_000, &[1, 1, 1]), vec![334, 333, 333]);
+        assert_eq!(allocate(1_000, &[2, 1]), vec![667, 333]);
+        // 33.33% and 66.67% of $0.10.
+        assert_eq!(allocate(10, &[3_333, 6_667]), vec![3, 7]);
+        assert_eq!(allocate(-1_000, &[1, 1, 1]), vec![-334, -333, -333]);
+        assert_eq!(allocate(i64::MAX, &[1, 1]).iter().sum::<i64>(), i64::MAX);
+    }
+
+    #[test]
+    fn test_balances() {
+        let expenses = vec![
+            shared("alice", 9_000, &[("alice", 1), ("bob", 1), ("carol", 1)]),
+            shared("bob", 3_000, &[("alice", 1), ("bob", 2)]),
+        ];
+        let balances = balances(&expenses);
+        let balances: Vec<(&str, i64, i64, i64)> = balances
+            .iter()
+            .map(|b| (&*b.member, b.paid, b.owed, b.balance))
+            .collect();
+        assert_eq!(
+            balances,
+            vec![
+                ("alice", 9_000, 4_000, 5_000),
+                ("bob", 3_000, 5_000, -2_000),
+                ("carol", 0, 3_000, -3_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_settle() {
+        let expenses = vec![
+            shared("alice", 9_000, &[("alice", 1), ("bob", 1), ("carol", 1)]),
+            shared("bob", 3_000, &[("alice", 1), ("bob", 2)]),
+        ];
+        assert_eq!(
+            settle(&balances(&expenses)),
+            vec![
+                settlement("carol", "alice", 3_000),
+                settlement("bob", "alice", 2_000),
+            ]
+        );
+
+        // Paying the suggestion back squares everyone.
+        let mut squared = expenses;
+        squared.push(shared_to("carol", "alice", 3_000));
+        squared.push(shared_to("bob", "alice", 2_000));
+        assert!(balances(&squared).iter().all(|b| b.balance == 0));
+        assert!(settle(&balances(&squared)).is_empty());
+    }
+
+    fn shared_to(from: &str, to: &str, amount: i64) -> SharedAmount {
+        shared(from, amount, &[(to, 1)])
+    }
+}
@@ -0,0 +1,141 @@
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{Rng, RngCore};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+const ISSUER: &str = "Accounting";
+const SECRET_LEN: usize = 20;
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+// Accept codes from one step before and after to tolerate clock drift.
+const SKEW_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_LEN: usize = 10;
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_LEN];
+    rand::thread_rng().fill_bytes(&mut secret);
+
+    base32::encode(base32::Alphabet::RFC4648 { padding: false }, &secret)
+}
+
+pub fn provisioning_uri(secret: &str, account: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = ISSUER,
+        account = account,
+        secret = secret,
+        digits = DIGITS,
+        period = STEP_SECONDS,
+    )
+}
+
+pub fn current_step(timestamp: i64) -> i64 {
+    timestamp / STEP_SECONDS
+}
+
+/// Checks `code` against the steps around `now` and returns the matched step.
+/// Steps not after `last_step` are rejected so a code can't be replayed.
+pub fn verify(secret: &str, code: &str, now: i64, last_step: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let key = base32::decode(base32::Alphabet::RFC4648 { padding: false }, secret)?;
+    let step = current_step(now);
+
+    (step - SKEW_STEPS..=step + SKEW_STEPS)
+        .filter(|candidate| *candidate > last_step)
+        .find(|candidate| code_at(&key, *candidate, DIGITS) == code)
+}
+
+fn code_at(key: &[u8], step: i64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length.");
+    mac.update(&(step as u64).to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // Dynamic truncation, see RFC 4226 section 5.3.
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    binary % 10u32.pow(digits)
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            (&mut rng)
+                .sample_iter(Alphanumeric)
+                .take(RECOVERY_CODE_LEN)
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    let digest = Sha256::digest(code.trim().to_lowercase().as_bytes());
+
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The SHA1 seed from RFC 6238 appendix B.
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_rfc6238_vectors() {
+        let vectors = [
+            (59, 94_287_082),
+            (1_111_111_109, 7_081_804),
+            (1_111_111_111, 14_050_471),
+            (1_234_567_890, 89_005_924),
+            (2_000_000_000, 69_279_037),
+        ];
+
+        for (time, expected) in vectors.iter() {
+            assert_eq!(code_at(RFC_KEY, current_step(*time), 8), *expected);
+        }
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, RFC_KEY);
+
+        assert_eq!(
+            verify(&secret, "081804", 1_111_111_109, 0),
+            Some(37_037_036)
+        );
+        // Previous step is still accepted.
+        assert_eq!(
+            verify(&secret, "081804", 1_111_111_139, 0),
+            Some(37_037_036)
+        );
+        // A step that was already used is rejected.
+        assert_eq!(verify(&secret, "081804", 1_111_111_109, 37_037_036), None);
+        assert_eq!(verify(&secret, "000000", 1_111_111_109, 0), None);
+        assert_eq!(verify(&secret, "81804", 1_111_111_109, 0), None);
+    }
+
+    #[test]
+    fn test_recovery_code_hash_is_normalized() {
+        let codes = generate_recovery_codes();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&format!(" {} ", codes[0].to_uppercase()))
+        );
+    }
+}
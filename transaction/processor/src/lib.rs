@@ -1,6 +1,7 @@
 use crate::error::Error;
 use crate::parser::{AlipayParser, CgbCreditParser, CmbDebitParser, WeChatPayParser};
 use encoding::{label, DecoderTrap};
+use entities::time::Tz;
 use entities::Transaction;
 use std::fs::OpenOptions;
 use std::io::Read;
@@ -29,6 +30,25 @@ pub struct ParserConfig {
     source: TransactionSource,
     encoding: Option<String>,
     account_name: String,
+    /// The timezone of the times in the statement, normally the ledger's.
+    timezone: Tz,
+}
+
+impl ParserConfig {
+    /// Reads statements of `account_name` from `source`, guessing the encoding if it's `None`.
+    pub fn new(
+        source: TransactionSource,
+        encoding: Option<String>,
+        account_name: String,
+        timezone: Tz,
+    ) -> Self {
+        ParserConfig {
+            source,
+            encoding,
+            account_name,
+            timezone,
+        }
+    }
 }
 
 pub fn parse<P: AsRef<Path>>(path: P, config: ParserConfig) -> Result<Vec<Transaction>> {
@@ -67,15 +87,18 @@ fn get_parser(config: ParserConfig) -> Box<dyn Parser> {
     match config.source {
         TransactionSource::CgbCredit => Box::new(CgbCreditParser {
             account_name: config.account_name,
+            timezone: config.timezone,
         }),
         TransactionSource::CmbDebit => Box::new(CmbDebitParser {
             account_name: config.account_name,
         }),
         TransactionSource::Alipay => Box::new(AlipayParser {
             account_name: config.account_name,
+            timezone: config.timezone,
         }),
         TransactionSource::WeChatPay => Box::new(WeChatPayParser {
             account_name: config.account_name,
+            timezone: config.timezone,
         }),
     }
 }
@@ -92,12 +115,23 @@ mod tests {
                 source: TransactionSource::CgbCredit,
                 encoding: None,
                 account_name: "CGB".to_owned(),
+                timezone: Tz::Asia__Shanghai,
             },
         )?;
 
-        for trans in transactions {
-            println!("{:?}", trans);
-        }
+        // The header row isn't a transaction and is skipped.
+        let transactions: Vec<(i64, &str, &str, i64, &str)> = transactions
+            .iter()
+            .map(|t| (t.datetime, &*t.from, &*t.to, t.amount, &*t.description))
+            .collect();
+        assert_eq!(
+            transactions,
+            vec![
+                (1_582_992_000_000, "CGB", "", -3_500, "星巴克咖啡"),
+                (1_583_078_400_000, "", "CGB", 120_050, "还款"),
+                (1_583_337_600_000, "CGB", "", -29_990, "京东商城"),
+            ]
+        );
 
         Ok(())
     }
@@ -2,7 +2,8 @@ use crate::Parser;
 use crate::Result;
 use chrono::{NaiveDate, NaiveDateTime};
 use csv::{StringRecord, Trim};
-use entities::Transaction;
+use entities::time::Tz;
+use entities::{Money, Timestamp, Transaction, DEFAULT_CURRENCY};
 
 pub trait CsvParser {
     fn map_row(&self, record: StringRecord) -> Option<Transaction>;
@@ -28,17 +29,24 @@ impl<T: CsvParser> Parser for T {
 
 pub(crate) struct CgbCreditParser {
     pub account_name: String,
+    /// Where the statement's times are from, as they're written without an offset.
+    pub timezone: Tz,
 }
 
 pub(crate) struct AlipayParser {
     pub account_name: String,
+    /// Where the statement's times are from, as they're written without an offset.
+    pub timezone: Tz,
 }
 
 pub(crate) struct WeChatPayParser {
     pub account_name: String,
+    /// Where the statement's times are from, as they're written without an offset.
+    pub timezone: Tz,
 }
 
 pub(crate) struct CmbDebitParser {
+    #[allow(dead_code)]
     pub account_name: String,
 }
 
@@ -46,17 +54,17 @@ impl CsvParser for CgbCreditParser {
     fn map_row(&self, record: StringRecord) -> Option<Transaction> {
         let date = NaiveDate::parse_from_str(record.get(0)?, "%Y-%m-%d").ok()?;
         let description = record.get(1)?;
-        let amount = record.get(3)?.parse::<f64>().ok()?;
+        let amount = parse_amount(record.get(3)?)?;
 
-        let timestamp = date.and_hms(0, 0, 0).timestamp();
+        let timestamp = Timestamp::from_local_date(date, self.timezone).millis();
 
-        if amount > 0.0 {
+        if amount > 0 {
             // Income
             Some(Transaction {
                 datetime: timestamp,
                 from: "".to_owned(),
                 to: self.account_name.clone(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             })
         } else {
@@ -65,7 +73,7 @@ impl CsvParser for CgbCreditParser {
                 datetime: timestamp,
                 from: self.account_name.clone(),
                 to: "".to_owned(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             })
         }
@@ -75,24 +83,25 @@ impl CsvParser for CgbCreditParser {
 impl CsvParser for AlipayParser {
     fn map_row(&self, record: StringRecord) -> Option<Transaction> {
         let datetime = NaiveDateTime::parse_from_str(record.get(2)?, "%Y-%m-%d %T").ok()?;
+        let timestamp = Timestamp::from_local(datetime, self.timezone).millis();
         let target_account = record.get(7)?;
         let description = record.get(8)?;
-        let amount = record.get(9)?.parse::<f64>().ok()?;
+        let amount = parse_amount(record.get(9)?)?;
         let operation_type = record.get(15)?;
 
         match operation_type {
             "已收入" => Some(Transaction {
-                datetime: datetime.timestamp(),
+                datetime: timestamp,
                 from: target_account.to_owned(),
                 to: self.account_name.clone(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             }),
             "已支出" => Some(Transaction {
-                datetime: datetime.timestamp(),
+                datetime: timestamp,
                 from: self.account_name.clone(),
                 to: target_account.to_owned(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             }),
             _ => None,
@@ -103,26 +112,27 @@ impl CsvParser for AlipayParser {
 impl CsvParser for WeChatPayParser {
     fn map_row(&self, record: StringRecord) -> Option<Transaction> {
         let datetime = NaiveDateTime::parse_from_str(record.get(0)?, "%Y-%m-%d %T").ok()?;
+        let timestamp = Timestamp::from_local(datetime, self.timezone).millis();
         let description = record.get(1)?;
         let target_account = record.get(2)?;
         let operation_type = record.get(4)?;
         // Remove the first ¥
         let amount_str: String = record.get(5)?.chars().skip(1).collect();
-        let amount: f64 = amount_str.parse().ok()?;
+        let amount = parse_amount(&amount_str)?;
 
         match operation_type {
             "收入" => Some(Transaction {
-                datetime: datetime.timestamp(),
+                datetime: timestamp,
                 from: target_account.to_owned(),
                 to: self.account_name.clone(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             }),
             "支出" => Some(Transaction {
-                datetime: datetime.timestamp(),
+                datetime: timestamp,
                 from: self.account_name.clone(),
                 to: target_account.to_owned(),
-                amount: (amount * 100.0) as i64,
+                amount,
                 description: description.to_string(),
             }),
             _ => None,
@@ -130,8 +140,88 @@ impl CsvParser for WeChatPayParser {
     }
 }
 
+/// Reads an amount in yuan, which every supported statement is in, as fen.
+fn parse_amount(text: &str) -> Option<i64> {
+    Money::parse(text, DEFAULT_CURRENCY)
+        .ok()
+        .map(|money| money.minor())
+}
+
 impl Parser for CmbDebitParser {
     fn parse(&self, _: String) -> Result<Vec<Transaction>> {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entities::time::Tz::Asia__Shanghai as Shanghai;
+
+    fn record(fields: &[&str]) -> StringRecord {
+        StringRecord::from(fields.to_vec())
+    }
+
+    #[test]
+    fn test_amounts_are_exact() {
+        let parser = WeChatPayParser {
+            account_name: "WeChat".to_owned(),
+            timezone: Shanghai,
+        };
+        let row = |amount| {
+            record(&[
+                "2020-04-01 12:00:00",
+                "Lunch",
+                "Canteen",
+                "",
+                "支出",
+                amount,
+            ])
+        };
+
+        let transaction = parser.map_row(row("¥0.29")).unwrap();
+        assert_eq!(transaction.amount, 29);
+        assert_eq!(transaction.from, "WeChat");
+        assert_eq!(parser.map_row(row("¥1.15")).unwrap().amount, 115);
+        assert!(parser.map_row(row("¥1.005")).is_none());
+
+        let parser = CgbCreditParser {
+            account_name: "CGB".to_owned(),
+            timezone: Shanghai,
+        };
+        let refund = parser
+            .map_row(record(&["2020-04-01", "Refund", "", "0.57"]))
+            .unwrap();
+        assert_eq!(refund.amount, 57);
+        assert_eq!(refund.to, "CGB");
+    }
+
+    #[test]
+    fn test_times_are_read_in_the_timezone() {
+        let wechat = WeChatPayParser {
+            account_name: "WeChat".to_owned(),
+            timezone: Shanghai,
+        };
+        let lunch = wechat
+            .map_row(record(&[
+                "2020-04-01 12:00:00",
+                "Lunch",
+                "Canteen",
+                "",
+                "支出",
+                "¥15.00",
+            ]))
+            .unwrap();
+        // Noon in Shanghai is 04:00 UTC, in milliseconds.
+        assert_eq!(lunch.datetime, 1_585_713_600_000);
+
+        let cgb = CgbCreditParser {
+            account_name: "CGB".to_owned(),
+            timezone: Tz::UTC,
+        };
+        let refund = cgb
+            .map_row(record(&["2020-04-01", "Refund", "", "0.57"]))
+            .unwrap();
+        assert_eq!(refund.datetime, 1_585_699_200_000);
+    }
+}